@@ -0,0 +1,353 @@
+//! Keyboard and screen reader support for the tray and app windows
+//!
+//! Every tray action is also a command windows can run without a mouse,
+//! with a keyboard shortcut in the tray menu. State the tray shows with a
+//! symbol (✓, ○, ⚠) also comes as a plain sentence for screen readers,
+//! which read those symbols poorly or not at all.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, SecureTokenStorage, TrayIconStyle};
+use crate::{db, selftest, sync};
+
+/// Something the user can do from the tray or a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Action {
+    SignIn,
+    SignOut,
+    SyncNow,
+    SyncHistory,
+    ExportLatest,
+    CopyLatestLink,
+    UsageReport,
+    Settings,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::SignIn,
+        Action::SignOut,
+        Action::SyncNow,
+        Action::SyncHistory,
+        Action::ExportLatest,
+        Action::CopyLatestLink,
+        Action::UsageReport,
+        Action::Settings,
+        Action::Quit,
+    ];
+
+    /// Tray menu item id
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::SignIn => "sign_in",
+            Action::SignOut => "sign_out",
+            Action::SyncNow => "sync_now",
+            Action::SyncHistory => "sync_history",
+            Action::ExportLatest => "export_latest",
+            Action::CopyLatestLink => "copy_latest_link",
+            Action::UsageReport => "usage_report",
+            Action::Settings => "settings",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// The action behind a tray menu item id
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    /// Menu text, read as is by screen readers
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::SignIn => "Sign In...",
+            Action::SignOut => "Sign Out",
+            Action::SyncNow => "Sync Now",
+            Action::SyncHistory => "Sync All History",
+            Action::ExportLatest => "Export Latest Conversation",
+            Action::CopyLatestLink => "Copy Latest Conversation Link",
+            Action::UsageReport => "Storage Report...",
+            Action::Settings => "Settings...",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Keyboard shortcut while the tray menu is open, as a Tauri accelerator
+    pub fn shortcut(self) -> Option<&'static str> {
+        match self {
+            Action::SignIn | Action::SignOut | Action::SyncHistory => None,
+            Action::SyncNow => Some("CmdOrCtrl+R"),
+            Action::ExportLatest => Some("CmdOrCtrl+E"),
+            Action::CopyLatestLink => Some("CmdOrCtrl+L"),
+            Action::UsageReport => Some("CmdOrCtrl+U"),
+            Action::Settings => Some("CmdOrCtrl+,"),
+            Action::Quit => Some("CmdOrCtrl+Q"),
+        }
+    }
+}
+
+/// An action as offered right now
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionInfo {
+    pub action: Action,
+    pub label: &'static str,
+    pub shortcut: Option<&'static str>,
+    /// Whether the action can run in the current state
+    pub enabled: bool,
+}
+
+/// A piece of state shown in the tray
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusLabel {
+    /// Tray menu item id
+    pub id: &'static str,
+    /// Text as shown, possibly led by a symbol
+    pub text: String,
+    /// The same as a sentence for screen readers
+    pub label: String,
+}
+
+/// The app's state as the tray and windows describe it
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusDescription {
+    /// One line, as in the tray tooltip
+    pub summary: String,
+    pub labels: Vec<StatusLabel>,
+}
+
+/// What the tray reports about the app
+#[derive(Debug, Clone, Default)]
+pub struct AppStatus {
+    /// Project directories being watched
+    pub watch_count: usize,
+    pub signed_in: bool,
+    pub keychain_locked: bool,
+    /// Sync state writes are failing; see [`db::storage_failing`]
+    pub storage_failing: bool,
+    /// Files waiting while the API is unreachable
+    pub offline_queued: Option<usize>,
+    /// Progress of the history backfill under way
+    pub backfill: Option<sync::BackfillProgress>,
+    /// Summary of the latest sync pass, if any file failed
+    pub last_failure: Option<String>,
+    /// Capabilities the startup self-test found missing
+    pub limitations: Option<String>,
+}
+
+impl AppStatus {
+    /// The state now
+    pub fn current(watch_count: usize) -> Self {
+        Self {
+            watch_count,
+            signed_in: SecureTokenStorage::new().has_tokens(),
+            keychain_locked: config::keychain_locked(),
+            storage_failing: db::storage_failing(),
+            offline_queued: sync::offline_queued(),
+            backfill: sync::backfill_progress(),
+            last_failure: sync::last_report().filter(|r| !r.is_success()).map(|r| r.summary()),
+            limitations: selftest::last_report().and_then(|r| r.summary()),
+        }
+    }
+
+    /// Actions in tray menu order; signing in or out, whichever applies
+    pub fn actions(&self) -> Vec<ActionInfo> {
+        Action::ALL
+            .into_iter()
+            .filter(|action| match action {
+                Action::SignIn => !self.signed_in,
+                Action::SignOut => self.signed_in,
+                _ => true,
+            })
+            .map(|action| ActionInfo {
+                action,
+                label: action.label(),
+                shortcut: action.shortcut(),
+                enabled: match action {
+                    // A locked keychain reads as signed out; don't offer to sign in again
+                    Action::SignIn => !self.keychain_locked,
+                    Action::SyncNow => self.signed_in,
+                    // One backfill at a time
+                    Action::SyncHistory => self.signed_in && self.backfill.is_none(),
+                    _ => true,
+                },
+            })
+            .collect()
+    }
+
+    /// State in tray menu order
+    pub fn labels(&self) -> Vec<StatusLabel> {
+        let projects = plural(self.watch_count, "project");
+        let mut labels = vec![StatusLabel {
+            id: "status",
+            text: format!("Watching {}", projects),
+            label: format!("Watching {}", projects),
+        }];
+        labels.push(if self.keychain_locked {
+            StatusLabel {
+                id: "auth_status",
+                text: "🔒 Unlock Keychain to Continue".to_string(),
+                label: "Keychain locked. Unlock it to continue syncing.".to_string(),
+            }
+        } else if self.signed_in {
+            StatusLabel {
+                id: "auth_status",
+                text: "✓ Signed In".to_string(),
+                label: "Signed in".to_string(),
+            }
+        } else {
+            StatusLabel {
+                id: "auth_status",
+                text: "○ Not Signed In".to_string(),
+                label: "Not signed in".to_string(),
+            }
+        });
+        if self.storage_failing {
+            labels.push(StatusLabel {
+                id: "storage_warning",
+                text: "⚠ Can't Save Sync State (Disk Full?)".to_string(),
+                label: "Warning: sync state can't be saved. The disk may be full.".to_string(),
+            });
+        }
+        if let Some(queued) = self.offline_queued {
+            labels.push(StatusLabel {
+                id: "offline",
+                text: format!("○ Offline, {} queued", plural(queued, "item")),
+                label: format!("Offline. {} queued until the network is back.", plural(queued, "item")),
+            });
+        }
+        if let Some(progress) = self.backfill {
+            labels.push(StatusLabel {
+                id: "backfill",
+                text: format!("Syncing History: {} of {}", progress.done, progress.total),
+                label: format!(
+                    "Syncing history. {} of {} done.",
+                    progress.done,
+                    plural(progress.total, "conversation")
+                ),
+            });
+        }
+        if let Some(limitations) = &self.limitations {
+            labels.push(StatusLabel {
+                id: "limitations",
+                text: limitations.clone(),
+                label: limitations.clone(),
+            });
+        }
+        labels
+    }
+
+    /// One line for the tray tooltip: the most pressing state
+    pub fn summary(&self) -> String {
+        if self.keychain_locked {
+            "Duplex Stream - unlock the keychain to continue syncing".to_string()
+        } else if self.storage_failing {
+            "Duplex Stream - can't save sync state, check free disk space".to_string()
+        } else if let Some(queued) = self.offline_queued {
+            format!("Duplex Stream - offline, {} queued", plural(queued, "item"))
+        } else if let Some(failure) = &self.last_failure {
+            format!("Duplex Stream - last sync: {}", failure)
+        } else if let Some(progress) = self.backfill {
+            format!("Duplex Stream - syncing history, {} of {}", progress.done, progress.total)
+        } else {
+            self.limitations.clone().unwrap_or_else(|| "Duplex Stream".to_string())
+        }
+    }
+
+    pub fn describe(&self) -> StatusDescription {
+        StatusDescription {
+            summary: self.summary(),
+            labels: self.labels(),
+        }
+    }
+}
+
+/// Recolor an RGBA icon for `style`, or `None` to keep it as is
+///
+/// The high-contrast variants draw every mostly-opaque pixel in solid black
+/// or white and clear the rest, so the icon keeps its shape without the
+/// anti-aliased edges and mid-tones that wash out against the menu bar.
+pub fn tray_icon_rgba(rgba: &[u8], style: TrayIconStyle) -> Option<Vec<u8>> {
+    let color = match style {
+        TrayIconStyle::Default => return None,
+        TrayIconStyle::HighContrastBlack => [0, 0, 0],
+        TrayIconStyle::HighContrastWhite => [255, 255, 255],
+    };
+    let recolored = rgba
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            if pixel[3] >= 128 {
+                [color[0], color[1], color[2], 255]
+            } else {
+                [0, 0, 0, 0]
+            }
+        })
+        .collect();
+    Some(recolored)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions_follow_state() {
+        let signed_out = AppStatus::default();
+        let actions = signed_out.actions();
+        assert_eq!(actions[0].action, Action::SignIn);
+        assert!(actions.iter().all(|a| a.action != Action::SignOut));
+        let sync_now = actions.iter().find(|a| a.action == Action::SyncNow).unwrap();
+        assert!(!sync_now.enabled);
+
+        let signed_in = AppStatus {
+            signed_in: true,
+            ..Default::default()
+        };
+        let actions = signed_in.actions();
+        assert_eq!(actions[0].action, Action::SignOut);
+        assert!(actions.iter().all(|a| a.enabled));
+
+        for action in Action::ALL {
+            assert_eq!(Action::from_id(action.id()), Some(action));
+        }
+    }
+
+    #[test]
+    fn test_labels_spell_out_symbols() {
+        let status = AppStatus {
+            watch_count: 1,
+            keychain_locked: true,
+            offline_queued: Some(3),
+            backfill: Some(sync::BackfillProgress { done: 2, total: 10 }),
+            ..Default::default()
+        };
+        let labels = status.labels();
+        let ids: Vec<_> = labels.iter().map(|l| l.id).collect();
+        assert_eq!(ids, vec!["status", "auth_status", "offline", "backfill"]);
+        assert_eq!(labels[0].label, "Watching 1 project");
+        assert_eq!(labels[2].text, "○ Offline, 3 items queued");
+        assert_eq!(labels[2].label, "Offline. 3 items queued until the network is back.");
+        assert_eq!(labels[3].label, "Syncing history. 2 of 10 conversations done.");
+        assert!(labels.iter().all(|l| l.label.is_ascii()));
+        assert_eq!(status.summary(), "Duplex Stream - unlock the keychain to continue syncing");
+    }
+
+    #[test]
+    fn test_high_contrast_icon() {
+        let rgba = [10, 20, 30, 255, 10, 20, 30, 40, 200, 100, 50, 128];
+        assert_eq!(tray_icon_rgba(&rgba, TrayIconStyle::Default), None);
+        assert_eq!(
+            tray_icon_rgba(&rgba, TrayIconStyle::HighContrastWhite).unwrap(),
+            vec![255, 255, 255, 255, 0, 0, 0, 0, 255, 255, 255, 255]
+        );
+        assert_eq!(
+            tray_icon_rgba(&rgba, TrayIconStyle::HighContrastBlack).unwrap(),
+            vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 255]
+        );
+    }
+}
@@ -0,0 +1,1302 @@
+//! Typed client for the Duplex API
+//!
+//! Every backend call goes through [`DuplexApiClient`], which attaches the
+//! access token, routes through the configured proxy, logs requests (see
+//! `http_log.rs`) and retries transient failures. Retries are limited to
+//! failures where the server cannot have acted on the request (connection
+//! errors, 429 and 503), so non-idempotent calls are never duplicated.
+//!
+//! Workspace, org and device lookups are cached for [`LOOKUP_TTL`], so the
+//! UI and the engine can ask as often as they like. The cache empties when
+//! the access token changes or the server answers 401.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::auth;
+use crate::config::{self, Config, ConfigError, ConnectionConfig, IpPreference, PolicyConfig, WorkspaceToken};
+use crate::errors::{self, ErrorCategory};
+use crate::git::GitContext;
+use crate::http_log::RequestLogger;
+use crate::parsers::{ContentType, Message, ParserRegistry};
+use crate::policy::SignedPolicy;
+use crate::recordings::Recording;
+use crate::signing::{self, RequestSigner, SigningError};
+use crate::team_stats::TeamReport;
+use crate::watcher::expand_path;
+
+/// Request timeout
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Retries after the first attempt for transient failures
+const MAX_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for each further one
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long an HTTP/2 keep-alive ping may go unanswered
+const HTTP2_PING_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long a workspace, org or device lookup is reused
+const LOOKUP_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long a connectivity probe waits for any answer
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header describing the client on every request, see [`ClientInfo`]
+pub const CLIENT_HEADER: &str = "X-Duplex-Client";
+
+/// Newest upload payload version this client can send
+///
+/// 1 uploads the raw file as `content`; 2 uploads structured `messages`.
+/// The server advertises what it accepts in every extraction response.
+pub const PAYLOAD_VERSION: u32 = 2;
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Not authenticated - run 'duplex auth login'")]
+    NotAuthenticated,
+    #[error("{status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("Request signing: {0}")]
+    Signing(#[from] SigningError),
+    #[error("Invalid sync settings: {0}")]
+    Config(String),
+}
+
+impl ApiError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ApiError::Http(e) => errors::classify_http(e),
+            ApiError::NotAuthenticated => ErrorCategory::Auth,
+            ApiError::Status { status, .. } if *status == StatusCode::FORBIDDEN => ErrorCategory::Auth,
+            ApiError::Status { .. } => ErrorCategory::Server,
+            ApiError::Signing(e) => e.category(),
+            ApiError::Config(_) => ErrorCategory::Config,
+        }
+    }
+
+    /// What a user can do about this error
+    pub fn user_hint(&self) -> &'static str {
+        match self {
+            ApiError::Status { .. } if self.is_rejection() => {
+                "The server refused this conversation; run `duplex errors` to see why"
+            }
+            ApiError::Status { status, .. } if *status == StatusCode::UNAUTHORIZED => {
+                ErrorCategory::Auth.user_hint()
+            }
+            ApiError::Signing(_) => "Check sync.signing in config.jsonc",
+            _ => self.category().user_hint(),
+        }
+    }
+
+    /// Whether the API answered 409, e.g. for an append whose base differs
+    /// from the server's copy
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, ApiError::Status { status, .. } if *status == StatusCode::CONFLICT)
+    }
+
+    /// Another machine's newer copy of the session, when the API answered
+    /// 409 because this upload would overwrite it
+    pub fn newer_version(&self) -> Option<NewerVersion> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Body {
+            newer_version: NewerVersion,
+        }
+
+        match self {
+            ApiError::Status { body, .. } if self.is_conflict() => {
+                serde_json::from_str::<Body>(body).ok().map(|body| body.newer_version)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the API answered 404
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ApiError::Status { status, .. } if *status == StatusCode::NOT_FOUND)
+    }
+
+    /// Whether the API couldn't be reached at all, e.g. connection refused
+    /// or the host name didn't resolve, as when the machine is offline
+    pub fn is_offline(&self) -> bool {
+        matches!(self, ApiError::Http(e) if e.is_connect())
+    }
+
+    /// Whether the server refused the payload itself (400 or 422), so
+    /// sending it again unchanged would fail the same way
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Status { status, .. }
+                if *status == StatusCode::BAD_REQUEST || *status == StatusCode::UNPROCESSABLE_ENTITY
+        )
+    }
+
+    /// Validation messages from a rejection, as `field: message` where the
+    /// server names the field; empty for other errors
+    pub fn validation_errors(&self) -> Vec<String> {
+        match self {
+            ApiError::Status { body, .. } if self.is_rejection() => parse_validation_errors(body),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Messages from the common validation error shapes: `errors` (a list or a
+/// field map), `detail`, `issues`, or a bare `message` or `error`
+fn parse_validation_errors(body: &str) -> Vec<String> {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+        let line = body.lines().map(str::trim).find(|l| !l.is_empty());
+        return line.map(|l| vec![l.to_string()]).unwrap_or_default();
+    };
+
+    fn describe(item: &serde_json::Value) -> Option<String> {
+        if let Some(message) = item.as_str() {
+            return Some(message.to_string());
+        }
+        let message = ["message", "msg", "error"].iter().find_map(|key| item[key].as_str())?;
+        let path = ["path", "loc", "field"].iter().find_map(|key| match &item[key] {
+            serde_json::Value::String(path) => Some(path.clone()),
+            serde_json::Value::Array(parts) => Some(
+                parts
+                    .iter()
+                    .map(|part| part.as_str().map(str::to_string).unwrap_or_else(|| part.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("."),
+            ),
+            _ => None,
+        });
+        Some(match path.filter(|p| !p.is_empty()) {
+            Some(path) => format!("{}: {}", path, message),
+            None => message.to_string(),
+        })
+    }
+
+    for key in ["errors", "detail", "issues"] {
+        match &json[key] {
+            serde_json::Value::Array(items) => return items.iter().filter_map(describe).collect(),
+            serde_json::Value::Object(fields) => {
+                return fields
+                    .iter()
+                    .flat_map(|(field, messages)| {
+                        let messages = match messages {
+                            serde_json::Value::Array(messages) => messages.iter().collect(),
+                            message => vec![message],
+                        };
+                        messages
+                            .into_iter()
+                            .filter_map(describe)
+                            .map(move |message| format!("{}: {}", field, message))
+                    })
+                    .collect();
+            }
+            serde_json::Value::String(message) => return vec![message.clone()],
+            _ => {}
+        }
+    }
+    ["message", "error"]
+        .iter()
+        .find_map(|key| json[key].as_str())
+        .map(|message| vec![message.to_string()])
+        .unwrap_or_default()
+}
+
+/// Whether a request needs the access token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Auth {
+    /// Fail with `NotAuthenticated` when there is no token
+    Required,
+    /// Send the token when there is one
+    Optional,
+    /// Never send the token (e.g. presigned storage URLs)
+    None,
+}
+
+/// Conversation upload, with the content inline or already in storage
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractRequest<'a> {
+    /// Payload version: 1 sends `content`, 2 sends `messages`. A stored
+    /// object holds whichever the version says, as JSON for `messages`.
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<&'a [Message]>,
+    /// Storage key from [`DuplexApiClient::upload_url`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r2_key: Option<&'a str>,
+    pub source_path: String,
+    pub source: &'a str,
+    pub session_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<&'a str>,
+    /// Display name of the project, see [`crate::projects`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<&'a str>,
+    /// SHA-256 of the content; with `session_id`, the server's dedup key
+    pub content_hash: &'a str,
+    /// [`crate::machine::machine_id`] of the uploading machine
+    pub machine_id: &'a str,
+    pub content_type: ContentType,
+    pub workspace_id: &'a str,
+    pub git: Option<&'a GitContext>,
+    pub tags: &'a [String],
+    pub terminal_recordings: &'a [Recording],
+    pub related_sessions: &'a [RelatedSession],
+    /// Set when `content` or `messages` hold only lines added since an
+    /// earlier upload, which the server extends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub append: Option<Append<'a>>,
+    /// Set when `content` is sealed on this machine; `content_hash` is then
+    /// keyed, see [`crate::encryption`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Encryption<'a>>,
+    /// Workflow of another machine's newer version of this session, which
+    /// the server keeps; this upload is stored beside it, not over it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflicts_with: Option<&'a str>,
+    /// Set when the conversation was over `sync.payloadLimit` and split,
+    /// each part going up as its own upload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<Part<'a>>,
+}
+
+/// Where an upload falls among the parts of a split conversation
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Part<'a> {
+    /// From 1
+    pub index: usize,
+    pub count: usize,
+    /// Workflow of the first part, for the parts after it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_workflow_id: Option<&'a str>,
+}
+
+/// How an upload's content was encrypted
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Encryption<'a> {
+    /// e.g. `aes-256-gcm`
+    pub algorithm: &'a str,
+    /// Fingerprint of the key that opens the content
+    pub key_fingerprint: &'a str,
+}
+
+/// Where appended lines go, for servers that advertise `acceptsAppend`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Append<'a> {
+    /// Workflow of the upload being extended
+    pub workflow_id: &'a str,
+    /// Bytes of the conversation already uploaded
+    pub offset: u64,
+    /// Lines already uploaded
+    pub lines: u64,
+    /// SHA-256 of the bytes already uploaded; the server answers 409 if
+    /// its copy differs
+    pub base_hash: &'a str,
+    /// The session is streaming and more lines follow within seconds, so
+    /// the server may show them before extracting
+    pub live: bool,
+}
+
+/// Another tool's conversation in the same project and time window, or
+/// another machine's version of the same session
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedSession {
+    pub session_id: Option<String>,
+    pub source: Option<String>,
+    /// Set once the related conversation has been uploaded
+    pub workflow_id: Option<String>,
+}
+
+/// Response from the extraction API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractionResponse {
+    pub workflow_id: String,
+    pub status: String,
+    /// Another machine already uploaded this session and content;
+    /// `workflow_id` is that upload's workflow
+    #[serde(default)]
+    pub duplicate: bool,
+    /// Machine that made the original upload, for duplicates
+    #[serde(default)]
+    pub synced_by: Option<String>,
+    /// Newest payload version the server accepts; absent on v1-only servers
+    #[serde(default)]
+    pub max_payload_version: Option<u32>,
+    /// The server extends earlier uploads with appended lines
+    #[serde(default)]
+    pub accepts_append: bool,
+}
+
+/// The server's copy of a session, newer than an upload from this machine
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewerVersion {
+    pub workflow_id: String,
+    /// Machine that uploaded it
+    #[serde(default)]
+    pub machine_id: Option<String>,
+    /// When it was uploaded, Unix seconds
+    #[serde(default)]
+    pub updated_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadUrlRequest<'a> {
+    pub filename: &'a str,
+    pub content_hash: &'a str,
+    pub session_id: Option<&'a str>,
+    pub machine_id: &'a str,
+    pub source: &'a str,
+    pub workspace_id: &'a str,
+}
+
+/// Response from the upload-url API
+///
+/// When another machine already uploaded the same session and content the
+/// server skips the upload and returns that extraction as `duplicate`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadUrlResponse {
+    #[serde(default)]
+    pub upload_url: String,
+    #[serde(default)]
+    pub r2_key: String,
+    #[serde(default)]
+    pub duplicate: Option<ExtractionResponse>,
+}
+
+/// Progress of an extraction workflow, and what it produced
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowStatus {
+    pub workflow_id: String,
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Summary of the conversation, once extracted
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Tags the server assigned
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Extraction output, in whatever shape the server's extractors produce
+    #[serde(default)]
+    pub output: Option<serde_json::Value>,
+    /// Title the server holds, as uploaded or edited since
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl WorkflowStatus {
+    /// Whether extraction has ended, successfully or not
+    pub fn is_finished(&self) -> bool {
+        self.error.is_some() || matches!(self.status.as_str(), "complete" | "completed" | "failed")
+    }
+}
+
+/// Title and tag changes to a workflow; fields left `None` are unchanged
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationUpdate<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<&'a str>,
+    /// Replaces the workflow's tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<&'a [String]>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWorkspaceRequest<'a> {
+    pub name: &'a str,
+    pub project_path: &'a str,
+    pub git_remote: Option<&'a str>,
+}
+
+/// Response from the workspaces API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceResponse {
+    pub id: String,
+}
+
+/// A workspace the account can upload to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+}
+
+/// The signed-in account's organization and its role there
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrgMembership {
+    pub org_id: String,
+    #[serde(default)]
+    pub org_name: Option<String>,
+    pub role: String,
+}
+
+/// A registered machine as the server knows it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    pub device_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub last_seen_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceRegistration<'a> {
+    pub device_id: &'a str,
+    pub name: &'a str,
+    pub platform: &'a str,
+    pub app_version: &'a str,
+    /// Same details as the `X-Duplex-Client` header
+    pub client: &'a ClientInfo,
+}
+
+/// App version, platform and parser versions, so the backend can warn
+/// about outdated clients and gate features by version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientInfo {
+    pub app_version: String,
+    /// `<os>-<arch>`, e.g. `macos-aarch64`
+    pub platform: String,
+    /// Enabled parsers and their versions
+    pub parsers: BTreeMap<String, String>,
+}
+
+impl ClientInfo {
+    /// Describe this build with the parsers the config enables
+    pub fn from_config(config: &Config) -> Self {
+        let registry = ParserRegistry::new();
+        let parsers = config
+            .enabled_parsers()
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), registry.get(&name)?.version().to_string())))
+            .collect();
+
+        Self {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            parsers,
+        }
+    }
+
+    /// `X-Duplex-Client` value, e.g.
+    /// `app=0.1.0; platform=macos-aarch64; parsers=claude-code/1,codex/1`
+    pub fn header_value(&self) -> String {
+        let parsers: Vec<String> = self
+            .parsers
+            .iter()
+            .map(|(name, version)| format!("{}/{}", name, version))
+            .collect();
+        format!("app={}; platform={}; parsers={}", self.app_version, self.platform, parsers.join(","))
+    }
+
+    /// Default `User-Agent`, e.g. `DuplexStream/0.1.0 (macos-aarch64)`
+    pub fn user_agent(&self) -> String {
+        format!("DuplexStream/{} ({})", self.app_version, self.platform)
+    }
+}
+
+/// Response from the devices API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceResponse {
+    pub device_id: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchExtractRequest<'a> {
+    conversations: &'a [ExtractRequest<'a>],
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchExtractResponse {
+    results: Vec<ExtractionResponse>,
+}
+
+/// Lookup responses, each reused for the cache's TTL
+///
+/// Entries belong to the token they were fetched with; asking with another
+/// (after signing in, out or as someone else) empties the cache first.
+struct LookupCache {
+    ttl: Duration,
+    token: Option<String>,
+    entries: HashMap<String, (Instant, Box<dyn Any + Send + Sync>)>,
+}
+
+impl LookupCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            token: None,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The response to `path` fetched with `token`, if still fresh
+    fn get<T: Clone + 'static>(&mut self, token: Option<&str>, path: &str) -> Option<T> {
+        if self.token.as_deref() != token {
+            self.entries.clear();
+            self.token = token.map(str::to_string);
+            return None;
+        }
+        let (fetched_at, value) = self.entries.get(path)?;
+        if fetched_at.elapsed() >= self.ttl {
+            return None;
+        }
+        value.downcast_ref::<T>().cloned()
+    }
+
+    /// Keep the response to `path`, unless the token changed while it was fetched
+    fn insert<T: Send + Sync + 'static>(&mut self, token: Option<&str>, path: &str, value: T) {
+        if self.token.as_deref() == token {
+            self.entries.insert(path.to_string(), (Instant::now(), Box::new(value)));
+        }
+    }
+
+    /// Drop responses to paths starting with `prefix`
+    fn invalidate(&mut self, prefix: &str) {
+        self.entries.retain(|path, _| !path.starts_with(prefix));
+    }
+}
+
+/// Client for the Duplex API
+pub struct DuplexApiClient {
+    client: Client,
+    base_url: String,
+    /// Token used when the keyring has none (e.g. `DUPLEX_ACCESS_TOKEN`)
+    fallback_token: Option<String>,
+    http_log: RequestLogger,
+    /// Payload version the server last advertised
+    server_payload_version: AtomicU32,
+    /// Whether the server last advertised accepting appends
+    server_accepts_append: AtomicBool,
+    client_info: ClientInfo,
+    /// Signs requests to the API when `sync.signing` asks for HMAC
+    signer: Option<RequestSigner>,
+    /// Workspace, org and device lookups
+    lookups: Mutex<LookupCache>,
+    /// Web URL of a conversation, with `{workflowId}` to fill in
+    conversation_url: String,
+    /// Workspaces with their own token, from `workspaces.tokens`
+    workspace_tokens: BTreeMap<String, WorkspaceToken>,
+    /// Workspace tokens read so far, by workspace ID
+    loaded_workspace_tokens: Mutex<HashMap<String, String>>,
+    /// `sync.extraHeaders`, added to requests to the API
+    extra_headers: HeaderMap,
+    /// Whether requests carry the sync token and `sync.extraHeaders`
+    credentials: bool,
+}
+
+impl DuplexApiClient {
+    /// Create a client for the API at `base_url`
+    pub fn new(base_url: String, fallback_token: Option<String>, config: &Config) -> Result<Self, ApiError> {
+        let client_info = ClientInfo::from_config(config);
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(&client_info.header_value()) {
+            headers.insert(CLIENT_HEADER, value);
+        }
+        let user_agent = config.sync.user_agent.clone().unwrap_or_else(|| client_info.user_agent());
+
+        let builder = Client::builder().timeout(TIMEOUT).user_agent(user_agent).default_headers(headers);
+        let mut builder = configure_connections(builder, &config.sync.connection);
+        if let Some(proxy) = &config.sync.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(path) = &config.sync.ca_bundle {
+            for cert in load_ca_bundle(path)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        let signing = signing::for_url(&config.sync.signing, &base_url);
+        if let Some(cert) = signing.and_then(|signing| signing.client_cert.as_ref()) {
+            builder = builder.identity(signing::client_identity(cert, &base_url)?);
+        }
+        let signer = signing
+            .and_then(|signing| signing.hmac.as_ref())
+            .map(RequestSigner::load)
+            .transpose()?;
+
+        Ok(Self {
+            client: builder.build()?,
+            fallback_token,
+            http_log: RequestLogger::new(config.debug.log_requests),
+            server_payload_version: AtomicU32::new(1),
+            server_accepts_append: AtomicBool::new(false),
+            client_info,
+            signer,
+            lookups: Mutex::new(LookupCache::new(LOOKUP_TTL)),
+            conversation_url: config
+                .sync
+                .conversation_url
+                .clone()
+                .unwrap_or_else(|| format!("{}/conversations/{{workflowId}}", base_url.trim_end_matches('/'))),
+            base_url,
+            workspace_tokens: config.workspaces.tokens.clone(),
+            loaded_workspace_tokens: Mutex::new(HashMap::new()),
+            extra_headers: extra_headers(&config.sync.extra_headers)?,
+            credentials: true,
+        })
+    }
+
+    /// Client for [`crate::team_stats`]. A `teamStats.url` outside the sync
+    /// API gets none of its credentials, so they can't leak to a stats host.
+    pub fn for_team_stats(config: &Config) -> Result<Self, ApiError> {
+        let api_url = config::get_api_url();
+        let url = config.team_stats.url.clone().unwrap_or_else(|| api_url.clone());
+        let mut client = Self::new(url.clone(), None, config)?;
+        client.credentials = same_origin(&url, &api_url);
+        Ok(client)
+    }
+
+    /// Web URL of the conversation uploaded as `workflow_id`
+    pub fn conversation_url(&self, workflow_id: &str) -> String {
+        self.conversation_url.replace("{workflowId}", workflow_id)
+    }
+
+    /// What this client reports about itself on every request
+    pub fn client_info(&self) -> &ClientInfo {
+        &self.client_info
+    }
+
+    /// Payload version to upload with
+    ///
+    /// Starts at 1 and follows what the server advertises in extraction
+    /// responses, capped at [`PAYLOAD_VERSION`].
+    pub fn payload_version(&self) -> u32 {
+        self.server_payload_version
+            .load(Ordering::Relaxed)
+            .min(PAYLOAD_VERSION)
+    }
+
+    /// Whether uploads may send only lines appended since the last one
+    ///
+    /// Starts false and follows what the server advertises in extraction
+    /// responses.
+    pub fn accepts_append(&self) -> bool {
+        self.server_accepts_append.load(Ordering::Relaxed)
+    }
+
+    /// Start a conversation extraction
+    pub async fn extract(&self, request: &ExtractRequest<'_>) -> Result<ExtractionResponse, ApiError> {
+        let url = self.url("/extraction/conversations/extract");
+        let response = self
+            .send_to_workspace(self.client.post(&url).json(request), Auth::Optional, request.workspace_id)
+            .await?;
+        let extraction: ExtractionResponse = response.json().await?;
+        self.note_capabilities(&extraction);
+        Ok(extraction)
+    }
+
+    /// Start several extractions in one request
+    pub async fn extract_batch(&self, requests: &[ExtractRequest<'_>]) -> Result<Vec<ExtractionResponse>, ApiError> {
+        let url = self.url("/extraction/conversations/batch");
+        let body = BatchExtractRequest { conversations: requests };
+        let response = self
+            .send(self.client.post(&url).json(&body), Auth::Required)
+            .await?;
+        let results = response.json::<BatchExtractResponse>().await?.results;
+        if let Some(extraction) = results.first() {
+            self.note_capabilities(extraction);
+        }
+        Ok(results)
+    }
+
+    /// Get a presigned URL for uploading large content
+    pub async fn upload_url(&self, request: &UploadUrlRequest<'_>) -> Result<UploadUrlResponse, ApiError> {
+        let url = self.url("/extraction/upload-url");
+        let response = self
+            .send_to_workspace(self.client.post(&url).json(request), Auth::Required, request.workspace_id)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Upload content to a presigned storage URL
+    pub async fn put_object(&self, upload_url: &str, content: String) -> Result<(), ApiError> {
+        self.send(self.client.put(upload_url).body(content), Auth::None)
+            .await?;
+        Ok(())
+    }
+
+    /// Get the status of an extraction workflow, with its results once it
+    /// has them
+    pub async fn workflow_status(&self, workflow_id: &str) -> Result<WorkflowStatus, ApiError> {
+        self.get_json(&format!("/extraction/workflows/{}", urlencoding::encode(workflow_id)))
+            .await
+    }
+
+    /// Change a workflow's title or tags
+    pub async fn update_annotations(&self, workflow_id: &str, update: &AnnotationUpdate<'_>) -> Result<(), ApiError> {
+        let url = self.url(&format!("/extraction/workflows/{}", urlencoding::encode(workflow_id)));
+        self.send(self.client.patch(&url).json(update), Auth::Required)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete an uploaded conversation and its extraction results
+    pub async fn delete_conversation(&self, workflow_id: &str) -> Result<(), ApiError> {
+        let url = self.url(&format!(
+            "/extraction/conversations/{}",
+            urlencoding::encode(workflow_id)
+        ));
+        self.send(self.client.request(Method::DELETE, &url), Auth::Required)
+            .await?;
+        Ok(())
+    }
+
+    /// Send aggregate usage stats; see [`crate::team_stats`]
+    pub async fn send_team_stats(&self, report: &TeamReport) -> Result<(), ApiError> {
+        let url = self.url("/team-stats");
+        self.send(self.client.post(&url).json(report), Auth::Required)
+            .await?;
+        Ok(())
+    }
+
+    /// Create a workspace, or get the existing one with the same name
+    pub async fn create_workspace(&self, request: &CreateWorkspaceRequest<'_>) -> Result<WorkspaceResponse, ApiError> {
+        let workspace = self.post_json("/workspaces", request).await?;
+        self.lookups.lock().unwrap().invalidate("/workspaces");
+        Ok(workspace)
+    }
+
+    /// Workspaces the account can upload to, cached for [`LOOKUP_TTL`]
+    pub async fn workspaces(&self) -> Result<Vec<Workspace>, ApiError> {
+        self.get_cached("/workspaces").await
+    }
+
+    /// The account's organization and role, cached for [`LOOKUP_TTL`]
+    pub async fn org_membership(&self) -> Result<OrgMembership, ApiError> {
+        self.get_cached("/org/membership").await
+    }
+
+    /// A registered machine, cached for [`LOOKUP_TTL`]
+    pub async fn device(&self, device_id: &str) -> Result<DeviceInfo, ApiError> {
+        self.get_cached(&format!("/devices/{}", urlencoding::encode(device_id)))
+            .await
+    }
+
+    /// Forget cached lookups, e.g. after the account's workspaces or
+    /// membership changed elsewhere
+    pub fn invalidate_lookups(&self) {
+        self.lookups.lock().unwrap().invalidate("");
+    }
+
+    /// Fetch the signed org policy overlay; `None` if the org publishes none
+    pub async fn org_policy_overlay(&self) -> Result<Option<SignedPolicy>, ApiError> {
+        match self.get_json("/org/config-overlay").await {
+            Ok(signed) => Ok(Some(signed)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetch the org policy baseline; `None` if the org publishes none
+    pub async fn org_policy_baseline(&self) -> Result<Option<PolicyConfig>, ApiError> {
+        match self.get_json("/org/policy-baseline").await {
+            Ok(baseline) => Ok(Some(baseline)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Register this machine with the account
+    pub async fn register_device(&self, device: &DeviceRegistration<'_>) -> Result<DeviceResponse, ApiError> {
+        let registered = self.post_json("/devices", device).await?;
+        self.lookups.lock().unwrap().invalidate("/devices");
+        Ok(registered)
+    }
+
+    /// Remember the payload version and append support a response advertises
+    fn note_capabilities(&self, response: &ExtractionResponse) {
+        let version = response.max_payload_version.unwrap_or(1);
+        let previous = self.server_payload_version.swap(version, Ordering::Relaxed);
+        if previous != version {
+            tracing::debug!("Server accepts upload payload version {}", version);
+        }
+        if self.server_accepts_append.swap(response.accepts_append, Ordering::Relaxed) != response.accepts_append {
+            tracing::debug!("Server accepts appends: {}", response.accepts_append);
+        }
+    }
+
+    /// Whether the API answers at all; any HTTP response counts
+    pub async fn is_reachable(&self) -> bool {
+        match self.client.get(&self.base_url).timeout(PROBE_TIMEOUT).send().await {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::debug!("API unreachable: {}", e);
+                false
+            }
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        let response = self
+            .send(self.client.get(self.url(path)), Auth::Required)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// GET a lookup, reusing a response fetched with the same token
+    async fn get_cached<T>(&self, path: &str) -> Result<T, ApiError>
+    where
+        T: DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        let token = self.token().await;
+        if let Some(value) = self.lookups.lock().unwrap().get(token.as_deref(), path) {
+            return Ok(value);
+        }
+        let value: T = self.get_json(path).await?;
+        self.lookups.lock().unwrap().insert(token.as_deref(), path, value.clone());
+        Ok(value)
+    }
+
+    async fn post_json<T: DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> Result<T, ApiError> {
+        let response = self
+            .send(self.client.post(self.url(path)).json(body), Auth::Required)
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    /// Get a valid access token, with auto-refresh
+    pub async fn token(&self) -> Option<String> {
+        // An API key from the environment overrides any stored login
+        if let Some(key) = config::env_api_key() {
+            return Some(key);
+        }
+
+        // Then try to get a valid token from auth system (with auto-refresh)
+        match auth::get_valid_token().await {
+            Ok(token) => return Some(token),
+            Err(auth::AuthError::Config(ConfigError::NotAuthenticated)) => {
+                // Not logged in - fall back to initial token if provided
+            }
+            Err(auth::AuthError::ClientIdNotConfigured) => {
+                // WorkOS not configured - fall back to initial token
+                tracing::debug!("WorkOS client ID not configured, using fallback token");
+            }
+            Err(e) => {
+                // Other auth errors (e.g., refresh failed)
+                tracing::warn!("Failed to get valid token: {}", e);
+            }
+        }
+
+        // Fall back to the initial token passed at construction
+        self.fallback_token.clone()
+    }
+
+    /// A workspace's own token, if `workspaces.tokens` gives it one
+    fn workspace_token(&self, workspace_id: &str) -> Option<String> {
+        let source = self.workspace_tokens.get(workspace_id)?;
+        if let Some(token) = self.loaded_workspace_tokens.lock().unwrap().get(workspace_id) {
+            return Some(token.clone());
+        }
+        let token = source.load(workspace_id)?;
+        self.loaded_workspace_tokens
+            .lock()
+            .unwrap()
+            .insert(workspace_id.to_string(), token.clone());
+        Some(token)
+    }
+
+    /// Send a request for a workspace, with the workspace's own token if it
+    /// has one and the account's otherwise
+    async fn send_to_workspace(
+        &self,
+        request: RequestBuilder,
+        auth: Auth,
+        workspace_id: &str,
+    ) -> Result<Response, ApiError> {
+        let Some(token) = self.workspace_token(workspace_id) else {
+            return self.send(request, auth).await;
+        };
+        let result = self.send(request.bearer_auth(token), Auth::None).await;
+        if matches!(result, Err(ApiError::NotAuthenticated)) {
+            // Read it again next time, in case it was replaced
+            tracing::warn!("The token for workspace {} was rejected", workspace_id);
+            self.loaded_workspace_tokens.lock().unwrap().remove(workspace_id);
+        }
+        result
+    }
+
+    /// Send a request with auth, logging and retries, failing on error statuses
+    async fn send(&self, mut request: RequestBuilder, auth: Auth) -> Result<Response, ApiError> {
+        if auth != Auth::None && self.credentials {
+            match self.token().await {
+                Some(token) => request = request.bearer_auth(token),
+                None if auth == Auth::Required => return Err(ApiError::NotAuthenticated),
+                None => tracing::warn!("No authentication token available, request may fail"),
+            }
+        }
+
+        let mut request = request.build()?;
+        let url = request.url().to_string();
+        // Presigned storage URLs are outside the gateway. A prefix match
+        // would also let `https://api.example.com.evil.net` through.
+        if same_origin(&url, &self.base_url) {
+            if self.credentials {
+                request.headers_mut().extend(self.extra_headers.clone());
+            }
+            if let Some(signer) = &self.signer {
+                signer.sign(&mut request);
+            }
+        }
+        let mut attempt = 0;
+
+        let response = loop {
+            let retry = request.try_clone().filter(|_| attempt < MAX_RETRIES);
+            let result = self.http_log.execute(&self.client, request).await;
+
+            let transient = match &result {
+                Ok(response) => is_transient(response.status()),
+                Err(e) => e.is_connect(),
+            };
+            match retry {
+                Some(next) if transient => {
+                    let delay = RETRY_BACKOFF * 2u32.pow(attempt);
+                    tracing::debug!("Retrying {} in {}ms", url, delay.as_millis());
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                    attempt += 1;
+                }
+                _ => break result?,
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        self.http_log.log_error_body(&url, status, &body);
+        if status == StatusCode::UNAUTHORIZED {
+            // Whatever was looked up may not be this account's
+            self.invalidate_lookups();
+            return Err(ApiError::NotAuthenticated);
+        }
+        Err(ApiError::Status { status, body })
+    }
+}
+
+/// Whether two URLs have the same scheme, host and port
+fn same_origin(a: &str, b: &str) -> bool {
+    match (reqwest::Url::parse(a), reqwest::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+/// Parse `sync.extraHeaders`
+fn extra_headers(headers: &BTreeMap<String, String>) -> Result<HeaderMap, ApiError> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| ApiError::Config(format!("invalid header name {:?} in sync.extraHeaders", name)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| ApiError::Config(format!("invalid value for header {} in sync.extraHeaders", name)))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Read the certificates in `sync.caBundle`
+fn load_ca_bundle(path: &str) -> Result<Vec<reqwest::Certificate>, ApiError> {
+    let pem = std::fs::read(expand_path(path))
+        .map_err(|e| ApiError::Config(format!("could not read sync.caBundle {}: {}", path, e)))?;
+    let certs = reqwest::Certificate::from_pem_bundle(&pem)
+        .map_err(|e| ApiError::Config(format!("invalid certificate in sync.caBundle {}: {}", path, e)))?;
+    if certs.is_empty() {
+        return Err(ApiError::Config(format!("no certificates in sync.caBundle {}", path)));
+    }
+    Ok(certs)
+}
+
+/// Apply `sync.connection`: protocol, pooling, keep-alive and address family
+fn configure_connections(builder: reqwest::ClientBuilder, connection: &ConnectionConfig) -> reqwest::ClientBuilder {
+    let keep_alive = Some(Duration::from_secs(connection.keep_alive_seconds)).filter(|d| !d.is_zero());
+    let mut builder = builder
+        .pool_idle_timeout(Duration::from_secs(connection.pool_idle_timeout_seconds))
+        .pool_max_idle_per_host(connection.pool_max_idle_per_host)
+        .tcp_keepalive(keep_alive);
+
+    builder = if connection.http2 {
+        builder
+            .http2_adaptive_window(true)
+            .http2_keep_alive_interval(keep_alive)
+            .http2_keep_alive_timeout(HTTP2_PING_TIMEOUT)
+    } else {
+        builder.http1_only()
+    };
+
+    if connection.ip_preference != IpPreference::Auto {
+        builder = builder.dns_resolver(Arc::new(FamilyFirstResolver(connection.ip_preference)));
+    }
+    builder
+}
+
+/// System resolver that lists the preferred address family first
+///
+/// The connector tries the first family and races the other after a short
+/// delay, so the preference never makes an address unreachable.
+struct FamilyFirstResolver(IpPreference);
+
+impl Resolve for FamilyFirstResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let preference = self.0;
+        Box::pin(async move {
+            let mut addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            sort_by_family(&mut addrs, preference);
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Stable-sort addresses so the preferred family comes first
+fn sort_by_family(addrs: &mut [SocketAddr], preference: IpPreference) {
+    addrs.sort_by_key(|addr| match preference {
+        IpPreference::Auto => false,
+        IpPreference::Ipv4 => addr.is_ipv6(),
+        IpPreference::Ipv6 => addr.is_ipv4(),
+    });
+}
+
+/// Statuses where the server did not act on the request and a retry is safe
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_request_payload() {
+        let tags = vec!["auth".to_string()];
+        let request = ExtractRequest {
+            version: 1,
+            content: Some("{}"),
+            messages: None,
+            r2_key: None,
+            source_path: "/tmp/s.jsonl".to_string(),
+            source: "claude-code",
+            session_id: Some("s1"),
+            title: Some("Fix tests"),
+            project_name: Some("acme/billing"),
+            content_hash: "abc123",
+            machine_id: "m1",
+            content_type: ContentType::Memory,
+            workspace_id: "default",
+            git: None,
+            tags: &tags,
+            terminal_recordings: &[],
+            related_sessions: &[],
+            append: None,
+            encryption: None,
+            conflicts_with: None,
+            part: None,
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["content"], "{}");
+        assert!(json.get("messages").is_none());
+        assert!(json.get("r2Key").is_none());
+        assert_eq!(json["contentType"], "memory");
+        assert_eq!(json["sessionId"], "s1");
+        assert_eq!(json["title"], "Fix tests");
+        assert_eq!(json["projectName"], "acme/billing");
+        assert_eq!(json["contentHash"], "abc123");
+        assert_eq!(json["machineId"], "m1");
+        assert_eq!(json["workspaceId"], "default");
+        assert!(json["git"].is_null());
+        assert_eq!(json["tags"][0], "auth");
+        assert!(json.get("append").is_none());
+    }
+
+    #[test]
+    fn test_error_categories() {
+        let status = |code: u16| ApiError::Status {
+            status: StatusCode::from_u16(code).unwrap(),
+            body: String::new(),
+        };
+        assert_eq!(status(403).category(), ErrorCategory::Auth);
+        assert_eq!(status(500).category(), ErrorCategory::Server);
+        assert!(status(404).is_not_found());
+        assert!(is_transient(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient(StatusCode::INTERNAL_SERVER_ERROR));
+
+        assert!(status(401).user_hint().contains("duplex auth login"));
+        assert!(status(422).user_hint().contains("duplex errors"));
+        assert_eq!(status(500).user_hint(), ErrorCategory::Server.user_hint());
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let rejected = |code: u16, body: &str| {
+            ApiError::Status {
+                status: StatusCode::from_u16(code).unwrap(),
+                body: body.to_string(),
+            }
+            .validation_errors()
+        };
+
+        let zod = r#"{"issues":[{"path":["messages",3,"role"],"message":"Invalid enum value"}]}"#;
+        assert_eq!(rejected(400, zod), vec!["messages.3.role: Invalid enum value"]);
+        let fastapi = r#"{"detail":[{"loc":["body","sourcePath"],"msg":"field required"}]}"#;
+        assert_eq!(rejected(422, fastapi), vec!["body.sourcePath: field required"]);
+        let rails = r#"{"errors":{"contentHash":["is too short","is invalid"]}}"#;
+        assert_eq!(
+            rejected(422, rails),
+            vec!["contentHash: is too short", "contentHash: is invalid"]
+        );
+        assert_eq!(rejected(400, r#"{"error":"Bad payload"}"#), vec!["Bad payload"]);
+        assert_eq!(rejected(400, "\nnot json\n"), vec!["not json"]);
+        assert!(rejected(500, zod).is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_family() {
+        let v6: SocketAddr = "[2001:db8::1]:0".parse().unwrap();
+        let v4a: SocketAddr = "192.0.2.1:0".parse().unwrap();
+        let v4b: SocketAddr = "192.0.2.2:0".parse().unwrap();
+
+        let mut addrs = vec![v6, v4a, v4b];
+        sort_by_family(&mut addrs, IpPreference::Ipv4);
+        assert_eq!(addrs, vec![v4a, v4b, v6]);
+
+        sort_by_family(&mut addrs, IpPreference::Ipv6);
+        assert_eq!(addrs, vec![v6, v4a, v4b]);
+    }
+
+    #[test]
+    fn test_client_info() {
+        let info = ClientInfo::from_config(&Config::default());
+        assert_eq!(info.parsers.keys().collect::<Vec<_>>(), vec!["claude-code", "codex", "generic-jsonl"]);
+
+        let header = info.header_value();
+        assert!(header.starts_with(&format!("app={}; platform=", env!("CARGO_PKG_VERSION"))));
+        assert!(header.ends_with("; parsers=claude-code/1,codex/1,generic-jsonl/1"));
+        assert!(info.user_agent().starts_with("DuplexStream/"));
+    }
+
+    #[test]
+    fn test_conversation_url() {
+        let mut config = Config::default();
+        let client = DuplexApiClient::new("https://api.example.com/".to_string(), None, &config).unwrap();
+        assert_eq!(client.conversation_url("wf-1"), "https://api.example.com/conversations/wf-1");
+
+        config.sync.conversation_url = Some("https://app.example.com/c/{workflowId}?ref=desktop".to_string());
+        let client = DuplexApiClient::new("https://api.example.com".to_string(), None, &config).unwrap();
+        assert_eq!(client.conversation_url("wf-1"), "https://app.example.com/c/wf-1?ref=desktop");
+    }
+
+    #[test]
+    fn test_network_settings() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "sync": {
+                "proxyUrl": "http://proxy.corp:3128",
+                "extraHeaders": { "X-Gateway-Tenant": "acme" },
+            }
+        }))
+        .unwrap();
+        assert_eq!(config.sync.proxy.as_deref(), Some("http://proxy.corp:3128"));
+        let client = DuplexApiClient::new("https://api.example.com".to_string(), None, &config).unwrap();
+        assert_eq!(client.extra_headers["x-gateway-tenant"], "acme");
+
+        let mut bad = config.clone();
+        bad.sync.extra_headers.insert("Bad Header".to_string(), "x".to_string());
+        let err = DuplexApiClient::new("https://api.example.com".to_string(), None, &bad).err().unwrap();
+        assert_eq!(err.category(), ErrorCategory::Config);
+
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("ca.pem");
+        std::fs::write(&bundle, "not a certificate").unwrap();
+        for path in [bundle, dir.path().join("missing.pem")] {
+            let mut bad = config.clone();
+            bad.sync.ca_bundle = Some(path.to_string_lossy().to_string());
+            let err = DuplexApiClient::new("https://api.example.com".to_string(), None, &bad).err().unwrap();
+            assert!(matches!(err, ApiError::Config(_)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let base = "https://api.example.com";
+        assert!(same_origin("https://api.example.com/extraction/workflows", base));
+        assert!(same_origin("https://API.example.com:443/x", base));
+        assert!(!same_origin("https://api.example.com.evil.net/x", base));
+        assert!(!same_origin("https://api.example.com:8443/x", base));
+        assert!(!same_origin("http://api.example.com/x", base));
+        assert!(!same_origin("https://storage.example.com/bucket/key", base));
+        assert!(!same_origin("not a url", base));
+    }
+
+    #[test]
+    fn test_team_stats_credentials() {
+        let mut config = Config::default();
+        assert!(DuplexApiClient::for_team_stats(&config).unwrap().credentials);
+
+        config.team_stats.url = Some(format!("{}/", config::get_api_url()));
+        assert!(DuplexApiClient::for_team_stats(&config).unwrap().credentials);
+
+        config.team_stats.url = Some("https://stats.example.com".to_string());
+        assert!(!DuplexApiClient::for_team_stats(&config).unwrap().credentials);
+    }
+
+    #[test]
+    fn test_lookup_cache() {
+        let mut cache = LookupCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get::<u32>(Some("a"), "/workspaces"), None);
+        cache.insert(Some("a"), "/workspaces", 1u32);
+        cache.insert(Some("a"), "/devices/m1", 2u32);
+        assert_eq!(cache.get(Some("a"), "/workspaces"), Some(1u32));
+
+        cache.invalidate("/devices");
+        assert_eq!(cache.get::<u32>(Some("a"), "/devices/m1"), None);
+        assert_eq!(cache.get(Some("a"), "/workspaces"), Some(1u32));
+
+        // Another account's token sees nothing from the first
+        assert_eq!(cache.get::<u32>(Some("b"), "/workspaces"), None);
+        assert_eq!(cache.get::<u32>(Some("a"), "/workspaces"), None);
+
+        let mut expired = LookupCache::new(Duration::ZERO);
+        expired.insert(None, "/workspaces", 1u32);
+        assert_eq!(expired.get::<u32>(None, "/workspaces"), None);
+    }
+}
@@ -10,6 +10,7 @@ use std::time::Duration;
 use thiserror::Error;
 
 use crate::config::{save_credentials, Credentials, SecureTokenStorage};
+use crate::errors::{self, ErrorCategory};
 use crate::oauth::{LoopbackServer, OAuthError, PkceChallenge};
 
 /// WorkOS API base URL
@@ -42,6 +43,34 @@ pub enum AuthError {
     OAuthNotStarted,
 }
 
+impl AuthError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AuthError::Http(e) => errors::classify_http(e),
+            AuthError::Json(_) => ErrorCategory::Parse,
+            AuthError::Api(_) => ErrorCategory::Server,
+            AuthError::Config(e) => e.category(),
+            AuthError::ClientIdNotConfigured => ErrorCategory::Config,
+            AuthError::DeviceCodeExpired
+            | AuthError::AuthorizationPending
+            | AuthError::AuthorizationDenied
+            | AuthError::OAuth(_)
+            | AuthError::OAuthNotStarted => ErrorCategory::Auth,
+        }
+    }
+
+    /// What a user can do about this error
+    pub fn user_hint(&self) -> &'static str {
+        match self {
+            AuthError::DeviceCodeExpired => "Run `duplex auth login` again and finish signing in before the code expires",
+            AuthError::AuthorizationDenied => "Sign-in was declined; run `duplex auth login` to try again",
+            AuthError::ClientIdNotConfigured => "Set WORKOS_CLIENT_ID to your WorkOS client ID",
+            _ => self.category().user_hint(),
+        }
+    }
+}
+
 /// Response from the device authorization endpoint
 #[derive(Debug, Clone, Deserialize)]
 pub struct DeviceCodeResponse {
@@ -0,0 +1,231 @@
+//! Local copies of parsed conversations
+//!
+//! With `cache.enabled`, each conversation the sync engine parses is kept
+//! gzip-compressed in the database, so export and search can read it
+//! without re-parsing the source file, including after the tool that wrote
+//! it has pruned it. The cache is capped at `cache.maxSizeMb`; the least
+//! recently read conversations are evicted first.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::config::CacheConfig;
+use crate::db::{Database, SessionMatch};
+use crate::errors::ErrorCategory;
+use crate::parsers::{ContentType, Conversation, ParserRegistry};
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl CacheError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            CacheError::Io(_) | CacheError::Sqlite(_) => ErrorCategory::Io,
+            CacheError::Json(_) => ErrorCategory::Parse,
+        }
+    }
+}
+
+/// A conversation as stored in the cache
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    source: String,
+    session_id: Option<String>,
+    project_path: Option<PathBuf>,
+    content: String,
+    content_type: ContentType,
+    title: Option<String>,
+}
+
+/// Writes parsed conversations to the cache, keeping it under its cap
+pub struct ContentCache {
+    max_bytes: u64,
+}
+
+impl ContentCache {
+    /// The cache described by `config`, if it is enabled
+    pub fn from_config(config: &CacheConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            max_bytes: config.max_size_mb.saturating_mul(1024 * 1024),
+        })
+    }
+
+    /// Cache a conversation under its sync state key: its source path, or
+    /// for one of several conversations in a file, the path and session ID
+    pub fn store(&self, db: &Database, key: &str, conversation: &Conversation) -> Result<(), CacheError> {
+        let entry = Entry {
+            source: conversation.source.clone(),
+            session_id: conversation.session_id.clone(),
+            project_path: conversation.project_path.clone(),
+            content: conversation.content.clone(),
+            content_type: conversation.content_type,
+            title: conversation.title.clone(),
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&serde_json::to_vec(&entry)?)?;
+        let data = encoder.finish()?;
+
+        db.put_cached_content(key, &data, now())?;
+        let evicted = db.evict_cached_content(self.max_bytes)?;
+        if evicted > 0 {
+            tracing::debug!("Evicted {} conversations from the content cache", evicted);
+        }
+        Ok(())
+    }
+}
+
+/// Key for one of several conversations in a file, in the content cache
+/// and failed upload records
+pub fn conversation_key(file_path: &str, session_id: &str) -> String {
+    format!("{}#{}", file_path, session_id)
+}
+
+/// The cached copy of a conversation, if there is one: the file at `path`,
+/// or with `session_id`, that one of several conversations in it
+pub fn load(db: &Database, path: &Path, session_id: Option<&str>) -> Result<Option<Conversation>, CacheError> {
+    let key = match session_id {
+        Some(session_id) => conversation_key(&path.to_string_lossy(), session_id),
+        None => path.to_string_lossy().to_string(),
+    };
+    let Some(data) = db.get_cached_content(&key)? else {
+        return Ok(None);
+    };
+    let mut json = Vec::new();
+    GzDecoder::new(data.as_slice()).read_to_end(&mut json)?;
+    let entry: Entry = serde_json::from_slice(&json)?;
+
+    // Reads only keep an entry from eviction; a read-only database can still serve it
+    if let Err(e) = db.touch_cached_content(&key, now()) {
+        tracing::debug!("Could not record content cache read for {}: {}", key, e);
+    }
+
+    Ok(Some(Conversation {
+        source_path: path.to_path_buf(),
+        source: entry.source,
+        session_id: entry.session_id,
+        project_path: entry.project_path,
+        content: entry.content,
+        content_type: entry.content_type,
+        title: entry.title,
+    }))
+}
+
+/// A conversation's content from the cache, else read from its file
+///
+/// One of several conversations in a file is parsed out of it.
+pub fn read_content(db: &Database, found: &SessionMatch) -> std::io::Result<String> {
+    let path = Path::new(&found.state.file_path);
+    let session_id = found.session_in_file();
+    match load(db, path, session_id) {
+        Ok(Some(conversation)) => return Ok(conversation.content),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Could not read cached content for {:?}: {}", path, e),
+    }
+    let Some(session_id) = session_id else {
+        return std::fs::read_to_string(path);
+    };
+
+    let registry = ParserRegistry::new();
+    let parser = found
+        .state
+        .source
+        .as_deref()
+        .and_then(|name| registry.get(name))
+        .or_else(|| registry.detect(path))
+        .ok_or_else(|| std::io::Error::other(format!("No parser for {:?}", path)))?;
+    parser
+        .parse_all(path)
+        .map_err(std::io::Error::other)?
+        .into_iter()
+        .find(|conversation| conversation.session_id.as_deref() == Some(session_id))
+        .map(|conversation| conversation.content)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No conversation {} in {:?}", session_id, path),
+            )
+        })
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SyncState, SyncStatus};
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let path = dir.path().join("session.jsonl");
+        let cache = ContentCache::from_config(&CacheConfig {
+            enabled: true,
+            max_size_mb: 1,
+        })
+        .unwrap();
+
+        let conversation = Conversation {
+            source_path: path.clone(),
+            source: "claude-code".to_string(),
+            session_id: Some("s1".to_string()),
+            project_path: Some(PathBuf::from("/work/app")),
+            content: "{\"type\":\"user\"}\n".repeat(1000),
+            content_type: ContentType::Conversation,
+            title: Some("Fix the build".to_string()),
+        };
+        cache.store(&db, &path.to_string_lossy(), &conversation).unwrap();
+
+        // The source file was never written, as if the tool had pruned it
+        let cached = load(&db, &path, None).unwrap().unwrap();
+        assert_eq!(cached.content, conversation.content);
+        assert_eq!(cached.session_id.as_deref(), Some("s1"));
+        assert_eq!(cached.title.as_deref(), Some("Fix the build"));
+        db.upsert_sync_state(&SyncState {
+            file_path: path.to_string_lossy().to_string(),
+            content_hash: "abc".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1,
+            workflow_id: None,
+            status: SyncStatus::Complete,
+            session_id: Some("s1".to_string()),
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        })
+        .unwrap();
+        let found = db.find_session("s1").unwrap().unwrap();
+        assert_eq!(read_content(&db, &found).unwrap(), conversation.content);
+        assert!(load(&db, &dir.path().join("other.jsonl"), None).unwrap().is_none());
+
+        // One of several conversations in a file is kept under its own key
+        let second = Conversation {
+            session_id: Some("s2".to_string()),
+            content: "second".to_string(),
+            ..conversation.clone()
+        };
+        cache.store(&db, &conversation_key(&path.to_string_lossy(), "s2"), &second).unwrap();
+        assert_eq!(load(&db, &path, Some("s2")).unwrap().unwrap().content, "second");
+        assert!(load(&db, &path, Some("s3")).unwrap().is_none());
+
+        assert!(ContentCache::from_config(&CacheConfig::default()).is_none());
+    }
+}
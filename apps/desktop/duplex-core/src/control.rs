@@ -0,0 +1,301 @@
+//! Control socket for commanding the running app
+//!
+//! The desktop app listens on a loopback TCP port and writes the port number
+//! to `control.port` in the config directory. CLI commands connect to it,
+//! pair with the app (see [`crate::pairing`]) and exchange one sealed JSON
+//! request and one sealed JSON response per line.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+
+use crate::files;
+use crate::jobs;
+use crate::logging;
+use crate::metrics;
+use crate::pairing::{AppHandshake, ClientHandshake, PairingError, PairingSecret, Session};
+use crate::sync;
+
+/// Timeout for CLI requests to the running app
+const CLIENT_TIMEOUT_SECS: u64 = 10;
+
+/// Signalled when a client asks the app to quit
+static QUIT: OnceLock<Notify> = OnceLock::new();
+
+/// Set when a client has marked conversations for re-sync
+static RESYNC: AtomicBool = AtomicBool::new(false);
+
+/// Set when a client has asked for a history backfill, until it is queued
+static BACKFILL: AtomicBool = AtomicBool::new(false);
+
+#[derive(Error, Debug)]
+pub enum ControlError {
+    #[error("Duplex is not running (start the desktop app first)")]
+    NotRunning,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] crate::config::ConfigError),
+    #[error("{0}")]
+    Pairing(#[from] PairingError),
+    #[error("{0}")]
+    Remote(String),
+}
+
+/// Request sent to the control socket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Get the active log filter
+    GetLogLevel,
+    /// Replace the active log filter
+    SetLogLevel { level: String },
+    /// Get runtime status: error counts by category and periodic job stats
+    Status,
+    /// Quit the app, draining in-flight uploads first
+    Quit,
+    /// Pick up conversations just marked pending by `duplex resync`
+    Resync,
+    /// Queue every existing conversation, as on first run
+    Backfill,
+}
+
+/// Response from the control socket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ControlResponse {
+    fn success(result: serde_json::Value) -> Self {
+        Self {
+            ok: true,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(error: impl ToString) -> Self {
+        Self {
+            ok: false,
+            result: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// Convert into the result payload, turning remote failures into errors
+    pub fn into_result(self) -> Result<serde_json::Value, ControlError> {
+        if self.ok {
+            Ok(self.result.unwrap_or(serde_json::Value::Null))
+        } else {
+            Err(ControlError::Remote(
+                self.error.unwrap_or_else(|| "Unknown error".to_string()),
+            ))
+        }
+    }
+}
+
+/// Handle a single control request
+fn handle_request(request: ControlRequest) -> ControlResponse {
+    match request {
+        ControlRequest::GetLogLevel => match logging::current_filter() {
+            Ok(filter) => ControlResponse::success(serde_json::json!({ "filter": filter })),
+            Err(e) => ControlResponse::failure(e),
+        },
+        ControlRequest::SetLogLevel { level } => match logging::set_filter(&level) {
+            Ok(filter) => ControlResponse::success(serde_json::json!({ "filter": filter })),
+            Err(e) => ControlResponse::failure(e),
+        },
+        ControlRequest::Status => {
+            ControlResponse::success(serde_json::json!({
+                "errors": metrics::error_counts(),
+                "changes": metrics::change_counts(),
+                "jobs": jobs::statuses(),
+                "backfill": sync::backfill_progress(),
+                "backfillRequested": BACKFILL.load(Ordering::SeqCst),
+            }))
+        }
+        ControlRequest::Quit => {
+            tracing::info!("Quit requested over the control socket");
+            QUIT.get_or_init(Notify::new).notify_one();
+            ControlResponse::success(serde_json::Value::Null)
+        }
+        ControlRequest::Resync => {
+            RESYNC.store(true, Ordering::SeqCst);
+            ControlResponse::success(serde_json::Value::Null)
+        }
+        ControlRequest::Backfill => {
+            BACKFILL.store(true, Ordering::SeqCst);
+            ControlResponse::success(serde_json::Value::Null)
+        }
+    }
+}
+
+/// Whether a client has sent [`ControlRequest::Backfill`] that isn't queued yet
+pub fn backfill_requested() -> bool {
+    BACKFILL.load(Ordering::SeqCst)
+}
+
+/// Mark a requested backfill as queued, once its progress is published
+pub fn clear_backfill_request() {
+    BACKFILL.store(false, Ordering::SeqCst);
+}
+
+/// Whether a client has sent [`ControlRequest::Resync`] since the last call
+pub fn take_resync_request() -> bool {
+    RESYNC.swap(false, Ordering::SeqCst)
+}
+
+/// Wait until a client sends [`ControlRequest::Quit`]
+pub async fn quit_requested() {
+    QUIT.get_or_init(Notify::new).notified().await
+}
+
+/// Run the control socket server until the process exits
+pub async fn serve() -> Result<(), ControlError> {
+    let secret = PairingSecret::load_or_create()?;
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let port = listener.local_addr()?.port();
+
+    let port_path = crate::config::get_control_port_path()?;
+    files::write_private(&port_path, port.to_string())?;
+
+    tracing::info!("Control socket listening on 127.0.0.1:{}", port);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let secret = secret.clone();
+
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = tokio::io::BufReader::new(reader).lines();
+
+            let (handshake, hello) = AppHandshake::start();
+            if writer.write_all(format!("{}\n", hello).as_bytes()).await.is_err() {
+                return;
+            }
+            let Ok(Some(line)) = lines.next_line().await else {
+                return;
+            };
+            let (session, reply) = handshake.accept(&secret, &line);
+            let _ = writer.write_all(format!("{}\n", reply).as_bytes()).await;
+            let mut session = match session {
+                Ok(session) => session,
+                Err(e) => {
+                    tracing::warn!("Control client failed to pair: {}", e);
+                    return;
+                }
+            };
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let line = match session.open(&line) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        tracing::warn!("Dropping control client: {}", e);
+                        break;
+                    }
+                };
+                let response = match serde_json::from_str::<ControlRequest>(&line) {
+                    Ok(request) => {
+                        tracing::debug!("Control request: {:?}", request);
+                        handle_request(request)
+                    }
+                    Err(e) => ControlResponse::failure(format!("Invalid request: {}", e)),
+                };
+
+                let mut payload = match serde_json::to_string(&response) {
+                    Ok(p) => session.seal(&p),
+                    Err(e) => {
+                        tracing::error!("Failed to serialize control response: {}", e);
+                        break;
+                    }
+                };
+                payload.push('\n');
+
+                if let Err(e) = writer.write_all(payload.as_bytes()).await {
+                    tracing::debug!("Control client disconnected: {}", e);
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Send a request to the running app and wait for its response
+pub fn send(request: &ControlRequest) -> Result<ControlResponse, ControlError> {
+    let port_path = crate::config::get_control_port_path()?;
+    let port: u16 = std::fs::read_to_string(&port_path)
+        .ok()
+        .and_then(|p| p.trim().parse().ok())
+        .ok_or(ControlError::NotRunning)?;
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(CLIENT_TIMEOUT_SECS))
+        .map_err(|_| ControlError::NotRunning)?;
+    stream.set_read_timeout(Some(Duration::from_secs(CLIENT_TIMEOUT_SECS)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut session = pair(&mut stream, &mut reader)?;
+
+    let mut payload = session.seal(&serde_json::to_string(request)?);
+    payload.push('\n');
+    stream.write_all(payload.as_bytes())?;
+
+    let line = read_line(&mut reader)?;
+    Ok(serde_json::from_str(&session.open(&line)?)?)
+}
+
+/// Pair with the app on a new connection
+fn pair(stream: &mut TcpStream, reader: &mut BufReader<TcpStream>) -> Result<Session, ControlError> {
+    let secret = PairingSecret::load()?;
+    let (handshake, response) = ClientHandshake::respond(&secret, &read_line(reader)?)?;
+    stream.write_all(format!("{}\n", response).as_bytes())?;
+    Ok(handshake.finish(&read_line(reader)?)?)
+}
+
+/// Read a line, failing if the app closed the connection instead
+fn read_line(reader: &mut BufReader<TcpStream>) -> Result<String, ControlError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_serialization() {
+        let request = ControlRequest::SetLogLevel {
+            level: "debug".to_string(),
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"command":"set_log_level","level":"debug"}"#);
+
+        let parsed: ControlRequest = serde_json::from_str(r#"{"command":"get_log_level"}"#).unwrap();
+        assert!(matches!(parsed, ControlRequest::GetLogLevel));
+    }
+
+    #[test]
+    fn test_response_into_result() {
+        let ok = ControlResponse::success(serde_json::json!({ "filter": "duplex=info" }));
+        assert_eq!(ok.into_result().unwrap()["filter"], "duplex=info");
+
+        let err = ControlResponse::failure("boom");
+        assert!(matches!(err.into_result(), Err(ControlError::Remote(msg)) if msg == "boom"));
+    }
+}
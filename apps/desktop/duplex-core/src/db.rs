@@ -0,0 +1,2483 @@
+use rusqlite::{Connection, ErrorCode, OptionalExtension, Result as SqliteResult};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+use crate::errors::ErrorCategory;
+use crate::files;
+use crate::git::GitContext;
+
+#[derive(Error, Debug)]
+pub enum DatabaseError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] crate::config::ConfigError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl DatabaseError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            DatabaseError::Sqlite(_) | DatabaseError::Io(_) => ErrorCategory::Io,
+            DatabaseError::Config(e) => e.category(),
+        }
+    }
+}
+
+/// Set while sync state cannot be written to disk
+static STORAGE_FAILING: AtomicBool = AtomicBool::new(false);
+
+/// Whether sync state writes are failing and being held in memory
+pub fn storage_failing() -> bool {
+    STORAGE_FAILING.load(Ordering::SeqCst)
+}
+
+pub(crate) fn set_storage_failing(failing: bool) {
+    if STORAGE_FAILING.swap(failing, Ordering::SeqCst) != failing {
+        if failing {
+            tracing::error!("Cannot save sync state, holding changes in memory until storage recovers");
+        } else {
+            tracing::info!("Sync state saved, storage recovered");
+        }
+    }
+}
+
+/// Whether an error comes from the storage under the database (a full disk,
+/// lost permissions, an I/O fault) rather than from the query itself
+pub fn is_storage_failure(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(
+            ErrorCode::DiskFull
+                | ErrorCode::SystemIoFailure
+                | ErrorCode::ReadOnly
+                | ErrorCode::CannotOpen
+                | ErrorCode::PermissionDenied
+        )
+    )
+}
+
+/// Schema migrations, applied in order. The database's `user_version`
+/// records how many have already run.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS sync_state (
+        file_path TEXT PRIMARY KEY,
+        content_hash TEXT NOT NULL,
+        last_synced_at INTEGER,
+        last_modified_at INTEGER NOT NULL,
+        workflow_id TEXT,
+        status TEXT NOT NULL DEFAULT 'pending'
+    );
+    CREATE INDEX IF NOT EXISTS idx_sync_state_status ON sync_state(status);",
+    // 2: conversation metadata
+    "ALTER TABLE sync_state ADD COLUMN session_id TEXT;
+    ALTER TABLE sync_state ADD COLUMN project_path TEXT;
+    ALTER TABLE sync_state ADD COLUMN source TEXT;
+    CREATE INDEX IF NOT EXISTS idx_sync_state_session ON sync_state(session_id);",
+    // 3: git context
+    "ALTER TABLE sync_state ADD COLUMN git_remote TEXT;
+    ALTER TABLE sync_state ADD COLUMN git_branch TEXT;
+    ALTER TABLE sync_state ADD COLUMN git_commit TEXT;",
+    // 4: conversation tags
+    "CREATE TABLE IF NOT EXISTS tags (
+        file_path TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (file_path, tag)
+    );
+    CREATE INDEX IF NOT EXISTS idx_tags_tag ON tags(tag);",
+    // 5: conversations excluded from sync
+    "CREATE TABLE IF NOT EXISTS do_not_sync (
+        file_path TEXT PRIMARY KEY,
+        created_at INTEGER NOT NULL
+    );",
+    // 6: conversation time windows
+    "ALTER TABLE sync_state ADD COLUMN started_at INTEGER;
+    ALTER TABLE sync_state ADD COLUMN ended_at INTEGER;
+    CREATE INDEX IF NOT EXISTS idx_sync_state_project ON sync_state(project_path);",
+    // 7: auto-provisioned workspaces
+    "CREATE TABLE IF NOT EXISTS workspaces (
+        project_path TEXT PRIMARY KEY,
+        workspace_id TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );",
+    // 8: conversation titles
+    "ALTER TABLE sync_state ADD COLUMN title TEXT;",
+    // 9: failed upload attempts
+    "CREATE TABLE IF NOT EXISTS sync_attempts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        file_path TEXT NOT NULL,
+        attempted_at INTEGER NOT NULL,
+        status_code INTEGER,
+        error TEXT NOT NULL,
+        validation_errors TEXT NOT NULL,
+        response_body TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_sync_attempts_file ON sync_attempts(file_path);",
+    // 10: cached conversation content
+    "CREATE TABLE IF NOT EXISTS content_cache (
+        file_path TEXT PRIMARY KEY,
+        data BLOB NOT NULL,
+        size INTEGER NOT NULL,
+        accessed_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_content_cache_accessed ON content_cache(accessed_at);",
+    // 11: conversations of files holding several
+    "CREATE TABLE IF NOT EXISTS file_conversations (
+        file_path TEXT NOT NULL,
+        session_id TEXT NOT NULL,
+        content_hash TEXT NOT NULL,
+        workflow_id TEXT,
+        status TEXT NOT NULL,
+        PRIMARY KEY (file_path, session_id)
+    );",
+    // 12: how much of an append-only file the server already has
+    "ALTER TABLE sync_state ADD COLUMN synced_offset INTEGER;
+    ALTER TABLE sync_state ADD COLUMN synced_lines INTEGER;
+    ALTER TABLE sync_state ADD COLUMN synced_hash TEXT;
+    ALTER TABLE sync_state ADD COLUMN synced_workflow_id TEXT;",
+    // 13: automatic retries of failed uploads
+    "ALTER TABLE sync_state ADD COLUMN retry_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE sync_state ADD COLUMN next_retry_at INTEGER;
+    CREATE INDEX IF NOT EXISTS idx_sync_state_retry ON sync_state(next_retry_at);",
+    // 14: app-wide settings, and server state put aside when switching backends
+    "CREATE TABLE IF NOT EXISTS app_state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS backend_sync_state (
+        backend TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        content_hash TEXT NOT NULL,
+        last_synced_at INTEGER,
+        workflow_id TEXT,
+        status TEXT NOT NULL,
+        synced_offset INTEGER,
+        synced_lines INTEGER,
+        synced_hash TEXT,
+        synced_workflow_id TEXT,
+        PRIMARY KEY (backend, file_path)
+    );
+    CREATE TABLE IF NOT EXISTS backend_file_conversations (
+        backend TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        session_id TEXT NOT NULL,
+        content_hash TEXT NOT NULL,
+        workflow_id TEXT,
+        status TEXT NOT NULL,
+        PRIMARY KEY (backend, file_path, session_id)
+    );
+    CREATE TABLE IF NOT EXISTS backend_workspaces (
+        backend TEXT NOT NULL,
+        project_path TEXT NOT NULL,
+        workspace_id TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (backend, project_path)
+    );",
+    // 15: when an upload started, to recover ones a crash left behind
+    "ALTER TABLE sync_state ADD COLUMN syncing_since INTEGER;",
+    // 16: sessions another machine had uploaded a newer version of
+    "CREATE TABLE IF NOT EXISTS sync_conflicts (
+        file_path TEXT PRIMARY KEY,
+        session_id TEXT,
+        remote_workflow_id TEXT NOT NULL,
+        remote_machine_id TEXT,
+        remote_updated_at INTEGER,
+        workflow_id TEXT,
+        detected_at INTEGER NOT NULL,
+        dismissed INTEGER NOT NULL DEFAULT 0
+    );",
+    // 17: what the server produced for uploaded conversations
+    "CREATE TABLE IF NOT EXISTS remote_results (
+        workflow_id TEXT PRIMARY KEY,
+        status TEXT NOT NULL,
+        finished INTEGER NOT NULL,
+        summary TEXT,
+        tags TEXT NOT NULL,
+        output TEXT,
+        error TEXT,
+        fetched_at INTEGER NOT NULL
+    );",
+    // 18: hash and size of each version of a file uploaded
+    "CREATE TABLE IF NOT EXISTS sync_versions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        file_path TEXT NOT NULL,
+        content_hash TEXT NOT NULL,
+        bytes INTEGER NOT NULL,
+        lines INTEGER NOT NULL,
+        workflow_id TEXT NOT NULL,
+        synced_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_sync_versions_file ON sync_versions(file_path, id);",
+    // 19: how the last upload of a file over the payload limit was handled
+    "ALTER TABLE sync_state ADD COLUMN oversize_strategy TEXT;
+    ALTER TABLE sync_state ADD COLUMN oversize_bytes INTEGER;",
+    // 20: project aliases suggested from git repositories
+    "CREATE TABLE IF NOT EXISTS project_aliases (
+        project_path TEXT PRIMARY KEY,
+        alias TEXT NOT NULL
+    );",
+    // 21: server titles, and title and tag edits waiting to be pushed
+    "ALTER TABLE remote_results ADD COLUMN title TEXT;
+    CREATE TABLE IF NOT EXISTS annotation_edits (
+        workflow_id TEXT PRIMARY KEY,
+        title TEXT,
+        tags TEXT,
+        edited_at INTEGER NOT NULL
+    );",
+];
+
+/// `app_state` key of the API base URL the sync state belongs to
+const BACKEND_KEY: &str = "api_base_url";
+
+/// `app_state` key of the number of stuck uploads returned to pending
+const RECOVERED_KEY: &str = "recovered_uploads";
+
+/// Failed attempts kept; older ones are pruned as new ones are recorded
+const MAX_FAILED_ATTEMPTS: i64 = 500;
+
+/// Uploaded versions kept per file; older ones are pruned as new ones are recorded
+const MAX_SYNC_VERSIONS: i64 = 20;
+
+/// Longest response body kept with a failed attempt, in bytes
+const MAX_CAPTURED_BODY: usize = 64 * 1024;
+
+/// Columns selected for a `SyncState`, in the order `row_to_state` reads them
+const SYNC_STATE_COLUMNS: &str = "file_path, content_hash, last_synced_at, last_modified_at, \
+    workflow_id, status, session_id, project_path, source, git_remote, git_branch, git_commit, title";
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub file_path: String,
+    pub content_hash: String,
+    pub last_synced_at: Option<i64>,
+    pub last_modified_at: i64,
+    pub workflow_id: Option<String>,
+    pub status: SyncStatus,
+    /// Session ID reported by the parser
+    pub session_id: Option<String>,
+    /// Project path reported by the parser
+    pub project_path: Option<String>,
+    /// Parser that produced this conversation (e.g., "claude-code")
+    pub source: Option<String>,
+    /// Git context of the project at the last sync
+    pub git: Option<GitContext>,
+    /// Human-readable title reported by the parser
+    pub title: Option<String>,
+}
+
+/// Columns selected for a `SyncConflict`, in the order `row_to_conflict` reads them
+const CONFLICT_COLUMNS: &str = "file_path, session_id, remote_workflow_id, remote_machine_id, \
+    remote_updated_at, workflow_id, detected_at";
+
+fn row_to_conflict(row: &rusqlite::Row) -> SqliteResult<SyncConflict> {
+    Ok(SyncConflict {
+        file_path: row.get(0)?,
+        session_id: row.get(1)?,
+        remote_workflow_id: row.get(2)?,
+        remote_machine_id: row.get(3)?,
+        remote_updated_at: row.get(4)?,
+        workflow_id: row.get(5)?,
+        detected_at: row.get(6)?,
+    })
+}
+
+fn row_to_state(row: &rusqlite::Row) -> SqliteResult<SyncState> {
+    Ok(SyncState {
+        file_path: row.get(0)?,
+        content_hash: row.get(1)?,
+        last_synced_at: row.get(2)?,
+        last_modified_at: row.get(3)?,
+        workflow_id: row.get(4)?,
+        status: SyncStatus::from_str(&row.get::<_, String>(5)?),
+        session_id: row.get(6)?,
+        project_path: row.get(7)?,
+        source: row.get(8)?,
+        git: {
+            let git = GitContext {
+                remote_url: row.get(9)?,
+                branch: row.get(10)?,
+                commit: row.get(11)?,
+            };
+            (!git.is_empty()).then_some(git)
+        },
+        title: row.get(12)?,
+    })
+}
+
+/// Sync state of one conversation in a file holding several
+///
+/// The file itself keeps its [`SyncState`]; each conversation in it is
+/// tracked here by session ID, so one changed session doesn't re-upload the
+/// rest.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileConversation {
+    pub file_path: String,
+    pub session_id: String,
+    pub content_hash: String,
+    pub workflow_id: Option<String>,
+    pub status: SyncStatus,
+}
+
+/// A conversation found by session ID; see [`Database::find_session`]
+#[derive(Debug, Clone)]
+pub struct SessionMatch {
+    /// Sync state of the file the conversation is in
+    pub state: SyncState,
+    /// The conversation's own state, when it is one of several in the file
+    pub conversation: Option<FileConversation>,
+}
+
+impl SessionMatch {
+    /// Session ID of one of several conversations in the file, which keys
+    /// its cached copy and picks it out of the parser's `parse_all`
+    pub fn session_in_file(&self) -> Option<&str> {
+        self.conversation.as_ref().map(|c| c.session_id.as_str())
+    }
+
+    /// The file's sync state with the conversation's own session, upload
+    /// and status in place of the file's
+    pub fn into_state(self) -> SyncState {
+        let mut state = self.state;
+        if let Some(conversation) = self.conversation {
+            state.session_id = Some(conversation.session_id);
+            state.content_hash = conversation.content_hash;
+            state.workflow_id = conversation.workflow_id;
+            state.status = conversation.status;
+        }
+        state
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStatus {
+    Pending,
+    Syncing,
+    Complete,
+    Error,
+    /// Not synced because its parser was disabled
+    Skipped,
+    /// File removed by the source tool
+    Deleted,
+    /// Binary or undecodable content its parser can't read
+    Unsupported,
+    /// File removed by retention after it was fully synced
+    Retired,
+}
+
+impl SyncStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncStatus::Pending => "pending",
+            SyncStatus::Syncing => "syncing",
+            SyncStatus::Complete => "complete",
+            SyncStatus::Error => "error",
+            SyncStatus::Skipped => "skipped",
+            SyncStatus::Deleted => "deleted",
+            SyncStatus::Unsupported => "unsupported",
+            SyncStatus::Retired => "retired",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "pending" => SyncStatus::Pending,
+            "syncing" => SyncStatus::Syncing,
+            "complete" => SyncStatus::Complete,
+            "error" => SyncStatus::Error,
+            "skipped" => SyncStatus::Skipped,
+            "deleted" => SyncStatus::Deleted,
+            "unsupported" => SyncStatus::Unsupported,
+            "retired" => SyncStatus::Retired,
+            _ => SyncStatus::Pending,
+        }
+    }
+}
+
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Open or create the database at the default location
+    pub fn open() -> Result<Self, DatabaseError> {
+        let db_path = crate::config::get_database_path()?;
+
+        // Ensure parent directory exists
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let db = Self::open_at(&db_path)?;
+        if let Err(e) = files::restrict_permissions(&db_path) {
+            tracing::warn!("Failed to restrict database permissions: {}", e);
+        }
+        Ok(db)
+    }
+
+    /// Open or create the database at a specific path
+    pub fn open_at(path: &Path) -> Result<Self, DatabaseError> {
+        let conn = Connection::open(path)?;
+
+        let db = Self { conn };
+        db.initialize()?;
+
+        tracing::debug!("Database opened at {:?}", path);
+        Ok(db)
+    }
+
+    /// Initialize the database schema, running any pending migrations
+    fn initialize(&self) -> SqliteResult<()> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            self.conn.execute_batch(&format!(
+                "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                migration,
+                i + 1
+            ))?;
+            tracing::debug!("Applied database migration {}", i + 1);
+        }
+
+        Ok(())
+    }
+
+    /// Refuse writes, as a read-only or full disk would
+    #[cfg(test)]
+    pub(crate) fn set_read_only(&self, read_only: bool) -> SqliteResult<()> {
+        self.conn.pragma_update(None, "query_only", read_only)
+    }
+
+    /// Find a conversation by session ID or file path
+    pub fn find_conversation(&self, id_or_path: &str) -> SqliteResult<Option<SyncState>> {
+        match self.get_by_session_id(id_or_path)? {
+            Some(state) => Ok(Some(state)),
+            None => self.get_sync_state(id_or_path),
+        }
+    }
+
+    /// Write a consistent copy of the database to `dest`
+    pub fn backup_to(&self, dest: &Path) -> SqliteResult<()> {
+        self.conn.execute("VACUUM INTO ?1", [dest.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Get sync state for a file
+    pub fn get_sync_state(&self, file_path: &str) -> SqliteResult<Option<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state WHERE file_path = ?1",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query([file_path])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(row_to_state(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the most recently modified sync state for a session
+    pub fn get_by_session_id(&self, session_id: &str) -> SqliteResult<Option<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state WHERE session_id = ?1
+             ORDER BY last_modified_at DESC LIMIT 1",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query([session_id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(row_to_state(row)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Find a conversation by session ID, including one of several in a
+    /// file, which only `file_conversations` knows by its own ID
+    pub fn find_session(&self, session_id: &str) -> SqliteResult<Option<SessionMatch>> {
+        let file_path: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT file_path FROM file_conversations WHERE session_id = ?1 ORDER BY file_path LIMIT 1",
+                [session_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(file_path) = file_path {
+            if let Some(state) = self.get_sync_state(&file_path)? {
+                let conversation = self.get_file_conversation(&file_path, session_id)?;
+                return Ok(Some(SessionMatch { state, conversation }));
+            }
+        }
+
+        Ok(self
+            .get_by_session_id(session_id)?
+            .map(|state| SessionMatch { state, conversation: None }))
+    }
+
+    /// Upsert sync state for a file
+    ///
+    /// Conversation metadata (session, project, source) is only overwritten
+    /// when the new state provides it. Retries already made are kept while
+    /// the content is unchanged; any scheduled one is dropped.
+    pub fn upsert_sync_state(&self, state: &SyncState) -> SqliteResult<()> {
+        let syncing_since = (state.status == SyncStatus::Syncing).then(unix_now);
+        self.conn.execute(
+            "INSERT INTO sync_state (file_path, content_hash, last_synced_at, last_modified_at, workflow_id, status,
+                                     session_id, project_path, source, syncing_since)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT(file_path) DO UPDATE SET
+                content_hash = excluded.content_hash,
+                last_synced_at = excluded.last_synced_at,
+                last_modified_at = excluded.last_modified_at,
+                workflow_id = excluded.workflow_id,
+                status = excluded.status,
+                session_id = COALESCE(excluded.session_id, sync_state.session_id),
+                project_path = COALESCE(excluded.project_path, sync_state.project_path),
+                source = COALESCE(excluded.source, sync_state.source),
+                retry_count = CASE WHEN excluded.content_hash = sync_state.content_hash
+                                   THEN sync_state.retry_count ELSE 0 END,
+                next_retry_at = NULL,
+                syncing_since = excluded.syncing_since",
+            (
+                &state.file_path,
+                &state.content_hash,
+                &state.last_synced_at,
+                &state.last_modified_at,
+                &state.workflow_id,
+                state.status.as_str(),
+                &state.session_id,
+                &state.project_path,
+                &state.source,
+                syncing_since,
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    /// Record the conversation metadata reported by a parser
+    pub fn update_metadata(
+        &self,
+        file_path: &str,
+        session_id: Option<&str>,
+        project_path: Option<&str>,
+        source: &str,
+        title: Option<&str>,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET session_id = ?1, project_path = ?2, source = ?3, title = ?4 WHERE file_path = ?5",
+            (session_id, project_path, source, title, file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// Record the git context collected for a conversation's project
+    pub fn update_git_context(&self, file_path: &str, git: &GitContext) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET git_remote = ?1, git_branch = ?2, git_commit = ?3 WHERE file_path = ?4",
+            (&git.remote_url, &git.branch, &git.commit, file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// Tag a conversation; tagging twice is a no-op
+    pub fn add_tag(&self, file_path: &str, tag: &str) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.conn.execute(
+            "INSERT OR IGNORE INTO tags (file_path, tag, created_at) VALUES (?1, ?2, ?3)",
+            (file_path, tag, now),
+        )?;
+
+        Ok(())
+    }
+
+    /// Remove a tag from a conversation
+    pub fn remove_tag(&self, file_path: &str, tag: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "DELETE FROM tags WHERE file_path = ?1 AND tag = ?2",
+            (file_path, tag),
+        )?;
+
+        Ok(())
+    }
+
+    /// Get the tags on a conversation, sorted alphabetically
+    pub fn get_tags(&self, file_path: &str) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag FROM tags WHERE file_path = ?1 ORDER BY tag")?;
+        let tags = stmt
+            .query_map([file_path], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<String>>>()?;
+        Ok(tags)
+    }
+
+    /// List every tag in use, most used first
+    pub fn list_tags(&self) -> SqliteResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tag FROM tags GROUP BY tag ORDER BY COUNT(*) DESC, tag",
+        )?;
+        let tags = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<String>>>()?;
+        Ok(tags)
+    }
+
+    /// Exclude a conversation from sync, or include it again
+    pub fn set_do_not_sync(&self, file_path: &str, do_not_sync: bool) -> SqliteResult<()> {
+        if do_not_sync {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+            self.conn.execute(
+                "INSERT OR IGNORE INTO do_not_sync (file_path, created_at) VALUES (?1, ?2)",
+                (file_path, now),
+            )?;
+        } else {
+            self.conn
+                .execute("DELETE FROM do_not_sync WHERE file_path = ?1", [file_path])?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a conversation has been excluded from sync
+    pub fn is_do_not_sync(&self, file_path: &str) -> SqliteResult<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM do_not_sync WHERE file_path = ?1",
+            [file_path],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Get the workspace provisioned for a project
+    pub fn get_workspace(&self, project_path: &str) -> SqliteResult<Option<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT workspace_id FROM workspaces WHERE project_path = ?1")?;
+        let mut rows = stmt.query([project_path])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Record a failed upload of `file_path`, with what the server sent back
+    pub fn record_failed_attempt(
+        &self,
+        file_path: &str,
+        status_code: Option<u16>,
+        error: &str,
+        validation_errors: &[String],
+        response_body: Option<&str>,
+    ) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let validation_errors = serde_json::to_string(validation_errors).unwrap_or_default();
+        let response_body = response_body.map(|body| {
+            let mut end = body.len().min(MAX_CAPTURED_BODY);
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            &body[..end]
+        });
+
+        self.conn.execute(
+            "INSERT INTO sync_attempts (file_path, attempted_at, status_code, error, validation_errors, response_body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (file_path, now, status_code, error, validation_errors, response_body),
+        )?;
+        self.conn.execute(
+            "DELETE FROM sync_attempts WHERE id <= (SELECT MAX(id) FROM sync_attempts) - ?1",
+            [MAX_FAILED_ATTEMPTS],
+        )?;
+
+        Ok(())
+    }
+
+    /// Latest failed attempt for each file that has not synced since, newest first
+    pub fn list_failed_attempts(&self, limit: usize) -> SqliteResult<Vec<FailedAttempt>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT a.file_path, a.attempted_at, a.status_code, a.error, a.validation_errors, a.response_body
+             FROM sync_attempts a JOIN sync_state s ON s.file_path = a.file_path
+             WHERE s.status = 'error'
+               AND a.id = (SELECT MAX(id) FROM sync_attempts WHERE file_path = a.file_path)
+             ORDER BY a.id DESC LIMIT ?1",
+        )?;
+
+        let rows = stmt.query_map([limit as i64], |row| {
+            Ok(FailedAttempt {
+                file_path: row.get(0)?,
+                attempted_at: row.get(1)?,
+                status_code: row.get(2)?,
+                error: row.get(3)?,
+                validation_errors: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+                response_body: row.get(5)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    /// Record that the server holds another machine's newer version of the
+    /// conversation at `file_path`
+    ///
+    /// A conflict with a different version than the one recorded is listed
+    /// again even if it was dismissed.
+    pub fn record_conflict(
+        &self,
+        file_path: &str,
+        session_id: Option<&str>,
+        remote_workflow_id: &str,
+        remote_machine_id: Option<&str>,
+        remote_updated_at: Option<i64>,
+    ) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.conn.execute(
+            "INSERT INTO sync_conflicts (file_path, session_id, remote_workflow_id, remote_machine_id,
+                 remote_updated_at, detected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(file_path) DO UPDATE SET
+                 session_id = excluded.session_id,
+                 dismissed = sync_conflicts.dismissed
+                     AND sync_conflicts.remote_workflow_id = excluded.remote_workflow_id,
+                 remote_workflow_id = excluded.remote_workflow_id,
+                 remote_machine_id = excluded.remote_machine_id,
+                 remote_updated_at = excluded.remote_updated_at,
+                 workflow_id = NULL,
+                 detected_at = excluded.detected_at",
+            (file_path, session_id, remote_workflow_id, remote_machine_id, remote_updated_at, now),
+        )?;
+
+        Ok(())
+    }
+
+    /// Note the workflow this machine's version of a conflicting
+    /// conversation was uploaded as
+    pub fn set_conflict_workflow(&self, file_path: &str, workflow_id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_conflicts SET workflow_id = ?1 WHERE file_path = ?2",
+            (workflow_id, file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// The conflict recorded for the conversation at `file_path`, dismissed or not
+    pub fn get_conflict(&self, file_path: &str) -> SqliteResult<Option<SyncConflict>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM sync_conflicts WHERE file_path = ?1", CONFLICT_COLUMNS),
+                [file_path],
+                row_to_conflict,
+            )
+            .optional()
+    }
+
+    /// Conflicts not dismissed yet, newest first
+    pub fn list_conflicts(&self, limit: usize) -> SqliteResult<Vec<SyncConflict>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_conflicts WHERE dismissed = 0 ORDER BY detected_at DESC, file_path LIMIT ?1",
+            CONFLICT_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([limit as i64], row_to_conflict)?;
+        rows.collect()
+    }
+
+    /// Stop listing the current conflicts, returning how many there were
+    ///
+    /// Later uploads of those conversations still go beside the other
+    /// machine's version.
+    pub fn dismiss_conflicts(&self) -> SqliteResult<usize> {
+        self.conn
+            .execute("UPDATE sync_conflicts SET dismissed = 1 WHERE dismissed = 0", [])
+    }
+
+    /// Save what the server produced for a workflow, replacing what was
+    /// fetched before
+    pub fn store_remote_result(&self, result: &RemoteResult) -> SqliteResult<()> {
+        let tags = serde_json::to_string(&result.tags).unwrap_or_default();
+        let output = result.output.as_ref().map(|output| output.to_string());
+        self.conn.execute(
+            "INSERT OR REPLACE INTO remote_results
+                 (workflow_id, status, finished, summary, tags, output, error, fetched_at, title)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            (
+                &result.workflow_id,
+                &result.status,
+                result.finished,
+                &result.summary,
+                tags,
+                output,
+                &result.error,
+                result.fetched_at,
+                &result.title,
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    /// Pulled results for the conversation at `file_path`, or for each
+    /// conversation in it when it holds several, with edits not pushed yet
+    /// in place of the server's title and tags
+    pub fn remote_results_for(&self, file_path: &str) -> SqliteResult<Vec<RemoteResult>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT r.workflow_id, status, finished, summary, COALESCE(e.tags, r.tags), output, error, fetched_at,
+                 COALESCE(e.title, r.title), e.workflow_id IS NOT NULL
+             FROM remote_results r LEFT JOIN annotation_edits e ON e.workflow_id = r.workflow_id
+             WHERE r.workflow_id IN (
+                 SELECT workflow_id FROM sync_state WHERE file_path = ?1
+                 UNION
+                 SELECT workflow_id FROM file_conversations WHERE file_path = ?1
+             )
+             ORDER BY r.workflow_id",
+        )?;
+
+        let rows = stmt.query_map([file_path], |row| {
+            Ok(RemoteResult {
+                workflow_id: row.get(0)?,
+                status: row.get(1)?,
+                finished: row.get(2)?,
+                summary: row.get(3)?,
+                tags: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+                output: row
+                    .get::<_, Option<String>>(5)?
+                    .and_then(|output| serde_json::from_str(&output).ok()),
+                error: row.get(6)?,
+                fetched_at: row.get(7)?,
+                title: row.get(8)?,
+                edited: row.get(9)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Change a workflow's title or tags locally until the change is pushed
+    ///
+    /// `None` leaves that annotation as it is; a later edit before the push
+    /// is merged into this one.
+    pub fn edit_annotations(
+        &self,
+        workflow_id: &str,
+        title: Option<&str>,
+        tags: Option<&[String]>,
+    ) -> SqliteResult<()> {
+        let tags = tags.map(|tags| serde_json::to_string(tags).unwrap_or_default());
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        self.conn.execute(
+            "INSERT INTO annotation_edits (workflow_id, title, tags, edited_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(workflow_id) DO UPDATE SET
+                 title = COALESCE(excluded.title, title),
+                 tags = COALESCE(excluded.tags, tags),
+                 edited_at = MAX(excluded.edited_at, edited_at + 1)",
+            (workflow_id, title, tags, now),
+        )?;
+        Ok(())
+    }
+
+    /// Title and tag edits not pushed yet, oldest first
+    pub fn pending_annotation_edits(&self) -> SqliteResult<Vec<AnnotationEdit>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT workflow_id, title, tags, edited_at FROM annotation_edits ORDER BY edited_at",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(AnnotationEdit {
+                workflow_id: row.get(0)?,
+                title: row.get(1)?,
+                tags: row
+                    .get::<_, Option<String>>(2)?
+                    .and_then(|tags| serde_json::from_str(&tags).ok()),
+                edited_at: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Record an edit as pushed: the pulled result takes it on and the edit
+    /// is dropped, unless it was edited again meanwhile
+    pub fn annotation_pushed(&self, edit: &AnnotationEdit) -> SqliteResult<()> {
+        let tags = edit.tags.as_ref().map(|tags| serde_json::to_string(tags).unwrap_or_default());
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE remote_results SET title = COALESCE(?2, title), tags = COALESCE(?3, tags) WHERE workflow_id = ?1",
+            (&edit.workflow_id, &edit.title, tags),
+        )?;
+        tx.execute(
+            "DELETE FROM annotation_edits WHERE workflow_id = ?1 AND edited_at = ?2",
+            (&edit.workflow_id, edit.edited_at),
+        )?;
+        tx.commit()
+    }
+
+    /// Uploaded workflows whose results are due a fetch, never-fetched first
+    ///
+    /// Due are those not fetched yet, unfinished ones last fetched before
+    /// `unfinished_before` and finished ones last fetched before
+    /// `finished_before`.
+    pub fn results_to_pull(
+        &self,
+        unfinished_before: i64,
+        finished_before: i64,
+        limit: usize,
+    ) -> SqliteResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT w.workflow_id FROM (
+                 SELECT workflow_id FROM sync_state
+                 WHERE workflow_id IS NOT NULL AND status IN ('complete', 'retired')
+                 UNION
+                 SELECT workflow_id FROM file_conversations
+                 WHERE workflow_id IS NOT NULL AND status = 'complete'
+             ) w LEFT JOIN remote_results r ON r.workflow_id = w.workflow_id
+             WHERE r.workflow_id IS NULL
+                OR r.fetched_at < CASE WHEN r.finished THEN ?2 ELSE ?1 END
+             ORDER BY COALESCE(r.fetched_at, 0), w.workflow_id LIMIT ?3",
+        )?;
+
+        let rows = stmt.query_map((unfinished_before, finished_before, limit as i64), |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Record a version of a file as uploaded, unless it is the same as the
+    /// last one recorded
+    pub fn record_sync_version(&self, version: &SyncVersion) -> SqliteResult<()> {
+        let latest = self.sync_versions(&version.file_path, 1)?;
+        if latest.first().is_some_and(|latest| latest.content_hash == version.content_hash) {
+            return Ok(());
+        }
+
+        self.conn.execute(
+            "INSERT INTO sync_versions (file_path, content_hash, bytes, lines, workflow_id, synced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                &version.file_path,
+                &version.content_hash,
+                version.bytes as i64,
+                version.lines as i64,
+                &version.workflow_id,
+                version.synced_at,
+            ),
+        )?;
+        self.conn.execute(
+            "DELETE FROM sync_versions WHERE file_path = ?1 AND id NOT IN (
+                 SELECT id FROM sync_versions WHERE file_path = ?1 ORDER BY id DESC LIMIT ?2
+             )",
+            (&version.file_path, MAX_SYNC_VERSIONS),
+        )?;
+
+        Ok(())
+    }
+
+    /// Uploaded versions of a file, newest first
+    pub fn sync_versions(&self, file_path: &str, limit: usize) -> SqliteResult<Vec<SyncVersion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT file_path, content_hash, bytes, lines, workflow_id, synced_at FROM sync_versions
+             WHERE file_path = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+
+        let rows = stmt.query_map((file_path, limit as i64), |row| {
+            Ok(SyncVersion {
+                file_path: row.get(0)?,
+                content_hash: row.get(1)?,
+                bytes: row.get::<_, i64>(2)? as u64,
+                lines: row.get::<_, i64>(3)? as u64,
+                workflow_id: row.get(4)?,
+                synced_at: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Remember the alias suggested for a project
+    pub fn set_suggested_alias(&self, project_path: &str, alias: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO project_aliases (project_path, alias) VALUES (?1, ?2)",
+            (project_path, alias),
+        )?;
+        Ok(())
+    }
+
+    /// Aliases suggested for projects, by project path
+    pub fn suggested_aliases(&self) -> SqliteResult<HashMap<String, String>> {
+        let mut stmt = self.conn.prepare("SELECT project_path, alias FROM project_aliases")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Remember the workspace provisioned for a project
+    pub fn set_workspace(&self, project_path: &str, workspace_id: &str) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.conn.execute(
+            "INSERT OR REPLACE INTO workspaces (project_path, workspace_id, created_at) VALUES (?1, ?2, ?3)",
+            (project_path, workspace_id, now),
+        )?;
+
+        Ok(())
+    }
+
+    /// List the distinct workspaces provisioned for projects
+    pub fn list_workspaces(&self) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT workspace_id FROM workspaces ORDER BY workspace_id")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Record the time span covered by a conversation's messages
+    pub fn update_time_window(&self, file_path: &str, started_at: i64, ended_at: i64) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET started_at = ?1, ended_at = ?2 WHERE file_path = ?3",
+            (started_at, ended_at, file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// The part of a file the server already has, if it was uploaded whole
+    pub fn get_synced_prefix(&self, file_path: &str) -> SqliteResult<Option<SyncedPrefix>> {
+        let prefix = self
+            .conn
+            .query_row(
+                "SELECT synced_offset, synced_lines, synced_hash, synced_workflow_id FROM sync_state
+                 WHERE file_path = ?1",
+                [file_path],
+                |row| {
+                    let (offset, lines): (Option<i64>, Option<i64>) = (row.get(0)?, row.get(1)?);
+                    let (hash, workflow_id): (Option<String>, Option<String>) = (row.get(2)?, row.get(3)?);
+                    Ok(offset.zip(lines).zip(hash.zip(workflow_id)).map(|((offset, lines), (hash, workflow_id))| {
+                        SyncedPrefix {
+                            offset: offset as u64,
+                            lines: lines as u64,
+                            hash,
+                            workflow_id,
+                        }
+                    }))
+                },
+            )
+            .optional()?;
+        Ok(prefix.flatten())
+    }
+
+    /// Record the part of a file the server has, or clear it with `None`
+    pub fn set_synced_prefix(&self, file_path: &str, prefix: Option<&SyncedPrefix>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET synced_offset = ?1, synced_lines = ?2, synced_hash = ?3, synced_workflow_id = ?4
+             WHERE file_path = ?5",
+            (
+                prefix.map(|p| p.offset as i64),
+                prefix.map(|p| p.lines as i64),
+                prefix.map(|p| p.hash.as_str()),
+                prefix.map(|p| p.workflow_id.as_str()),
+                file_path,
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    /// Record how the last upload of a file over the payload limit was
+    /// handled, or clear it with `None` once one fits
+    pub fn set_oversize(&self, file_path: &str, oversize: Option<&Oversize>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET oversize_strategy = ?1, oversize_bytes = ?2 WHERE file_path = ?3",
+            (
+                oversize.map(|o| o.strategy.as_str()),
+                oversize.map(|o| o.bytes as i64),
+                file_path,
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    /// How the last upload of a file over the payload limit was handled
+    pub fn get_oversize(&self, file_path: &str) -> SqliteResult<Option<Oversize>> {
+        let oversize = self
+            .conn
+            .query_row(
+                "SELECT oversize_strategy, oversize_bytes FROM sync_state WHERE file_path = ?1",
+                [file_path],
+                |row| {
+                    let (strategy, bytes): (Option<String>, Option<i64>) = (row.get(0)?, row.get(1)?);
+                    Ok(strategy.zip(bytes).map(|(strategy, bytes)| Oversize {
+                        strategy,
+                        bytes: bytes as u64,
+                    }))
+                },
+            )
+            .optional()?;
+        Ok(oversize.flatten())
+    }
+
+    /// Conversations from other tools in the same project whose time window
+    /// overlaps the given one, widened by `slack_secs` on each side
+    pub fn find_related(
+        &self,
+        file_path: &str,
+        source: &str,
+        project_path: &str,
+        window: (i64, i64),
+        slack_secs: i64,
+    ) -> SqliteResult<Vec<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state
+             WHERE project_path = ?1 AND file_path != ?2 AND source != ?3
+               AND started_at <= ?4 AND ended_at >= ?5
+             ORDER BY started_at",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let related = stmt
+            .query_map(
+                (
+                    project_path,
+                    file_path,
+                    source,
+                    window.1 + slack_secs,
+                    window.0 - slack_secs,
+                ),
+                row_to_state,
+            )?
+            .collect::<SqliteResult<Vec<_>>>()?;
+        Ok(related)
+    }
+
+    /// Forget a file's sync state, and any cached copy of it
+    pub fn delete_sync_state(&self, file_path: &str) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM sync_state WHERE file_path = ?1", [file_path])?;
+        self.conn
+            .execute("DELETE FROM content_cache WHERE file_path = ?1", [file_path])?;
+        self.delete_file_conversations(file_path)
+    }
+
+    /// Get the state of one conversation in a file holding several
+    pub fn get_file_conversation(&self, file_path: &str, session_id: &str) -> SqliteResult<Option<FileConversation>> {
+        Ok(self.list_file_conversations(file_path)?.into_iter().find(|c| c.session_id == session_id))
+    }
+
+    /// Get the state of every conversation tracked in a file
+    pub fn list_file_conversations(&self, file_path: &str) -> SqliteResult<Vec<FileConversation>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT file_path, session_id, content_hash, workflow_id, status FROM file_conversations
+             WHERE file_path = ?1 ORDER BY session_id",
+        )?;
+        let rows = stmt.query_map([file_path], |row| {
+            Ok(FileConversation {
+                file_path: row.get(0)?,
+                session_id: row.get(1)?,
+                content_hash: row.get(2)?,
+                workflow_id: row.get(3)?,
+                status: SyncStatus::from_str(&row.get::<_, String>(4)?),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Insert or update the state of one conversation in a file; a missing
+    /// workflow ID keeps the one from the last upload
+    pub fn upsert_file_conversation(&self, conversation: &FileConversation) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO file_conversations (file_path, session_id, content_hash, workflow_id, status)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(file_path, session_id) DO UPDATE SET
+                content_hash = excluded.content_hash,
+                workflow_id = COALESCE(excluded.workflow_id, file_conversations.workflow_id),
+                status = excluded.status",
+            (
+                &conversation.file_path,
+                &conversation.session_id,
+                &conversation.content_hash,
+                &conversation.workflow_id,
+                conversation.status.as_str(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Forget the conversations tracked in a file
+    pub fn delete_file_conversations(&self, file_path: &str) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM file_conversations WHERE file_path = ?1", [file_path])?;
+        Ok(())
+    }
+
+    /// Store a file's cached content, replacing any older copy
+    pub fn put_cached_content(&self, file_path: &str, data: &[u8], now: i64) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO content_cache (file_path, data, size, accessed_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![file_path, data, data.len() as i64, now],
+        )?;
+        Ok(())
+    }
+
+    /// Get a file's cached content
+    pub fn get_cached_content(&self, file_path: &str) -> SqliteResult<Option<Vec<u8>>> {
+        self.conn
+            .query_row("SELECT data FROM content_cache WHERE file_path = ?1", [file_path], |row| row.get(0))
+            .optional()
+    }
+
+    /// Record that a file's cached content was read
+    pub fn touch_cached_content(&self, file_path: &str, now: i64) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE content_cache SET accessed_at = ?2 WHERE file_path = ?1",
+            rusqlite::params![file_path, now],
+        )?;
+        Ok(())
+    }
+
+    /// Evict the least recently read cached content until the rest fits
+    /// in `max_bytes`, returning how many entries were evicted
+    pub fn evict_cached_content(&self, max_bytes: u64) -> SqliteResult<usize> {
+        self.conn.execute(
+            "DELETE FROM content_cache WHERE file_path IN (
+                SELECT file_path FROM (
+                    SELECT file_path, SUM(size) OVER (ORDER BY accessed_at DESC, file_path) AS kept
+                    FROM content_cache
+                ) WHERE kept > ?1
+            )",
+            [max_bytes as i64],
+        )
+    }
+
+    /// Update just the status of a sync state
+    pub fn update_status(&self, file_path: &str, status: SyncStatus) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = ?1 WHERE file_path = ?2",
+            (status.as_str(), file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// Mark an upload started
+    pub fn mark_syncing(&self, file_path: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'syncing', syncing_since = ?1 WHERE file_path = ?2",
+            (unix_now(), file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// Update status and workflow_id after sync completes
+    pub fn mark_complete(&self, file_path: &str, workflow_id: &str) -> SqliteResult<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'complete', workflow_id = ?1, last_synced_at = ?2,
+                                   retry_count = 0, next_retry_at = NULL
+             WHERE file_path = ?3",
+            (workflow_id, now, file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// Failed uploads of a file since it last synced or changed
+    pub fn get_retry_count(&self, file_path: &str) -> SqliteResult<u32> {
+        let count = self
+            .conn
+            .query_row("SELECT retry_count FROM sync_state WHERE file_path = ?1", [file_path], |row| row.get(0))
+            .optional()?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Mark a file's upload failed, with the failures so far and when to
+    /// try again, or `None` to wait for its next change
+    pub fn mark_failed(&self, file_path: &str, retry_count: u32, next_retry_at: Option<i64>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'error', retry_count = ?1, next_retry_at = ?2 WHERE file_path = ?3",
+            (retry_count, next_retry_at, file_path),
+        )?;
+
+        Ok(())
+    }
+
+    /// Failed files whose next retry is due by `now`, longest waiting first
+    pub fn get_due_retries(&self, now: i64) -> SqliteResult<Vec<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state
+             WHERE status = 'error' AND next_retry_at <= ?1 AND file_path NOT LIKE 'ingest://%'
+             ORDER BY next_retry_at",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([now], row_to_state)?;
+        rows.collect()
+    }
+
+    /// Return uploads interrupted mid-flight to pending, returning how many
+    pub fn reset_interrupted(&self) -> SqliteResult<usize> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'pending' WHERE status = 'syncing'",
+            [],
+        )
+    }
+
+    /// Return uploads that started before `cutoff` and never finished, as
+    /// after a crash, to pending with the lost attempt counted. Returns how
+    /// many, which are also added to [`Self::recovered_upload_count`].
+    pub fn recover_stuck_uploads(&self, cutoff: i64) -> SqliteResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let recovered = tx.execute(
+            "UPDATE sync_state SET status = 'pending', retry_count = retry_count + 1, syncing_since = NULL
+             WHERE status = 'syncing' AND COALESCE(syncing_since, 0) <= ?1",
+            [cutoff],
+        )?;
+        if recovered > 0 {
+            let total = self.recovered_upload_count()? + recovered as u64;
+            self.set_app_state(RECOVERED_KEY, &total.to_string())?;
+        }
+        tx.commit()?;
+        Ok(recovered)
+    }
+
+    /// Stuck uploads returned to pending so far
+    pub fn recovered_upload_count(&self) -> SqliteResult<u64> {
+        Ok(self
+            .get_app_state(RECOVERED_KEY)?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0))
+    }
+
+    /// Return synced or failed conversations to pending, returning how many
+    ///
+    /// Matches conversations started (or, without a time window, last
+    /// changed) at or after `since`, in `project` or below it. Ingested
+    /// content and do-not-sync files are left alone.
+    pub fn reset_for_resync(&self, since: Option<i64>, project: Option<&str>) -> SqliteResult<usize> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'pending'
+             WHERE status IN ('complete', 'error')
+               AND file_path NOT LIKE 'ingest://%'
+               AND file_path NOT IN (SELECT file_path FROM do_not_sync)
+               AND (?1 IS NULL OR COALESCE(started_at, last_modified_at) >= ?1)
+               AND (?2 IS NULL OR project_path = ?2
+                    OR substr(project_path, 1, length(?2) + 1) = ?2 || ?3)",
+            (since, project, std::path::MAIN_SEPARATOR.to_string()),
+        )
+    }
+
+    /// Mark a parser's pending and in-flight files skipped, returning how many
+    pub fn skip_source(&self, source: &str) -> SqliteResult<usize> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'skipped' \
+             WHERE source = ?1 AND status IN ('pending', 'syncing')",
+            [source],
+        )
+    }
+
+    /// Return a parser's skipped files to pending, returning how many
+    pub fn unskip_source(&self, source: &str) -> SqliteResult<usize> {
+        self.conn.execute(
+            "UPDATE sync_state SET status = 'pending' WHERE source = ?1 AND status = 'skipped'",
+            [source],
+        )
+    }
+
+    /// Files tracked from a parser's directories that aren't marked deleted
+    /// or retired
+    ///
+    /// Ingested content is left out; it has no file to go missing.
+    pub fn get_tracked_files(&self) -> SqliteResult<Vec<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state
+             WHERE status NOT IN ('deleted', 'retired') AND source IS NOT NULL AND file_path NOT LIKE 'ingest://%'",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([], row_to_state)?;
+        rows.collect()
+    }
+
+    /// Deleted files whose conversation is still on the server
+    pub fn get_deleted_uploaded(&self) -> SqliteResult<Vec<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state WHERE status = 'deleted' AND workflow_id IS NOT NULL",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([], row_to_state)?;
+        rows.collect()
+    }
+
+    /// Forget the server copy of a deleted file once it is deleted there too
+    pub fn clear_workflow_id(&self, file_path: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE sync_state SET workflow_id = NULL WHERE file_path = ?1",
+            [file_path],
+        )?;
+
+        Ok(())
+    }
+
+    /// A value from the app-wide settings
+    pub fn get_app_state(&self, key: &str) -> SqliteResult<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM app_state WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+    }
+
+    /// Set an app-wide setting
+    pub fn set_app_state(&self, key: &str, value: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO app_state (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            (key, value),
+        )?;
+
+        Ok(())
+    }
+
+    /// API base URL the sync state belongs to, once one has been recorded
+    pub fn get_backend(&self) -> SqliteResult<Option<String>> {
+        self.get_app_state(BACKEND_KEY)
+    }
+
+    /// Record the API base URL the sync state belongs to
+    pub fn set_backend(&self, backend: &str) -> SqliteResult<()> {
+        self.set_app_state(BACKEND_KEY, backend)
+    }
+
+    /// Whether server state for `backend` was put aside by an earlier switch
+    pub fn has_backend_state(&self, backend: &str) -> SqliteResult<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM backend_sync_state WHERE backend = ?1)
+                 OR EXISTS (SELECT 1 FROM backend_workspaces WHERE backend = ?1)",
+            [backend],
+            |row| row.get(0),
+        )
+    }
+
+    /// Move the sync state from backend `from` to backend `to`
+    ///
+    /// Workflow IDs, synced prefixes and workspace mappings only mean
+    /// something to the backend that issued them. They are put aside under
+    /// `from`, to come back if it is used again. State put aside for `to`
+    /// is restored; conversations changed since, or uploaded only to
+    /// `from`, go back to `pending`. Without state for `to`, uploaded
+    /// conversations stay `complete` with `keep_complete`, or are uploaded
+    /// again without it. Conflicts with other machines' versions and pulled
+    /// results are forgotten.
+    pub fn switch_backend(&self, from: &str, to: &str, keep_complete: bool) -> SqliteResult<BackendSwitch> {
+        let tx = self.conn.unchecked_transaction()?;
+        let restore = self.has_backend_state(to)?;
+
+        tx.execute("DELETE FROM backend_sync_state WHERE backend = ?1", [from])?;
+        tx.execute(
+            "INSERT INTO backend_sync_state (backend, file_path, content_hash, last_synced_at, workflow_id,
+                 status, synced_offset, synced_lines, synced_hash, synced_workflow_id)
+             SELECT ?1, file_path, content_hash, last_synced_at, workflow_id, status,
+                 synced_offset, synced_lines, synced_hash, synced_workflow_id
+             FROM sync_state WHERE workflow_id IS NOT NULL OR synced_workflow_id IS NOT NULL",
+            [from],
+        )?;
+        tx.execute("DELETE FROM backend_file_conversations WHERE backend = ?1", [from])?;
+        tx.execute(
+            "INSERT INTO backend_file_conversations (backend, file_path, session_id, content_hash, workflow_id, status)
+             SELECT ?1, file_path, session_id, content_hash, workflow_id, status
+             FROM file_conversations WHERE workflow_id IS NOT NULL",
+            [from],
+        )?;
+        tx.execute("DELETE FROM backend_workspaces WHERE backend = ?1", [from])?;
+        tx.execute(
+            "INSERT INTO backend_workspaces (backend, project_path, workspace_id, created_at)
+             SELECT ?1, project_path, workspace_id, created_at FROM workspaces",
+            [from],
+        )?;
+
+        // Nothing the old backend issued applies any more
+        let keep_complete = keep_complete && !restore;
+        tx.execute(
+            "UPDATE sync_state SET workflow_id = NULL, synced_offset = NULL, synced_lines = NULL,
+                 synced_hash = NULL, synced_workflow_id = NULL, retry_count = 0, next_retry_at = NULL,
+                 status = CASE
+                     WHEN status = 'complete' AND ?1 THEN 'complete'
+                     WHEN status IN ('complete', 'syncing', 'error') THEN 'pending'
+                     ELSE status
+                 END",
+            [keep_complete],
+        )?;
+        tx.execute(
+            "UPDATE file_conversations SET workflow_id = NULL,
+                 status = CASE WHEN status = 'complete' AND ?1 THEN 'complete' ELSE 'pending' END",
+            [keep_complete],
+        )?;
+        tx.execute("DELETE FROM workspaces", [])?;
+        tx.execute("DELETE FROM sync_conflicts", [])?;
+        tx.execute("DELETE FROM remote_results", [])?;
+        tx.execute("DELETE FROM annotation_edits", [])?;
+        tx.execute("DELETE FROM sync_versions", [])?;
+
+        if restore {
+            tx.execute(
+                "UPDATE sync_state SET workflow_id = s.workflow_id, last_synced_at = s.last_synced_at,
+                     synced_offset = s.synced_offset, synced_lines = s.synced_lines, synced_hash = s.synced_hash,
+                     synced_workflow_id = s.synced_workflow_id,
+                     status = CASE
+                         WHEN sync_state.status IN ('deleted', 'retired', 'skipped', 'unsupported') THEN sync_state.status
+                         WHEN s.status = 'complete' AND s.content_hash = sync_state.content_hash THEN 'complete'
+                         ELSE 'pending'
+                     END
+                 FROM backend_sync_state s
+                 WHERE s.backend = ?1 AND s.file_path = sync_state.file_path",
+                [to],
+            )?;
+            tx.execute(
+                "UPDATE file_conversations SET workflow_id = s.workflow_id,
+                     status = CASE WHEN s.content_hash = file_conversations.content_hash THEN s.status ELSE 'pending' END
+                 FROM backend_file_conversations s
+                 WHERE s.backend = ?1 AND s.file_path = file_conversations.file_path
+                     AND s.session_id = file_conversations.session_id",
+                [to],
+            )?;
+            tx.execute(
+                "INSERT INTO workspaces (project_path, workspace_id, created_at)
+                 SELECT project_path, workspace_id, created_at FROM backend_workspaces WHERE backend = ?1",
+                [to],
+            )?;
+            for table in ["backend_sync_state", "backend_file_conversations", "backend_workspaces"] {
+                tx.execute(&format!("DELETE FROM {} WHERE backend = ?1", table), [to])?;
+            }
+        }
+
+        tx.execute(
+            "INSERT INTO app_state (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            (BACKEND_KEY, to),
+        )?;
+        tx.commit()?;
+
+        Ok(if restore {
+            BackendSwitch::Restored
+        } else if keep_complete {
+            BackendSwitch::Kept
+        } else {
+            BackendSwitch::Reset
+        })
+    }
+
+    /// Get all pending sync states
+    pub fn get_pending(&self) -> SqliteResult<Vec<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state WHERE status = 'pending' ORDER BY last_modified_at ASC",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map([], row_to_state)?;
+        rows.collect()
+    }
+
+    /// List conversations, most recently modified first
+    pub fn list_conversations(
+        &self,
+        project_path: Option<&str>,
+        limit: usize,
+    ) -> SqliteResult<Vec<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state
+             WHERE (?1 IS NULL OR project_path = ?1)
+             ORDER BY last_modified_at DESC LIMIT ?2",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map((project_path, limit as i64), row_to_state)?;
+        rows.collect()
+    }
+
+    /// The conversation uploaded most recently, if any has a workflow ID
+    pub fn latest_synced(&self) -> SqliteResult<Option<SyncState>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sync_state
+             WHERE status = 'complete' AND workflow_id IS NOT NULL
+             ORDER BY last_synced_at DESC LIMIT 1",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        stmt.query_row([], row_to_state).optional()
+    }
+
+    /// Conversations on a git branch and overlapping a time range, oldest
+    /// first; unset filters match everything
+    ///
+    /// Conversations without a recorded time window count as happening when
+    /// their file last changed. Deleted files are left out.
+    pub fn list_worklog(
+        &self,
+        branch: Option<&str>,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> SqliteResult<Vec<ConversationSpan>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, COALESCE(started_at, last_modified_at), COALESCE(ended_at, last_modified_at)
+             FROM sync_state
+             WHERE status != 'deleted'
+               AND (?1 IS NULL OR git_branch = ?1)
+               AND (?2 IS NULL OR COALESCE(ended_at, last_modified_at) >= ?2)
+               AND (?3 IS NULL OR COALESCE(started_at, last_modified_at) < ?3)
+             ORDER BY 14, file_path",
+            SYNC_STATE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map((branch, since, until), |row| {
+            Ok(ConversationSpan {
+                state: row_to_state(row)?,
+                started_at: row.get(13)?,
+                ended_at: row.get(14)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Summarize conversations per project
+    pub fn list_projects(&self) -> SqliteResult<Vec<ProjectSummary>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project_path, COUNT(*), MAX(last_modified_at), MAX(last_synced_at)
+             FROM sync_state WHERE project_path IS NOT NULL
+             GROUP BY project_path ORDER BY MAX(last_modified_at) DESC",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(ProjectSummary {
+                project_path: row.get(0)?,
+                conversation_count: row.get::<_, i64>(1)? as usize,
+                last_modified_at: row.get(2)?,
+                last_synced_at: row.get(3)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    /// Count conversations started in each hour or day, per project
+    ///
+    /// Conversations without a recorded time window count at their last
+    /// change. Buckets are in local time, oldest first.
+    pub fn activity_histogram(&self, bucket: ActivityBucket) -> SqliteResult<Vec<ActivityCount>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT project_path,
+                    strftime(?1, COALESCE(started_at, last_modified_at), 'unixepoch', 'localtime') AS bucket,
+                    COUNT(*)
+             FROM sync_state
+             GROUP BY project_path, bucket ORDER BY bucket, project_path",
+        )?;
+
+        let rows = stmt.query_map([bucket.format()], |row| {
+            Ok(ActivityCount {
+                project_path: row.get(0)?,
+                bucket: row.get(1)?,
+                conversations: row.get::<_, i64>(2)? as usize,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    /// Sessions started on each UTC day in `[since, until)` (Unix seconds),
+    /// per tool, with their lengths capped at `max_seconds` and added up
+    ///
+    /// Sessions without a recorded time window count at their last change,
+    /// with no length. Deleted files are left out.
+    pub fn daily_sessions(&self, since: i64, until: i64, max_seconds: i64) -> SqliteResult<Vec<DailySessions>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date(COALESCE(started_at, last_modified_at), 'unixepoch') AS day,
+                    COALESCE(source, 'unknown') AS tool,
+                    COUNT(*),
+                    SUM(MIN(MAX(COALESCE(ended_at - started_at, 0), 0), ?3))
+             FROM sync_state
+             WHERE COALESCE(started_at, last_modified_at) >= ?1
+               AND COALESCE(started_at, last_modified_at) < ?2
+               AND status != 'deleted'
+             GROUP BY day, tool ORDER BY day, tool",
+        )?;
+
+        let rows = stmt.query_map((since, until, max_seconds), |row| {
+            Ok(DailySessions {
+                date: row.get(0)?,
+                tool: row.get(1)?,
+                sessions: row.get::<_, i64>(2)? as u64,
+                total_seconds: row.get::<_, i64>(3)? as u64,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    /// Count conversations that reached the server
+    pub fn count_uploaded(&self) -> SqliteResult<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sync_state WHERE workflow_id IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Get count of items by status
+    pub fn get_status_counts(&self) -> SqliteResult<StatusCounts> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT status, COUNT(*) FROM sync_state GROUP BY status")?;
+
+        let mut counts = StatusCounts::default();
+        let rows = stmt.query_map([], |row| {
+            let status: String = row.get(0)?;
+            let count: i64 = row.get(1)?;
+            Ok((status, count))
+        })?;
+
+        for row in rows {
+            let (status, count) = row?;
+            match status.as_str() {
+                "pending" => counts.pending = count as usize,
+                "syncing" => counts.syncing = count as usize,
+                "complete" => counts.complete = count as usize,
+                "error" => counts.error = count as usize,
+                "skipped" => counts.skipped = count as usize,
+                "deleted" => counts.deleted = count as usize,
+                "unsupported" => counts.unsupported = count as usize,
+                "retired" => counts.retired = count as usize,
+                _ => {}
+            }
+        }
+
+        Ok(counts)
+    }
+}
+
+/// A conversation and the time its messages span, in Unix seconds
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSpan {
+    #[serde(flatten)]
+    pub state: SyncState,
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+/// How [`Database::switch_backend`] treated conversations already uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendSwitch {
+    /// State put aside for the new backend earlier was brought back
+    Restored,
+    /// Uploaded conversations stayed `complete`
+    Kept,
+    /// Uploaded conversations were marked `pending` to upload again
+    Reset,
+}
+
+/// Conversation totals for a single project
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSummary {
+    pub project_path: String,
+    pub conversation_count: usize,
+    pub last_modified_at: i64,
+    pub last_synced_at: Option<i64>,
+}
+
+/// An upload the server refused or that failed, kept for debugging
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedAttempt {
+    /// Local file that was being uploaded
+    pub file_path: String,
+    pub attempted_at: i64,
+    /// HTTP status, when the server answered
+    pub status_code: Option<u16>,
+    pub error: String,
+    /// Validation messages from a rejected payload, most relevant first
+    pub validation_errors: Vec<String>,
+    /// Response body, cut to 64 KiB
+    pub response_body: Option<String>,
+}
+
+/// A session another machine had uploaded a newer version of
+///
+/// This machine's version is uploaded beside it rather than over it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    /// Sync state key of the local conversation
+    pub file_path: String,
+    pub session_id: Option<String>,
+    /// Workflow of the other machine's version
+    pub remote_workflow_id: String,
+    pub remote_machine_id: Option<String>,
+    pub remote_updated_at: Option<i64>,
+    /// Workflow of this machine's version, once uploaded
+    pub workflow_id: Option<String>,
+    pub detected_at: i64,
+}
+
+/// A version of a file as it was uploaded
+///
+/// Only its hash and size are kept; for an append-only file, the file as it
+/// is now holds every version still a prefix of it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncVersion {
+    pub file_path: String,
+    /// SHA-256 of the content before redaction
+    pub content_hash: String,
+    /// Length in bytes
+    pub bytes: u64,
+    pub lines: u64,
+    pub workflow_id: String,
+    pub synced_at: i64,
+}
+
+/// What the server produced for an uploaded conversation, as last fetched
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteResult {
+    pub workflow_id: String,
+    /// Extraction status as the server reports it
+    pub status: String,
+    /// Whether extraction had ended when fetched
+    pub finished: bool,
+    pub summary: Option<String>,
+    /// Tags the server assigned, separate from local ones
+    pub tags: Vec<String>,
+    /// Extraction output as the server sent it
+    pub output: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub fetched_at: i64,
+    /// Title the server holds, if it has one
+    pub title: Option<String>,
+    /// Whether `title` or `tags` were edited here and not pushed yet
+    pub edited: bool,
+}
+
+/// A title or tag change made locally, waiting to be pushed to the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationEdit {
+    pub workflow_id: String,
+    /// New title, `None` if unchanged
+    pub title: Option<String>,
+    /// New tags replacing the server's, `None` if unchanged
+    pub tags: Option<Vec<String>>,
+    /// Milliseconds since the epoch
+    pub edited_at: i64,
+}
+
+/// A file's content was over the payload limit when last uploaded
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Oversize {
+    /// `sync.payloadLimit.strategy` applied, e.g. `truncateOldest`
+    pub strategy: String,
+    /// Size of the content before it was applied
+    pub bytes: u64,
+}
+
+/// Leading part of an append-only file that has been uploaded
+///
+/// Later syncs send only what follows it, as long as the file still
+/// starts with the same bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedPrefix {
+    /// Length in bytes
+    pub offset: u64,
+    /// Complete lines it holds
+    pub lines: u64,
+    /// SHA-256 of those bytes
+    pub hash: String,
+    /// Workflow of the upload that holds them
+    pub workflow_id: String,
+}
+
+/// Width of the time buckets in [`Database::activity_histogram`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityBucket {
+    Hour,
+    Day,
+}
+
+impl ActivityBucket {
+    /// `strftime` format of a bucket's label
+    fn format(self) -> &'static str {
+        match self {
+            ActivityBucket::Hour => "%Y-%m-%dT%H:00",
+            ActivityBucket::Day => "%Y-%m-%d",
+        }
+    }
+}
+
+impl std::str::FromStr for ActivityBucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(ActivityBucket::Hour),
+            "day" => Ok(ActivityBucket::Day),
+            other => Err(format!("unknown bucket '{}', expected hour or day", other)),
+        }
+    }
+}
+
+/// Conversations a project started within one time bucket
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityCount {
+    pub project_path: Option<String>,
+    /// Local start of the bucket, `YYYY-MM-DD` or `YYYY-MM-DDTHH:00`
+    pub bucket: String,
+    pub conversations: usize,
+}
+
+/// Sessions one tool started on one UTC day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailySessions {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Parser that produced the sessions, e.g. `claude-code`
+    pub tool: String,
+    pub sessions: u64,
+    /// Lengths of the sessions added up, each capped
+    pub total_seconds: u64,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct StatusCounts {
+    pub pending: usize,
+    pub syncing: usize,
+    pub complete: usize,
+    pub error: usize,
+    pub skipped: usize,
+    pub deleted: usize,
+    pub unsupported: usize,
+    pub retired: usize,
+}
+
+/// Normalize a user-supplied tag (`#Experiment` -> `experiment`)
+///
+/// Returns `None` for empty tags or tags containing whitespace.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        None
+    } else {
+        Some(tag)
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_is_storage_failure() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        db.set_read_only(true).unwrap();
+        let error = db.set_workspace("/work/app", "ws-1").unwrap_err();
+        assert!(is_storage_failure(&error), "{:?}", error);
+
+        let error = db.conn.execute("SELECT * FROM missing", []).unwrap_err();
+        assert!(!is_storage_failure(&error));
+    }
+
+    #[test]
+    fn test_database_operations() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        let db = Database::open_at(&db_path).unwrap();
+
+        // Test insert
+        let state = SyncState {
+            file_path: "/test/file.jsonl".to_string(),
+            content_hash: "abc123".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1234567890,
+            workflow_id: None,
+            status: SyncStatus::Pending,
+            session_id: None,
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        };
+
+        db.upsert_sync_state(&state).unwrap();
+
+        // Test get
+        let retrieved = db.get_sync_state("/test/file.jsonl").unwrap().unwrap();
+        assert_eq!(retrieved.content_hash, "abc123");
+        assert_eq!(retrieved.status, SyncStatus::Pending);
+
+        // Test update status
+        db.mark_complete("/test/file.jsonl", "workflow-123")
+            .unwrap();
+        let updated = db.get_sync_state("/test/file.jsonl").unwrap().unwrap();
+        assert_eq!(updated.status, SyncStatus::Complete);
+        assert_eq!(updated.workflow_id, Some("workflow-123".to_string()));
+        assert_eq!(db.count_uploaded().unwrap(), 1);
+        let latest = db.latest_synced().unwrap().unwrap();
+        assert_eq!(latest.workflow_id.as_deref(), Some("workflow-123"));
+    }
+
+    #[test]
+    fn test_conversation_metadata() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        let state = SyncState {
+            file_path: "/test/session.jsonl".to_string(),
+            content_hash: "abc123".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1234567890,
+            workflow_id: None,
+            status: SyncStatus::Pending,
+            session_id: None,
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        };
+        db.upsert_sync_state(&state).unwrap();
+        db.update_metadata("/test/session.jsonl", Some("session-1"), Some("/work/app"), "claude-code", Some("Fix tests"))
+            .unwrap();
+
+        // A later upsert without metadata keeps what the parser reported
+        db.upsert_sync_state(&state).unwrap();
+
+        let found = db.get_by_session_id("session-1").unwrap().unwrap();
+        assert_eq!(found.project_path.as_deref(), Some("/work/app"));
+        assert_eq!(found.title.as_deref(), Some("Fix tests"));
+        assert!(found.git.is_none());
+
+        let git = GitContext {
+            remote_url: Some("git@github.com:org/app.git".to_string()),
+            branch: Some("main".to_string()),
+            commit: Some("deadbeef".to_string()),
+        };
+        db.update_git_context("/test/session.jsonl", &git).unwrap();
+        let found = db.get_sync_state("/test/session.jsonl").unwrap().unwrap();
+        assert_eq!(found.git, Some(git));
+
+        let projects = db.list_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].conversation_count, 1);
+
+        assert_eq!(db.list_conversations(Some("/work/app"), 10).unwrap().len(), 1);
+        assert_eq!(db.list_conversations(Some("/other"), 10).unwrap().len(), 0);
+
+        db.update_time_window("/test/session.jsonl", 1000, 2000).unwrap();
+        let worklog = db.list_worklog(Some("main"), Some(1500), None).unwrap();
+        assert_eq!(worklog.len(), 1);
+        assert_eq!((worklog[0].started_at, worklog[0].ended_at), (1000, 2000));
+        assert!(db.list_worklog(Some("feature/x"), None, None).unwrap().is_empty());
+        assert!(db.list_worklog(None, Some(2001), None).unwrap().is_empty());
+        assert!(db.list_worklog(None, None, Some(1000)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recover_stuck_uploads() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let state = |file_path: &str, status: SyncStatus| SyncState {
+            file_path: file_path.to_string(),
+            content_hash: "abc123".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1000,
+            workflow_id: None,
+            status,
+            session_id: None,
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        };
+        db.upsert_sync_state(&state("/stuck.jsonl", SyncStatus::Pending)).unwrap();
+        db.upsert_sync_state(&state("/fresh.jsonl", SyncStatus::Syncing)).unwrap();
+        db.upsert_sync_state(&state("/done.jsonl", SyncStatus::Complete)).unwrap();
+        db.mark_syncing("/stuck.jsonl").unwrap();
+        db.conn
+            .execute("UPDATE sync_state SET syncing_since = 100 WHERE file_path = '/stuck.jsonl'", [])
+            .unwrap();
+
+        // Only the upload that started before the cutoff is stuck
+        assert_eq!(db.recover_stuck_uploads(unix_now() - 60).unwrap(), 1);
+        assert_eq!(db.get_sync_state("/stuck.jsonl").unwrap().unwrap().status, SyncStatus::Pending);
+        assert_eq!(db.get_retry_count("/stuck.jsonl").unwrap(), 1);
+        assert_eq!(db.get_sync_state("/fresh.jsonl").unwrap().unwrap().status, SyncStatus::Syncing);
+        assert_eq!(db.get_sync_state("/done.jsonl").unwrap().unwrap().status, SyncStatus::Complete);
+
+        assert_eq!(db.recover_stuck_uploads(unix_now()).unwrap(), 1);
+        assert_eq!(db.recovered_upload_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_switch_backend() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let state = |file_path: &str| SyncState {
+            file_path: file_path.to_string(),
+            content_hash: "abc123".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1000,
+            workflow_id: None,
+            status: SyncStatus::Pending,
+            session_id: None,
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        };
+        db.upsert_sync_state(&state("/a.jsonl")).unwrap();
+        db.upsert_sync_state(&state("/b.jsonl")).unwrap();
+        db.mark_complete("/a.jsonl", "wf-prod").unwrap();
+        db.set_workspace("/work/app", "ws-prod").unwrap();
+        db.set_backend("https://api.example.com").unwrap();
+        assert!(!db.has_backend_state("https://staging.example.com").unwrap());
+
+        // A new backend, keeping what was uploaded as synced
+        let switch = db.switch_backend("https://api.example.com", "https://staging.example.com", true).unwrap();
+        assert_eq!(switch, BackendSwitch::Kept);
+        assert_eq!(db.get_backend().unwrap().as_deref(), Some("https://staging.example.com"));
+        let a = db.get_sync_state("/a.jsonl").unwrap().unwrap();
+        assert_eq!((a.status, a.workflow_id), (SyncStatus::Complete, None));
+        assert_eq!(db.get_workspace("/work/app").unwrap(), None);
+        assert!(db.has_backend_state("https://api.example.com").unwrap());
+
+        db.mark_complete("/b.jsonl", "wf-staging").unwrap();
+
+        // Back to the first backend: its IDs return, and what only went to staging uploads again
+        let switch = db.switch_backend("https://staging.example.com", "https://api.example.com", false).unwrap();
+        assert_eq!(switch, BackendSwitch::Restored);
+        let a = db.get_sync_state("/a.jsonl").unwrap().unwrap();
+        assert_eq!((a.status, a.workflow_id.as_deref()), (SyncStatus::Complete, Some("wf-prod")));
+        let b = db.get_sync_state("/b.jsonl").unwrap().unwrap();
+        assert_eq!((b.status, b.workflow_id), (SyncStatus::Pending, None));
+        assert_eq!(db.get_workspace("/work/app").unwrap().as_deref(), Some("ws-prod"));
+        assert!(!db.has_backend_state("https://api.example.com").unwrap());
+
+        // A third backend, uploading everything again
+        let switch = db.switch_backend("https://api.example.com", "http://localhost:8787", false).unwrap();
+        assert_eq!(switch, BackendSwitch::Reset);
+        assert_eq!(db.get_sync_state("/a.jsonl").unwrap().unwrap().status, SyncStatus::Pending);
+    }
+
+    #[test]
+    fn test_tags() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        db.add_tag("/a.jsonl", "experiment").unwrap();
+        db.add_tag("/a.jsonl", "experiment").unwrap();
+        db.add_tag("/a.jsonl", "auth").unwrap();
+        db.add_tag("/b.jsonl", "experiment").unwrap();
+
+        assert_eq!(db.get_tags("/a.jsonl").unwrap(), vec!["auth", "experiment"]);
+        assert_eq!(db.list_tags().unwrap(), vec!["experiment", "auth"]);
+
+        db.remove_tag("/a.jsonl", "auth").unwrap();
+        assert_eq!(db.get_tags("/a.jsonl").unwrap(), vec!["experiment"]);
+
+        assert_eq!(normalize_tag(" #Experiment "), Some("experiment".to_string()));
+        assert_eq!(normalize_tag("#"), None);
+        assert_eq!(normalize_tag("two words"), None);
+    }
+
+    #[test]
+    fn test_workspaces() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        assert_eq!(db.get_workspace("/work/app").unwrap(), None);
+        db.set_workspace("/work/app", "ws-1").unwrap();
+        db.set_workspace("/work/app", "ws-2").unwrap();
+        assert_eq!(db.get_workspace("/work/app").unwrap().as_deref(), Some("ws-2"));
+
+        db.set_workspace("/work/lib", "ws-2").unwrap();
+        db.set_workspace("/work/api", "ws-3").unwrap();
+        assert_eq!(db.list_workspaces().unwrap(), vec!["ws-2", "ws-3"]);
+    }
+
+    #[test]
+    fn test_content_cache() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        db.put_cached_content("/a.jsonl", &[1; 40], 100).unwrap();
+        db.put_cached_content("/b.jsonl", &[2; 40], 200).unwrap();
+        db.put_cached_content("/c.jsonl", &[3; 40], 300).unwrap();
+        db.touch_cached_content("/a.jsonl", 400).unwrap();
+
+        // The least recently read entry goes first
+        assert_eq!(db.evict_cached_content(100).unwrap(), 1);
+        assert_eq!(db.get_cached_content("/b.jsonl").unwrap(), None);
+        assert_eq!(db.get_cached_content("/a.jsonl").unwrap(), Some(vec![1; 40]));
+
+        db.delete_sync_state("/c.jsonl").unwrap();
+        assert_eq!(db.get_cached_content("/c.jsonl").unwrap(), None);
+    }
+
+    #[test]
+    fn test_synced_prefix() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        db.upsert_sync_state(&SyncState {
+            file_path: "/a.jsonl".to_string(),
+            content_hash: "hash".to_string(),
+            last_synced_at: None,
+            last_modified_at: 0,
+            workflow_id: None,
+            status: SyncStatus::Pending,
+            session_id: None,
+            project_path: None,
+            source: None,
+            git: None,
+            title: None,
+        })
+        .unwrap();
+        assert_eq!(db.get_synced_prefix("/a.jsonl").unwrap(), None);
+
+        let prefix = SyncedPrefix {
+            offset: 120,
+            lines: 3,
+            hash: "prefix-hash".to_string(),
+            workflow_id: "wf-1".to_string(),
+        };
+        db.set_synced_prefix("/a.jsonl", Some(&prefix)).unwrap();
+        assert_eq!(db.get_synced_prefix("/a.jsonl").unwrap(), Some(prefix));
+        db.set_synced_prefix("/a.jsonl", None).unwrap();
+        assert_eq!(db.get_synced_prefix("/a.jsonl").unwrap(), None);
+        assert_eq!(db.get_synced_prefix("/missing.jsonl").unwrap(), None);
+    }
+
+    #[test]
+    fn test_file_conversations() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let conversation = |session_id: &str, workflow_id: Option<&str>, status| FileConversation {
+            file_path: "/history.json".to_string(),
+            session_id: session_id.to_string(),
+            content_hash: format!("hash-{}", session_id),
+            workflow_id: workflow_id.map(str::to_string),
+            status,
+        };
+
+        db.upsert_file_conversation(&conversation("s2", Some("wf-2"), SyncStatus::Complete)).unwrap();
+        db.upsert_file_conversation(&conversation("s1", Some("wf-1"), SyncStatus::Complete)).unwrap();
+        // A failed re-upload keeps the earlier workflow
+        db.upsert_file_conversation(&conversation("s1", None, SyncStatus::Error)).unwrap();
+
+        let s1 = db.get_file_conversation("/history.json", "s1").unwrap().unwrap();
+        assert_eq!((s1.workflow_id.as_deref(), s1.status), (Some("wf-1"), SyncStatus::Error));
+        let sessions: Vec<_> = db.list_file_conversations("/history.json").unwrap();
+        assert_eq!(sessions.iter().map(|c| c.session_id.as_str()).collect::<Vec<_>>(), vec!["s1", "s2"]);
+
+        db.delete_sync_state("/history.json").unwrap();
+        assert!(db.list_file_conversations("/history.json").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_annotation_edits() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        db.edit_annotations("wf-1", Some("Billing fix"), None).unwrap();
+        db.edit_annotations("wf-1", None, Some(&tags(&["billing"]))).unwrap();
+        let edits = db.pending_annotation_edits().unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].title.as_deref(), Some("Billing fix"));
+        assert_eq!(edits[0].tags, Some(tags(&["billing"])));
+
+        // Edited again while the push was under way: the newer edit stays
+        db.edit_annotations("wf-1", Some("Billing hotfix"), None).unwrap();
+        db.annotation_pushed(&edits[0]).unwrap();
+        assert_eq!(db.pending_annotation_edits().unwrap()[0].title.as_deref(), Some("Billing hotfix"));
+
+        let edits = db.pending_annotation_edits().unwrap();
+        db.annotation_pushed(&edits[0]).unwrap();
+        assert!(db.pending_annotation_edits().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_related() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        let sessions = [
+            ("/claude.jsonl", "claude-code", "/work/app", 1000, 2000),
+            ("/cursor.db", "cursor", "/work/app", 2500, 3000),
+            ("/codex.jsonl", "codex", "/work/app", 9000, 9500),
+            ("/other.db", "cursor", "/work/other", 1000, 2000),
+            ("/claude-2.jsonl", "claude-code", "/work/app", 1000, 2000),
+        ];
+        for (file_path, source, project, started_at, ended_at) in sessions {
+            db.upsert_sync_state(&SyncState {
+                file_path: file_path.to_string(),
+                content_hash: "abc".to_string(),
+                last_synced_at: None,
+                last_modified_at: 1,
+                workflow_id: None,
+                status: SyncStatus::Complete,
+                session_id: None,
+                project_path: None,
+                source: None,
+                git: None,
+                title: None,
+            })
+            .unwrap();
+            db.update_metadata(file_path, None, Some(project), source, None).unwrap();
+            db.update_time_window(file_path, started_at, ended_at).unwrap();
+        }
+
+        let related = db
+            .find_related("/claude.jsonl", "claude-code", "/work/app", (1000, 2000), 900)
+            .unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].file_path, "/cursor.db");
+    }
+
+    #[test]
+    fn test_daily_sessions() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        // 2026-06-01 00:00 UTC
+        let day = 1_780_272_000;
+        let sessions = [
+            ("/a.jsonl", "claude-code", day + 600, Some(day + 1800)),
+            ("/b.jsonl", "claude-code", day + 7200, Some(day + 7200 + 5 * 3600)),
+            ("/c.jsonl", "codex", day + 86_400, None),
+            ("/d.jsonl", "codex", day + 2 * 86_400, None),
+        ];
+        for (file_path, source, started_at, ended_at) in sessions {
+            db.upsert_sync_state(&SyncState {
+                file_path: file_path.to_string(),
+                content_hash: "abc".to_string(),
+                last_synced_at: None,
+                last_modified_at: started_at,
+                workflow_id: None,
+                status: SyncStatus::Complete,
+                session_id: None,
+                project_path: None,
+                source: Some(source.to_string()),
+                git: None,
+                title: None,
+            })
+            .unwrap();
+            if let Some(ended_at) = ended_at {
+                db.update_time_window(file_path, started_at, ended_at).unwrap();
+            }
+        }
+
+        let sessions = db.daily_sessions(day, day + 2 * 86_400, 4 * 3600).unwrap();
+        let summary: Vec<_> = sessions
+            .iter()
+            .map(|s| (s.date.as_str(), s.tool.as_str(), s.sessions, s.total_seconds))
+            .collect();
+        // The five-hour session counts as four
+        assert_eq!(
+            summary,
+            vec![("2026-06-01", "claude-code", 2, 1200 + 4 * 3600), ("2026-06-02", "codex", 1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_activity_histogram() {
+        use chrono::{Local, TimeZone};
+
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        let at = |day, hour, minute| Local.with_ymd_and_hms(2026, 6, day, hour, minute, 0).unwrap().timestamp();
+        let sessions = [
+            ("/a.jsonl", "/work/app", at(1, 10, 5)),
+            ("/b.jsonl", "/work/app", at(1, 10, 50)),
+            ("/c.jsonl", "/work/app", at(1, 14, 0)),
+            ("/d.jsonl", "/work/lib", at(2, 9, 30)),
+        ];
+        for (file_path, project, started_at) in sessions {
+            db.upsert_sync_state(&SyncState {
+                file_path: file_path.to_string(),
+                content_hash: "abc".to_string(),
+                last_synced_at: None,
+                last_modified_at: started_at,
+                workflow_id: None,
+                status: SyncStatus::Complete,
+                session_id: None,
+                project_path: Some(project.to_string()),
+                source: None,
+                git: None,
+                title: None,
+            })
+            .unwrap();
+        }
+        // The time window wins over the last change
+        db.update_time_window("/c.jsonl", at(1, 10, 30), at(1, 14, 0)).unwrap();
+
+        let count = |project: &str, bucket: &str, conversations| ActivityCount {
+            project_path: Some(project.to_string()),
+            bucket: bucket.to_string(),
+            conversations,
+        };
+        assert_eq!(
+            db.activity_histogram(ActivityBucket::Day).unwrap(),
+            vec![count("/work/app", "2026-06-01", 3), count("/work/lib", "2026-06-02", 1)]
+        );
+        assert_eq!(
+            db.activity_histogram(ActivityBucket::Hour).unwrap(),
+            vec![count("/work/app", "2026-06-01T10:00", 3), count("/work/lib", "2026-06-02T09:00", 1)]
+        );
+        assert_eq!("hour".parse(), Ok(ActivityBucket::Hour));
+        assert!("week".parse::<ActivityBucket>().is_err());
+    }
+}
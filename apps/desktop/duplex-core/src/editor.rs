@@ -0,0 +1,302 @@
+//! Editor companion socket
+//!
+//! A loopback JSON-RPC 2.0 endpoint for editor extensions (VS Code,
+//! JetBrains). The app writes the port to `editor.port` in the config
+//! directory; clients connect and exchange one JSON message per line.
+//!
+//! A connection must open with `initialize`, passing the token in
+//! `editor.token` (readable by the owner only) as `params.token`, or it is
+//! closed. So is one that sends a line that isn't JSON, such as the HTTP
+//! request of a web page probing local ports.
+//!
+//! Every method identifies a conversation with one of `sessionId`,
+//! `filePath` or `projectPath` (the most recent conversation in that
+//! project, for editors that only know their workspace folder).
+//!
+//! Methods:
+//! - `initialize` - `{ token }`; server name, version and supported methods
+//! - `session/status` - sync state of the conversation
+//! - `session/sync` - upload the conversation now, even if unchanged
+//! - `session/setDoNotSync` - exclude (`doNotSync: true`) or include it again
+
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::db::{Database, SessionMatch, SyncState};
+use crate::files;
+use crate::sync::SyncHandle;
+use crate::timestamps;
+
+/// Methods advertised by `initialize`
+const METHODS: &[&str] = &["session/status", "session/sync", "session/setDoNotSync"];
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// Application error: the connection didn't open with a valid `initialize`
+const UNAUTHORIZED: i64 = -32000;
+/// Application error: no conversation matches the given identifier
+const SESSION_NOT_FOUND: i64 = -32001;
+
+type RpcResult = Result<Value, (i64, String)>;
+
+/// Run the editor socket until the process exits
+pub async fn serve(engine: SyncHandle) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db = Arc::new(Mutex::new(Database::open()?));
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let port = listener.local_addr()?.port();
+
+    let port_path = crate::config::get_editor_port_path()?;
+    files::write_private(&port_path, port.to_string())?;
+    let token: Arc<str> = files::private_token(&crate::config::get_editor_token_path()?)?.into();
+
+    tracing::info!("Editor socket listening on 127.0.0.1:{}", port);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let db = db.clone();
+        let engine = engine.clone();
+        let token = token.clone();
+
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = tokio::io::BufReader::new(reader).lines();
+            let mut authorized = false;
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let (response, close) = match serde_json::from_str::<Value>(&line) {
+                    Err(e) => (Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())), true),
+                    Ok(message) if !authorized => match authorize(&message, &token) {
+                        Ok(()) => {
+                            authorized = true;
+                            (handle_message(&message, &db, &engine), false)
+                        }
+                        Err(response) => (Some(response), true),
+                    },
+                    Ok(message) => (handle_message(&message, &db, &engine), false),
+                };
+
+                if let Some(response) = response {
+                    let payload = format!("{}\n", response);
+                    if let Err(e) = writer.write_all(payload.as_bytes()).await {
+                        tracing::debug!("Editor client disconnected: {}", e);
+                        break;
+                    }
+                }
+                if close {
+                    tracing::debug!("Closing editor connection that didn't initialize with the token");
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Check a connection's first message: `initialize` with the token. The
+/// error response to send before closing the connection if it isn't.
+fn authorize(message: &Value, token: &str) -> Result<(), Value> {
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    if message["method"] != "initialize" {
+        return Err(error_response(id, UNAUTHORIZED, "Call initialize with the token first"));
+    }
+    match message["params"]["token"].as_str() {
+        Some(given) if files::token_matches(token, given) => Ok(()),
+        _ => Err(error_response(id, UNAUTHORIZED, "Missing or wrong token")),
+    }
+}
+
+/// Handle a JSON-RPC message, returning a response for requests
+fn handle_message(message: &Value, db: &Mutex<Database>, engine: &SyncHandle) -> Option<Value> {
+    let method = message["method"].as_str().unwrap_or("");
+    let params = &message["params"];
+
+    // Notifications have no id and get no response
+    let id = message.get("id")?.clone();
+    tracing::debug!("Editor request: {}", method);
+
+    let result = match method {
+        "initialize" => Ok(json!({
+            "serverInfo": { "name": "duplex", "version": env!("CARGO_PKG_VERSION") },
+            "methods": METHODS,
+        })),
+        "session/status" => session_status(params, db),
+        "session/sync" => session_sync(params, db, engine),
+        "session/setDoNotSync" => set_do_not_sync(params, db),
+        _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    };
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, &message),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn internal(e: impl std::fmt::Display) -> (i64, String) {
+    (INTERNAL_ERROR, e.to_string())
+}
+
+/// Find the conversation identified by the request params
+fn resolve(params: &Value, db: &Database) -> Result<SyncState, (i64, String)> {
+    let found = if let Some(session_id) = params["sessionId"].as_str() {
+        db.find_session(session_id).map_err(internal)?.map(SessionMatch::into_state)
+    } else if let Some(file_path) = params["filePath"].as_str() {
+        db.get_sync_state(file_path).map_err(internal)?
+    } else if let Some(project_path) = params["projectPath"].as_str() {
+        let project_path = project_path.trim_end_matches(['/', '\\']);
+        db.list_conversations(Some(project_path), 1)
+            .map_err(internal)?
+            .into_iter()
+            .next()
+    } else {
+        return Err((
+            INVALID_PARAMS,
+            "One of sessionId, filePath or projectPath is required".to_string(),
+        ));
+    };
+
+    found.ok_or_else(|| (SESSION_NOT_FOUND, "No synced conversation found".to_string()))
+}
+
+fn session_status(params: &Value, db: &Mutex<Database>) -> RpcResult {
+    let db = db.lock().unwrap();
+    let state = resolve(params, &db)?;
+    let do_not_sync = db.is_do_not_sync(&state.file_path).map_err(internal)?;
+
+    Ok(json!({
+        "sessionId": state.session_id,
+        "filePath": state.file_path,
+        "projectPath": state.project_path,
+        "status": state.status,
+        "workflowId": state.workflow_id,
+        "lastSyncedAt": state.last_synced_at.map(timestamps::to_rfc3339),
+        "doNotSync": do_not_sync,
+    }))
+}
+
+fn session_sync(params: &Value, db: &Mutex<Database>, engine: &SyncHandle) -> RpcResult {
+    let state = {
+        let db = db.lock().unwrap();
+        let state = resolve(params, &db)?;
+        if db.is_do_not_sync(&state.file_path).map_err(internal)? {
+            return Err((
+                INVALID_PARAMS,
+                "Conversation is marked do-not-sync".to_string(),
+            ));
+        }
+        state
+    };
+
+    // Uploads can take a while; answer without waiting, like the tray's Sync Now
+    let engine = engine.clone();
+    let path = PathBuf::from(&state.file_path);
+    tokio::spawn(async move {
+        let queued = engine
+            .call(move |engine| match engine.force_sync(&path) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::error!("Failed to queue {:?} for sync: {}", path, e);
+                    false
+                }
+            })
+            .await;
+        if !queued.unwrap_or(false) {
+            return;
+        }
+        if let Err(e) = engine.sync_now().await {
+            tracing::error!("Sync failed: {}", e);
+        }
+    });
+
+    Ok(json!({ "queued": true, "filePath": state.file_path }))
+}
+
+fn set_do_not_sync(params: &Value, db: &Mutex<Database>) -> RpcResult {
+    let do_not_sync = params["doNotSync"]
+        .as_bool()
+        .ok_or_else(|| (INVALID_PARAMS, "doNotSync (boolean) is required".to_string()))?;
+
+    let db = db.lock().unwrap();
+    let state = resolve(params, &db)?;
+    db.set_do_not_sync(&state.file_path, do_not_sync)
+        .map_err(internal)?;
+
+    Ok(json!({ "filePath": state.file_path, "doNotSync": do_not_sync }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SyncStatus;
+
+    #[test]
+    fn test_resolve_and_do_not_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        db.upsert_sync_state(&SyncState {
+            file_path: "/test/session.jsonl".to_string(),
+            content_hash: "abc".to_string(),
+            last_synced_at: Some(1_735_725_600),
+            last_modified_at: 1,
+            workflow_id: None,
+            status: SyncStatus::Complete,
+            session_id: None,
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        })
+        .unwrap();
+        db.update_metadata("/test/session.jsonl", Some("s1"), Some("/work/app"), "claude-code", None)
+            .unwrap();
+        let db = Mutex::new(db);
+
+        let status = session_status(&json!({ "projectPath": "/work/app/" }), &db).unwrap();
+        assert_eq!(status["sessionId"], "s1");
+        assert_eq!(status["status"], "complete");
+        assert_eq!(status["doNotSync"], false);
+        assert_eq!(status["lastSyncedAt"], "2025-01-01T10:00:00Z");
+
+        set_do_not_sync(&json!({ "sessionId": "s1", "doNotSync": true }), &db).unwrap();
+        let status = session_status(&json!({ "sessionId": "s1" }), &db).unwrap();
+        assert_eq!(status["doNotSync"], true);
+
+        let missing = session_status(&json!({ "sessionId": "nope" }), &db);
+        assert!(matches!(missing, Err((SESSION_NOT_FOUND, _))));
+        assert!(matches!(session_status(&json!({}), &db), Err((INVALID_PARAMS, _))));
+    }
+
+    #[test]
+    fn test_authorize() {
+        let initialize = |params: Value| json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": params });
+        assert!(authorize(&initialize(json!({ "token": "secret" })), "secret").is_ok());
+
+        for message in [
+            initialize(json!({ "token": "secreT" })),
+            initialize(json!({})),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "session/status", "params": { "token": "secret" } }),
+        ] {
+            let response = authorize(&message, "secret").unwrap_err();
+            assert_eq!(response["error"]["code"], UNAUTHORIZED);
+            assert_eq!(response["id"], message["id"]);
+        }
+    }
+}
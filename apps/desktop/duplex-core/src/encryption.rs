@@ -0,0 +1,171 @@
+//! Client-side encryption of uploads
+//!
+//! With `encryption.enabled`, conversation content is sealed on this machine
+//! before it is sent, so the server stores only ciphertext. The key is 32
+//! random bytes, base64-encoded, kept in the keyring (or given in
+//! `DUPLEX_ENCRYPTION_KEY`); it never leaves the machine. Uploads name the
+//! key by its fingerprint so the holder can tell which key opens them.
+//!
+//! Sealed layout: `base64(nonce (12) | ciphertext)`, AES-256-GCM. Titles,
+//! structured messages and appends aren't sent with encrypted uploads, as
+//! the server can't use them; paths, git context and tags still are.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::config::{self, SecureTokenStorage};
+use crate::errors::ErrorCategory;
+
+/// Algorithm named in encrypted uploads
+pub const ALGORITHM: &str = "aes-256-gcm";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("No encryption key; run `duplex encryption init` or `duplex encryption import`")]
+    NoKey,
+    #[error("Invalid encryption key, expected {KEY_LEN} bytes in base64")]
+    InvalidKey,
+    #[error("Config error: {0}")]
+    Config(#[from] config::ConfigError),
+    #[error("Encryption failed")]
+    Seal,
+    #[error("Wrong key or corrupted data")]
+    Open,
+}
+
+impl EncryptionError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            EncryptionError::NoKey => ErrorCategory::Auth,
+            EncryptionError::InvalidKey => ErrorCategory::Config,
+            EncryptionError::Config(e) => e.category(),
+            EncryptionError::Seal => ErrorCategory::Io,
+            EncryptionError::Open => ErrorCategory::Parse,
+        }
+    }
+}
+
+/// Key uploads are sealed with
+#[derive(Clone)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl EncryptionKey {
+    /// A new random key
+    pub fn generate() -> Self {
+        let mut key = [0u8; KEY_LEN];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// Parse a key as stored: 32 bytes in base64
+    pub fn from_base64(encoded: &str) -> Result<Self, EncryptionError> {
+        let bytes = STANDARD.decode(encoded.trim()).map_err(|_| EncryptionError::InvalidKey)?;
+        let key = bytes.try_into().map_err(|_| EncryptionError::InvalidKey)?;
+        Ok(Self(key))
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    /// Short ID of the key, sent with uploads: the first 8 bytes of its
+    /// SHA-256 in hex
+    pub fn fingerprint(&self) -> String {
+        hex::encode(&Sha256::digest(self.0)[..8])
+    }
+
+    /// Hash of `content` that only holders of the key can reproduce
+    ///
+    /// Sent in place of the plain content hash, which would let the server
+    /// confirm guesses at what a conversation says.
+    pub fn content_hash(&self, content: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.0);
+        hasher.update(content.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Encrypt `plaintext` to the sealed layout
+    pub fn seal(&self, plaintext: &[u8]) -> Result<String, EncryptionError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| EncryptionError::Seal)?;
+
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(out))
+    }
+
+    /// Decrypt content sealed with [`Self::seal`]
+    pub fn open(&self, sealed: &str) -> Result<Vec<u8>, EncryptionError> {
+        let data = STANDARD.decode(sealed).map_err(|_| EncryptionError::Open)?;
+        if data.len() < NONCE_LEN {
+            return Err(EncryptionError::Open);
+        }
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0));
+        cipher
+            .decrypt(Nonce::from_slice(&data[..NONCE_LEN]), &data[NONCE_LEN..])
+            .map_err(|_| EncryptionError::Open)
+    }
+}
+
+/// The key to seal uploads with: `DUPLEX_ENCRYPTION_KEY`, else the keyring's
+pub fn load_key() -> Result<EncryptionKey, EncryptionError> {
+    let encoded = match config::env_encryption_key() {
+        Some(key) => key,
+        None => SecureTokenStorage::new()
+            .get_encryption_key()?
+            .ok_or(EncryptionError::NoKey)?,
+    };
+    EncryptionKey::from_base64(&encoded)
+}
+
+/// Save `key` to the keyring for later uploads
+pub fn store_key(key: &EncryptionKey) -> Result<(), EncryptionError> {
+    SecureTokenStorage::new().store_encryption_key(&key.to_base64())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let key = EncryptionKey::generate();
+        let sealed = key.seal(b"refactor the parser").unwrap();
+        assert!(!sealed.contains("parser"));
+        assert_ne!(sealed, key.seal(b"refactor the parser").unwrap());
+        assert_eq!(key.open(&sealed).unwrap(), b"refactor the parser");
+
+        let other = EncryptionKey::generate();
+        assert!(matches!(other.open(&sealed), Err(EncryptionError::Open)));
+        assert!(matches!(key.open("not sealed"), Err(EncryptionError::Open)));
+    }
+
+    #[test]
+    fn test_key_encoding() {
+        let key = EncryptionKey::generate();
+        let parsed = EncryptionKey::from_base64(&format!("{}\n", key.to_base64())).unwrap();
+        assert_eq!(parsed.fingerprint(), key.fingerprint());
+        assert_eq!(key.fingerprint().len(), 16);
+        assert_eq!(parsed.content_hash("a"), key.content_hash("a"));
+        assert_ne!(key.content_hash("a"), EncryptionKey::generate().content_hash("a"));
+
+        assert!(matches!(EncryptionKey::from_base64("c2hvcnQ="), Err(EncryptionError::InvalidKey)));
+        assert!(matches!(EncryptionKey::from_base64("%%%"), Err(EncryptionError::InvalidKey)));
+    }
+}
@@ -0,0 +1,95 @@
+//! Stable error categories
+//!
+//! Error enums across the crate map their variants onto a small, fixed set of
+//! categories so failures can be counted and reported consistently.
+
+use serde::{Deserialize, Serialize};
+
+/// Broad classification of a failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorCategory {
+    /// Connection, DNS or timeout failures
+    Network,
+    /// Missing, expired or rejected credentials
+    Auth,
+    /// The API responded with an error
+    Server,
+    /// Malformed data from a file or the API
+    Parse,
+    /// Local filesystem or database failures
+    Io,
+    /// Missing or invalid configuration
+    Config,
+}
+
+impl ErrorCategory {
+    /// All categories, in reporting order
+    pub const ALL: [ErrorCategory; 6] = [
+        ErrorCategory::Network,
+        ErrorCategory::Auth,
+        ErrorCategory::Server,
+        ErrorCategory::Parse,
+        ErrorCategory::Io,
+        ErrorCategory::Config,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Network => "network",
+            ErrorCategory::Auth => "auth",
+            ErrorCategory::Server => "server",
+            ErrorCategory::Parse => "parse",
+            ErrorCategory::Io => "io",
+            ErrorCategory::Config => "config",
+        }
+    }
+
+    /// What a user can do about a failure of this kind, for errors with no
+    /// more specific advice
+    pub fn user_hint(&self) -> &'static str {
+        match self {
+            ErrorCategory::Network => "Check your internet connection, and DUPLEX_API_URL if you set it",
+            ErrorCategory::Auth => "Run `duplex auth login` to sign in again",
+            ErrorCategory::Server => "The Duplex service had a problem; queued changes are retried automatically",
+            ErrorCategory::Parse => "The file is not in a format Duplex understands; it is tried again when it changes",
+            ErrorCategory::Io => "Check free disk space and permissions on the Duplex config directory",
+            ErrorCategory::Config => "Check config.jsonc in the Duplex config directory for mistakes",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Classify an HTTP client error
+pub fn classify_http(error: &reqwest::Error) -> ErrorCategory {
+    if error.is_decode() {
+        ErrorCategory::Parse
+    } else if error.is_status() {
+        match error.status() {
+            Some(status) if status.as_u16() == 401 || status.as_u16() == 403 => ErrorCategory::Auth,
+            _ => ErrorCategory::Server,
+        }
+    } else if error.is_builder() {
+        ErrorCategory::Config
+    } else {
+        ErrorCategory::Network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_names_match_serialization() {
+        for category in ErrorCategory::ALL {
+            let json = serde_json::to_string(&category).unwrap();
+            assert_eq!(json, format!("\"{}\"", category.as_str()));
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Conversation export
+//!
+//! Renders parsed conversations into Markdown or standalone HTML for sharing
+//! outside the platform. Tool calls are collapsed into `<details>` blocks so
+//! the dialogue stays readable.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::cache;
+use crate::db::Database;
+use crate::parsers::{Conversation, ConversationParser, Message, ParserRegistry};
+use crate::timestamps;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("No conversation found for: {0}")]
+    NotFound(String),
+    #[error("No parser found for: {0}")]
+    NoParser(String),
+    #[error("Parser error: {0}")]
+    Parser(#[from] crate::parsers::ParserError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// Output format for an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    /// File extension for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            other => Err(format!("Unknown export format: {} (use markdown or html)", other)),
+        }
+    }
+}
+
+/// Export a conversation given its session ID or file path
+///
+/// A copy in the content cache is used when there is one, so conversations
+/// whose source file was pruned can still be exported.
+pub fn export_conversation(
+    registry: &ParserRegistry,
+    db: &Database,
+    id_or_path: &str,
+    format: ExportFormat,
+) -> Result<String, ExportError> {
+    let (path, source, session_id) = match db.find_session(id_or_path)? {
+        Some(found) => {
+            let session_id = found.session_in_file().map(str::to_string);
+            (PathBuf::from(found.state.file_path), found.state.source, session_id)
+        }
+        None => {
+            let path = PathBuf::from(id_or_path);
+            let state = db.get_sync_state(&path.to_string_lossy())?;
+            if !path.is_file() && state.is_none() {
+                return Err(ExportError::NotFound(id_or_path.to_string()));
+            }
+            (path, state.and_then(|s| s.source), None)
+        }
+    };
+
+    match cache::load(db, &path, session_id.as_deref()) {
+        Ok(Some(conversation)) => {
+            let messages = registry
+                .get(&conversation.source)
+                .and_then(|parser| parser.parse_messages(&conversation.content))
+                .map(timestamps::normalized);
+            return Ok(render(&conversation, messages.as_deref(), format));
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Could not read cached copy of {:?}: {}", path, e),
+    }
+
+    let parser = source
+        .as_deref()
+        .and_then(|name| registry.get(name))
+        .or_else(|| registry.detect(&path))
+        .ok_or_else(|| ExportError::NoParser(path.to_string_lossy().to_string()))?;
+
+    match session_id {
+        Some(session_id) => export_session(parser, &path, &session_id, format),
+        None => export_file(parser, &path, format),
+    }
+}
+
+/// Parse a conversation file and render it in the given format
+pub fn export_file(
+    parser: &dyn ConversationParser,
+    path: &Path,
+    format: ExportFormat,
+) -> Result<String, ExportError> {
+    let conversation = parser.parse(path)?;
+    let messages = parser.parse_messages(&conversation.content).map(timestamps::normalized);
+    Ok(render(&conversation, messages.as_deref(), format))
+}
+
+/// Render one of several conversations in a file
+fn export_session(
+    parser: &dyn ConversationParser,
+    path: &Path,
+    session_id: &str,
+    format: ExportFormat,
+) -> Result<String, ExportError> {
+    let conversation = parser
+        .parse_all(path)?
+        .into_iter()
+        .find(|conversation| conversation.session_id.as_deref() == Some(session_id))
+        .ok_or_else(|| ExportError::NotFound(session_id.to_string()))?;
+    let messages = parser.parse_messages(&conversation.content).map(timestamps::normalized);
+    Ok(render(&conversation, messages.as_deref(), format))
+}
+
+/// Render a conversation
+///
+/// When the parser could not split the conversation into messages, the raw
+/// content is included as-is.
+pub fn render(conversation: &Conversation, messages: Option<&[Message]>, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Markdown => render_markdown(conversation, messages),
+        ExportFormat::Html => render_html(conversation, messages),
+    }
+}
+
+fn title(conversation: &Conversation) -> String {
+    match (&conversation.title, &conversation.session_id) {
+        (Some(title), _) => title.clone(),
+        (None, Some(id)) => format!("Conversation {}", id),
+        (None, None) => "Conversation".to_string(),
+    }
+}
+
+fn role_label(role: &str) -> &str {
+    match role {
+        "user" => "User",
+        "assistant" => "Assistant",
+        "system" => "System",
+        "tool" => "Tool output",
+        other => other,
+    }
+}
+
+fn render_markdown(conversation: &Conversation, messages: Option<&[Message]>) -> String {
+    let mut out = format!("# {}\n\n", title(conversation));
+    out.push_str(&format!("- **Source:** {}\n", conversation.source));
+    if let Some(project) = &conversation.project_path {
+        out.push_str(&format!("- **Project:** `{}`\n", project.display()));
+    }
+    out.push('\n');
+
+    let Some(messages) = messages else {
+        out.push_str("```\n");
+        out.push_str(&conversation.content);
+        out.push_str("\n```\n");
+        return out;
+    };
+
+    for message in messages {
+        out.push_str(&format!("## {}", role_label(&message.role)));
+        if let Some(ts) = &message.timestamp {
+            out.push_str(&format!(" · {}", ts));
+        }
+        out.push_str("\n\n");
+
+        if message.role == "tool" {
+            out.push_str("<details>\n<summary>Output</summary>\n\n```\n");
+            out.push_str(&message.content);
+            out.push_str("\n```\n\n</details>\n\n");
+        } else if !message.content.is_empty() {
+            out.push_str(&message.content);
+            out.push_str("\n\n");
+        }
+
+        for call in &message.tool_calls {
+            out.push_str(&format!(
+                "<details>\n<summary>Tool call: {}</summary>\n\n```json\n{}\n```\n\n</details>\n\n",
+                call.name, call.input
+            ));
+        }
+    }
+
+    out
+}
+
+fn render_html(conversation: &Conversation, messages: Option<&[Message]>) -> String {
+    let title = escape_html(&title(conversation));
+    let mut body = format!("<h1>{}</h1>\n<ul class=\"meta\">\n", title);
+    body.push_str(&format!(
+        "<li><strong>Source:</strong> {}</li>\n",
+        escape_html(&conversation.source)
+    ));
+    if let Some(project) = &conversation.project_path {
+        body.push_str(&format!(
+            "<li><strong>Project:</strong> <code>{}</code></li>\n",
+            escape_html(&project.to_string_lossy())
+        ));
+    }
+    body.push_str("</ul>\n");
+
+    match messages {
+        None => {
+            body.push_str(&format!("<pre>{}</pre>\n", escape_html(&conversation.content)));
+        }
+        Some(messages) => {
+            for message in messages {
+                body.push_str(&format!(
+                    "<section class=\"message {}\">\n<h2>{}",
+                    escape_html(&message.role),
+                    escape_html(role_label(&message.role))
+                ));
+                if let Some(ts) = &message.timestamp {
+                    body.push_str(&format!(" <time>{}</time>", escape_html(ts)));
+                }
+                body.push_str("</h2>\n");
+
+                if message.role == "tool" {
+                    body.push_str(&format!(
+                        "<details><summary>Output</summary><pre>{}</pre></details>\n",
+                        escape_html(&message.content)
+                    ));
+                } else if !message.content.is_empty() {
+                    body.push_str(&format!("<pre class=\"text\">{}</pre>\n", escape_html(&message.content)));
+                }
+
+                for call in &message.tool_calls {
+                    body.push_str(&format!(
+                        "<details><summary>Tool call: {}</summary><pre>{}</pre></details>\n",
+                        escape_html(&call.name),
+                        escape_html(&call.input)
+                    ));
+                }
+
+                body.push_str("</section>\n");
+            }
+        }
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+body {{ font-family: system-ui; max-width: 860px; margin: 40px auto; padding: 0 20px; color: #1f2328; }}
+.message {{ border-top: 1px solid #d0d7de; padding: 8px 0; }}
+.message h2 {{ font-size: 1em; margin: 8px 0; }}
+.message time {{ color: #656d76; font-weight: normal; font-size: 0.9em; }}
+.message.user h2 {{ color: #0969da; }}
+pre {{ white-space: pre-wrap; word-break: break-word; background: #f6f8fa; padding: 8px; border-radius: 6px; }}
+pre.text {{ background: none; padding: 0; font-family: inherit; }}
+details {{ margin: 6px 0; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        title, body
+    )
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::{ContentType, ToolCall};
+
+    fn conversation() -> Conversation {
+        Conversation {
+            source_path: PathBuf::from("/tmp/session.jsonl"),
+            source: "claude-code".to_string(),
+            session_id: Some("abc".to_string()),
+            project_path: Some(PathBuf::from("/work/app")),
+            content: "raw <content>".to_string(),
+            content_type: ContentType::Conversation,
+            title: None,
+        }
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message {
+                role: "user".to_string(),
+                content: "Fix the <bug>".to_string(),
+                timestamp: Some("2025-01-01T10:00:00Z".to_string()),
+                utc_offset: None,
+                tool_calls: vec![],
+                model: None,
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: "On it.".to_string(),
+                timestamp: None,
+                utc_offset: None,
+                tool_calls: vec![ToolCall {
+                    name: "Bash".to_string(),
+                    input: r#"{"command":"ls"}"#.to_string(),
+                }],
+                model: Some("claude-sonnet-4-5".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let md = render(&conversation(), Some(&messages()), ExportFormat::Markdown);
+        assert!(md.starts_with("# Conversation abc\n"));
+        assert!(md.contains("## User · 2025-01-01T10:00:00Z"));
+        assert!(md.contains("<summary>Tool call: Bash</summary>"));
+
+        let raw = render(&conversation(), None, ExportFormat::Markdown);
+        assert!(raw.contains("```\nraw <content>\n```"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_content() {
+        let html = render(&conversation(), Some(&messages()), ExportFormat::Html);
+        assert!(html.contains("Fix the &lt;bug&gt;"));
+        assert!(!html.contains("<bug>"));
+        assert!(html.contains("<summary>Tool call: Bash</summary>"));
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("md".parse::<ExportFormat>().unwrap(), ExportFormat::Markdown);
+        assert_eq!("HTML".parse::<ExportFormat>().unwrap(), ExportFormat::Html);
+        assert!("pdf".parse::<ExportFormat>().is_err());
+    }
+}
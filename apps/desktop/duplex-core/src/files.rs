@@ -0,0 +1,118 @@
+//! Crash-safe writes for local state
+//!
+//! Credentials, config and the files kept beside the database are written
+//! to a temporary file in the same directory and renamed over the target,
+//! so a crash mid-write leaves either the old file or the new one, never a
+//! truncated mix. On Unix they are readable by the owner only.
+
+use std::io::Write;
+use std::path::Path;
+
+/// Write `contents` to `path` atomically, creating its directory if needed
+pub fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let temp = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+    let result = write_synced(&temp, contents.as_ref()).and_then(|()| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Make an existing file readable by its owner only
+#[cfg(unix)]
+pub fn restrict_permissions(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+}
+
+/// Make an existing file readable by its owner only; other platforms
+/// rely on the per-user data directory
+#[cfg(not(unix))]
+pub fn restrict_permissions(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// The access token kept in `path`, created at random on first use
+///
+/// Local servers require it of their clients: a client that can read the
+/// file runs as the same user as the app.
+pub fn private_token(path: &Path) -> std::io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut bytes = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut bytes);
+    let token = hex::encode(bytes);
+    write_private(path, &token)?;
+    Ok(token)
+}
+
+/// Whether `given` is `token`, compared in constant time
+pub fn token_matches(token: &str, given: &str) -> bool {
+    token.len() == given.len() && token.bytes().zip(given.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("credentials.json");
+
+        write_private(&path, "{\"v\":1}").unwrap();
+        write_private(&path, "{\"v\":2}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"v\":2}");
+
+        // Only the target is left behind
+        let entries: Vec<_> = std::fs::read_dir(path.parent().unwrap()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_private_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local_api.token");
+
+        let token = private_token(&path).unwrap();
+        assert_eq!(token.len(), 64);
+        assert_eq!(private_token(&path).unwrap(), token);
+
+        assert!(token_matches(&token, &token));
+        assert!(!token_matches(&token, &token[1..]));
+        assert!(!token_matches(&token, &token.replace(&token[..1], "x")));
+    }
+}
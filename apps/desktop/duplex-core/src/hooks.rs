@@ -0,0 +1,116 @@
+//! Post-sync automation hooks
+//!
+//! Commands from `hooks.onSyncComplete` run through the system shell after a
+//! conversation uploads successfully. Placeholders in the template are
+//! replaced with shell-quoted values, and the same values are exported as
+//! `DUPLEX_*` environment variables for scripts that prefer them.
+
+use std::process::Command;
+
+/// Details of a completed sync passed to hooks
+#[derive(Debug, Clone, Default)]
+pub struct SyncCompleteEvent {
+    pub file: String,
+    pub project: Option<String>,
+    pub workflow_id: String,
+    pub session_id: Option<String>,
+    pub source: String,
+}
+
+impl SyncCompleteEvent {
+    /// Template placeholders, environment variable names and values
+    fn variables(&self) -> [(&'static str, &'static str, &str); 5] {
+        [
+            ("{file}", "DUPLEX_FILE", &self.file),
+            ("{project}", "DUPLEX_PROJECT", self.project.as_deref().unwrap_or("")),
+            ("{workflowId}", "DUPLEX_WORKFLOW_ID", &self.workflow_id),
+            ("{sessionId}", "DUPLEX_SESSION_ID", self.session_id.as_deref().unwrap_or("")),
+            ("{source}", "DUPLEX_SOURCE", &self.source),
+        ]
+    }
+}
+
+/// Run every `onSyncComplete` hook in the background
+///
+/// Hooks never block or fail the sync; their failures are only logged.
+pub fn run_sync_complete(commands: &[String], event: &SyncCompleteEvent) {
+    for template in commands {
+        let command = render(template, event);
+        let env: Vec<(&str, String)> = event
+            .variables()
+            .iter()
+            .map(|(_, name, value)| (*name, value.to_string()))
+            .collect();
+
+        std::thread::spawn(move || {
+            tracing::debug!("Running onSyncComplete hook: {}", command);
+            match shell(&command).envs(env).output() {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => tracing::warn!(
+                    "onSyncComplete hook exited with {}: {} ({})",
+                    output.status,
+                    command,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => tracing::warn!("Failed to run onSyncComplete hook {}: {}", command, e),
+            }
+        });
+    }
+}
+
+/// Substitute placeholders with shell-quoted values
+fn render(template: &str, event: &SyncCompleteEvent) -> String {
+    event
+        .variables()
+        .iter()
+        .fold(template.to_string(), |command, (placeholder, _, value)| {
+            command.replace(placeholder, &quote(value))
+        })
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(target_os = "windows")]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Quote a value so the shell passes it through as a single argument
+#[cfg(not(target_os = "windows"))]
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(target_os = "windows")]
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_render_quotes_values() {
+        let event = SyncCompleteEvent {
+            file: "/home/me/it's here.jsonl".to_string(),
+            project: None,
+            workflow_id: "wf-1".to_string(),
+            session_id: Some("s1".to_string()),
+            source: "claude-code".to_string(),
+        };
+
+        assert_eq!(
+            render("echo {workflowId} {file} {project} >> log", &event),
+            r"echo 'wf-1' '/home/me/it'\''s here.jsonl' '' >> log"
+        );
+    }
+}
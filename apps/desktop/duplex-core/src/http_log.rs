@@ -0,0 +1,230 @@
+//! Debug logging for API requests
+//!
+//! When enabled, every request sent by the sync engine is logged to the
+//! `duplex::http` target with its URL, headers, payload size, duration and
+//! status. Credentials are redacted before anything is written.
+
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Request, Response, StatusCode};
+use std::time::Instant;
+
+/// Log target for request logging
+pub const LOG_TARGET: &str = "duplex::http";
+
+/// Environment variable that enables request logging regardless of config
+pub const LOG_REQUESTS_ENV: &str = "DUPLEX_LOG_REQUESTS";
+
+/// Headers whose values are never logged
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-duplex-signature",
+];
+
+/// Query parameters whose values are never logged (matched case-insensitively)
+const REDACTED_QUERY_PARAMS: &[&str] = &[
+    "x-amz-signature",
+    "x-amz-credential",
+    "x-amz-security-token",
+    "signature",
+    "token",
+    "access_token",
+    "code",
+    "key",
+];
+
+/// Maximum number of response body bytes logged on error
+const MAX_LOGGED_BODY: usize = 4096;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Logs API requests and responses when debug logging is enabled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLogger {
+    enabled: bool,
+}
+
+impl RequestLogger {
+    /// Create a logger, enabled by config or the `DUPLEX_LOG_REQUESTS` env var
+    pub fn new(config_enabled: bool) -> Self {
+        let env_enabled = std::env::var(LOG_REQUESTS_ENV)
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Self {
+            enabled: config_enabled || env_enabled,
+        }
+    }
+
+    /// Execute a request, logging its metadata and outcome
+    pub async fn execute(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+        if !self.enabled {
+            return client.execute(request).await;
+        }
+
+        let method = request.method().clone();
+        let url = redact_url(request.url());
+        let headers = redact_headers(request.headers());
+        let payload_size = request
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| b.len())
+            .unwrap_or(0);
+
+        tracing::info!(
+            target: LOG_TARGET,
+            "--> {} {} ({} bytes) headers={:?}",
+            method,
+            url,
+            payload_size,
+            headers
+        );
+
+        let start = Instant::now();
+        let result = client.execute(request).await;
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(response) => {
+                tracing::info!(
+                    target: LOG_TARGET,
+                    "<-- {} {} {} {:?} in {}ms",
+                    method,
+                    url,
+                    response.status(),
+                    response.version(),
+                    elapsed.as_millis()
+                );
+            }
+            Err(e) => {
+                tracing::info!(
+                    target: LOG_TARGET,
+                    "<-- {} {} failed after {}ms: {}",
+                    method,
+                    url,
+                    elapsed.as_millis(),
+                    e
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Log the body of an unsuccessful response
+    pub fn log_error_body(&self, url: &str, status: StatusCode, body: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        let url = url::Url::parse(url)
+            .map(|u| redact_url(&u))
+            .unwrap_or_else(|_| url.to_string());
+
+        tracing::info!(
+            target: LOG_TARGET,
+            "<-- {} {} body: {}",
+            status,
+            url,
+            truncate_body(body)
+        );
+    }
+}
+
+/// Render headers as name/value pairs with credentials removed
+fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str().to_string();
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                value.to_str().unwrap_or("<binary>").to_string()
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+/// Render a URL with signatures and tokens removed from the query string
+fn redact_url(url: &url::Url) -> String {
+    if url.query().is_none() {
+        return url.to_string();
+    }
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| {
+            let lower = k.to_ascii_lowercase();
+            let value = if REDACTED_QUERY_PARAMS.contains(&lower.as_str()) {
+                REDACTED.to_string()
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), value)
+        })
+        .collect();
+
+    let mut redacted = url.clone();
+    redacted.query_pairs_mut().clear().extend_pairs(pairs);
+    redacted.to_string()
+}
+
+/// Truncate a response body to a loggable size
+fn truncate_body(body: &str) -> String {
+    if body.len() <= MAX_LOGGED_BODY {
+        return body.to_string();
+    }
+
+    let mut end = MAX_LOGGED_BODY;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes total)", &body[..end], body.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_redact_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+
+        let redacted = redact_headers(&headers);
+        assert!(redacted.contains(&("authorization".to_string(), REDACTED.to_string())));
+        assert!(redacted.contains(&("content-type".to_string(), "application/json".to_string())));
+    }
+
+    #[test]
+    fn test_redact_url() {
+        let url = url::Url::parse(
+            "https://r2.example.com/upload?X-Amz-Signature=abc123&X-Amz-Expires=3600",
+        )
+        .unwrap();
+
+        let redacted = redact_url(&url);
+        assert!(!redacted.contains("abc123"));
+        assert!(redacted.contains("X-Amz-Expires=3600"));
+
+        let plain = url::Url::parse("https://api.example.com/extraction/upload-url").unwrap();
+        assert_eq!(redact_url(&plain), "https://api.example.com/extraction/upload-url");
+    }
+
+    #[test]
+    fn test_truncate_body() {
+        assert_eq!(truncate_body("short"), "short");
+
+        let long = "x".repeat(MAX_LOGGED_BODY + 10);
+        let truncated = truncate_body(&long);
+        assert!(truncated.starts_with(&"x".repeat(MAX_LOGGED_BODY)));
+        assert!(truncated.ends_with(&format!("({} bytes total)", long.len())));
+    }
+}
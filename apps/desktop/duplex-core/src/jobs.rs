@@ -0,0 +1,269 @@
+//! Periodic background jobs
+//!
+//! Features with work to repeat (reconciliation, pruning, polling) register
+//! a [`Job`] with the app's [`Scheduler`] instead of starting their own
+//! thread and sleep loop. One scheduler thread runs every job in turn:
+//! - each job runs on a fixed interval or daily at a local time, plus an
+//!   optional random jitter so machines don't run it in lockstep
+//! - no job runs while the system sleeps or the app is shutting down, and
+//!   jobs marked [`Job::pause_on_battery`] wait for mains power, as set by
+//!   the `power` config section (see [`power`](crate::power))
+//! - run counts, failures and timings per job are kept in memory and
+//!   reported through the control socket, like [`metrics`](crate::metrics)
+
+use chrono::{Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::PowerConfig;
+use crate::power;
+use crate::shutdown::SharedShutdown;
+
+/// How often the scheduler thread looks for due jobs
+const TICK: Duration = Duration::from_millis(250);
+
+/// Status of every registered job, by name
+static STATUSES: Mutex<BTreeMap<&'static str, JobStatus>> = Mutex::new(BTreeMap::new());
+
+/// Outcome of one run; the error is logged and kept in the job's status
+pub type JobResult = Result<(), Box<dyn std::error::Error>>;
+
+/// When a job runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cadence {
+    /// Repeatedly, this long after the previous run
+    Every(Duration),
+    /// Once a day at this local time
+    Daily(NaiveTime),
+}
+
+impl Cadence {
+    /// Time from `now` until the next run
+    fn until_next(self, now: chrono::DateTime<Local>) -> Duration {
+        match self {
+            Cadence::Every(interval) => interval,
+            Cadence::Daily(time) => {
+                let today = now.date_naive().and_time(time);
+                let next = if today > now.naive_local() { today } else { today + chrono::Duration::days(1) };
+                // A time skipped by a DST change runs an hour later
+                let next = Local
+                    .from_local_datetime(&next)
+                    .earliest()
+                    .unwrap_or_else(|| now + chrono::Duration::hours(1));
+                (next - now).to_std().unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// A unit of periodic work
+pub struct Job {
+    name: &'static str,
+    cadence: Cadence,
+    jitter: Duration,
+    pause_on_battery: bool,
+    run: Box<dyn FnMut() -> JobResult + Send>,
+}
+
+impl Job {
+    pub fn new(name: &'static str, cadence: Cadence, run: impl FnMut() -> JobResult + Send + 'static) -> Self {
+        Self {
+            name,
+            cadence,
+            jitter: Duration::ZERO,
+            pause_on_battery: false,
+            run: Box::new(run),
+        }
+    }
+
+    /// Delay each run, the first included, by a random amount up to `jitter`
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Hold the job while the machine runs on battery or in low power mode
+    pub fn pause_on_battery(mut self) -> Self {
+        self.pause_on_battery = true;
+        self
+    }
+
+    /// When the run after one at `now` should start
+    fn next_due(&self, now: Instant) -> Instant {
+        let jitter = self.jitter.mul_f64(rand::random::<f64>());
+        now + self.cadence.until_next(Local::now()) + jitter
+    }
+}
+
+/// Counters and timings for one job, as reported over the control socket
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    pub name: String,
+    pub runs: u64,
+    pub failures: u64,
+    /// Runs held back by battery power or low power mode
+    pub skipped: u64,
+    /// Unix seconds
+    pub last_run_at: Option<i64>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+    /// Unix seconds
+    pub next_run_at: Option<i64>,
+}
+
+/// Status of every registered job
+pub fn statuses() -> Vec<JobStatus> {
+    STATUSES.lock().unwrap().values().cloned().collect()
+}
+
+fn update_status(name: &'static str, update: impl FnOnce(&mut JobStatus)) {
+    let mut statuses = STATUSES.lock().unwrap();
+    let status = statuses.entry(name).or_insert_with(|| JobStatus {
+        name: name.to_string(),
+        ..Default::default()
+    });
+    update(status);
+}
+
+/// Unix seconds for an instant, for reporting
+fn unix_at(at: Instant) -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let offset = at.saturating_duration_since(Instant::now());
+    (now + offset).as_secs() as i64
+}
+
+/// Runs registered jobs on one background thread
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<(Job, Instant)>,
+    power: PowerConfig,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a job; its first run is one cadence (plus jitter) from now
+    pub fn register(&mut self, job: Job) {
+        let due = job.next_due(Instant::now());
+        update_status(job.name, |status| status.next_run_at = Some(unix_at(due)));
+        tracing::debug!("Registered job: {}", job.name);
+        self.jobs.push((job, due));
+    }
+
+    /// When jobs marked [`Job::pause_on_battery`] are held
+    pub fn set_power(&mut self, config: PowerConfig) {
+        self.power = config;
+    }
+
+    /// Run the jobs on a thread that stops with `shutdown`
+    pub fn start(mut self, shutdown: &SharedShutdown) {
+        let pause = shutdown.clone();
+        let power = self.power.clone();
+        shutdown.spawn("scheduler", move |token| {
+            while !token.is_cancelled() {
+                std::thread::sleep(TICK);
+                if !pause.is_paused() {
+                    self.run_due(Instant::now(), || power::is_constrained(&power));
+                }
+            }
+            tracing::info!("Stopped running jobs");
+        });
+    }
+
+    /// Run every job due at `now`, returning how many ran
+    fn run_due(&mut self, now: Instant, constrained: impl Fn() -> bool) -> usize {
+        let mut ran = 0;
+        for (job, due) in &mut self.jobs {
+            if *due > now {
+                continue;
+            }
+            // Catch up once rather than once per missed run
+            *due = job.next_due(now);
+            let next_run_at = unix_at(*due);
+
+            if job.pause_on_battery && constrained() {
+                tracing::debug!("On battery or in low power mode, skipping job: {}", job.name);
+                update_status(job.name, |status| {
+                    status.skipped += 1;
+                    status.next_run_at = Some(next_run_at);
+                });
+                continue;
+            }
+
+            let started = Instant::now();
+            let result = (job.run)();
+            let elapsed = started.elapsed();
+            if let Err(e) = &result {
+                tracing::warn!("Job {} failed: {}", job.name, e);
+            }
+            update_status(job.name, |status| {
+                status.runs += 1;
+                status.last_run_at = Some(unix_at(started));
+                status.last_duration_ms = Some(elapsed.as_millis() as u64);
+                status.next_run_at = Some(next_run_at);
+                match &result {
+                    Ok(()) => status.last_error = None,
+                    Err(e) => {
+                        status.failures += 1;
+                        status.last_error = Some(e.to_string());
+                    }
+                }
+            });
+            ran += 1;
+        }
+        ran
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_daily_cadence() {
+        let morning = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let now = Local.with_ymd_and_hms(2026, 3, 10, 8, 30, 0).unwrap();
+        assert_eq!(Cadence::Daily(morning).until_next(now), Duration::from_secs(30 * 60));
+
+        let later = Local.with_ymd_and_hms(2026, 3, 10, 9, 0, 0).unwrap();
+        assert!(Cadence::Daily(morning).until_next(later) > Duration::from_secs(22 * 3600));
+    }
+
+    #[test]
+    fn test_run_due() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let mut scheduler = Scheduler::new();
+        scheduler.register(Job::new("test-every-minute", Cadence::Every(Duration::from_secs(60)), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err("no network".into())
+        }));
+        scheduler.register(
+            Job::new("test-on-mains", Cadence::Every(Duration::from_secs(60)), || Ok(())).pause_on_battery(),
+        );
+
+        let start = Instant::now();
+        assert_eq!(scheduler.run_due(start, || true), 0);
+
+        // Missed runs are caught up once
+        let late = start + Duration::from_secs(5 * 60);
+        assert_eq!(scheduler.run_due(late, || true), 1);
+        assert_eq!(scheduler.run_due(late, || true), 0);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let statuses = statuses();
+        let status = |name: &str| statuses.iter().find(|s| s.name == name).unwrap().clone();
+        let failing = status("test-every-minute");
+        assert_eq!((failing.runs, failing.failures), (1, 1));
+        assert_eq!(failing.last_error.as_deref(), Some("no network"));
+        let held = status("test-on-mains");
+        assert_eq!((held.runs, held.skipped), (0, 1));
+    }
+}
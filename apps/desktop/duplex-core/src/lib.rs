@@ -0,0 +1,59 @@
+//! Duplex Stream core: conversation parsers, sync state, the sync engine,
+//! file watching, config and auth. The desktop app and CLI are frontends
+//! over this crate and it has no GUI dependencies.
+
+pub mod accessibility;
+pub mod api;
+pub mod auth;
+pub mod cache;
+pub mod config;
+pub mod control;
+pub mod db;
+pub mod diff;
+pub mod editor;
+pub mod encryption;
+pub mod errors;
+pub mod explain;
+pub mod export;
+pub mod files;
+pub mod git;
+pub mod hooks;
+pub mod http_log;
+pub mod jobs;
+pub mod local_api;
+pub mod logging;
+pub mod machine;
+pub mod mcp;
+pub mod metrics;
+pub mod migrate;
+pub mod oauth;
+pub mod pairing;
+pub mod parsers;
+pub mod policy;
+pub mod power;
+pub mod projects;
+pub mod recordings;
+pub mod replay;
+pub mod resync;
+pub mod retention;
+pub mod schedule;
+pub mod selftest;
+pub mod shutdown;
+pub mod signing;
+pub mod stats;
+pub mod sync;
+pub mod team_stats;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+pub mod timestamps;
+pub mod token_manager;
+pub mod uninstall;
+pub mod usage;
+pub mod watcher;
+pub mod worklog;
+
+// Re-export for Tauri
+pub use config::Config;
+pub use db::Database;
+pub use sync::SyncEngine;
+pub use watcher::FileWatcher;
@@ -0,0 +1,328 @@
+//! Local read-only REST API
+//!
+//! Serves what the app knows about synced conversations on a localhost port
+//! so scripts and launcher extensions can query it without touching the
+//! remote backend. Disabled unless `localApi.enabled` is set.
+//!
+//! Every request needs `Authorization: Bearer <token>`, the token in
+//! `local_api.token` in the config directory (readable by the owner only).
+//! Requests must also name the server itself in `Host` and come from no
+//! other web page, so a page that rebinds its domain to 127.0.0.1 can't read
+//! transcripts through the browser.
+//!
+//! Routes:
+//! - `GET /status` - sync status counts and error counts
+//! - `GET /projects` - conversation totals per project
+//!
+//! Projects and conversations carry `projectName`, the project's alias (see
+//! [`crate::projects`]) or its path when it has none.
+//!
+//! - `GET /conversations?project=<path>&limit=<n>` - recent conversations
+//! - `GET /conversations/:id` - a single conversation by session ID, with
+//!   what the server produced for it
+//! - `GET /errors?limit=<n>` - failed uploads with the server's response, and
+//!   sessions another machine uploaded a newer version of
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+use crate::cache;
+use crate::config::{self, ProjectsConfig};
+use crate::db::Database;
+use crate::files;
+use crate::metrics;
+use crate::projects::ProjectNames;
+
+/// Default number of conversations returned by `/conversations`
+const DEFAULT_LIST_LIMIT: usize = 100;
+
+/// Maximum number of conversations returned by `/conversations`
+const MAX_LIST_LIMIT: usize = 1000;
+
+/// Who may call the server: a client on this machine holding the token
+struct Access {
+    port: u16,
+    token: String,
+}
+
+impl Access {
+    /// Why the request is refused, if it is
+    fn check<B>(&self, req: &Request<B>) -> Result<(), (StatusCode, &'static str)> {
+        let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+        let local = |authority: &str| {
+            [format!("127.0.0.1:{}", self.port), format!("localhost:{}", self.port)]
+                .iter()
+                .any(|allowed| authority.eq_ignore_ascii_case(allowed))
+        };
+
+        if !header(hyper::header::HOST).is_some_and(local) {
+            return Err((StatusCode::FORBIDDEN, "Unexpected Host header"));
+        }
+        if let Some(origin) = header(hyper::header::ORIGIN) {
+            if !origin.strip_prefix("http://").is_some_and(local) {
+                return Err((StatusCode::FORBIDDEN, "Cross-origin requests are not allowed"));
+            }
+        }
+        let given = header(hyper::header::AUTHORIZATION).and_then(|v| v.strip_prefix("Bearer "));
+        if !given.is_some_and(|given| files::token_matches(&self.token, given.trim())) {
+            return Err((StatusCode::UNAUTHORIZED, "Missing or wrong bearer token"));
+        }
+        Ok(())
+    }
+}
+
+/// Run the local API server until the process exits
+pub async fn serve(port: u16, projects: ProjectsConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db = Arc::new(Mutex::new(Database::open()?));
+    let projects = Arc::new(projects);
+    let token = files::private_token(&config::get_local_api_token_path()?)?;
+    let access = Arc::new(Access { port, token });
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("Local API listening on http://{}", addr);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (db, projects, access) = (db.clone(), projects.clone(), access.clone());
+        let io = TokioIo::new(stream);
+
+        tokio::spawn(async move {
+            let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                let (db, projects, access) = (db.clone(), projects.clone(), access.clone());
+                async move { Ok::<_, hyper::Error>(handle(req, &db, &projects, &access)) }
+            });
+
+            if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
+                tracing::debug!("Local API connection error: {}", e);
+            }
+        });
+    }
+}
+
+/// Handle a single request
+fn handle<B>(
+    req: Request<B>,
+    db: &Mutex<Database>,
+    projects: &ProjectsConfig,
+    access: &Access,
+) -> Response<Full<Bytes>> {
+    let params: HashMap<String, String> = url::form_urlencoded::parse(
+        req.uri().query().unwrap_or("").as_bytes(),
+    )
+    .into_owned()
+    .collect();
+
+    let (status, body) = if let Err((status, error)) = access.check(&req) {
+        (status, serde_json::json!({ "error": error }))
+    } else if req.method() != Method::GET {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            serde_json::json!({ "error": "Only GET is supported" }),
+        )
+    } else {
+        let db = db.lock().unwrap();
+        route(req.uri().path(), &params, &db, projects)
+    };
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+/// Resolve a path to a JSON response
+fn route(
+    path: &str,
+    params: &HashMap<String, String>,
+    db: &Database,
+    projects: &ProjectsConfig,
+) -> (StatusCode, serde_json::Value) {
+    let names = match ProjectNames::load(projects, db) {
+        Ok(names) => names,
+        Err(e) => {
+            tracing::warn!("Could not read project aliases: {}", e);
+            ProjectNames::from_config(projects)
+        }
+    };
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let result = match segments.as_slice() {
+        ["status"] => db.get_status_counts().map(|counts| {
+            Some(serde_json::json!({
+                "sync": counts,
+                "errors": metrics::error_counts(),
+                "changes": metrics::change_counts(),
+            }))
+        }),
+        ["projects"] => db.list_projects().map(|projects| {
+            let projects: Vec<_> = projects.iter().map(|project| named(&names, project)).collect();
+            Some(serde_json::json!({ "projects": projects }))
+        }),
+        ["conversations"] => {
+            let limit = params
+                .get("limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(DEFAULT_LIST_LIMIT)
+                .min(MAX_LIST_LIMIT);
+
+            db.list_conversations(params.get("project").map(|p| p.as_str()), limit)
+                .map(|conversations| {
+                    let conversations: Vec<_> = conversations.iter().map(|state| named(&names, state)).collect();
+                    Some(serde_json::json!({ "conversations": conversations }))
+                })
+        }
+        ["errors"] => {
+            let limit = params
+                .get("limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(DEFAULT_LIST_LIMIT)
+                .min(MAX_LIST_LIMIT);
+
+            db.list_failed_attempts(limit).and_then(|failures| {
+                let conflicts = db.list_conflicts(limit)?;
+                Ok(Some(serde_json::json!({ "errors": failures, "conflicts": conflicts })))
+            })
+        }
+        ["conversations", id] => db.find_session(id).map(|found| {
+            found.map(|found| {
+                let content = cache::read_content(db, &found).ok();
+                let mut results = db.remote_results_for(&found.state.file_path).unwrap_or_default();
+                let state = found.into_state();
+                if let Some(workflow_id) = state.workflow_id.as_deref() {
+                    // Of a file holding several conversations, only this one's
+                    results.retain(|result| result.workflow_id == workflow_id);
+                }
+                let mut value = named(&names, &state);
+                value["content"] = serde_json::json!(content);
+                value["results"] = serde_json::json!(results);
+                value
+            })
+        }),
+        _ => Ok(None),
+    };
+
+    match result {
+        Ok(Some(body)) => (StatusCode::OK, body),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            serde_json::json!({ "error": "Not found" }),
+        ),
+        Err(e) => {
+            tracing::error!("Local API query failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({ "error": e.to_string() }),
+            )
+        }
+    }
+}
+
+/// JSON of a value with a `projectPath`, with the project's name beside it
+fn named(names: &ProjectNames, value: &impl serde::Serialize) -> serde_json::Value {
+    let mut value = serde_json::to_value(value).unwrap_or_default();
+    if let Some(path) = value["projectPath"].as_str() {
+        value["projectName"] = names.display(path).into();
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SyncState, SyncStatus};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_routes() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        db.upsert_sync_state(&SyncState {
+            file_path: "/test/session.jsonl".to_string(),
+            content_hash: "abc123".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1234567890,
+            workflow_id: None,
+            status: SyncStatus::Pending,
+            session_id: Some("session-1".to_string()),
+            project_path: Some("/work/app".to_string()),
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        })
+        .unwrap();
+
+        db.set_suggested_alias("/work/app", "app").unwrap();
+
+        let params = HashMap::new();
+        let projects = ProjectsConfig::default();
+
+        let (status, body) = route("/conversations", &params, &db, &projects);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["conversations"][0]["sessionId"], "session-1");
+        assert_eq!(body["conversations"][0]["projectName"], "app");
+
+        let (status, body) = route("/conversations/session-1", &params, &db, &projects);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["projectPath"], "/work/app");
+
+        let projects = ProjectsConfig {
+            aliases: [("/work".to_string(), "work".to_string())].into(),
+            ..Default::default()
+        };
+        let (status, body) = route("/projects", &params, &db, &projects);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["projects"][0]["projectName"], "work/app");
+
+        let (status, body) = route("/status", &params, &db, &projects);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["sync"]["pending"], 1);
+
+        let (status, _) = route("/conversations/missing", &params, &db, &projects);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = route("/unknown", &params, &db, &projects);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_access() {
+        let access = Access { port: 8787, token: "secret".to_string() };
+        let request = |headers: &[(&str, &str)]| {
+            let mut builder = Request::get("/status");
+            for (name, value) in headers {
+                builder = builder.header(*name, *value);
+            }
+            access.check(&builder.body(()).unwrap()).map_err(|(status, _)| status)
+        };
+        let auth = ("Authorization", "Bearer secret");
+
+        assert_eq!(request(&[("Host", "127.0.0.1:8787"), auth]), Ok(()));
+        assert_eq!(request(&[("Host", "localhost:8787"), ("Origin", "http://localhost:8787"), auth]), Ok(()));
+
+        // Rebound domains, other ports and other pages are refused
+        assert_eq!(request(&[("Host", "attacker.example:8787"), auth]), Err(StatusCode::FORBIDDEN));
+        assert_eq!(request(&[("Host", "127.0.0.1:9000"), auth]), Err(StatusCode::FORBIDDEN));
+        assert_eq!(request(&[auth]), Err(StatusCode::FORBIDDEN));
+        assert_eq!(
+            request(&[("Host", "127.0.0.1:8787"), ("Origin", "https://attacker.example"), auth]),
+            Err(StatusCode::FORBIDDEN)
+        );
+
+        // So are missing and wrong tokens
+        assert_eq!(request(&[("Host", "127.0.0.1:8787")]), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            request(&[("Host", "127.0.0.1:8787"), ("Authorization", "Bearer secreT")]),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Logging setup with a runtime-reloadable filter
+//!
+//! The tracing filter is installed behind a reload layer so the log level can
+//! be changed while the app is running (from the control socket or the tray)
+//! without restarting and losing in-memory state.
+
+use std::sync::OnceLock;
+use thiserror::Error;
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Filter used when `RUST_LOG` is not set
+const DEFAULT_DIRECTIVE: &str = "duplex=info";
+
+/// Levels that can be given without a full filter expression
+const LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error"];
+
+#[derive(Error, Debug)]
+pub enum LoggingError {
+    #[error("Logging has not been initialized")]
+    NotInitialized,
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(String),
+    #[error("Failed to reload log filter: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Subscriber that output layers are stacked on, after the reloadable filter
+pub type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+
+/// Initialize the global tracing subscriber
+///
+/// Logs go to stderr so stdout stays free for command output and the MCP
+/// stdio transport.
+pub fn init() {
+    init_with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+}
+
+/// Initialize the global tracing subscriber with a custom output layer
+///
+/// For processes without a console, e.g. the Windows service.
+pub fn init_with<L>(output: L)
+where
+    L: Layer<FilteredRegistry> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::from_default_env().add_directive(DEFAULT_DIRECTIVE.parse().unwrap());
+    let (filter_layer, handle) = reload::Layer::new(filter);
+
+    tracing_subscriber::registry().with(filter_layer).with(output).init();
+
+    let _ = FILTER_HANDLE.set(handle);
+}
+
+/// Get the currently active filter expression
+pub fn current_filter() -> Result<String, LoggingError> {
+    let handle = FILTER_HANDLE.get().ok_or(LoggingError::NotInitialized)?;
+    Ok(handle.with_current(|filter| filter.to_string())?)
+}
+
+/// Replace the active filter
+///
+/// Accepts either a bare level (`debug`), which applies to the app's own
+/// targets, or a full filter expression (`duplex=debug,reqwest=info`).
+pub fn set_filter(level_or_filter: &str) -> Result<String, LoggingError> {
+    let handle = FILTER_HANDLE.get().ok_or(LoggingError::NotInitialized)?;
+
+    let directives = normalize_filter(level_or_filter);
+    let filter = EnvFilter::try_new(&directives)
+        .map_err(|e| LoggingError::InvalidFilter(format!("{}: {}", level_or_filter, e)))?;
+
+    handle.reload(filter)?;
+    tracing::info!("Log filter changed to '{}'", directives);
+    Ok(directives)
+}
+
+/// Expand a bare level into a filter expression for the app's targets
+fn normalize_filter(level_or_filter: &str) -> String {
+    let trimmed = level_or_filter.trim();
+    let lower = trimmed.to_ascii_lowercase();
+
+    if LEVELS.contains(&lower.as_str()) {
+        format!("duplex={}", lower)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_filter() {
+        assert_eq!(normalize_filter("debug"), "duplex=debug");
+        assert_eq!(normalize_filter(" TRACE "), "duplex=trace");
+        assert_eq!(
+            normalize_filter("duplex=debug,reqwest=info"),
+            "duplex=debug,reqwest=info"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Stable per-machine identifier
+//!
+//! Sent with every upload so the server can tell machines apart when the
+//! same session files are visible on several of them (e.g. a synced home
+//! directory). Derived from the OS machine ID rather than stored under the
+//! home directory, which may itself be synced between machines. The raw OS
+//! ID is hashed before it leaves the machine.
+
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::files;
+
+static MACHINE_ID: OnceLock<String> = OnceLock::new();
+
+/// This machine's ID (32 hex characters)
+pub fn machine_id() -> &'static str {
+    MACHINE_ID.get_or_init(|| {
+        let raw = os_machine_id().or_else(generated_machine_id).unwrap_or_else(|| {
+            tracing::warn!("No stable machine ID available; using a per-run ID");
+            random_id()
+        });
+        hash_id(&raw)
+    })
+}
+
+fn hash_id(raw: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"duplex-machine:");
+    hasher.update(raw.trim().as_bytes());
+    hex::encode(&hasher.finalize()[..16])
+}
+
+/// The ID the OS assigns to this installation
+fn os_machine_id() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .map(|id| id.trim().to_string())
+            .find(|id| !id.is_empty())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("IOPlatformUUID"))
+            .and_then(|line| line.split('"').nth(3))
+            .map(String::from)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let output = std::process::Command::new("reg")
+            .args(["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("MachineGuid"))
+            .and_then(|line| line.split_whitespace().last())
+            .map(String::from)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+/// A random ID kept in local (non-roaming) app data, created on first use
+fn generated_machine_id() -> Option<String> {
+    let path = generated_id_path()?;
+    if let Ok(id) = std::fs::read_to_string(&path) {
+        if !id.trim().is_empty() {
+            return Some(id);
+        }
+    }
+
+    let id = random_id();
+    files::write_private(&path, &id).ok()?;
+    Some(id)
+}
+
+fn generated_id_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("duplex").join("machine-id"))
+}
+
+fn random_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_machine_id_is_stable() {
+        assert_eq!(machine_id(), machine_id());
+        assert_eq!(machine_id().len(), 32);
+        assert_eq!(hash_id("abc\n"), hash_id("abc"));
+        assert_ne!(hash_id("abc"), hash_id("abd"));
+    }
+}
@@ -0,0 +1,369 @@
+//! MCP (Model Context Protocol) server
+//!
+//! Exposes synced conversation history to coding agents over stdio using
+//! JSON-RPC 2.0, one message per line. Register it with an agent as a stdio
+//! server running `duplex mcp`.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+use crate::cache;
+use crate::config::ProjectsConfig;
+use crate::db::{Database, SessionMatch, SyncState};
+use crate::projects::ProjectNames;
+
+/// MCP protocol revision implemented by this server
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Default number of results for list and search tools
+const DEFAULT_LIMIT: usize = 20;
+
+/// Maximum characters of conversation content returned by `get_conversation`
+const DEFAULT_MAX_CHARS: usize = 100_000;
+
+/// Characters of context shown on each side of a search match
+const SNIPPET_CONTEXT: usize = 120;
+
+/// Maximum number of conversation files scanned by a search
+const SEARCH_SCAN_LIMIT: usize = 2000;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serve MCP over stdin/stdout until stdin closes
+pub fn serve_stdio(db: &Database, projects: &ProjectsConfig) -> std::io::Result<()> {
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle_message(&message, db, projects),
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a JSON-RPC message, returning a response for requests
+fn handle_message(message: &Value, db: &Database, projects: &ProjectsConfig) -> Option<Value> {
+    let method = message["method"].as_str().unwrap_or("");
+    let params = &message["params"];
+
+    // Notifications have no id and get no response
+    let id = match message.get("id") {
+        Some(id) => id.clone(),
+        None => {
+            tracing::debug!("MCP notification: {}", method);
+            return None;
+        }
+    };
+
+    let result = match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "duplex", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+        "tools/call" => call_tool(params, db, projects),
+        _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    };
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, &message),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "search_conversations",
+            "description": "Search synced coding agent conversations for text. Returns matching sessions with snippets.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Text to search for (case-insensitive)" },
+                    "project": { "type": "string", "description": "Only search conversations for this project path" },
+                    "limit": { "type": "integer", "description": "Maximum number of results" }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "get_conversation",
+            "description": "Get the full transcript of a conversation by session ID.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "Session ID" },
+                    "maxChars": { "type": "integer", "description": "Maximum characters of content to return" }
+                },
+                "required": ["id"]
+            }
+        },
+        {
+            "name": "list_recent_sessions",
+            "description": "List the most recently active conversations.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "project": { "type": "string", "description": "Only list conversations for this project path" },
+                    "limit": { "type": "integer", "description": "Maximum number of sessions" }
+                }
+            }
+        }
+    ])
+}
+
+/// Dispatch a `tools/call` request
+fn call_tool(params: &Value, db: &Database, projects: &ProjectsConfig) -> Result<Value, (i64, String)> {
+    let name = params["name"]
+        .as_str()
+        .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+    let args = &params["arguments"];
+    let names = ProjectNames::load(projects, db).unwrap_or_else(|_| ProjectNames::from_config(projects));
+
+    let output = match name {
+        "search_conversations" => {
+            let query = args["query"]
+                .as_str()
+                .ok_or((INVALID_PARAMS, "Missing 'query' argument".to_string()))?;
+            search_conversations(db, &names, query, args["project"].as_str(), limit_arg(args))
+        }
+        "get_conversation" => {
+            let id = args["id"]
+                .as_str()
+                .ok_or((INVALID_PARAMS, "Missing 'id' argument".to_string()))?;
+            let max_chars = args["maxChars"]
+                .as_u64()
+                .map(|n| n as usize)
+                .unwrap_or(DEFAULT_MAX_CHARS);
+            get_conversation(db, &names, id, max_chars)
+        }
+        "list_recent_sessions" => list_recent_sessions(db, &names, args["project"].as_str(), limit_arg(args)),
+        _ => return Err((INVALID_PARAMS, format!("Unknown tool: {}", name))),
+    };
+
+    // Tool failures are reported in the result so the agent can see them
+    Ok(match output {
+        Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+        Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+    })
+}
+
+fn limit_arg(args: &Value) -> usize {
+    args["limit"]
+        .as_u64()
+        .map(|n| n as usize)
+        .unwrap_or(DEFAULT_LIMIT)
+}
+
+fn describe(state: &SyncState, names: &ProjectNames) -> Value {
+    json!({
+        "id": state.session_id,
+        "title": state.title,
+        "source": state.source,
+        "projectPath": state.project_path,
+        "projectName": state.project_path.as_deref().map(|path| names.display(path)),
+        "lastModifiedAt": state.last_modified_at,
+        "status": state.status.as_str(),
+    })
+}
+
+fn list_recent_sessions(
+    db: &Database,
+    names: &ProjectNames,
+    project: Option<&str>,
+    limit: usize,
+) -> Result<String, String> {
+    let sessions: Vec<Value> = db
+        .list_conversations(project, limit)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .filter(|s| s.session_id.is_some())
+        .map(|state| describe(state, names))
+        .collect();
+
+    serde_json::to_string_pretty(&sessions).map_err(|e| e.to_string())
+}
+
+fn get_conversation(db: &Database, names: &ProjectNames, id: &str, max_chars: usize) -> Result<String, String> {
+    let found = db
+        .find_session(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No conversation with ID {}", id))?;
+
+    let content = cache::read_content(db, &found)
+        .map_err(|e| format!("Conversation file is no longer readable: {}", e))?;
+    let state = found.into_state();
+
+    let truncated = content.chars().count() > max_chars;
+    let content: String = content.chars().take(max_chars).collect();
+
+    let mut result = describe(&state, names);
+    result["content"] = json!(content);
+    result["truncated"] = json!(truncated);
+
+    serde_json::to_string_pretty(&result).map_err(|e| e.to_string())
+}
+
+fn search_conversations(
+    db: &Database,
+    names: &ProjectNames,
+    query: &str,
+    project: Option<&str>,
+    limit: usize,
+) -> Result<String, String> {
+    let needle = query.to_lowercase();
+    let mut matches = Vec::new();
+
+    let states = db
+        .list_conversations(project, SEARCH_SCAN_LIMIT)
+        .map_err(|e| e.to_string())?;
+    for state in states {
+        // A file holding several conversations is searched one at a time;
+        // `source:` entries are other parsers' readings of a single one
+        let conversations: Vec<_> = db
+            .list_file_conversations(&state.file_path)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|conversation| !conversation.session_id.starts_with("source:"))
+            .collect();
+        let found: Vec<_> = if conversations.is_empty() {
+            vec![SessionMatch { state, conversation: None }]
+        } else {
+            conversations
+                .into_iter()
+                .map(|conversation| SessionMatch {
+                    state: state.clone(),
+                    conversation: Some(conversation),
+                })
+                .collect()
+        };
+
+        for found in found {
+            if matches.len() >= limit {
+                break;
+            }
+
+            let Ok(content) = cache::read_content(db, &found) else {
+                continue;
+            };
+
+            if let Some(snippet) = find_snippet(&content, &needle) {
+                let mut result = describe(&found.into_state(), names);
+                result["snippet"] = json!(snippet);
+                matches.push(result);
+            }
+        }
+    }
+
+    serde_json::to_string_pretty(&matches).map_err(|e| e.to_string())
+}
+
+/// Find the first case-insensitive match and return surrounding context
+fn find_snippet(content: &str, needle_lower: &str) -> Option<String> {
+    let haystack = content.to_lowercase();
+    let pos = haystack.find(needle_lower)?;
+
+    // Lowercasing can change byte lengths, so map back through char indices
+    let char_pos = haystack[..pos].chars().count();
+    let needle_chars = needle_lower.chars().count();
+    let start = char_pos.saturating_sub(SNIPPET_CONTEXT);
+    let len = needle_chars + 2 * SNIPPET_CONTEXT;
+
+    Some(content.chars().skip(start).take(len).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SyncStatus;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_initialize_and_list_tools() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let projects = ProjectsConfig::default();
+
+        let response = handle_message(
+            &json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            &db,
+            &projects,
+        )
+        .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSION);
+
+        let response =
+            handle_message(&json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }), &db, &projects).unwrap();
+        assert_eq!(response["result"]["tools"].as_array().unwrap().len(), 3);
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(handle_message(&notification, &db, &projects).is_none());
+    }
+
+    #[test]
+    fn test_search_conversations() {
+        let dir = tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let session_path = dir.path().join("session.jsonl");
+        std::fs::write(&session_path, "{\"message\":\"Refactor the Billing module\"}\n").unwrap();
+
+        db.upsert_sync_state(&SyncState {
+            file_path: session_path.to_string_lossy().to_string(),
+            content_hash: "abc123".to_string(),
+            last_synced_at: None,
+            last_modified_at: 1234567890,
+            workflow_id: None,
+            status: SyncStatus::Complete,
+            session_id: Some("session-1".to_string()),
+            project_path: Some("/work/app".to_string()),
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        })
+        .unwrap();
+
+        let response = handle_message(
+            &json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": { "name": "search_conversations", "arguments": { "query": "billing" } }
+            }),
+            &db,
+            &ProjectsConfig::default(),
+        )
+        .unwrap();
+
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        let results: Vec<Value> = serde_json::from_str(text).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["id"], "session-1");
+        assert!(results[0]["snippet"].as_str().unwrap().contains("Billing"));
+    }
+}
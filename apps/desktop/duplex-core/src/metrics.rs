@@ -0,0 +1,77 @@
+//! In-process metrics
+//!
+//! Counters are kept in memory for the lifetime of the app and reported
+//! through the control socket.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::errors::ErrorCategory;
+
+static ERROR_COUNTS: [AtomicU64; ErrorCategory::ALL.len()] =
+    [const { AtomicU64::new(0) }; ErrorCategory::ALL.len()];
+static COALESCED_CHANGES: AtomicU64 = AtomicU64::new(0);
+static DROPPED_CHANGES: AtomicU64 = AtomicU64::new(0);
+
+/// File changes the watcher didn't pass on to the sync engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeCounts {
+    /// Merged into a change to the same file already waiting
+    pub coalesced: u64,
+    /// Dropped because the queue to the engine was full
+    pub dropped: u64,
+}
+
+fn index(category: ErrorCategory) -> usize {
+    ErrorCategory::ALL
+        .iter()
+        .position(|c| *c == category)
+        .unwrap_or(0)
+}
+
+/// Record an error of the given category
+pub fn record_error(category: ErrorCategory) {
+    ERROR_COUNTS[index(category)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a file change merged into one already waiting
+pub fn record_coalesced_change() {
+    COALESCED_CHANGES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a file change dropped because the engine is behind
+pub fn record_dropped_change() {
+    DROPPED_CHANGES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Get the number of file changes coalesced and dropped
+pub fn change_counts() -> ChangeCounts {
+    ChangeCounts {
+        coalesced: COALESCED_CHANGES.load(Ordering::Relaxed),
+        dropped: DROPPED_CHANGES.load(Ordering::Relaxed),
+    }
+}
+
+/// Get the number of errors recorded per category
+pub fn error_counts() -> BTreeMap<ErrorCategory, u64> {
+    ErrorCategory::ALL
+        .iter()
+        .map(|c| (*c, ERROR_COUNTS[index(*c)].load(Ordering::Relaxed)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_error() {
+        let before = error_counts()[&ErrorCategory::Parse];
+        record_error(ErrorCategory::Parse);
+        record_error(ErrorCategory::Parse);
+        assert_eq!(error_counts()[&ErrorCategory::Parse], before + 2);
+        assert_eq!(error_counts().len(), ErrorCategory::ALL.len());
+    }
+}
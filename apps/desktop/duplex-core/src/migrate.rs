@@ -0,0 +1,265 @@
+//! Machine migration bundles
+//!
+//! `duplex migrate export` packs the config file, the sync database and,
+//! optionally, the keyring credentials into one passphrase-encrypted file.
+//! `duplex migrate import` restores it on another machine so sync history
+//! carries over and nothing is uploaded again.
+//!
+//! Bundle layout: `MAGIC | salt (16) | nonce (12) | ciphertext`, where the
+//! ciphertext is a ChaCha20-Poly1305 sealed JSON [`Bundle`] and the key is
+//! derived from the passphrase with Argon2id.
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::config::{self, SecureTokenStorage};
+use crate::db::Database;
+use crate::files;
+
+/// File signature and format version
+const MAGIC: &[u8; 8] = b"DUPLXMG1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] config::ConfigError),
+    #[error("Database error: {0}")]
+    Database(#[from] crate::db::DatabaseError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Not a Duplex migration bundle")]
+    InvalidBundle,
+    #[error("Wrong passphrase or corrupted bundle")]
+    Decrypt,
+    #[error("Encryption failed: {0}")]
+    Crypto(String),
+    #[error("Existing data found at {0} (use --force to overwrite)")]
+    WouldOverwrite(String),
+}
+
+/// Contents of a migration bundle
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Bundle {
+    created_at: u64,
+    /// Raw config file (JSONC, comments preserved)
+    config: Option<String>,
+    /// SQLite database file, base64 encoded
+    database: Option<String>,
+    credentials: Option<BundledCredentials>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundledCredentials {
+    access_token: String,
+    refresh_token: String,
+    expires_at: u64,
+}
+
+/// What was written to or restored from a bundle
+#[derive(Debug, Default)]
+pub struct MigrateSummary {
+    pub config: bool,
+    pub database: bool,
+    pub credentials: bool,
+}
+
+/// Write the local state to an encrypted bundle
+pub fn export(out: &Path, passphrase: &str, include_credentials: bool) -> Result<MigrateSummary, MigrateError> {
+    let config_path = config::get_config_path()?;
+    let config = if config_path.exists() {
+        Some(std::fs::read_to_string(&config_path)?)
+    } else {
+        None
+    };
+
+    let database = if config::get_database_path()?.exists() {
+        let snapshot = snapshot(&Database::open()?, &config::get_config_dir()?)?;
+        Some(STANDARD.encode(snapshot))
+    } else {
+        None
+    };
+
+    let credentials = if include_credentials {
+        let tokens = SecureTokenStorage::new().get_tokens()?;
+        Some(BundledCredentials {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_at: tokens.expires_at,
+        })
+    } else {
+        None
+    };
+
+    let summary = MigrateSummary {
+        config: config.is_some(),
+        database: database.is_some(),
+        credentials: credentials.is_some(),
+    };
+
+    let bundle = Bundle {
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        config,
+        database,
+        credentials,
+    };
+
+    let sealed = seal(&serde_json::to_vec(&bundle)?, passphrase)?;
+    files::write_private(out, sealed)?;
+
+    Ok(summary)
+}
+
+/// A copy of the database, taken through SQLite so a running app can't
+/// hand us a torn file
+///
+/// The copy is made in `dir`, beside the database rather than in a shared
+/// temp directory, readable by the owner only and removed once read.
+fn snapshot(db: &Database, dir: &Path) -> Result<Vec<u8>, MigrateError> {
+    let file = tempfile::Builder::new().prefix(".duplex-migrate-").suffix(".db").tempfile_in(dir)?;
+    db.backup_to(file.path())?;
+    Ok(std::fs::read(file.path())?)
+}
+
+/// Restore local state from an encrypted bundle
+///
+/// Refuses to replace an existing database unless `force` is set.
+pub fn import(bundle_path: &Path, passphrase: &str, force: bool) -> Result<MigrateSummary, MigrateError> {
+    let data = std::fs::read(bundle_path)?;
+    let bundle: Bundle = serde_json::from_slice(&open(&data, passphrase)?)?;
+
+    let db_path = config::get_database_path()?;
+    if bundle.database.is_some() && db_path.exists() && !force {
+        return Err(MigrateError::WouldOverwrite(db_path.to_string_lossy().to_string()));
+    }
+
+    std::fs::create_dir_all(config::get_config_dir()?)?;
+    let mut summary = MigrateSummary::default();
+
+    if let Some(config) = &bundle.config {
+        files::write_private(&config::get_config_path()?, config)?;
+        summary.config = true;
+    }
+
+    if let Some(database) = &bundle.database {
+        let bytes = STANDARD.decode(database).map_err(|_| MigrateError::InvalidBundle)?;
+        // Stale WAL/journal files would be replayed over the restored database
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let _ = std::fs::remove_file(format!("{}{}", db_path.to_string_lossy(), suffix));
+        }
+        files::write_private(&db_path, bytes)?;
+        // Apply any migrations newer than the exporting machine's schema
+        Database::open()?;
+        summary.database = true;
+    }
+
+    if let Some(credentials) = bundle.credentials {
+        SecureTokenStorage::new().store_tokens(
+            credentials.access_token,
+            credentials.refresh_token,
+            credentials.expires_at,
+        )?;
+        summary.credentials = true;
+    }
+
+    Ok(summary)
+}
+
+/// Derive a 256-bit key from a passphrase
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, MigrateError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| MigrateError::Crypto(e.to_string()))?;
+    Ok(Key::from(key))
+}
+
+/// Encrypt a payload with a passphrase
+fn seal(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, MigrateError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|e| MigrateError::Crypto(e.to_string()))?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt a payload sealed with [`seal`]
+fn open(data: &[u8], passphrase: &str) -> Result<Vec<u8>, MigrateError> {
+    let header_len = MAGIC.len() + SALT_LEN + NONCE_LEN;
+    if data.len() < header_len || &data[..MAGIC.len()] != MAGIC {
+        return Err(MigrateError::InvalidBundle);
+    }
+
+    let salt = &data[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = &data[MAGIC.len() + SALT_LEN..header_len];
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), &data[header_len..])
+        .map_err(|_| MigrateError::Decrypt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let sealed = seal(b"sync history", "correct horse").unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert_eq!(open(&sealed, "correct horse").unwrap(), b"sync history");
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("sync.db")).unwrap();
+        db.set_app_state("migrated", "yes").unwrap();
+
+        let bytes = snapshot(&db, dir.path()).unwrap();
+        let copy = dir.path().join("copy.db");
+        std::fs::write(&copy, bytes).unwrap();
+        let copy_db = Database::open_at(&copy).unwrap();
+        assert_eq!(copy_db.get_app_state("migrated").unwrap().as_deref(), Some("yes"));
+
+        // Nothing is left behind
+        let names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(".duplex-migrate-"))
+            .collect();
+        assert!(names.is_empty(), "{:?}", names);
+    }
+
+    #[test]
+    fn test_open_rejects_bad_input() {
+        let sealed = seal(b"sync history", "correct horse").unwrap();
+        assert!(matches!(open(&sealed, "wrong"), Err(MigrateError::Decrypt)));
+        assert!(matches!(open(b"not a bundle", "x"), Err(MigrateError::InvalidBundle)));
+    }
+}
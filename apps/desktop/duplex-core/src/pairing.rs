@@ -0,0 +1,382 @@
+//! Pairing of CLI commands with the running app over the control socket
+//!
+//! Any local process can connect to a loopback port, including another
+//! user's on a shared machine. So the app and the CLI share a pairing
+//! secret: 32 random bytes the app creates on first start and keeps in the
+//! keyring, or in `control.key` (readable by the owner only) where there is
+//! no keyring to reach. Each connection opens with a handshake, one JSON
+//! line each way:
+//!
+//! 1. app: `{ nonce }`, fresh for the connection
+//! 2. client: `{ nonce, proof }`, its own nonce and
+//!    `HMAC(secret, "duplex-control client" | app nonce | client nonce)`
+//! 3. app: `{ proof }`, `HMAC(secret, "duplex-control app" | client nonce | app nonce)`,
+//!    or `{ error }` and the connection closes
+//!
+//! Each side proves it holds the secret without sending it, and neither
+//! proof can be replayed on another connection. After the handshake every
+//! line is `base64(ciphertext)`, sealed with ChaCha20-Poly1305 under a key
+//! derived from the secret and both nonces. Nonces count the lines sent in
+//! each direction, so lines can't be read, altered, dropped or reordered.
+
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::{self, ConfigError, SecureTokenStorage};
+use crate::files;
+
+const SECRET_LEN: usize = 32;
+const NONCE_LEN: usize = 32;
+
+const CLIENT_PROOF: &[u8] = b"duplex-control client";
+const APP_PROOF: &[u8] = b"duplex-control app";
+const SESSION_KEY: &[u8] = b"duplex-control session";
+
+#[derive(Error, Debug)]
+pub enum PairingError {
+    #[error("Not paired with the app (start the desktop app first)")]
+    NotPaired,
+    #[error("Invalid pairing secret, expected {SECRET_LEN} bytes in base64")]
+    InvalidSecret,
+    #[error("Pairing failed: {0}")]
+    Rejected(String),
+    #[error("Malformed handshake: {0}")]
+    Malformed(String),
+    #[error("Message failed to decrypt")]
+    Open,
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Secret shared by the app and the CLI commands that command it
+#[derive(Clone)]
+pub struct PairingSecret([u8; SECRET_LEN]);
+
+impl PairingSecret {
+    /// The app's secret, created and stored on first start
+    pub fn load_or_create() -> Result<Self, PairingError> {
+        if let Some(secret) = Self::load_stored()? {
+            return Ok(secret);
+        }
+
+        let mut secret = [0u8; SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let encoded = STANDARD.encode(secret);
+        if let Err(e) = SecureTokenStorage::new().store_control_secret(&encoded) {
+            tracing::warn!("Can't store the control socket secret in the keyring, using a private file: {}", e);
+            files::write_private(&config::get_control_key_path()?, &encoded)?;
+        }
+        tracing::info!("Created control socket pairing secret");
+        Ok(Self(secret))
+    }
+
+    /// The secret the running app paired with
+    pub fn load() -> Result<Self, PairingError> {
+        Self::load_stored()?.ok_or(PairingError::NotPaired)
+    }
+
+    /// The stored secret: from the keyring, else from `control.key`
+    fn load_stored() -> Result<Option<Self>, PairingError> {
+        Self::from_stored(SecureTokenStorage::new().get_control_secret(), &config::get_control_key_path()?)
+    }
+
+    /// The secret given the keyring lookup. Only a keyring without the
+    /// entry falls back to the file; a locked or failing keyring is an
+    /// error, as a new secret would unpair every client of the running app.
+    fn from_stored(
+        keyring: Result<Option<String>, ConfigError>,
+        key_path: &Path,
+    ) -> Result<Option<Self>, PairingError> {
+        let stored = match keyring? {
+            Some(secret) => Some(secret),
+            None => match std::fs::read_to_string(key_path) {
+                Ok(secret) => Some(secret),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            },
+        };
+        stored.map(|secret| Self::from_base64(&secret)).transpose()
+    }
+
+    fn from_base64(encoded: &str) -> Result<Self, PairingError> {
+        let bytes = STANDARD.decode(encoded.trim()).map_err(|_| PairingError::InvalidSecret)?;
+        let secret = bytes.try_into().map_err(|_| PairingError::InvalidSecret)?;
+        Ok(Self(secret))
+    }
+
+    fn key(&self) -> hmac::Key {
+        hmac::Key::new(hmac::HMAC_SHA256, &self.0)
+    }
+}
+
+/// A handshake line; which fields are set depends on the step
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Handshake {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proof: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Handshake {
+    fn parse(line: &str) -> Result<Self, PairingError> {
+        let handshake: Self = serde_json::from_str(line).map_err(|e| PairingError::Malformed(e.to_string()))?;
+        match handshake.error {
+            Some(error) => Err(PairingError::Rejected(error)),
+            None => Ok(handshake),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        // Only strings, which always serialize
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// App side of a connection's handshake
+pub struct AppHandshake {
+    nonce: [u8; NONCE_LEN],
+}
+
+impl AppHandshake {
+    /// Start the handshake, returning the line to send the client
+    pub fn start() -> (Self, String) {
+        let nonce = random_nonce();
+        let line = Handshake {
+            nonce: Some(hex::encode(nonce)),
+            ..Default::default()
+        };
+        (Self { nonce }, line.to_line())
+    }
+
+    /// Check the client's proof, returning the session and the line to send
+    /// back. On failure the line tells the client why; the connection should
+    /// close after it.
+    pub fn accept(self, secret: &PairingSecret, line: &str) -> (Result<Session, PairingError>, String) {
+        let result = Handshake::parse(line).and_then(|handshake| {
+            let client_nonce = decode_field(handshake.nonce, "nonce")?;
+            let proof = decode_field(handshake.proof, "proof")?;
+            let signed = [CLIENT_PROOF, &self.nonce[..], &client_nonce[..]].concat();
+            hmac::verify(&secret.key(), &signed, &proof)
+                .map_err(|_| PairingError::Rejected("the client doesn't hold this user's secret".to_string()))?;
+            Ok(client_nonce)
+        });
+
+        match result {
+            Ok(client_nonce) => {
+                let signed = [APP_PROOF, &client_nonce[..], &self.nonce[..]].concat();
+                let reply = Handshake {
+                    proof: Some(hex::encode(hmac::sign(&secret.key(), &signed))),
+                    ..Default::default()
+                };
+                let session = Session::new(secret, &self.nonce, &client_nonce, Direction::FromApp);
+                (Ok(session), reply.to_line())
+            }
+            Err(e) => {
+                let error = match &e {
+                    PairingError::Rejected(reason) => reason.clone(),
+                    e => e.to_string(),
+                };
+                let reply = Handshake {
+                    error: Some(error),
+                    ..Default::default()
+                };
+                (Err(e), reply.to_line())
+            }
+        }
+    }
+}
+
+/// Client side of a connection's handshake
+pub struct ClientHandshake {
+    secret: PairingSecret,
+    app_nonce: Vec<u8>,
+    nonce: [u8; NONCE_LEN],
+}
+
+impl ClientHandshake {
+    /// Answer the app's opening line, returning the line to send back
+    pub fn respond(secret: &PairingSecret, line: &str) -> Result<(Self, String), PairingError> {
+        let app_nonce = decode_field(Handshake::parse(line)?.nonce, "nonce")?;
+        let nonce = random_nonce();
+        let signed = [CLIENT_PROOF, &app_nonce[..], &nonce[..]].concat();
+        let reply = Handshake {
+            nonce: Some(hex::encode(nonce)),
+            proof: Some(hex::encode(hmac::sign(&secret.key(), &signed))),
+            ..Default::default()
+        };
+        let handshake = Self {
+            secret: secret.clone(),
+            app_nonce,
+            nonce,
+        };
+        Ok((handshake, reply.to_line()))
+    }
+
+    /// Check the app's proof, completing the handshake
+    pub fn finish(self, line: &str) -> Result<Session, PairingError> {
+        let proof = decode_field(Handshake::parse(line)?.proof, "proof")?;
+        let signed = [APP_PROOF, &self.nonce[..], &self.app_nonce[..]].concat();
+        hmac::verify(&self.secret.key(), &signed, &proof)
+            .map_err(|_| PairingError::Rejected("the app doesn't hold this user's secret".to_string()))?;
+        Ok(Session::new(&self.secret, &self.app_nonce, &self.nonce, Direction::FromClient))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    FromApp = 0,
+    FromClient = 1,
+}
+
+/// Seals and opens the lines of a paired connection
+pub struct Session {
+    cipher: ChaCha20Poly1305,
+    outgoing: Direction,
+    sent: u64,
+    received: u64,
+}
+
+impl Session {
+    fn new(secret: &PairingSecret, app_nonce: &[u8], client_nonce: &[u8], outgoing: Direction) -> Self {
+        let derived = hmac::sign(&secret.key(), &[SESSION_KEY, app_nonce, client_nonce].concat());
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(derived.as_ref())),
+            outgoing,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Seal the next line to send
+    pub fn seal(&mut self, plaintext: &str) -> String {
+        let nonce = line_nonce(self.outgoing, self.sent);
+        self.sent += 1;
+        // Only fails for plaintext beyond ChaCha20's 256 GiB limit
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .unwrap_or_default();
+        STANDARD.encode(sealed)
+    }
+
+    /// Open the next line received
+    pub fn open(&mut self, line: &str) -> Result<String, PairingError> {
+        let incoming = match self.outgoing {
+            Direction::FromApp => Direction::FromClient,
+            Direction::FromClient => Direction::FromApp,
+        };
+        let nonce = line_nonce(incoming, self.received);
+        let sealed = STANDARD.decode(line.trim()).map_err(|_| PairingError::Open)?;
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), sealed.as_slice())
+            .map_err(|_| PairingError::Open)?;
+        self.received += 1;
+        String::from_utf8(plaintext).map_err(|_| PairingError::Open)
+    }
+}
+
+/// Nonce of the `counter`th line sent in a direction
+fn line_nonce(direction: Direction, counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[0] = direction as u8;
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+fn random_nonce() -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+/// A hex field a handshake step requires
+fn decode_field(value: Option<String>, name: &str) -> Result<Vec<u8>, PairingError> {
+    let value = value.ok_or_else(|| PairingError::Malformed(format!("missing {}", name)))?;
+    hex::decode(value).map_err(|_| PairingError::Malformed(format!("{} is not hex", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(byte: u8) -> PairingSecret {
+        PairingSecret([byte; SECRET_LEN])
+    }
+
+    fn pair(app_secret: &PairingSecret, client_secret: &PairingSecret) -> Result<(Session, Session), PairingError> {
+        let (app, hello) = AppHandshake::start();
+        let (client, response) = ClientHandshake::respond(client_secret, &hello)?;
+        let (app_session, reply) = app.accept(app_secret, &response);
+        let client_session = client.finish(&reply)?;
+        Ok((app_session?, client_session))
+    }
+
+    #[test]
+    fn test_paired_session() {
+        let (mut app, mut client) = pair(&secret(1), &secret(1)).unwrap();
+
+        let request = client.seal(r#"{"command":"status"}"#);
+        assert!(!request.contains("status"));
+        assert_eq!(app.open(&request).unwrap(), r#"{"command":"status"}"#);
+        let response = app.seal(r#"{"ok":true}"#);
+        assert_eq!(client.open(&response).unwrap(), r#"{"ok":true}"#);
+
+        // Replayed, reflected and tampered lines don't open
+        assert!(app.open(&request).is_err());
+        let echo = client.seal("echo");
+        assert!(client.open(&echo).is_err());
+        let mut tampered = STANDARD.decode(app.seal("x")).unwrap();
+        tampered[0] ^= 1;
+        assert!(client.open(&STANDARD.encode(tampered)).is_err());
+    }
+
+    #[test]
+    fn test_locked_keyring() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("control.key");
+        std::fs::write(&key_path, STANDARD.encode([2u8; SECRET_LEN])).unwrap();
+        let stored = STANDARD.encode([1u8; SECRET_LEN]);
+
+        // Locked: an error, not the file's secret or a new one
+        let locked = PairingSecret::from_stored(Err(ConfigError::KeychainLocked), &key_path);
+        assert!(matches!(locked, Err(PairingError::Config(ConfigError::KeychainLocked))));
+        let failed = PairingSecret::from_stored(Err(ConfigError::Keyring("no backend".into())), &key_path);
+        assert!(matches!(failed, Err(PairingError::Config(ConfigError::Keyring(_)))));
+
+        // Unlocked: the keyring's secret
+        let unlocked = PairingSecret::from_stored(Ok(Some(stored)), &key_path).unwrap().unwrap();
+        assert_eq!(unlocked.0, [1u8; SECRET_LEN]);
+
+        // No entry: the file's, and nothing once that's gone too
+        let file = PairingSecret::from_stored(Ok(None), &key_path).unwrap().unwrap();
+        assert_eq!(file.0, [2u8; SECRET_LEN]);
+        std::fs::remove_file(&key_path).unwrap();
+        assert!(PairingSecret::from_stored(Ok(None), &key_path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_wrong_secret() {
+        let err = pair(&secret(1), &secret(2)).err().unwrap();
+        assert!(matches!(err, PairingError::Rejected(_)), "{}", err);
+
+        // A proof from one connection is no good on another
+        let (app, hello) = AppHandshake::start();
+        let (_, response) = ClientHandshake::respond(&secret(1), &hello).unwrap();
+        let (other, _) = AppHandshake::start();
+        assert!(other.accept(&secret(1), &response).0.is_err());
+        assert!(app.accept(&secret(1), &response).0.is_ok());
+    }
+}
@@ -0,0 +1,579 @@
+use super::{
+    conversation_title, read_jsonl, ContentType, Conversation, ConversationFile, ConversationParser, Decoding,
+    Message, ParserError, Preview, ToolCall,
+};
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::config::ClaudeCodeConfig;
+use crate::watcher::expand_path;
+
+/// Environment variable that overrides Claude Code's config directory
+const CONFIG_DIR_ENV: &str = "CLAUDE_CONFIG_DIR";
+
+/// Parser for Claude Code conversation files
+pub struct ClaudeCodeParser {
+    /// Projects directory under each candidate config root
+    base_dirs: Vec<PathBuf>,
+    /// Upload normalized messages instead of the raw JSONL
+    structured_messages: bool,
+}
+
+impl ClaudeCodeParser {
+    pub fn new() -> Self {
+        Self::with_config(&ClaudeCodeConfig::default())
+    }
+
+    pub fn with_config(config: &ClaudeCodeConfig) -> Self {
+        let base_dirs = Self::candidate_config_roots(config)
+            .into_iter()
+            .map(|root| root.join("projects"))
+            .collect();
+
+        Self {
+            base_dirs,
+            structured_messages: config.structured_messages,
+        }
+    }
+
+    /// Places Claude Code may keep its config, most specific first:
+    /// `$CLAUDE_CONFIG_DIR`, `$XDG_CONFIG_HOME/claude` (`~/.config/claude`)
+    /// and the legacy `~/.claude`; only `parsers.claudeCode.baseDir` when set
+    pub(super) fn candidate_config_roots(config: &ClaudeCodeConfig) -> Vec<PathBuf> {
+        if let Some(base_dir) = &config.base_dir {
+            return vec![expand_path(base_dir)];
+        }
+        candidate_roots(
+            std::env::var_os(CONFIG_DIR_ENV),
+            std::env::var_os("XDG_CONFIG_HOME"),
+            dirs::home_dir().as_deref(),
+        )
+    }
+
+    /// Claude Code config directories that exist
+    pub fn config_roots(config: &ClaudeCodeConfig) -> Vec<PathBuf> {
+        Self::candidate_config_roots(config)
+            .into_iter()
+            .filter(|root| root.is_dir())
+            .collect()
+    }
+
+    /// Projects directories that exist, one per config root
+    pub fn projects_dirs(config: &ClaudeCodeConfig) -> Vec<PathBuf> {
+        Self::config_roots(config)
+            .into_iter()
+            .map(|root| root.join("projects"))
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    /// Project roots of existing Claude Code projects
+    ///
+    /// Directory names are decoded lossily (dashes in the original path are
+    /// indistinguishable from separators), so only paths that exist are kept.
+    pub fn known_projects(config: &ClaudeCodeConfig) -> Vec<PathBuf> {
+        let mut projects: Vec<PathBuf> = Self::projects_dirs(config)
+            .into_iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter_map(|entry| entry.file_name().to_str().and_then(Self::decode_project_path))
+            .filter(|path| path.is_dir())
+            .collect();
+        projects.sort();
+        projects.dedup();
+        projects
+    }
+
+    fn is_base_dir(&self, path: &Path) -> bool {
+        self.base_dirs.iter().any(|dir| dir == path)
+    }
+
+    /// Extract project path from the encoded directory name
+    fn decode_project_path(encoded: &str) -> Option<PathBuf> {
+        // Claude Code encodes paths like "-Users-name-project" for "/Users/name/project"
+        if encoded.starts_with('-') {
+            let path = encoded.replace('-', "/");
+            Some(PathBuf::from(path))
+        } else {
+            None
+        }
+    }
+
+    /// Project a session file belongs to, from its directory's name
+    ///
+    /// The name is followed down existing directories where it can be, so
+    /// a project at `/work/my-app` isn't taken for `/work/my/app`.
+    pub fn project_path_of_file(path: &Path) -> Option<PathBuf> {
+        let encoded = path.parent()?.file_name()?.to_str()?;
+        let decoded = Self::decode_project_path(encoded)?;
+        Some(resolve_project_path(encoded).unwrap_or(decoded))
+    }
+
+    /// Extract session ID from filename
+    fn extract_session_id(filename: &str) -> Option<String> {
+        // Session files are like "abc123-def456-789.jsonl" (UUID format)
+        if filename.ends_with(".jsonl") {
+            let name = filename.trim_end_matches(".jsonl");
+            // Basic UUID validation (36 chars with hyphens)
+            if name.len() == 36 && name.chars().filter(|c| *c == '-').count() == 4 {
+                return Some(name.to_string());
+            }
+        }
+        None
+    }
+
+    /// Messages in a session, and the title from its latest `summary` record
+    fn read_records(content: &str) -> (Vec<Message>, Option<String>) {
+        let mut messages = Vec::new();
+        let mut summary = None;
+        for record in content.lines().filter_map(|line| serde_json::from_str::<Value>(line).ok()) {
+            // Claude Code titles sessions with `summary` records; the latest wins
+            if record["type"] == "summary" {
+                if let Some(text) = record["summary"].as_str() {
+                    summary = Some(text.to_string());
+                }
+            } else if let Some(message) = Self::record_to_message(&record) {
+                messages.push(message);
+            }
+        }
+        (messages, summary)
+    }
+
+    /// Convert one JSONL record into a message, skipping non-dialogue records
+    fn record_to_message(record: &Value) -> Option<Message> {
+        let record_type = record["type"].as_str()?;
+        if record_type != "user" && record_type != "assistant" {
+            return None;
+        }
+
+        let message = &record["message"];
+        let mut role = message["role"].as_str().unwrap_or(record_type).to_string();
+        let mut text = Vec::new();
+        let mut tool_calls = Vec::new();
+
+        match &message["content"] {
+            Value::String(s) => text.push(s.clone()),
+            Value::Array(blocks) => {
+                for block in blocks {
+                    match block["type"].as_str() {
+                        Some("text") => {
+                            if let Some(t) = block["text"].as_str() {
+                                text.push(t.to_string());
+                            }
+                        }
+                        Some("tool_use") => tool_calls.push(ToolCall {
+                            name: block["name"].as_str().unwrap_or("tool").to_string(),
+                            input: block["input"].to_string(),
+                        }),
+                        Some("tool_result") => {
+                            // Tool output is sent back as a user message
+                            role = "tool".to_string();
+                            match &block["content"] {
+                                Value::String(s) => text.push(s.clone()),
+                                Value::Array(parts) => text.extend(
+                                    parts.iter().filter_map(|p| p["text"].as_str().map(String::from)),
+                                ),
+                                _ => {}
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if text.is_empty() && tool_calls.is_empty() {
+            return None;
+        }
+
+        Some(Message {
+            role,
+            content: text.join("\n\n"),
+            timestamp: record["timestamp"].as_str().map(String::from),
+            utc_offset: None,
+            tool_calls,
+            // Only assistant messages carry a model; "<synthetic>" marks ones Claude Code wrote itself
+            model: message["model"].as_str().filter(|model| *model != "<synthetic>").map(String::from),
+        })
+    }
+}
+
+impl Default for ClaudeCodeParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode an encoded project name against the filesystem: each dash is a
+/// separator unless the name with the dash kept exists
+fn resolve_project_path(encoded: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = encoded.strip_prefix('-')?.split('-').collect();
+    // Dots are encoded as dashes too, leaving empty parts
+    if parts.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+    let mut path = PathBuf::from("/");
+    let mut start = 0;
+    while start < parts.len() {
+        let end = (start + 1..=parts.len())
+            .rev()
+            .find(|&end| path.join(parts[start..end].join("-")).is_dir())?;
+        path.push(parts[start..end].join("-"));
+        start = end;
+    }
+    Some(path)
+}
+
+impl ConversationParser for ClaudeCodeParser {
+    fn name(&self) -> &str {
+        "claude-code"
+    }
+
+    fn project_path_of(&self, path: &Path) -> Option<PathBuf> {
+        Self::project_path_of_file(path)
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        // Check if this looks like a Claude Code projects directory
+        if self.is_base_dir(path) {
+            return true;
+        }
+
+        // Check if this is a project directory inside the base dir
+        if let Some(parent) = path.parent() {
+            if self.is_base_dir(parent) {
+                return true;
+            }
+        }
+
+        // Check for .jsonl files that look like Claude Code sessions
+        if path.is_file() && path.extension().map_or(false, |e| e == "jsonl") {
+            // Check if parent directory looks like a Claude Code project dir
+            if let Some(parent) = path.parent() {
+                if let Some(parent_parent) = parent.parent() {
+                    if self.is_base_dir(parent_parent) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        let mut files = Vec::new();
+
+        let search_dir = if self.is_base_dir(path) {
+            path.to_path_buf()
+        } else if path.is_dir() {
+            path.to_path_buf()
+        } else if path.is_file() {
+            // If given a file, just return that file
+            if let Some(session_id) = Self::extract_session_id(
+                path.file_name().and_then(|n| n.to_str()).unwrap_or(""),
+            ) {
+                let project_path = path
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .and_then(Self::decode_project_path);
+
+                files.push(ConversationFile {
+                    path: path.to_path_buf(),
+                    session_id: Some(session_id),
+                    project_path,
+                });
+            }
+            return files;
+        } else {
+            return files;
+        };
+
+        // Walk the directory structure
+        if let Ok(entries) = std::fs::read_dir(&search_dir) {
+            for entry in entries.flatten() {
+                let entry_path = entry.path();
+
+                if entry_path.is_dir() {
+                    // This is a project directory
+                    let project_name = entry_path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("");
+
+                    let project_path = Self::decode_project_path(project_name);
+
+                    // Look for session files in this project
+                    if let Ok(session_entries) = std::fs::read_dir(&entry_path) {
+                        for session_entry in session_entries.flatten() {
+                            let session_path = session_entry.path();
+                            if session_path.is_file() {
+                                if let Some(filename) =
+                                    session_path.file_name().and_then(|n| n.to_str())
+                                {
+                                    if let Some(session_id) = Self::extract_session_id(filename) {
+                                        files.push(ConversationFile {
+                                            path: session_path,
+                                            session_id: Some(session_id),
+                                            project_path: project_path.clone(),
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                } else if entry_path.is_file() {
+                    // Check if this is a session file
+                    if let Some(filename) = entry_path.file_name().and_then(|n| n.to_str()) {
+                        if let Some(session_id) = Self::extract_session_id(filename) {
+                            let project_path = search_dir
+                                .file_name()
+                                .and_then(|n| n.to_str())
+                                .and_then(Self::decode_project_path);
+
+                            files.push(ConversationFile {
+                                path: entry_path,
+                                session_id: Some(session_id),
+                                project_path,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        files
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        // Read the raw content - we send the full JSONL to the API for processing
+        let content = read_jsonl(file, self.decoding())?;
+
+        let filename = file.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let session_id = Self::extract_session_id(filename);
+
+        let (messages, summary) = Self::read_records(&content);
+        let title = conversation_title(&messages, summary.as_deref());
+
+        let project_path = file
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .and_then(Self::decode_project_path);
+
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: self.name().to_string(),
+            session_id,
+            project_path,
+            content,
+            content_type: ContentType::Conversation,
+            title,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        vec!["*.jsonl"]
+    }
+
+    fn decoding(&self) -> Decoding {
+        Decoding::Lossy
+    }
+
+    fn parse_messages(&self, content: &str) -> Option<Vec<Message>> {
+        let messages = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter_map(|record| Self::record_to_message(&record))
+            .collect();
+
+        Some(messages)
+    }
+
+    fn structured_upload(&self) -> bool {
+        self.structured_messages
+    }
+
+    fn append_only(&self) -> bool {
+        true
+    }
+
+    fn render_preview(&self, conversation: &Conversation) -> Preview {
+        let (messages, summary) = Self::read_records(&conversation.content);
+        Preview::from_messages(conversation, &messages, summary.as_deref())
+    }
+}
+
+/// Candidate config roots from the environment, without duplicates
+fn candidate_roots(config_dir: Option<OsString>, xdg_config_home: Option<OsString>, home: Option<&Path>) -> Vec<PathBuf> {
+    let xdg_config_home = xdg_config_home
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|h| h.join(".config")));
+
+    let mut roots: Vec<PathBuf> = Vec::new();
+    let candidates = [
+        config_dir.filter(|dir| !dir.is_empty()).map(PathBuf::from),
+        xdg_config_home.map(|dir| dir.join("claude")),
+        home.map(|h| h.join(".claude")),
+    ];
+    for root in candidates.into_iter().flatten() {
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidate_roots() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            candidate_roots(None, None, Some(home)),
+            vec![PathBuf::from("/home/me/.config/claude"), PathBuf::from("/home/me/.claude")]
+        );
+        assert_eq!(
+            candidate_roots(Some("/opt/claude".into()), Some("/xdg".into()), Some(home)),
+            vec![
+                PathBuf::from("/opt/claude"),
+                PathBuf::from("/xdg/claude"),
+                PathBuf::from("/home/me/.claude"),
+            ]
+        );
+        // An override pointing at the legacy path isn't listed twice
+        assert_eq!(
+            candidate_roots(Some("/home/me/.claude".into()), Some("".into()), Some(home)),
+            vec![PathBuf::from("/home/me/.claude"), PathBuf::from("/home/me/.config/claude")]
+        );
+    }
+
+    #[test]
+    fn test_decode_project_path() {
+        assert_eq!(
+            ClaudeCodeParser::decode_project_path("-Users-test-project"),
+            Some(PathBuf::from("/Users/test/project"))
+        );
+
+        assert_eq!(ClaudeCodeParser::decode_project_path("normaldir"), None);
+    }
+
+    #[test]
+    fn test_project_path_of_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("my-app");
+        std::fs::create_dir(&project).unwrap();
+        let encoded = project.to_string_lossy().replace('/', "-");
+        let session = Path::new("/home/me/.claude/projects").join(&encoded).join("s.jsonl");
+        assert_eq!(ClaudeCodeParser::project_path_of_file(&session), Some(project.clone()));
+
+        // Without the directory on disk, every dash is a separator
+        let gone = project.join("gone-too").to_string_lossy().replace('/', "-");
+        let session = Path::new("/home/me/.claude/projects").join(gone).join("s.jsonl");
+        assert_eq!(
+            ClaudeCodeParser::project_path_of_file(&session),
+            Some(PathBuf::from(format!("{}/gone/too", project.to_string_lossy().replace('-', "/"))))
+        );
+    }
+
+    #[test]
+    fn test_extract_session_id() {
+        assert_eq!(
+            ClaudeCodeParser::extract_session_id("a1b2c3d4-e5f6-7890-abcd-ef1234567890.jsonl"),
+            Some("a1b2c3d4-e5f6-7890-abcd-ef1234567890".to_string())
+        );
+
+        assert_eq!(ClaudeCodeParser::extract_session_id("not-a-uuid.jsonl"), None);
+        assert_eq!(ClaudeCodeParser::extract_session_id("file.txt"), None);
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let content = [
+            r#"{"type":"summary","summary":"Fix tests"}"#,
+            r#"{"type":"user","timestamp":"2025-01-01T10:00:00Z","message":{"role":"user","content":"Run the tests"}}"#,
+            r#"{"type":"assistant","timestamp":"2025-01-01T10:00:05Z","message":{"role":"assistant","model":"claude-sonnet-4-5","content":[{"type":"text","text":"Running them now."},{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test"}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
+            "not json",
+        ]
+        .join("\n");
+
+        let messages = ClaudeCodeParser::new().parse_messages(&content).unwrap();
+        assert_eq!(messages.len(), 3);
+
+        assert_eq!(messages[0].role, "user");
+        assert_eq!(messages[0].content, "Run the tests");
+        assert_eq!(messages[0].timestamp.as_deref(), Some("2025-01-01T10:00:00Z"));
+        assert_eq!(messages[0].model, None);
+
+        assert_eq!(messages[1].role, "assistant");
+        assert_eq!(messages[1].model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(messages[1].tool_calls.len(), 1);
+        assert_eq!(messages[1].tool_calls[0].name, "Bash");
+
+        assert_eq!(messages[2].role, "tool");
+        assert_eq!(messages[2].content, "ok");
+
+        assert!(ClaudeCodeParser::new().structured_upload());
+        let raw = ClaudeCodeParser::with_config(&ClaudeCodeConfig {
+            structured_messages: false,
+            ..Default::default()
+        });
+        assert!(!raw.structured_upload());
+    }
+
+    #[test]
+    fn test_render_preview() {
+        let conversation = |lines: &[&str]| Conversation {
+            source_path: PathBuf::from("/p/a1b2c3d4-e5f6-7890-abcd-ef1234567890.jsonl"),
+            source: "claude-code".to_string(),
+            session_id: Some("a1b2c3d4-e5f6-7890-abcd-ef1234567890".to_string()),
+            project_path: None,
+            content: lines.join("\n"),
+            content_type: ContentType::Conversation,
+            title: None,
+        };
+        let prompt = r#"{"type":"user","timestamp":"2025-01-01T10:00:00Z","message":{"role":"user","content":"Run the tests\nthen fix failures"}}"#;
+        let command = r#"{"type":"user","message":{"role":"user","content":"<command-name>/clear</command-name>"}}"#;
+        let reply = r#"{"type":"assistant","timestamp":"2025-01-01T10:02:30Z","message":{"role":"assistant","content":[{"type":"text","text":"Done."}]}}"#;
+
+        let parser = ClaudeCodeParser::new();
+        let preview = parser.render_preview(&conversation(&[command, prompt, reply]));
+        assert_eq!(preview.title, "Run the tests");
+        assert_eq!(preview.first_prompt.as_deref(), Some("Run the tests"));
+        assert_eq!(preview.message_count, 3);
+        assert_eq!(preview.duration, Some(std::time::Duration::from_secs(150)));
+
+        let summary = r#"{"type":"summary","summary":"Fix flaky tests"}"#;
+        let preview = parser.render_preview(&conversation(&[summary, prompt]));
+        assert_eq!(preview.title, "Fix flaky tests");
+        assert_eq!(preview.message_count, 1);
+
+        assert_eq!(parser.render_preview(&conversation(&[])).title, "Conversation a1b2c3d4");
+    }
+
+    #[test]
+    fn test_parse_title() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a1b2c3d4-e5f6-7890-abcd-ef1234567890.jsonl");
+        let prompt = r#"{"type":"user","message":{"role":"user","content":"Add a retry to the upload loop"}}"#;
+        let parser = ClaudeCodeParser::new();
+
+        std::fs::write(&file, format!("{}\n", prompt)).unwrap();
+        let title = parser.parse(&file).unwrap().title;
+        assert_eq!(title.as_deref(), Some("Add a retry to the upload loop"));
+
+        let summary = r#"{"type":"summary","summary":"Upload retries"}"#;
+        std::fs::write(&file, format!("{}\n{}\n", prompt, summary)).unwrap();
+        assert_eq!(parser.parse(&file).unwrap().title.as_deref(), Some("Upload retries"));
+
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(parser.parse(&file).unwrap().title, None);
+    }
+}
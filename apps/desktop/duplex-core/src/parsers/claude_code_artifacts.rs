@@ -0,0 +1,191 @@
+use super::{read_file, ClaudeCodeParser, ContentType, Conversation, ConversationFile, ConversationParser, ParserError};
+use std::path::{Path, PathBuf};
+
+use crate::config::ClaudeCodeConfig;
+
+/// Memory file names Claude Code reads from a project root or `~/.claude`
+pub const MEMORY_FILES: &[&str] = &["CLAUDE.md", "CLAUDE.local.md"];
+
+/// Parser for the working files Claude Code keeps beside its sessions:
+/// todo lists (`~/.claude/todos/*.json`), plans (`~/.claude/plans/*.md`)
+/// and `CLAUDE.md` memory, both user-level and per project
+pub struct ClaudeCodeArtifactsParser {
+    /// Claude Code's config directories (`~/.claude` and friends)
+    claude_dirs: Vec<PathBuf>,
+}
+
+impl ClaudeCodeArtifactsParser {
+    pub fn new() -> Self {
+        Self::with_config(&ClaudeCodeConfig::default())
+    }
+
+    /// Parser for the config directories `parsers.claudeCode` points at
+    pub fn with_config(config: &ClaudeCodeConfig) -> Self {
+        Self {
+            claude_dirs: ClaudeCodeParser::candidate_config_roots(config),
+        }
+    }
+
+    /// Whether `dir` is the `subdir` of one of Claude Code's config directories
+    fn is_config_subdir(&self, dir: &Path, subdir: &str) -> bool {
+        dir.file_name().is_some_and(|name| name == subdir)
+            && dir.parent().is_some_and(|parent| self.is_config_dir(parent))
+    }
+
+    fn is_config_dir(&self, dir: &Path) -> bool {
+        self.claude_dirs.iter().any(|claude_dir| claude_dir == dir)
+    }
+
+    /// Work out which kind of artifact a file is, if any
+    fn classify(&self, path: &Path) -> Option<ContentType> {
+        let filename = path.file_name()?.to_str()?;
+        let parent = path.parent()?;
+
+        if MEMORY_FILES.contains(&filename) {
+            Some(ContentType::Memory)
+        } else if self.is_config_subdir(parent, "todos") && filename.ends_with(".json") {
+            Some(ContentType::Todo)
+        } else if self.is_config_subdir(parent, "plans") && filename.ends_with(".md") {
+            Some(ContentType::Plan)
+        } else {
+            None
+        }
+    }
+
+    /// Extract the session ID from a todo file name
+    fn extract_session_id(filename: &str) -> Option<String> {
+        // Todo files are named "<session-uuid>-agent-<agent-uuid>.json"
+        let session_id = filename.get(..36)?;
+        if session_id.chars().filter(|c| *c == '-').count() == 4
+            && filename[36..].starts_with("-agent-")
+        {
+            return Some(session_id.to_string());
+        }
+        None
+    }
+
+    fn to_file(&self, path: &Path) -> ConversationFile {
+        let (session_id, project_path) = self.describe(path);
+        ConversationFile {
+            path: path.to_path_buf(),
+            session_id,
+            project_path,
+        }
+    }
+
+    /// Session and project a file belongs to
+    fn describe(&self, path: &Path) -> (Option<String>, Option<PathBuf>) {
+        match self.classify(path) {
+            Some(ContentType::Todo) => (
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(Self::extract_session_id),
+                None,
+            ),
+            // Project memory lives in the project root; user memory has no project
+            Some(ContentType::Memory) => (
+                None,
+                path.parent()
+                    .filter(|parent| !self.is_config_dir(parent))
+                    .map(Path::to_path_buf),
+            ),
+            _ => (None, None),
+        }
+    }
+}
+
+impl Default for ClaudeCodeArtifactsParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationParser for ClaudeCodeArtifactsParser {
+    fn name(&self) -> &str {
+        "claude-code-artifacts"
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        self.is_config_subdir(path, "todos")
+            || self.is_config_subdir(path, "plans")
+            || self.classify(path).is_some()
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        if path.is_file() {
+            return self
+                .classify(path)
+                .map(|_| vec![self.to_file(path)])
+                .unwrap_or_default();
+        }
+
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|p| p.is_file() && self.classify(p).is_some())
+            .map(|p| self.to_file(&p))
+            .collect()
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        let content_type = self.classify(file).ok_or(ParserError::UnsupportedFormat)?;
+        let content = read_file(file, self.decoding())?;
+        let (session_id, project_path) = self.describe(file);
+
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: self.name().to_string(),
+            session_id,
+            project_path,
+            content,
+            content_type,
+            title: None,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        let mut patterns = vec!["*.json", "*.md"];
+        patterns.extend(MEMORY_FILES);
+        patterns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_describe() {
+        let parser = ClaudeCodeArtifactsParser {
+            claude_dirs: vec![PathBuf::from("/home/me/.claude"), PathBuf::from("/opt/claude")],
+        };
+
+        let todo = Path::new(
+            "/home/me/.claude/todos/a1b2c3d4-e5f6-7890-abcd-ef1234567890-agent-a1b2c3d4-e5f6-7890-abcd-ef1234567890.json",
+        );
+        assert_eq!(parser.classify(todo), Some(ContentType::Todo));
+        assert_eq!(
+            parser.describe(todo).0.as_deref(),
+            Some("a1b2c3d4-e5f6-7890-abcd-ef1234567890")
+        );
+
+        let plan = Path::new("/home/me/.claude/plans/shiny-fixing-tests.md");
+        assert_eq!(parser.classify(plan), Some(ContentType::Plan));
+        let plan = Path::new("/opt/claude/plans/shiny-fixing-tests.md");
+        assert_eq!(parser.classify(plan), Some(ContentType::Plan));
+
+        let project_memory = Path::new("/work/app/CLAUDE.md");
+        assert_eq!(parser.classify(project_memory), Some(ContentType::Memory));
+        assert_eq!(parser.describe(project_memory).1, Some(PathBuf::from("/work/app")));
+
+        let user_memory = Path::new("/home/me/.claude/CLAUDE.md");
+        assert_eq!(parser.describe(user_memory).1, None);
+
+        assert_eq!(parser.classify(Path::new("/home/me/.claude/settings.json")), None);
+        assert_eq!(parser.classify(Path::new("/work/app/README.md")), None);
+    }
+}
@@ -0,0 +1,439 @@
+use super::{
+    conversation_title, read_file, read_jsonl, ContentType, Conversation, ConversationFile, ConversationParser,
+    Decoding, Message, ParserError, ToolCall,
+};
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::config::CodexConfig;
+use crate::watcher::expand_path;
+
+/// Environment variable that overrides Codex's home directory
+const CODEX_HOME_ENV: &str = "CODEX_HOME";
+
+/// Parser for OpenAI Codex CLI session rollouts
+///
+/// Codex writes one `rollout-<time>-<uuid>.jsonl` per session under
+/// `~/.codex/sessions/YYYY/MM/DD`, and moves archived ones to
+/// `~/.codex/archived_sessions`, read with `parsers.codex.includeArchived`.
+/// Three layouts are read: the current one,
+/// where each line wraps a `session_meta` or `response_item` payload; the
+/// earlier one, with a bare metadata line followed by bare items; and the
+/// original CLI's single JSON document of `session` and `items`.
+pub struct CodexParser {
+    /// Sessions directories under the Codex home: `sessions`, plus
+    /// `archived_sessions` if included
+    sessions_dirs: Vec<PathBuf>,
+}
+
+/// Metadata and conversation items read from a rollout
+#[derive(Default)]
+struct Rollout {
+    session_id: Option<String>,
+    cwd: Option<PathBuf>,
+    /// Response items with the time they were written, when recorded
+    items: Vec<(Option<String>, Value)>,
+}
+
+impl CodexParser {
+    pub fn new() -> Self {
+        Self::with_config(&CodexConfig::default())
+    }
+
+    pub fn with_config(config: &CodexConfig) -> Self {
+        let home = match &config.base_dir {
+            Some(base_dir) => Some(expand_path(base_dir)),
+            None => codex_home(std::env::var_os(CODEX_HOME_ENV), dirs::home_dir().as_deref()),
+        };
+        let mut subdirs = vec!["sessions"];
+        if config.include_archived {
+            subdirs.push("archived_sessions");
+        }
+        Self {
+            sessions_dirs: home
+                .map(|home| subdirs.iter().map(|subdir| home.join(subdir)).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Sessions directories that exist
+    pub fn sessions_dirs(config: &CodexConfig) -> Vec<PathBuf> {
+        Self::with_config(config).sessions_dirs.into_iter().filter(|dir| dir.is_dir()).collect()
+    }
+
+    fn in_sessions_dir(&self, path: &Path) -> bool {
+        self.sessions_dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Whether a file name looks like a Codex rollout
+    fn is_rollout(path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        name.starts_with("rollout-") && (name.ends_with(".jsonl") || name.ends_with(".json"))
+    }
+
+    /// Session ID from the UUID at the end of a rollout file name
+    fn extract_session_id(filename: &str) -> Option<String> {
+        let stem = filename.strip_suffix(".jsonl").or_else(|| filename.strip_suffix(".json"))?;
+        let id = stem.get(stem.len().checked_sub(36)?..)?;
+        (id.chars().filter(|c| *c == '-').count() == 4 && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-'))
+            .then(|| id.to_string())
+    }
+
+    /// Read any rollout layout into metadata and items
+    fn read_rollout(content: &str) -> Rollout {
+        let mut rollout = Rollout::default();
+
+        // The original CLI rewrote one JSON document per session
+        if let Ok(document) = serde_json::from_str::<Value>(content) {
+            if let Some(items) = document["items"].as_array() {
+                rollout.session_id = document["session"]["id"].as_str().map(String::from);
+                rollout.items = items.iter().map(|item| (None, item.clone())).collect();
+                rollout.cwd = Self::cwd_from_context(&rollout.items);
+                return rollout;
+            }
+        }
+
+        for record in content.lines().filter_map(|line| serde_json::from_str::<Value>(line).ok()) {
+            let timestamp = record["timestamp"].as_str().map(String::from);
+            match record["type"].as_str() {
+                Some("session_meta") => {
+                    let meta = &record["payload"];
+                    rollout.session_id = meta["id"].as_str().map(String::from);
+                    rollout.cwd = meta["cwd"].as_str().map(PathBuf::from);
+                }
+                Some("response_item") => rollout.items.push((timestamp, record["payload"].clone())),
+                // Bare items from before records were wrapped
+                Some("message" | "function_call" | "function_call_output" | "local_shell_call") => {
+                    rollout.items.push((None, record))
+                }
+                // Events, turn context and reasoning repeat what the items say
+                Some(_) => {}
+                None if record["id"].is_string() && record["timestamp"].is_string() => {
+                    rollout.session_id = record["id"].as_str().map(String::from);
+                }
+                None => {}
+            }
+        }
+
+        if rollout.cwd.is_none() {
+            rollout.cwd = Self::cwd_from_context(&rollout.items);
+        }
+        rollout
+    }
+
+    /// Working directory from the `<environment_context>` Codex sends as a
+    /// user message, for layouts without a `cwd` field
+    fn cwd_from_context(items: &[(Option<String>, Value)]) -> Option<PathBuf> {
+        items.iter().find_map(|(_, item)| {
+            let text = Self::item_text(item)?;
+            let start = text.find("<cwd>")? + "<cwd>".len();
+            let end = start + text[start..].find("</cwd>")?;
+            Some(PathBuf::from(text[start..end].trim()))
+        })
+    }
+
+    /// Text blocks of a message item
+    fn item_text(item: &Value) -> Option<String> {
+        let text: Vec<&str> = item["content"]
+            .as_array()?
+            .iter()
+            .filter(|block| matches!(block["type"].as_str(), Some("input_text" | "output_text" | "text")))
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        (!text.is_empty()).then(|| text.join("\n\n"))
+    }
+
+    /// Convert one response item into a message, skipping reasoning and
+    /// other non-dialogue items
+    fn item_to_message(timestamp: Option<String>, item: &Value) -> Option<Message> {
+        let (role, content, tool_calls) = match item["type"].as_str()? {
+            "message" => {
+                let role = match item["role"].as_str()? {
+                    "developer" => "system",
+                    role => role,
+                };
+                (role.to_string(), Self::item_text(item)?, Vec::new())
+            }
+            "function_call" | "custom_tool_call" => {
+                let input = item["arguments"].as_str().or(item["input"].as_str()).unwrap_or("");
+                let call = ToolCall {
+                    name: item["name"].as_str().unwrap_or("tool").to_string(),
+                    input: input.to_string(),
+                };
+                ("assistant".to_string(), String::new(), vec![call])
+            }
+            "local_shell_call" => {
+                let call = ToolCall {
+                    name: "shell".to_string(),
+                    input: item["action"].to_string(),
+                };
+                ("assistant".to_string(), String::new(), vec![call])
+            }
+            "function_call_output" | "custom_tool_call_output" => {
+                let output = match &item["output"] {
+                    // Shell output is a JSON string of `{ output, metadata }`
+                    Value::String(s) => serde_json::from_str::<Value>(s)
+                        .ok()
+                        .and_then(|v| v["output"].as_str().map(String::from))
+                        .unwrap_or_else(|| s.clone()),
+                    Value::Null => return None,
+                    other => other.to_string(),
+                };
+                ("tool".to_string(), output, Vec::new())
+            }
+            _ => return None,
+        };
+
+        Some(Message {
+            role,
+            content,
+            timestamp,
+            utc_offset: None,
+            tool_calls,
+            model: None,
+        })
+    }
+
+    fn messages(items: Vec<(Option<String>, Value)>) -> Vec<Message> {
+        items
+            .into_iter()
+            .filter_map(|(timestamp, item)| Self::item_to_message(timestamp, &item))
+            .collect()
+    }
+
+    fn to_file(path: &Path) -> ConversationFile {
+        let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        ConversationFile {
+            path: path.to_path_buf(),
+            session_id: Self::extract_session_id(filename),
+            // Only known once the file is read
+            project_path: None,
+        }
+    }
+}
+
+impl Default for CodexParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationParser for CodexParser {
+    fn name(&self) -> &str {
+        "codex"
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        if self.in_sessions_dir(path) {
+            return path.is_dir() || Self::is_rollout(path);
+        }
+        // A copied or mounted sessions directory
+        path.is_dir()
+            && path.file_name().is_some_and(|n| n == "sessions")
+            && path.parent().and_then(|p| p.file_name()).is_some_and(|n| n == ".codex")
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        if path.is_file() {
+            return if Self::is_rollout(path) { vec![Self::to_file(path)] } else { Vec::new() };
+        }
+
+        // Sessions are nested by date
+        let mut files = Vec::new();
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let entry_path = entry.path();
+                if entry_path.is_dir() {
+                    dirs.push(entry_path);
+                } else if Self::is_rollout(&entry_path) {
+                    files.push(Self::to_file(&entry_path));
+                }
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        if !Self::is_rollout(file) {
+            return Err(ParserError::UnsupportedFormat);
+        }
+        let content = if file.extension().is_some_and(|e| e == "jsonl") {
+            read_jsonl(file, self.decoding())?
+        } else {
+            read_file(file, self.decoding())?
+        };
+
+        let rollout = Self::read_rollout(&content);
+        let filename = file.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let messages = Self::messages(rollout.items);
+
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: self.name().to_string(),
+            session_id: rollout.session_id.or_else(|| Self::extract_session_id(filename)),
+            project_path: rollout.cwd,
+            title: conversation_title(&messages, None),
+            content,
+            content_type: ContentType::Conversation,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        vec!["rollout-*.jsonl", "rollout-*.json"]
+    }
+
+    fn decoding(&self) -> Decoding {
+        Decoding::Lossy
+    }
+
+    // JSONL rollouts grow by lines; the original single-document layout is
+    // rewritten, which the sync engine notices and uploads whole
+    fn append_only(&self) -> bool {
+        true
+    }
+
+    fn parse_messages(&self, content: &str) -> Option<Vec<Message>> {
+        Some(Self::messages(Self::read_rollout(content).items))
+    }
+}
+
+/// `$CODEX_HOME`, else `~/.codex`
+fn codex_home(codex_home: Option<OsString>, home: Option<&Path>) -> Option<PathBuf> {
+    codex_home
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|h| h.join(".codex")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_ID: &str = "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b";
+
+    #[test]
+    fn test_codex_home() {
+        let home = Path::new("/home/me");
+        assert_eq!(codex_home(None, Some(home)), Some(PathBuf::from("/home/me/.codex")));
+        assert_eq!(codex_home(Some("".into()), Some(home)), Some(PathBuf::from("/home/me/.codex")));
+        assert_eq!(codex_home(Some("/opt/codex".into()), Some(home)), Some(PathBuf::from("/opt/codex")));
+    }
+
+    #[test]
+    fn test_configured_home() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sessions")).unwrap();
+        std::fs::create_dir_all(dir.path().join("archived_sessions")).unwrap();
+        let archived = dir.path().join(format!("archived_sessions/rollout-2025-09-01T10-00-00-{}.jsonl", SESSION_ID));
+
+        let mut config = CodexConfig {
+            base_dir: Some(dir.path().to_string_lossy().to_string()),
+            include_archived: false,
+        };
+        assert_eq!(CodexParser::sessions_dirs(&config), vec![dir.path().join("sessions")]);
+        assert!(!CodexParser::with_config(&config).detect(&archived));
+
+        config.include_archived = true;
+        assert_eq!(CodexParser::sessions_dirs(&config).len(), 2);
+        assert!(CodexParser::with_config(&config).detect(&archived));
+    }
+
+    #[test]
+    fn test_extract_session_id() {
+        let filename = format!("rollout-2025-09-01T10-00-00-{}.jsonl", SESSION_ID);
+        assert_eq!(CodexParser::extract_session_id(&filename).as_deref(), Some(SESSION_ID));
+        assert_eq!(CodexParser::extract_session_id("rollout-notes.jsonl"), None);
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let content = [
+            format!(
+                r#"{{"timestamp":"2025-09-01T10:00:00Z","type":"session_meta","payload":{{"id":"{}","cwd":"/work/app"}}}}"#,
+                SESSION_ID
+            ),
+            r#"{"timestamp":"2025-09-01T10:00:01Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"Run the tests"}]}}"#.to_string(),
+            r#"{"timestamp":"2025-09-01T10:00:02Z","type":"response_item","payload":{"type":"reasoning","summary":[]}}"#.to_string(),
+            r#"{"timestamp":"2025-09-01T10:00:03Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"cargo\",\"test\"]}","call_id":"c1"}}"#.to_string(),
+            r#"{"timestamp":"2025-09-01T10:00:09Z","type":"response_item","payload":{"type":"function_call_output","call_id":"c1","output":"{\"output\":\"ok\",\"metadata\":{\"exit_code\":0}}"}}"#.to_string(),
+            r#"{"timestamp":"2025-09-01T10:00:10Z","type":"event_msg","payload":{"type":"agent_message","message":"Done."}}"#.to_string(),
+            r#"{"timestamp":"2025-09-01T10:00:10Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"Done."}]}}"#.to_string(),
+        ]
+        .join("\n");
+
+        let rollout = CodexParser::read_rollout(&content);
+        assert_eq!(rollout.session_id.as_deref(), Some(SESSION_ID));
+        assert_eq!(rollout.cwd, Some(PathBuf::from("/work/app")));
+
+        let messages = CodexParser::new().parse_messages(&content).unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "assistant"]);
+        assert_eq!(messages[0].timestamp.as_deref(), Some("2025-09-01T10:00:01Z"));
+        assert_eq!(messages[1].tool_calls[0].name, "shell");
+        assert_eq!(messages[2].content, "ok");
+        assert_eq!(messages[3].content, "Done.");
+    }
+
+    #[test]
+    fn test_read_earlier_layouts() {
+        let context = "<environment_context>\n  <cwd>/work/lib</cwd>\n</environment_context>";
+        let user = |text: &str| {
+            serde_json::json!({ "type": "message", "role": "user", "content": [{ "type": "input_text", "text": text }] })
+        };
+
+        let bare = [
+            serde_json::json!({ "id": SESSION_ID, "timestamp": "2025-05-01T10:00:00Z" }),
+            serde_json::json!({ "record_type": "state" }),
+            user(context),
+            user("Fix the build"),
+        ]
+        .map(|v| v.to_string())
+        .join("\n");
+        let rollout = CodexParser::read_rollout(&bare);
+        assert_eq!(rollout.session_id.as_deref(), Some(SESSION_ID));
+        assert_eq!(rollout.cwd, Some(PathBuf::from("/work/lib")));
+        assert_eq!(rollout.items.len(), 2);
+
+        let document = serde_json::json!({
+            "session": { "id": SESSION_ID, "timestamp": "2025-04-01T10:00:00Z" },
+            "items": [user(context), user("Fix the build")],
+        })
+        .to_string();
+        let rollout = CodexParser::read_rollout(&document);
+        assert_eq!(rollout.session_id.as_deref(), Some(SESSION_ID));
+        assert_eq!(rollout.cwd, Some(PathBuf::from("/work/lib")));
+
+        // The injected context is skipped when titling
+        let messages = CodexParser::messages(rollout.items);
+        assert_eq!(conversation_title(&messages, None).as_deref(), Some("Fix the build"));
+    }
+
+    #[test]
+    fn test_discover_and_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = dir.path().join("2025/09/01");
+        std::fs::create_dir_all(&day).unwrap();
+        let file = day.join(format!("rollout-2025-09-01T10-00-00-{}.jsonl", SESSION_ID));
+        let meta = r#"{"timestamp":"2025-09-01T10:00:00Z","type":"session_meta","payload":{"cwd":"/work/app"}}"#;
+        let prompt = r#"{"timestamp":"2025-09-01T10:00:01Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"Add a retry"}]}}"#;
+        std::fs::write(&file, format!("{}\n{}\n", meta, prompt)).unwrap();
+        std::fs::write(day.join("notes.txt"), "not a session").unwrap();
+
+        let parser = CodexParser::new();
+        let files = parser.discover(dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].session_id.as_deref(), Some(SESSION_ID));
+
+        let conversation = parser.parse(&file).unwrap();
+        assert_eq!(conversation.source, "codex");
+        assert_eq!(conversation.session_id.as_deref(), Some(SESSION_ID));
+        assert_eq!(conversation.project_path, Some(PathBuf::from("/work/app")));
+        assert_eq!(conversation.title.as_deref(), Some("Add a retry"));
+    }
+}
@@ -0,0 +1,514 @@
+use super::generic_jsonl::{lookup, normalize_role, text};
+use super::{
+    conversation_title, read_file, ContentType, Conversation, ConversationFile, ConversationParser, Decoding,
+    Message, ParserError,
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use jsonschema::JSONSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::errors::ErrorCategory;
+use crate::watcher::expand_path;
+
+#[derive(Error, Debug)]
+pub enum DefinitionError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid definition: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid JSON schema: {0}")]
+    Schema(String),
+    #[error("Invalid pattern: {0}")]
+    Pattern(#[from] globset::Error),
+    #[error("Definition file has no usable name: {0}")]
+    Name(PathBuf),
+}
+
+impl DefinitionError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            DefinitionError::Io(_) => ErrorCategory::Io,
+            _ => ErrorCategory::Config,
+        }
+    }
+}
+
+/// How a definition's files hold their records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    /// One JSON record per line
+    #[default]
+    Jsonl,
+    /// One JSON document holding an array of records
+    Json,
+}
+
+/// Where a conversation's session ID comes from when no record has the
+/// session ID field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionIdFallback {
+    /// The file name without its extension
+    #[default]
+    FileStem,
+    /// The name of the directory holding the file
+    ParentDir,
+}
+
+/// How the session ID is derived
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionIdMapping {
+    /// Dotted path of the record field holding the session ID
+    pub field: Option<String>,
+    pub fallback: SessionIdFallback,
+}
+
+/// Dotted paths of the record fields that make up a message
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldMapping {
+    pub role: String,
+    /// A string, or a list of strings and `{ "text": ... }` parts
+    pub content: String,
+    /// RFC 3339 timestamp
+    pub timestamp: Option<String>,
+    pub model: Option<String>,
+    /// Tool name, for records that are tool calls
+    pub tool_name: Option<String>,
+    /// Tool input, serialized as JSON
+    pub tool_input: Option<String>,
+    /// Working directory of the session, taken from the first record with it
+    pub project: Option<String>,
+    /// Conversation title, taken from the first record with it
+    pub title: Option<String>,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            role: "role".to_string(),
+            content: "content".to_string(),
+            timestamp: Some("timestamp".to_string()),
+            model: Some("model".to_string()),
+            tool_name: None,
+            tool_input: None,
+            project: Some("cwd".to_string()),
+            title: None,
+        }
+    }
+}
+
+/// A parser definition file, `<config dir>/parsers/<name>.toml`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct ParserDefinition {
+    /// Directories holding the agent's logs; `~` is expanded
+    pub paths: Vec<String>,
+    /// File name patterns of conversation files
+    #[serde(default = "default_patterns")]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub format: RecordFormat,
+    /// Dotted path of the record array in a `json` document; the document
+    /// itself when unset
+    pub records: Option<String>,
+    /// JSON Schema file records must match, relative to the definition;
+    /// records that don't are ignored
+    pub schema: Option<PathBuf>,
+    #[serde(default)]
+    pub session_id: SessionIdMapping,
+    #[serde(default)]
+    pub fields: FieldMapping,
+    /// The agent's role names mapped to `user`, `assistant`, `system` or
+    /// `tool`; common names are recognized without one
+    #[serde(default)]
+    pub roles: HashMap<String, String>,
+    /// Whether files only grow by whole lines; `jsonl` only
+    #[serde(default)]
+    pub append_only: bool,
+}
+
+fn default_patterns() -> Vec<String> {
+    vec!["*.jsonl".to_string()]
+}
+
+/// Parser built from a [`ParserDefinition`], for in-house agents
+///
+/// Named after its definition file. It watches the definition's `paths`
+/// and reads every file matching its patterns below them as one
+/// conversation, mapping each record to a message through the configured
+/// fields. Records that aren't JSON, don't match the schema or have no
+/// known role are skipped; a file where every record is skipped is an
+/// unsupported format.
+pub struct DeclarativeParser {
+    name: String,
+    definition: ParserDefinition,
+    dirs: Vec<PathBuf>,
+    matcher: GlobSet,
+    schema: Option<JSONSchema>,
+}
+
+impl DeclarativeParser {
+    /// Load the definition at `path`
+    pub fn load(path: &Path) -> Result<Self, DefinitionError> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .ok_or_else(|| DefinitionError::Name(path.to_path_buf()))?;
+        let definition: ParserDefinition = toml::from_str(&std::fs::read_to_string(path)?)?;
+
+        let schema = match &definition.schema {
+            Some(schema) => {
+                let schema_path = path.parent().unwrap_or(Path::new(".")).join(schema);
+                let schema: Value = serde_json::from_str(&std::fs::read_to_string(&schema_path)?)
+                    .map_err(|e| DefinitionError::Schema(format!("{:?}: {}", schema_path, e)))?;
+                Some(Self::compile_schema(&schema)?)
+            }
+            None => None,
+        };
+        Self::new(name, definition, schema)
+    }
+
+    /// Parser named `name` from an already loaded definition
+    pub fn new(name: &str, definition: ParserDefinition, schema: Option<JSONSchema>) -> Result<Self, DefinitionError> {
+        let mut matcher = GlobSetBuilder::new();
+        for pattern in &definition.patterns {
+            matcher.add(Glob::new(pattern)?);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            dirs: definition.paths.iter().map(|p| expand_path(p)).collect(),
+            matcher: matcher.build()?,
+            schema,
+            definition,
+        })
+    }
+
+    /// Compile a JSON Schema for [`Self::new`]
+    pub fn compile_schema(schema: &Value) -> Result<JSONSchema, DefinitionError> {
+        JSONSchema::compile(schema).map_err(|e| DefinitionError::Schema(e.to_string()))
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| self.matcher.is_match(name))
+    }
+
+    /// Records of a file, leaving out those the schema rejects
+    fn records(&self, content: &str) -> Vec<Value> {
+        let records: Vec<Value> = match self.definition.format {
+            RecordFormat::Jsonl => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect(),
+            RecordFormat::Json => {
+                let document: Value = serde_json::from_str(content).unwrap_or(Value::Null);
+                let array = match &self.definition.records {
+                    Some(path) => lookup(&document, path),
+                    None => Some(&document),
+                };
+                array.and_then(Value::as_array).cloned().unwrap_or_default()
+            }
+        };
+
+        match &self.schema {
+            Some(schema) => records.into_iter().filter(|record| schema.is_valid(record)).collect(),
+            None => records,
+        }
+    }
+
+    /// First string (or number) value of a field across records
+    fn first(records: &[Value], field: Option<&str>) -> Option<String> {
+        let field = field?;
+        records.iter().find_map(|record| match lookup(record, field)? {
+            Value::String(value) if !value.is_empty() => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        })
+    }
+
+    fn session_id(&self, file: &Path, records: &[Value]) -> Option<String> {
+        Self::first(records, self.definition.session_id.field.as_deref()).or_else(|| self.derived_session_id(file))
+    }
+
+    /// Session ID from the file's location alone
+    fn derived_session_id(&self, file: &Path) -> Option<String> {
+        let name = match self.definition.session_id.fallback {
+            SessionIdFallback::FileStem => file.file_stem(),
+            SessionIdFallback::ParentDir => file.parent()?.file_name(),
+        };
+        name.and_then(|n| n.to_str()).map(str::to_string)
+    }
+
+    fn role(&self, record: &Value) -> Option<&str> {
+        let role = lookup(record, &self.definition.fields.role)?.as_str()?;
+        match self.definition.roles.get(role) {
+            Some(mapped) => normalize_role(mapped),
+            None => normalize_role(role),
+        }
+    }
+
+    fn to_message(&self, record: &Value) -> Option<Message> {
+        let fields = &self.definition.fields;
+        let role = self.role(record)?;
+        let field = |path: &Option<String>| {
+            path.as_deref()
+                .and_then(|path| lookup(record, path))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+
+        let tool_calls: Vec<_> = field(&fields.tool_name)
+            .map(|name| super::ToolCall {
+                name,
+                input: fields
+                    .tool_input
+                    .as_deref()
+                    .and_then(|path| lookup(record, path))
+                    .map(Value::to_string)
+                    .unwrap_or_default(),
+            })
+            .into_iter()
+            .collect();
+        let content = lookup(record, &fields.content).map(text).unwrap_or_default();
+        if content.is_empty() && tool_calls.is_empty() {
+            return None;
+        }
+
+        Some(Message {
+            role: role.to_string(),
+            content,
+            timestamp: field(&fields.timestamp),
+            utc_offset: None,
+            tool_calls,
+            model: field(&fields.model),
+        })
+    }
+}
+
+impl ConversationParser for DeclarativeParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        self.dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        let mut files = Vec::new();
+        let mut dirs = vec![path.to_path_buf()];
+        if path.is_file() {
+            dirs.clear();
+            if self.matches(path) {
+                dirs.push(path.to_path_buf());
+            }
+        }
+
+        while let Some(dir) = dirs.pop() {
+            if dir.is_file() {
+                files.push(ConversationFile {
+                    session_id: self.derived_session_id(&dir).filter(|_| self.definition.session_id.field.is_none()),
+                    project_path: None,
+                    path: dir,
+                });
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let entry_path = entry.path();
+                if entry_path.is_dir() || self.matches(&entry_path) {
+                    dirs.push(entry_path);
+                }
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        let content = self.read(file)?;
+        let records = self.records(&content);
+        if records.is_empty() && !content.trim().is_empty() {
+            return Err(ParserError::UnsupportedFormat);
+        }
+        let messages: Vec<Message> = records.iter().filter_map(|record| self.to_message(record)).collect();
+        let fields = &self.definition.fields;
+
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: self.name.clone(),
+            session_id: self.session_id(file, &records),
+            project_path: Self::first(&records, fields.project.as_deref()).map(PathBuf::from),
+            title: conversation_title(&messages, Self::first(&records, fields.title.as_deref()).as_deref()),
+            content,
+            content_type: ContentType::Conversation,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        self.definition.patterns.iter().map(String::as_str).collect()
+    }
+
+    fn watch_dirs(&self) -> Vec<PathBuf> {
+        self.dirs.clone()
+    }
+
+    fn decoding(&self) -> Decoding {
+        Decoding::Lossy
+    }
+
+    fn read(&self, file: &Path) -> Result<String, ParserError> {
+        match self.definition.format {
+            RecordFormat::Jsonl => super::read_jsonl(file, self.decoding()),
+            RecordFormat::Json => read_file(file, self.decoding()),
+        }
+    }
+
+    fn append_only(&self) -> bool {
+        self.definition.append_only && self.definition.format == RecordFormat::Jsonl
+    }
+
+    fn parse_messages(&self, content: &str) -> Option<Vec<Message>> {
+        let messages: Vec<Message> = self.records(content).iter().filter_map(|record| self.to_message(record)).collect();
+        (!messages.is_empty()).then_some(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DEFINITION: &str = r#"
+        paths = ["/opt/acme/sessions"]
+        patterns = ["*.log.jsonl"]
+        schema = "acme.schema.json"
+
+        [session_id]
+        field = "meta.run"
+        fallback = "parent-dir"
+
+        [fields]
+        role = "speaker"
+        content = "body.text"
+        timestamp = "at"
+        tool_name = "tool.name"
+        tool_input = "tool.args"
+        project = "workspace"
+
+        [roles]
+        operator = "user"
+        acme = "assistant"
+    "#;
+
+    const TRANSCRIPT: &str = concat!(
+        r#"{"kind":"start","meta":{"run":"run-7"},"workspace":"/work/billing"}"#,
+        "\n",
+        r#"{"kind":"msg","speaker":"operator","body":{"text":"Reconcile the invoices"},"at":"2026-05-01T10:00:00Z"}"#,
+        "\n",
+        r#"{"kind":"msg","speaker":"acme","body":{"text":"Running the report."},"tool":{"name":"sql","args":{"q":"select 1"}}}"#,
+        "\n",
+        r#"{"kind":"msg","speaker":"acme","body":"no schema"}"#,
+        "\n",
+    );
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["kind"],
+            "properties": { "body": { "type": "object" } }
+        })
+    }
+
+    #[test]
+    fn test_load_definition() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acme-agent.toml");
+        std::fs::write(&path, DEFINITION).unwrap();
+        assert!(matches!(DeclarativeParser::load(&path), Err(DefinitionError::Io(_))));
+
+        std::fs::write(dir.path().join("acme.schema.json"), schema().to_string()).unwrap();
+        let parser = DeclarativeParser::load(&path).unwrap();
+        assert_eq!(parser.name(), "acme-agent");
+        assert_eq!(parser.watch_dirs(), vec![PathBuf::from("/opt/acme/sessions")]);
+        assert!(parser.detect(Path::new("/opt/acme/sessions/2026/a.log.jsonl")));
+        assert!(!parser.detect(Path::new("/opt/other")));
+
+        std::fs::write(&path, "paths = []\nunknown = 1\n").unwrap();
+        assert!(matches!(DeclarativeParser::load(&path), Err(DefinitionError::Toml(_))));
+    }
+
+    #[test]
+    fn test_parse_mapped_records() {
+        let definition: ParserDefinition = toml::from_str(DEFINITION).unwrap();
+        let schema = DeclarativeParser::compile_schema(&schema()).unwrap();
+        let parser = DeclarativeParser::new("acme-agent", definition, Some(schema)).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let session_dir = dir.path().join("session-1");
+        std::fs::create_dir_all(&session_dir).unwrap();
+        let path = session_dir.join("main.log.jsonl");
+        std::fs::write(&path, TRANSCRIPT).unwrap();
+        std::fs::write(session_dir.join("debug.txt"), "ignored").unwrap();
+
+        let files = parser.discover(dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, path);
+
+        let conversation = parser.parse(&path).unwrap();
+        assert_eq!(conversation.source, "acme-agent");
+        assert_eq!(conversation.session_id.as_deref(), Some("run-7"));
+        assert_eq!(conversation.project_path, Some(PathBuf::from("/work/billing")));
+        assert_eq!(conversation.title.as_deref(), Some("Reconcile the invoices"));
+
+        // The record with a string body fails the schema
+        let messages = parser.parse_messages(&conversation.content).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "user");
+        assert_eq!(messages[0].timestamp.as_deref(), Some("2026-05-01T10:00:00Z"));
+        assert_eq!(messages[1].role, "assistant");
+        assert_eq!(messages[1].tool_calls[0].name, "sql");
+        assert_eq!(messages[1].tool_calls[0].input, r#"{"q":"select 1"}"#);
+
+        std::fs::write(&path, "{\"kind\":\"msg\",\"speaker\":\"acme\"}\n").unwrap();
+        assert_eq!(parser.parse(&path).unwrap().session_id.as_deref(), Some("session-1"));
+        std::fs::write(&path, "{\"body\":{}}\n").unwrap();
+        assert!(matches!(parser.parse(&path), Err(ParserError::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn test_json_document() {
+        let definition: ParserDefinition = toml::from_str(
+            r#"
+            paths = ["/opt/acme"]
+            patterns = ["*.json"]
+            format = "json"
+            records = "history.turns"
+            "#,
+        )
+        .unwrap();
+        let parser = DeclarativeParser::new("acme-json", definition, None).unwrap();
+        assert!(!parser.append_only());
+
+        let content = json!({
+            "history": { "turns": [
+                { "role": "human", "content": "Hi" },
+                { "role": "ai", "content": [{ "text": "Hello" }] }
+            ]}
+        });
+        let messages = parser.parse_messages(&content.to_string()).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].content, "Hello");
+    }
+}
@@ -0,0 +1,359 @@
+use super::{
+    collect_paths, conversation_title, read_file, ContentType, Conversation, ConversationFile, ConversationParser,
+    Decoding, Message, ParserError, ToolCall,
+};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::config::GeminiConfig;
+use crate::watcher::expand_path;
+
+/// File some Gemini CLI versions keep in a project's temp directory,
+/// holding the project root
+const PROJECT_ROOT_FILE: &str = ".project_root";
+
+/// Parser for Gemini CLI chat logs
+///
+/// Gemini CLI records each session as one JSON document at
+/// `~/.gemini/tmp/<project hash>/chats/session-<time>-<id prefix>.json`,
+/// rewritten as the chat goes on. The project hash is the SHA-256 of the
+/// project root; the root itself is not recorded in the session, so it is
+/// read from a `.project_root` file beside `chats` when there is one, else
+/// found by hashing the directories of paths the session's tools touched.
+pub struct GeminiParser {
+    /// `~/.gemini/tmp`, holding one directory per project
+    tmp_dir: Option<PathBuf>,
+}
+
+/// Session metadata and messages read from a chat log
+#[derive(Default)]
+struct Session {
+    session_id: Option<String>,
+    project_hash: Option<String>,
+    summary: Option<String>,
+    messages: Vec<Message>,
+    /// Absolute paths from tool arguments, to match against the project hash
+    tool_paths: Vec<PathBuf>,
+}
+
+impl GeminiParser {
+    pub fn new() -> Self {
+        Self::with_config(&GeminiConfig::default())
+    }
+
+    pub fn with_config(config: &GeminiConfig) -> Self {
+        let gemini_dir = match &config.base_dir {
+            Some(base_dir) => Some(expand_path(base_dir)),
+            None => dirs::home_dir().map(|home| home.join(".gemini")),
+        };
+        Self {
+            tmp_dir: gemini_dir.map(|dir| dir.join("tmp")),
+        }
+    }
+
+    /// Project temp directory root, if it exists
+    pub fn tmp_dirs(config: &GeminiConfig) -> Vec<PathBuf> {
+        Self::with_config(config).tmp_dir.into_iter().filter(|dir| dir.is_dir()).collect()
+    }
+
+    fn in_tmp_dir(&self, path: &Path) -> bool {
+        self.tmp_dir.as_deref().is_some_and(|dir| path.starts_with(dir))
+    }
+
+    /// Whether a file looks like a Gemini CLI session log
+    fn is_session(path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        name.starts_with("session-")
+            && name.ends_with(".json")
+            && path.parent().and_then(|p| p.file_name()).is_some_and(|n| n == "chats")
+    }
+
+    /// Read a chat log into metadata and messages
+    fn read_session(content: &str) -> Result<Session, ParserError> {
+        let record: Value = serde_json::from_str(content)?;
+        let entries = record["messages"].as_array().ok_or(ParserError::UnsupportedFormat)?;
+
+        let mut session = Session {
+            session_id: record["sessionId"].as_str().map(String::from),
+            project_hash: record["projectHash"].as_str().map(String::from),
+            summary: record["summary"].as_str().map(String::from),
+            ..Default::default()
+        };
+        for entry in entries {
+            let timestamp = entry["timestamp"].as_str().map(String::from);
+            match entry["type"].as_str() {
+                Some("user") => session.messages.push(Message {
+                    role: "user".to_string(),
+                    content: part_text(&entry["content"]),
+                    timestamp,
+                    utc_offset: None,
+                    tool_calls: Vec::new(),
+                    model: None,
+                }),
+                Some("gemini") => {
+                    let calls = entry["toolCalls"].as_array().map(Vec::as_slice).unwrap_or_default();
+                    for call in calls {
+                        collect_paths(&call["args"], &mut session.tool_paths);
+                    }
+                    session.messages.push(Message {
+                        role: "assistant".to_string(),
+                        content: part_text(&entry["content"]),
+                        timestamp: timestamp.clone(),
+                        utc_offset: None,
+                        tool_calls: calls
+                            .iter()
+                            .map(|call| ToolCall {
+                                name: call["name"].as_str().unwrap_or("tool").to_string(),
+                                input: call["args"].to_string(),
+                            })
+                            .collect(),
+                        model: entry["model"].as_str().map(String::from),
+                    });
+                    // Results are recorded with their call
+                    session.messages.extend(calls.iter().filter_map(|call| {
+                        Some(Message {
+                            role: "tool".to_string(),
+                            content: tool_result(call)?,
+                            timestamp: call["timestamp"].as_str().map(String::from).or(timestamp.clone()),
+                            utc_offset: None,
+                            tool_calls: Vec::new(),
+                            model: None,
+                        })
+                    }));
+                }
+                // Info, warning and error notices are CLI chrome
+                _ => {}
+            }
+        }
+        Ok(session)
+    }
+
+    /// Project root for a session, from the `.project_root` file beside its
+    /// `chats` directory, else the nearest ancestor of a tool path whose hash
+    /// is the session's project hash
+    fn project_path(file: &Path, session: &Session) -> Option<PathBuf> {
+        let project_dir = file.parent()?.parent()?;
+        if let Ok(root) = std::fs::read_to_string(project_dir.join(PROJECT_ROOT_FILE)) {
+            if !root.trim().is_empty() {
+                return Some(PathBuf::from(root.trim()));
+            }
+        }
+
+        let hash = session
+            .project_hash
+            .clone()
+            .or_else(|| project_dir.file_name().map(|n| n.to_string_lossy().to_string()))?;
+        session
+            .tool_paths
+            .iter()
+            .flat_map(|path| path.ancestors())
+            .find(|dir| project_hash(dir) == hash)
+            .map(Path::to_path_buf)
+    }
+
+    fn to_file(path: &Path) -> ConversationFile {
+        ConversationFile {
+            path: path.to_path_buf(),
+            // The file name only has an ID prefix; both are known once the
+            // file is read
+            session_id: None,
+            project_path: None,
+        }
+    }
+}
+
+impl Default for GeminiParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationParser for GeminiParser {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        if self.in_tmp_dir(path) {
+            return path.is_dir() || Self::is_session(path);
+        }
+        // A copied or mounted temp directory
+        path.is_dir()
+            && path.file_name().is_some_and(|n| n == "tmp")
+            && path.parent().and_then(|p| p.file_name()).is_some_and(|n| n == ".gemini")
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        if path.is_file() {
+            return if Self::is_session(path) { vec![Self::to_file(path)] } else { Vec::new() };
+        }
+
+        // Sessions sit two levels down, in each project's `chats` directory
+        let mut files = Vec::new();
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let entry_path = entry.path();
+                if entry_path.is_dir() {
+                    dirs.push(entry_path);
+                } else if Self::is_session(&entry_path) {
+                    files.push(Self::to_file(&entry_path));
+                }
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        if !Self::is_session(file) {
+            return Err(ParserError::UnsupportedFormat);
+        }
+        let content = read_file(file, self.decoding())?;
+        let session = Self::read_session(&content)?;
+
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: self.name().to_string(),
+            project_path: Self::project_path(file, &session),
+            title: conversation_title(&session.messages, session.summary.as_deref()),
+            session_id: session.session_id,
+            content,
+            content_type: ContentType::Conversation,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        vec!["session-*.json"]
+    }
+
+    fn decoding(&self) -> Decoding {
+        Decoding::Lossy
+    }
+
+    fn parse_messages(&self, content: &str) -> Option<Vec<Message>> {
+        Self::read_session(content).ok().map(|session| session.messages)
+    }
+}
+
+/// Gemini CLI's hash of a project root: hex SHA-256 of the path
+fn project_hash(root: &Path) -> String {
+    hex::encode(Sha256::digest(root.to_string_lossy().as_bytes()))
+}
+
+/// Text of a Gemini part list: a string, one part, or an array of parts
+fn part_text(parts: &Value) -> String {
+    match parts {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(part_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        part => part["text"].as_str().unwrap_or("").to_string(),
+    }
+}
+
+/// Output of a recorded tool call, when it has finished
+fn tool_result(call: &Value) -> Option<String> {
+    if let Some(display) = call["resultDisplay"].as_str() {
+        return Some(display.to_string());
+    }
+    let parts = match &call["result"] {
+        Value::Null => return None,
+        Value::Array(parts) => parts.clone(),
+        part => vec![part.clone()],
+    };
+    let output: Vec<String> = parts
+        .iter()
+        .filter_map(|part| match &part["functionResponse"]["response"] {
+            Value::Null => part["text"].as_str().map(String::from),
+            response => Some(response["output"].as_str().map(String::from).unwrap_or_else(|| response.to_string())),
+        })
+        .collect();
+    (!output.is_empty()).then(|| output.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SESSION_ID: &str = "5f0c8a1e-2b3d-4c5e-8f9a-0b1c2d3e4f5a";
+
+    fn session(project_hash: &str) -> Value {
+        json!({
+            "sessionId": SESSION_ID,
+            "projectHash": project_hash,
+            "startTime": "2025-09-01T10:00:00.000Z",
+            "lastUpdated": "2025-09-01T10:00:09.000Z",
+            "messages": [
+                { "id": "m1", "timestamp": "2025-09-01T10:00:00.000Z", "type": "info", "content": "Logged in" },
+                { "id": "m2", "timestamp": "2025-09-01T10:00:01.000Z", "type": "user", "content": [{ "text": "Read the config" }] },
+                {
+                    "id": "m3",
+                    "timestamp": "2025-09-01T10:00:05.000Z",
+                    "type": "gemini",
+                    "content": "Reading it now.",
+                    "toolCalls": [{
+                        "id": "c1",
+                        "name": "read_file",
+                        "args": { "absolute_path": "/work/app/src/config.ts" },
+                        "result": [{ "functionResponse": { "id": "c1", "name": "read_file", "response": { "output": "export {}" } } }],
+                        "status": "success",
+                        "timestamp": "2025-09-01T10:00:06.000Z"
+                    }]
+                },
+                { "id": "m4", "timestamp": "2025-09-01T10:00:09.000Z", "type": "gemini", "content": "It exports nothing." }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let content = session(&project_hash(Path::new("/work/app"))).to_string();
+        let messages = GeminiParser::new().parse_messages(&content).unwrap();
+
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "assistant"]);
+        assert_eq!(messages[0].content, "Read the config");
+        assert_eq!(messages[1].tool_calls[0].name, "read_file");
+        assert_eq!(messages[2].content, "export {}");
+        assert_eq!(messages[2].timestamp.as_deref(), Some("2025-09-01T10:00:06.000Z"));
+
+        assert!(GeminiParser::new().parse_messages("[]").is_none());
+    }
+
+    #[test]
+    fn test_discover_and_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let hash = project_hash(Path::new("/work/app"));
+        let chats = dir.path().join(&hash).join("chats");
+        std::fs::create_dir_all(&chats).unwrap();
+        let file = chats.join("session-2025-09-01T10-00-5f0c8a1e.json");
+        std::fs::write(&file, session(&hash).to_string()).unwrap();
+        std::fs::write(dir.path().join(&hash).join("logs.json"), "[]").unwrap();
+
+        let parser = GeminiParser::new();
+        let files = parser.discover(dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, file);
+
+        // The project is found by hashing the directories tools touched
+        let conversation = parser.parse(&file).unwrap();
+        assert_eq!(conversation.source, "gemini");
+        assert_eq!(conversation.session_id.as_deref(), Some(SESSION_ID));
+        assert_eq!(conversation.project_path, Some(PathBuf::from("/work/app")));
+        assert_eq!(conversation.title.as_deref(), Some("Read the config"));
+
+        // A recorded project root wins
+        std::fs::write(dir.path().join(&hash).join(PROJECT_ROOT_FILE), "/work/other\n").unwrap();
+        let conversation = parser.parse(&file).unwrap();
+        assert_eq!(conversation.project_path, Some(PathBuf::from("/work/other")));
+    }
+}
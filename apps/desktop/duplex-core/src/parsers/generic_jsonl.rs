@@ -0,0 +1,251 @@
+use super::{
+    conversation_title, read_jsonl, ContentType, Conversation, ConversationFile, ConversationParser, Decoding,
+    Message, ParserError,
+};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Parser for JSONL transcripts written by any agent
+///
+/// Matches only directories listed in `discovery.additionalPaths` that no
+/// other parser claims, so it is registered last. Every `*.jsonl` file
+/// below such a directory is one conversation. The session ID comes from
+/// the configured JSON field of the first record that has it, else from
+/// the file name.
+///
+/// Records shaped like chat messages (`role` plus `content`, as a string
+/// or a list of text parts) become structured messages; anything else is
+/// uploaded as raw content.
+pub struct GenericJsonlParser {
+    /// Dotted path of the record field holding the session ID
+    session_id_field: Option<String>,
+}
+
+impl GenericJsonlParser {
+    pub fn new(session_id_field: Option<String>) -> Self {
+        Self { session_id_field }
+    }
+
+    fn is_jsonl(path: &Path) -> bool {
+        path.extension().is_some_and(|e| e == "jsonl")
+    }
+
+    /// Session ID from the first record with the configured field
+    fn session_id(&self, content: &str) -> Option<String> {
+        let field = self.session_id_field.as_deref()?;
+        records(content).find_map(|record| match lookup(&record, field)? {
+            Value::String(id) if !id.is_empty() => Some(id.clone()),
+            Value::Number(id) => Some(id.to_string()),
+            _ => None,
+        })
+    }
+
+    /// Working directory from the first record that has a `cwd`
+    fn project_path(content: &str) -> Option<PathBuf> {
+        records(content).find_map(|record| record.get("cwd")?.as_str().map(PathBuf::from))
+    }
+
+    fn read_messages(content: &str) -> Vec<Message> {
+        records(content).filter_map(|record| to_message(&record)).collect()
+    }
+}
+
+impl Default for GenericJsonlParser {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl ConversationParser for GenericJsonlParser {
+    fn name(&self) -> &str {
+        "generic-jsonl"
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        path.is_dir() || Self::is_jsonl(path)
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        let mut files = Vec::new();
+        let mut dirs = vec![path.to_path_buf()];
+        if path.is_file() {
+            dirs.clear();
+            if Self::is_jsonl(path) {
+                files.push(to_file(path));
+            }
+        }
+
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let entry_path = entry.path();
+                if entry_path.is_dir() {
+                    dirs.push(entry_path);
+                } else if Self::is_jsonl(&entry_path) {
+                    files.push(to_file(&entry_path));
+                }
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        if !Self::is_jsonl(file) {
+            return Err(ParserError::UnsupportedFormat);
+        }
+        let content = read_jsonl(file, self.decoding())?;
+        let messages = Self::read_messages(&content);
+
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: self.name().to_string(),
+            session_id: self.session_id(&content).or_else(|| to_file(file).session_id),
+            project_path: Self::project_path(&content),
+            title: conversation_title(&messages, None),
+            content,
+            content_type: ContentType::Conversation,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        vec!["*.jsonl"]
+    }
+
+    fn decoding(&self) -> Decoding {
+        Decoding::Lossy
+    }
+
+    fn append_only(&self) -> bool {
+        true
+    }
+
+    fn parse_messages(&self, content: &str) -> Option<Vec<Message>> {
+        let messages = Self::read_messages(content);
+        (!messages.is_empty()).then_some(messages)
+    }
+}
+
+/// Conversation file named by its stem
+fn to_file(path: &Path) -> ConversationFile {
+    ConversationFile {
+        path: path.to_path_buf(),
+        session_id: path.file_stem().and_then(|s| s.to_str()).map(str::to_string),
+        project_path: None,
+    }
+}
+
+/// Records that parse as JSON, skipping blank and malformed lines
+fn records(content: &str) -> impl Iterator<Item = Value> + '_ {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+}
+
+/// Value at a dotted path such as `meta.session_id`
+pub(super) fn lookup<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(record, |value, key| value.get(key))
+}
+
+/// Normalized role for common role names
+pub(super) fn normalize_role(role: &str) -> Option<&'static str> {
+    match role {
+        "user" | "human" => Some("user"),
+        "assistant" | "ai" | "model" => Some("assistant"),
+        "system" => Some("system"),
+        "tool" | "function" => Some("tool"),
+        _ => None,
+    }
+}
+
+/// Message from a record with a known role and some text
+fn to_message(record: &Value) -> Option<Message> {
+    let role = normalize_role(record.get("role")?.as_str()?)?;
+    let content = text(record.get("content")?);
+    if content.is_empty() {
+        return None;
+    }
+
+    Some(Message {
+        role: role.to_string(),
+        content,
+        timestamp: ["timestamp", "time", "created_at"]
+            .iter()
+            .find_map(|key| match record.get(*key)? {
+                // Epoch seconds or milliseconds
+                Value::Number(n) => Some(n.to_string()),
+                value => value.as_str().map(str::to_string),
+            }),
+        utc_offset: None,
+        tool_calls: Vec::new(),
+        model: record.get("model").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+/// Text of a content value: a string, or a list of strings and text parts
+pub(super) fn text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                Value::String(text) => Some(text.as_str()),
+                part => part.get("text")?.as_str(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSCRIPT: &str = concat!(
+        r#"{"event":"start","meta":{"run_id":"run-42"},"cwd":"/work/agent"}"#,
+        "\n",
+        r#"{"role":"user","content":"Summarize the logs","timestamp":"2026-05-01T10:00:00Z"}"#,
+        "\n",
+        r#"{"role":"assistant","content":[{"type":"text","text":"Two errors."}]}"#,
+        "\n",
+    );
+
+    #[test]
+    fn test_parse_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("2026").join("05");
+        std::fs::create_dir_all(&nested).unwrap();
+        let path = nested.join("a1.jsonl");
+        std::fs::write(&path, TRANSCRIPT).unwrap();
+        std::fs::write(nested.join("notes.txt"), "not a transcript").unwrap();
+
+        let parser = GenericJsonlParser::default();
+        assert!(parser.detect(dir.path()));
+        let files = parser.discover(dir.path());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].session_id.as_deref(), Some("a1"));
+
+        let conversation = parser.parse(&path).unwrap();
+        assert_eq!(conversation.session_id.as_deref(), Some("a1"));
+        assert_eq!(conversation.project_path, Some(PathBuf::from("/work/agent")));
+        assert_eq!(conversation.title.as_deref(), Some("Summarize the logs"));
+
+        let messages = parser.parse_messages(&conversation.content).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].content, "Two errors.");
+        assert_eq!(messages[0].timestamp.as_deref(), Some("2026-05-01T10:00:00Z"));
+
+        let parser = GenericJsonlParser::new(Some("meta.run_id".to_string()));
+        assert_eq!(parser.parse(&path).unwrap().session_id.as_deref(), Some("run-42"));
+    }
+
+    #[test]
+    fn test_unrecognized_records() {
+        let parser = GenericJsonlParser::default();
+        assert_eq!(parser.parse_messages("{\"level\":\"info\",\"msg\":\"started\"}\n"), None);
+    }
+}
@@ -0,0 +1,473 @@
+mod claude_code;
+mod claude_code_artifacts;
+mod codex;
+mod declarative;
+mod gemini;
+mod generic_jsonl;
+mod preview;
+mod read;
+mod subprocess;
+
+pub use claude_code::ClaudeCodeParser;
+pub use claude_code_artifacts::{ClaudeCodeArtifactsParser, MEMORY_FILES};
+pub use codex::CodexParser;
+pub use declarative::{DeclarativeParser, DefinitionError, ParserDefinition};
+pub use gemini::GeminiParser;
+pub use generic_jsonl::GenericJsonlParser;
+pub use preview::{conversation_title, Preview};
+pub use read::{read_file, read_jsonl, Decoding};
+pub use subprocess::SubprocessParser;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use thiserror::Error;
+
+use crate::config::ParsersConfig;
+use crate::errors::ErrorCategory;
+use crate::timestamps;
+
+#[derive(Error, Debug)]
+pub enum ParserError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON parse error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported file format")]
+    UnsupportedFormat,
+    #[error("File is locked by another process: {0}")]
+    Busy(PathBuf),
+    #[error("Not a text file: {0}")]
+    Binary(PathBuf),
+    #[error("Not valid UTF-8: {0}")]
+    InvalidUtf8(PathBuf),
+    #[error("External parser failed: {0}")]
+    External(String),
+}
+
+impl ParserError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ParserError::Io(_) | ParserError::Busy(_) => ErrorCategory::Io,
+            ParserError::Json(_) | ParserError::UnsupportedFormat => ErrorCategory::Parse,
+            ParserError::Binary(_) | ParserError::InvalidUtf8(_) | ParserError::External(_) => ErrorCategory::Parse,
+        }
+    }
+
+    /// Whether the file isn't text this parser can read, so retrying won't help
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, ParserError::Binary(_) | ParserError::InvalidUtf8(_))
+    }
+}
+
+/// Represents a discovered conversation file
+#[derive(Debug, Clone)]
+pub struct ConversationFile {
+    /// Path to the conversation file
+    pub path: PathBuf,
+    /// Session ID if available
+    pub session_id: Option<String>,
+    /// Project path this conversation belongs to
+    pub project_path: Option<PathBuf>,
+}
+
+/// Represents a parsed conversation ready for sync
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    /// Source file path
+    pub source_path: PathBuf,
+    /// Source type (e.g., "claude-code")
+    pub source: String,
+    /// Session ID if available
+    pub session_id: Option<String>,
+    /// Project path this conversation belongs to
+    pub project_path: Option<PathBuf>,
+    /// Raw content to upload
+    pub content: String,
+    /// What kind of content this is
+    pub content_type: ContentType,
+    /// Human-readable title, when the source records one or it can be derived
+    pub title: Option<String>,
+}
+
+/// Kind of content uploaded, sent as `contentType` in the payload
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    /// A conversation transcript
+    #[default]
+    Conversation,
+    /// A Claude Code todo list
+    Todo,
+    /// A Claude Code plan
+    Plan,
+    /// A `CLAUDE.md` memory file
+    Memory,
+}
+
+/// A single message in a conversation, normalized across tools
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Message {
+    /// "user", "assistant", "system" or "tool"
+    pub role: String,
+    /// Text content of the message
+    pub content: String,
+    /// Timestamp as written by the source tool, if any; RFC 3339 in UTC
+    /// once [`normalized`](crate::timestamps::normalized)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// UTC offset the source tool wrote the timestamp with, e.g. `+09:00`,
+    /// set when the timestamp is normalized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utc_offset: Option<String>,
+    /// Tool invocations made in this message
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Model that wrote an assistant message, when the source records it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// A tool invocation made by the assistant
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    /// Tool name (e.g., "Bash")
+    pub name: String,
+    /// Tool input, serialized as JSON
+    pub input: String,
+}
+
+impl ToolCall {
+    /// Absolute paths among the tool's arguments, e.g. files it read or edited
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Ok(input) = serde_json::from_str(&self.input) {
+            collect_paths(&input, &mut paths);
+        }
+        paths
+    }
+}
+
+/// Absolute paths among tool arguments, at any depth
+fn collect_paths(args: &serde_json::Value, paths: &mut Vec<PathBuf>) {
+    match args {
+        serde_json::Value::String(s) if Path::new(s).is_absolute() => paths.push(PathBuf::from(s)),
+        serde_json::Value::Array(values) => values.iter().for_each(|v| collect_paths(v, paths)),
+        serde_json::Value::Object(map) => map.values().for_each(|v| collect_paths(v, paths)),
+        _ => {}
+    }
+}
+
+/// Time span covered by a conversation's messages, in unix seconds
+///
+/// Uses the timestamps the source tool wrote, see [`timestamps::parse`];
+/// `None` when no message has one.
+pub fn conversation_window(messages: &[Message]) -> Option<(i64, i64)> {
+    let mut stamps = messages
+        .iter()
+        .filter_map(|m| m.timestamp.as_deref())
+        .filter_map(timestamps::parse)
+        .map(|time| time.unix());
+
+    let first = stamps.next()?;
+    let (start, end) = stamps.fold((first, first), |(lo, hi), ts| (lo.min(ts), hi.max(ts)));
+    Some((start, end))
+}
+
+/// Trait for conversation parsers
+pub trait ConversationParser: Send + Sync {
+    /// Parser name (e.g., "claude-code")
+    fn name(&self) -> &str;
+
+    /// Check if this parser can handle the given directory
+    fn detect(&self, path: &Path) -> bool;
+
+    /// Discover all conversation files in the given directory
+    fn discover(&self, path: &Path) -> Vec<ConversationFile>;
+
+    /// Parse a conversation file
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError>;
+
+    /// Parse a file into every conversation it holds
+    ///
+    /// Most tools write one conversation per file, which is the default.
+    /// Parsers for tools that keep many sessions in one file override this;
+    /// each conversation needs its own session ID, which tracks its sync
+    /// state within the file.
+    fn parse_all(&self, file: &Path) -> Result<Vec<Conversation>, ParserError> {
+        Ok(vec![self.parse(file)?])
+    }
+
+    /// Glob patterns to watch for changes (e.g., ["*.jsonl"])
+    fn watch_patterns(&self) -> Vec<&str>;
+
+    /// Project a conversation file belongs to, when its location tells
+    /// without reading it
+    fn project_path_of(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    /// Directories this parser always watches, besides auto-discovered
+    /// locations and `discovery.additionalPaths`
+    fn watch_dirs(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// How to read files that aren't valid UTF-8
+    ///
+    /// Strict by default; parsers of machine-written transcripts, where a
+    /// stray byte in tool output shouldn't cost the whole conversation,
+    /// decode lossily.
+    fn decoding(&self) -> Decoding {
+        Decoding::Strict
+    }
+
+    /// Read a file's content, as hashed to tell whether it changed
+    ///
+    /// Reads from disk with [`decoding`](Self::decoding); parsers whose
+    /// files live elsewhere override this.
+    fn read(&self, file: &Path) -> Result<String, ParserError> {
+        read_file(file, self.decoding())
+    }
+
+    /// Version of how this parser reads its source, reported to the API in
+    /// `X-Duplex-Client`; bump it when the conversations it produces change
+    fn version(&self) -> &str {
+        "1"
+    }
+
+    /// Split conversation content into messages
+    ///
+    /// Parsers that only understand files at the raw level return `None`.
+    fn parse_messages(&self, _content: &str) -> Option<Vec<Message>> {
+        None
+    }
+
+    /// Whether files only ever grow by whole lines, so uploads after the
+    /// first may send just the lines added since
+    fn append_only(&self) -> bool {
+        false
+    }
+
+    /// Whether uploads carry the [`parse_messages`](Self::parse_messages)
+    /// output in place of the raw content, once the server accepts payload v2
+    fn structured_upload(&self) -> bool {
+        true
+    }
+
+    /// Summarize a conversation for display
+    ///
+    /// The default builds the preview from [`parse_messages`](Self::parse_messages);
+    /// parsers whose source records a title should override this.
+    fn render_preview(&self, conversation: &Conversation) -> Preview {
+        match self.parse_messages(&conversation.content) {
+            Some(messages) => Preview::from_messages(conversation, &messages, None),
+            None => Preview::unparsed(conversation),
+        }
+    }
+}
+
+/// Parsers switched on or off by [`ParserRegistry::set_enabled`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ParserChanges {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+}
+
+/// Registry of available parsers
+///
+/// Every registered parser stays available for reading files; disabled ones
+/// are just not watched or synced.
+pub struct ParserRegistry {
+    parsers: Vec<Box<dyn ConversationParser>>,
+    /// Names of parsers turned off at runtime
+    disabled: RwLock<HashSet<String>>,
+}
+
+impl ParserRegistry {
+    /// Create a new registry with default parsers, all enabled
+    pub fn new() -> Self {
+        Self::with_config(&ParsersConfig::default())
+    }
+
+    /// Create a registry with default parsers set up from `config`, all enabled
+    pub fn with_config(config: &ParsersConfig) -> Self {
+        let mut registry = Self {
+            parsers: Vec::new(),
+            disabled: RwLock::new(HashSet::new()),
+        };
+
+        // Register built-in parsers
+        registry.register(Box::new(ClaudeCodeParser::with_config(&config.claude_code)));
+        registry.register(Box::new(ClaudeCodeArtifactsParser::with_config(&config.claude_code)));
+        registry.register(Box::new(CodexParser::with_config(&config.codex)));
+        registry.register(Box::new(GeminiParser::with_config(&config.gemini)));
+
+        // Claims any directory, so it must come after the specific parsers
+        let generic = GenericJsonlParser::new(config.generic_jsonl.session_id_field.clone());
+        let external = config
+            .external
+            .iter()
+            .map(|external| Box::new(SubprocessParser::new(external)) as Box<dyn ConversationParser>);
+        // Installed plugins and definitions only run once they are allowed
+        let allowed = |name: &str| config.plugins.iter().chain(&config.enabled).any(|n| n == name);
+        let plugins = crate::config::plugin_modules()
+            .into_iter()
+            .filter(|(name, _)| allowed(name))
+            .map(|(name, module)| Box::new(SubprocessParser::wasm(&name, &module)) as Box<dyn ConversationParser>);
+        let definitions = crate::config::parser_definitions()
+            .into_iter()
+            .filter(|path| path.file_stem().and_then(|stem| stem.to_str()).is_some_and(allowed))
+            .filter_map(|path| match DeclarativeParser::load(&path) {
+                Ok(parser) => Some(Box::new(parser) as Box<dyn ConversationParser>),
+                Err(e) => {
+                    tracing::warn!("Ignoring parser definition {:?}: {}", path, e);
+                    None
+                }
+            });
+        for parser in external.chain(plugins).chain(definitions) {
+            if registry.get(parser.name()).is_some() || parser.name() == generic.name() {
+                tracing::warn!("Parser {} has the name of another parser, ignoring it", parser.name());
+                continue;
+            }
+            registry.register(parser);
+        }
+        registry.register(Box::new(generic));
+
+        registry
+    }
+
+    /// Register a new parser
+    pub fn register(&mut self, parser: Box<dyn ConversationParser>) {
+        tracing::debug!("Registered parser: {}", parser.name());
+        self.parsers.push(parser);
+    }
+
+    /// Get a parser by name
+    pub fn get(&self, name: &str) -> Option<&dyn ConversationParser> {
+        self.parsers
+            .iter()
+            .find(|p| p.name() == name)
+            .map(|p| p.as_ref())
+    }
+
+    /// Get all registered parsers
+    pub fn all(&self) -> impl Iterator<Item = &dyn ConversationParser> {
+        self.parsers.iter().map(|p| p.as_ref())
+    }
+
+    /// Find a parser that can handle the given path
+    pub fn detect(&self, path: &Path) -> Option<&dyn ConversationParser> {
+        self.parsers.iter().find(|p| p.detect(path)).map(|p| p.as_ref())
+    }
+
+    /// Enabled parsers besides `owner` that also read `file`, most specific
+    /// first
+    pub fn co_owners(&self, file: &Path, owner: &str) -> Vec<&dyn ConversationParser> {
+        self.parsers
+            .iter()
+            .filter(|p| p.name() != owner && self.is_enabled(p.name()))
+            .filter(|p| p.discover(file).iter().any(|found| found.path == file))
+            .map(|p| p.as_ref())
+            .collect()
+    }
+
+    /// Summarize a conversation for display using its source's parser
+    pub fn render_preview(&self, conversation: &Conversation) -> Preview {
+        match self.get(&conversation.source) {
+            Some(parser) => parser.render_preview(conversation),
+            None => Preview::unparsed(conversation),
+        }
+    }
+
+    /// Get enabled parsers based on config
+    pub fn get_enabled(&self, enabled_names: &[String]) -> Vec<&dyn ConversationParser> {
+        enabled_names
+            .iter()
+            .filter_map(|name| self.get(name))
+            .collect()
+    }
+
+    /// Whether a parser is registered and enabled
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.get(name).is_some() && !self.disabled.read().unwrap().contains(name)
+    }
+
+    /// Enable exactly the named parsers, returning which ones changed
+    pub fn set_enabled(&self, enabled_names: &[String]) -> ParserChanges {
+        let mut disabled = self.disabled.write().unwrap();
+        let mut changes = ParserChanges::default();
+
+        for parser in &self.parsers {
+            let name = parser.name();
+            let enable = enabled_names.iter().any(|n| n == name);
+            if enable && disabled.remove(name) {
+                changes.enabled.push(name.to_string());
+            } else if !enable && disabled.insert(name.to_string()) {
+                changes.disabled.push(name.to_string());
+            }
+        }
+
+        for name in enabled_names {
+            if self.get(name).is_none() {
+                tracing::warn!("Unknown parser in parsers.enabled: {}", name);
+            }
+        }
+        changes
+    }
+}
+
+impl Default for ParserRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_enabled() {
+        let registry = ParserRegistry::new();
+        assert!(registry.is_enabled("claude-code"));
+
+        let changes = registry.set_enabled(&["claude-code".to_string()]);
+        assert_eq!(changes.disabled, vec!["claude-code-artifacts", "codex", "gemini", "generic-jsonl"]);
+        assert!(!registry.is_enabled("claude-code-artifacts"));
+        assert!(registry.get("claude-code-artifacts").is_some());
+
+        let changes = registry.set_enabled(&["claude-code-artifacts".to_string()]);
+        assert_eq!(
+            changes,
+            ParserChanges {
+                enabled: vec!["claude-code-artifacts".to_string()],
+                disabled: vec!["claude-code".to_string()],
+            }
+        );
+        assert_eq!(registry.set_enabled(&["claude-code-artifacts".to_string()]), ParserChanges::default());
+    }
+
+    #[test]
+    fn test_external_parsers() {
+        let external = |name: &str| crate::config::ExternalParserConfig {
+            name: name.to_string(),
+            command: "/usr/local/bin/duplex-nightly".to_string(),
+            args: Vec::new(),
+            watch_patterns: Vec::new(),
+        };
+        let config = ParsersConfig {
+            external: vec![external("nightly"), external("claude-code")],
+            ..Default::default()
+        };
+
+        let registry = ParserRegistry::with_config(&config);
+        let names: Vec<&str> = registry.all().map(|p| p.name()).collect();
+        assert_eq!(names.iter().filter(|n| **n == "claude-code").count(), 1);
+        assert_eq!(names[names.len() - 2..], ["nightly", "generic-jsonl"]);
+    }
+}
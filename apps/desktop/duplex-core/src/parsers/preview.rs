@@ -0,0 +1,134 @@
+//! Conversation previews for UI surfaces
+//!
+//! A preview gives the tray and status window something better to show than
+//! a UUID filename: a title, the opening prompt, how many messages there are
+//! and how long the conversation ran.
+
+use std::time::Duration;
+
+use super::{conversation_window, Conversation, Message};
+
+/// Longest title or prompt shown, in characters
+const MAX_PREVIEW_CHARS: usize = 80;
+
+/// Short description of a conversation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preview {
+    /// Title from the source tool, else the first prompt, else the session
+    pub title: String,
+    /// Opening user prompt, shortened to one line
+    pub first_prompt: Option<String>,
+    pub message_count: usize,
+    /// Time between the first and last timestamped message
+    pub duration: Option<Duration>,
+}
+
+impl Preview {
+    /// Build a preview from parsed messages, preferring `title` when the
+    /// source tool recorded one
+    pub fn from_messages(conversation: &Conversation, messages: &[Message], title: Option<&str>) -> Self {
+        let first_prompt = first_prompt(messages);
+        let title = conversation_title(messages, title).unwrap_or_else(|| fallback_title(conversation));
+
+        let duration = conversation_window(messages)
+            .map(|(start, end)| Duration::from_secs((end - start).max(0) as u64));
+
+        Self {
+            title,
+            first_prompt,
+            message_count: messages.len(),
+            duration,
+        }
+    }
+
+    /// Preview for a conversation whose content could not be split into messages
+    pub fn unparsed(conversation: &Conversation) -> Self {
+        Self {
+            title: fallback_title(conversation),
+            first_prompt: None,
+            message_count: 0,
+            duration: None,
+        }
+    }
+}
+
+/// Title from the source tool, else the opening prompt, shortened to one line
+pub fn conversation_title(messages: &[Message], tool_title: Option<&str>) -> Option<String> {
+    tool_title
+        .map(shorten)
+        .filter(|t| !t.is_empty())
+        .or_else(|| first_prompt(messages))
+}
+
+/// Opening user prompt, shortened to one line
+fn first_prompt(messages: &[Message]) -> Option<String> {
+    messages
+        .iter()
+        .filter(|m| m.role == "user")
+        .map(|m| m.content.trim())
+        // Skip tool-injected wrappers such as slash command markup
+        .find(|content| !content.is_empty() && !content.starts_with('<'))
+        .map(shorten)
+}
+
+/// Title from the session ID or file name
+fn fallback_title(conversation: &Conversation) -> String {
+    match &conversation.session_id {
+        Some(id) => format!("Conversation {}", id.split('-').next().unwrap_or(id)),
+        None => conversation
+            .source_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Conversation".to_string()),
+    }
+}
+
+/// First line of `text` with whitespace collapsed, cut to [`MAX_PREVIEW_CHARS`]
+fn shorten(text: &str) -> String {
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if line.chars().count() <= MAX_PREVIEW_CHARS {
+        return line;
+    }
+    let cut: String = line.chars().take(MAX_PREVIEW_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::ContentType;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("\n  Fix   the\tbuild \nand more"), "Fix the build");
+        let long = shorten(&"word ".repeat(40));
+        assert_eq!(long.chars().count(), MAX_PREVIEW_CHARS);
+        assert!(long.ends_with('…'));
+    }
+
+    #[test]
+    fn test_fallback_title() {
+        let conversation = Conversation {
+            source_path: PathBuf::from("/p/a1b2c3d4-e5f6-7890-abcd-ef1234567890.jsonl"),
+            source: "claude-code".to_string(),
+            session_id: Some("a1b2c3d4-e5f6-7890-abcd-ef1234567890".to_string()),
+            project_path: None,
+            content: String::new(),
+            content_type: ContentType::Conversation,
+            title: None,
+        };
+        assert_eq!(Preview::unparsed(&conversation).title, "Conversation a1b2c3d4");
+
+        let conversation = Conversation {
+            session_id: None,
+            ..conversation
+        };
+        assert_eq!(
+            Preview::unparsed(&conversation).title,
+            "a1b2c3d4-e5f6-7890-abcd-ef1234567890"
+        );
+    }
+}
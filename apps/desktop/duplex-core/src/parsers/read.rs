@@ -0,0 +1,146 @@
+//! Reading session files that an agent may still be writing
+//!
+//! Agents append to their session files while we read them, so a read can
+//! end partway through a JSONL record, and on Windows the file can be
+//! briefly locked. Both are retried after a short delay; an incomplete last
+//! record that persists is left out rather than uploaded truncated.
+//!
+//! Some tools also write binary blobs next to their transcripts. Files that
+//! look binary are refused, and invalid UTF-8 is either refused or replaced
+//! depending on the parser's [`Decoding`].
+
+use std::path::Path;
+use std::time::Duration;
+
+use super::ParserError;
+
+/// Reads attempted before giving up on a locked file or partial line
+const READ_ATTEMPTS: u32 = 3;
+
+/// Delay between read attempts
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Leading bytes searched for a NUL when telling text from binary, as git does
+const SNIFF_LEN: usize = 8000;
+
+/// How a parser reads files that aren't valid UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Decoding {
+    /// Refuse the file with [`ParserError::InvalidUtf8`]
+    #[default]
+    Strict,
+    /// Replace invalid sequences with U+FFFD
+    Lossy,
+}
+
+/// Read a text file, retrying while another process holds it locked
+///
+/// Returns [`ParserError::Busy`] if the file is still locked after the
+/// last attempt, and [`ParserError::Binary`] if it isn't text.
+pub fn read_file(path: &Path, decoding: Decoding) -> Result<String, ParserError> {
+    let mut attempt = 1;
+    loop {
+        match std::fs::read(path) {
+            Err(e) if is_sharing_violation(&e) => {
+                if attempt == READ_ATTEMPTS {
+                    return Err(ParserError::Busy(path.to_path_buf()));
+                }
+                tracing::debug!("{:?} is locked, retrying", path);
+            }
+            result => return decode(path, result?, decoding),
+        }
+        attempt += 1;
+        std::thread::sleep(RETRY_DELAY);
+    }
+}
+
+fn decode(path: &Path, bytes: Vec<u8>, decoding: Decoding) -> Result<String, ParserError> {
+    if bytes[..bytes.len().min(SNIFF_LEN)].contains(&0) {
+        return Err(ParserError::Binary(path.to_path_buf()));
+    }
+    match String::from_utf8(bytes) {
+        Ok(content) => Ok(content),
+        Err(e) if decoding == Decoding::Lossy => {
+            tracing::debug!("Replacing invalid UTF-8 in {:?}", path);
+            Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+        }
+        Err(_) => Err(ParserError::InvalidUtf8(path.to_path_buf())),
+    }
+}
+
+/// Read a JSONL file, waiting out a record that is still being written
+///
+/// If the last line stays incomplete it is dropped; the next change event
+/// picks up the finished record.
+pub fn read_jsonl(path: &Path, decoding: Decoding) -> Result<String, ParserError> {
+    let mut content = read_file(path, decoding)?;
+    for _ in 1..READ_ATTEMPTS {
+        if partial_line_start(&content).is_none() {
+            return Ok(content);
+        }
+        tracing::debug!("Partial line at end of {:?}, retrying", path);
+        std::thread::sleep(RETRY_DELAY);
+        content = read_file(path, decoding)?;
+    }
+
+    if let Some(start) = partial_line_start(&content) {
+        tracing::warn!("Leaving out incomplete last line of {:?}", path);
+        content.truncate(start);
+    }
+    Ok(content)
+}
+
+/// Byte offset of a trailing line that is not yet a complete JSON record
+fn partial_line_start(content: &str) -> Option<usize> {
+    if content.is_empty() || content.ends_with('\n') {
+        return None;
+    }
+
+    let start = content.rfind('\n').map_or(0, |i| i + 1);
+    // Some writers omit the final newline after a complete record
+    match serde_json::from_str::<serde::de::IgnoredAny>(&content[start..]) {
+        Ok(_) => None,
+        Err(_) => Some(start),
+    }
+}
+
+/// Whether a read failed because another process has the file open exclusively
+fn is_sharing_violation(error: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(error.raw_os_error(), Some(32) | Some(33))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_line_start() {
+        assert_eq!(partial_line_start(""), None);
+        assert_eq!(partial_line_start("{\"a\":1}\n"), None);
+        assert_eq!(partial_line_start("{\"a\":1}\n{\"b\":2}"), None);
+        assert_eq!(partial_line_start("{\"a\":1}\n{\"b\":"), Some(8));
+        assert_eq!(partial_line_start("{\"a\""), Some(0));
+    }
+
+    #[test]
+    fn test_read_jsonl_drops_partial_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        std::fs::write(&path, "{\"type\":\"user\"}\n{\"type\":\"assis").unwrap();
+
+        assert_eq!(read_jsonl(&path, Decoding::Strict).unwrap(), "{\"type\":\"user\"}\n");
+    }
+
+    #[test]
+    fn test_decode() {
+        let path = Path::new("session.jsonl");
+        let latin1 = b"caf\xe9\n".to_vec();
+        assert!(matches!(decode(path, latin1.clone(), Decoding::Strict), Err(ParserError::InvalidUtf8(_))));
+        assert_eq!(decode(path, latin1, Decoding::Lossy).unwrap(), "caf\u{fffd}\n");
+
+        let blob = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        assert!(matches!(decode(path, blob, Decoding::Lossy), Err(ParserError::Binary(_))));
+        assert_eq!(decode(path, b"ok\n".to_vec(), Decoding::Strict).unwrap(), "ok\n");
+    }
+}
@@ -0,0 +1,413 @@
+use super::{read_file, ContentType, Conversation, ConversationFile, ConversationParser, ParserError};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use wasmtime::{Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+use wasmtime_wasi::pipe::MemoryOutputPipe;
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use crate::config::ExternalParserConfig;
+use crate::watcher::expand_path;
+
+/// Longest a plugin may run before it is killed
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a running plugin is checked for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Instructions a WASM plugin may run per call, roughly a few seconds' worth
+const WASM_FUEL: u64 = 10_000_000_000;
+
+/// Memory a WASM plugin may grow to
+const WASM_MEMORY: usize = 512 * 1024 * 1024;
+
+/// Most a WASM plugin may write to stdout or stderr per call
+const WASM_OUTPUT: usize = 64 * 1024 * 1024;
+
+/// Parser backed by an external executable from `parsers.external`, or a
+/// WASM plugin from the plugins directory allowed in `parsers.plugins`
+///
+/// The executable is run once per call with a subcommand and a path, and
+/// answers with JSON on stdout:
+/// - `detect <path>`: `true` if it handles the directory or file
+/// - `discover <path>`: an array of `{ "path", "sessionId"?, "projectPath"? }`
+/// - `parse <file>`: `{ "content"?, "sessionId"?, "projectPath"?, "title"?,
+///   "contentType"? }`, where a missing `content` uploads the file as is,
+///   or an array of them with distinct session IDs for a file holding
+///   several conversations
+///
+/// A non-zero exit fails the call with the plugin's stderr. Plugins are
+/// killed after [`TIMEOUT`].
+///
+/// WASM plugins speak the same protocol as WASI command modules run by an
+/// embedded runtime. They get no environment, network or inherited handles,
+/// and only read access to the path being read (a file's directory), so
+/// untrusted parse code can't see credentials or alter transcripts. They
+/// are stopped after [`WASM_FUEL`] instructions or [`WASM_MEMORY`] bytes.
+pub struct SubprocessParser {
+    name: String,
+    launcher: Launcher,
+    watch_patterns: Vec<String>,
+}
+
+/// Answer to `parse`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParseOutput {
+    Many(Vec<ParsedFile>),
+    One(ParsedFile),
+}
+
+/// How a plugin is started
+enum Launcher {
+    /// An executable, with arguments placed before the subcommand
+    Command { command: PathBuf, args: Vec<String> },
+    /// A WASI command module, compiled on first use
+    Wasm {
+        module: PathBuf,
+        compiled: OnceLock<Result<(Engine, Module), String>>,
+    },
+}
+
+/// What a WASM plugin's store holds
+struct WasmState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// A file listed by `discover`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscoveredFile {
+    path: PathBuf,
+    session_id: Option<String>,
+    project_path: Option<PathBuf>,
+}
+
+/// A conversation returned by `parse`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParsedFile {
+    content: Option<String>,
+    session_id: Option<String>,
+    project_path: Option<PathBuf>,
+    title: Option<String>,
+    #[serde(default)]
+    content_type: ContentType,
+}
+
+impl SubprocessParser {
+    pub fn new(config: &ExternalParserConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            launcher: Launcher::Command {
+                command: expand_path(&config.command),
+                args: config.args.clone(),
+            },
+            watch_patterns: config.watch_patterns.clone(),
+        }
+    }
+
+    /// Parser for a WASM plugin, named after its module
+    pub fn wasm(name: &str, module: &Path) -> Self {
+        Self {
+            name: name.to_string(),
+            launcher: Launcher::Wasm {
+                module: module.to_path_buf(),
+                compiled: OnceLock::new(),
+            },
+            watch_patterns: Vec::new(),
+        }
+    }
+
+    /// Run the plugin and read its answer
+    fn run<T: DeserializeOwned>(&self, subcommand: &str, path: &Path) -> Result<T, ParserError> {
+        let failed = |detail: String| ParserError::External(format!("{} {}: {}", self.name, subcommand, detail));
+        let stdout = match &self.launcher {
+            Launcher::Command { command, args } => run_command(command, args, subcommand, path),
+            Launcher::Wasm { module, compiled } => self.run_wasm(module, compiled, subcommand, path),
+        };
+        Ok(serde_json::from_slice(&stdout.map_err(failed)?)?)
+    }
+
+    /// Run a WASM plugin in the embedded runtime, returning its stdout
+    fn run_wasm(
+        &self,
+        module: &Path,
+        compiled: &OnceLock<Result<(Engine, Module), String>>,
+        subcommand: &str,
+        path: &Path,
+    ) -> Result<Vec<u8>, String> {
+        let (engine, module) = compiled.get_or_init(|| compile(module)).as_ref().map_err(Clone::clone)?;
+
+        // Only the path being read is visible, read-only, under its own name
+        let dir = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+        let stdout = MemoryOutputPipe::new(WASM_OUTPUT);
+        let stderr = MemoryOutputPipe::new(WASM_OUTPUT);
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.args(&[self.name.as_str(), subcommand, &path.to_string_lossy()])
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .preopened_dir(dir, dir.to_string_lossy(), DirPerms::READ, FilePerms::READ)
+            .map_err(|e| format!("could not open {:?}: {}", dir, e))?;
+        let state = WasmState {
+            wasi: wasi.build_p1(),
+            limits: StoreLimitsBuilder::new().memory_size(WASM_MEMORY).instances(1).build(),
+        };
+
+        // The WASI host blocks on its own runtime, which can't nest in the caller's
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let mut store = Store::new(engine, state);
+                    store.limiter(|state| &mut state.limits);
+                    store.set_fuel(WASM_FUEL)?;
+                    let mut linker = Linker::new(engine);
+                    preview1::add_to_linker_sync(&mut linker, |state: &mut WasmState| &mut state.wasi)?;
+                    let instance = linker.instantiate(&mut store, module)?;
+                    instance.get_typed_func::<(), ()>(&mut store, "_start")?.call(&mut store, ())
+                })
+                .join()
+                .map_err(|_| wasmtime::Error::msg("plugin thread panicked"))?
+        });
+
+        match result {
+            Ok(()) => {}
+            Err(e) if e.downcast_ref::<I32Exit>().is_some_and(|exit| exit.0 == 0) => {}
+            Err(e) => {
+                let stderr = String::from_utf8_lossy(&stderr.contents()).trim().to_string();
+                return Err(format!("failed: {:#} {}", e, stderr).trim_end().to_string());
+            }
+        }
+        Ok(stdout.contents().to_vec())
+    }
+}
+
+/// Run an executable plugin, returning its stdout
+fn run_command(program: &Path, args: &[String], subcommand: &str, path: &Path) -> Result<Vec<u8>, String> {
+    let mut command = Command::new(program);
+    command.args(args).arg(subcommand).arg(path);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not start {:?}: {}", command.get_program(), e))?;
+
+    // Drain the pipes while waiting so a chatty plugin can't block on a full one
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if started.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("timed out after {}s", TIMEOUT.as_secs()));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    if !status.success() {
+        let stderr = stderr.join().unwrap_or_default();
+        return Err(format!("exited with {}: {}", status, String::from_utf8_lossy(&stderr).trim()));
+    }
+    Ok(stdout)
+}
+
+/// Compile a WASM plugin in an engine that meters its instructions
+fn compile(module: &Path) -> Result<(Engine, Module), String> {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+    let module = Module::from_file(&engine, module).map_err(|e| format!("could not load {:?}: {:#}", module, e))?;
+    Ok((engine, module))
+}
+
+/// Read a pipe to the end on a background thread
+fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+impl ConversationParser for SubprocessParser {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        self.run("detect", path).unwrap_or_else(|e| {
+            tracing::debug!("External parser detect failed: {}", e);
+            false
+        })
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        let files: Vec<DiscoveredFile> = match self.run("discover", path) {
+            Ok(files) => files,
+            Err(e) => {
+                tracing::warn!("External parser discover failed: {}", e);
+                return Vec::new();
+            }
+        };
+        files
+            .into_iter()
+            .map(|file| ConversationFile {
+                // Relative paths are relative to the directory searched
+                path: path.join(file.path),
+                session_id: file.session_id,
+                project_path: file.project_path,
+            })
+            .collect()
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        self.parse_all(file)?.into_iter().next().ok_or(ParserError::UnsupportedFormat)
+    }
+
+    fn parse_all(&self, file: &Path) -> Result<Vec<Conversation>, ParserError> {
+        let parsed = match self.run("parse", file)? {
+            ParseOutput::Many(parsed) => parsed,
+            ParseOutput::One(parsed) => vec![parsed],
+        };
+
+        let mut conversations = Vec::with_capacity(parsed.len());
+        for parsed in parsed {
+            let content = match parsed.content {
+                Some(content) => content,
+                None => read_file(file, self.decoding())?,
+            };
+            conversations.push(Conversation {
+                source_path: file.to_path_buf(),
+                source: self.name.clone(),
+                session_id: parsed.session_id,
+                project_path: parsed.project_path,
+                content,
+                content_type: parsed.content_type,
+                title: parsed.title,
+            });
+        }
+        Ok(conversations)
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        if self.watch_patterns.is_empty() {
+            return vec!["*"];
+        }
+        self.watch_patterns.iter().map(String::as_str).collect()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const PLUGIN: &str = r#"#!/bin/sh
+case "$1" in
+  detect) case "$2" in *.log|*/logs) echo true ;; *) echo false ;; esac ;;
+  discover) echo '[{"path": "run-1.log", "sessionId": "run-1"}]' ;;
+  parse) echo '{"sessionId": "run-1", "title": "Nightly run", "content": "user: hi"}' ;;
+  *) echo "unknown subcommand" >&2; exit 2 ;;
+esac
+"#;
+
+    fn plugin(dir: &Path) -> SubprocessParser {
+        let command = dir.join("plugin.sh");
+        std::fs::write(&command, PLUGIN).unwrap();
+        std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+        SubprocessParser::new(&ExternalParserConfig {
+            name: "nightly".to_string(),
+            command: command.to_string_lossy().to_string(),
+            args: Vec::new(),
+            watch_patterns: vec!["*.log".to_string()],
+        })
+    }
+
+    #[test]
+    fn test_subprocess_parser() {
+        let dir = tempfile::tempdir().unwrap();
+        let parser = plugin(dir.path());
+        let logs = dir.path().join("logs");
+
+        assert!(parser.detect(&logs));
+        assert!(!parser.detect(&dir.path().join("other")));
+
+        let files = parser.discover(&logs);
+        assert_eq!(files[0].path, logs.join("run-1.log"));
+        assert_eq!(files[0].session_id.as_deref(), Some("run-1"));
+
+        let conversation = parser.parse(&logs.join("run-1.log")).unwrap();
+        assert_eq!(conversation.source, "nightly");
+        assert_eq!(conversation.title.as_deref(), Some("Nightly run"));
+        assert_eq!(conversation.content, "user: hi");
+        assert_eq!(parser.watch_patterns(), vec!["*.log"]);
+    }
+
+    /// Tries to create `written.txt` in the first preopened directory and
+    /// reports whether it could
+    const WRITER: &str = r#"(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "written.txt")
+  (data (i32.const 32) "{\"content\":\"written\"}")
+  (data (i32.const 64) "{\"content\":\"denied\"}")
+  (func (export "_start")
+    (local $ptr i32) (local $len i32)
+    (if (i32.eqz (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 11)
+          (i32.const 9) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 128)))
+      (then (local.set $ptr (i32.const 32)) (local.set $len (i32.const 21)))
+      (else (local.set $ptr (i32.const 64)) (local.set $len (i32.const 20))))
+    (i32.store (i32.const 256) (local.get $ptr))
+    (i32.store (i32.const 260) (local.get $len))
+    (drop (call $fd_write (i32.const 1) (i32.const 256) (i32.const 1) (i32.const 264)))))"#;
+
+    #[test]
+    fn test_wasm_sandbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().join("writer.wat");
+        std::fs::write(&module, WRITER).unwrap();
+        let logs = dir.path().join("logs");
+        std::fs::create_dir(&logs).unwrap();
+        std::fs::write(logs.join("run-1.log"), "user: hi").unwrap();
+        let parser = SubprocessParser::wasm("writer", &module);
+
+        // The session directory is there to read but not to write
+        let conversation = parser.parse(&logs.join("run-1.log")).unwrap();
+        assert_eq!(conversation.content, "denied");
+        assert!(!logs.join("written.txt").exists());
+        assert_eq!(parser.watch_patterns(), vec!["*"]);
+
+        // A module that runs forever is stopped
+        let spinner = dir.path().join("spinner.wat");
+        std::fs::write(&spinner, r#"(module (memory (export "memory") 1) (func (export "_start") (loop $l (br $l))))"#)
+            .unwrap();
+        let parser = SubprocessParser::wasm("spinner", &spinner);
+        let error = parser.parse(&logs.join("run-1.log")).unwrap_err();
+        assert!(error.to_string().contains("spinner parse"), "{}", error);
+    }
+
+    #[test]
+    fn test_plugin_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let parser = plugin(dir.path());
+
+        let error = parser.run::<bool>("explode", dir.path()).unwrap_err();
+        assert!(error.to_string().contains("unknown subcommand"));
+    }
+}
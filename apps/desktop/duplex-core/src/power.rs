@@ -0,0 +1,180 @@
+//! Power source awareness
+//!
+//! On battery, or in the OS's low power mode, heavy work waits: the
+//! scheduler holds jobs marked [`Job::pause_on_battery`](crate::jobs::Job::pause_on_battery),
+//! and the sync engine defers backfill and waits longer after a file's last
+//! change before uploading it. The `power` config section controls both.
+//!
+//! The power source is polled at most once per [`CHECK_INTERVAL`]. Frontends
+//! that get OS notifications report them through [`set_low_power_mode`] and
+//! [`power_changed`], so changes apply without waiting for the next poll.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::PowerConfig;
+
+/// How long a power check is reused
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Last power check and its result
+static CACHED: Mutex<Option<(Instant, PowerStatus)>> = Mutex::new(None);
+
+/// Low power mode as last reported by an OS notification
+static LOW_POWER_MODE: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Where the machine's power comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    /// macOS Low Power Mode, or the low-power platform profile on Linux
+    pub low_power_mode: bool,
+}
+
+impl PowerStatus {
+    /// Whether heavy work should wait under `config`
+    pub fn is_constrained(&self, config: &PowerConfig) -> bool {
+        config.enabled && (self.on_battery || (config.low_power_mode && self.low_power_mode))
+    }
+}
+
+/// The power status, checked at most once per [`CHECK_INTERVAL`]
+pub fn status() -> PowerStatus {
+    let mut cached = CACHED.lock().unwrap();
+    let mut status = match *cached {
+        Some((checked_at, status)) if checked_at.elapsed() < CHECK_INTERVAL => status,
+        _ => {
+            let status = PowerStatus {
+                on_battery: power_source_is_battery(),
+                low_power_mode: low_power_mode_enabled(),
+            };
+            *cached = Some((Instant::now(), status));
+            status
+        }
+    };
+    if let Some(low_power_mode) = *LOW_POWER_MODE.lock().unwrap() {
+        status.low_power_mode = low_power_mode;
+    }
+    status
+}
+
+/// Whether heavy work should wait under `config` right now
+pub fn is_constrained(config: &PowerConfig) -> bool {
+    status().is_constrained(config)
+}
+
+/// Record a low power mode change reported by the OS
+pub fn set_low_power_mode(enabled: bool) {
+    let previous = LOW_POWER_MODE.lock().unwrap().replace(enabled);
+    if previous != Some(enabled) {
+        tracing::info!("Low power mode {}", if enabled { "on" } else { "off" });
+    }
+}
+
+/// Check the power source again on the next query, after the OS reports
+/// a change
+pub fn power_changed() {
+    *CACHED.lock().unwrap() = None;
+}
+
+/// Ask the OS whether the machine is on battery power
+///
+/// Reads the power supplies in sysfs on Linux and `pmset` on macOS. Other
+/// platforms report mains power.
+fn power_source_is_battery() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+            return false;
+        };
+        let mut has_battery = false;
+        for supply in supplies.flatten() {
+            let read = |name: &str| std::fs::read_to_string(supply.path().join(name)).unwrap_or_default();
+            match read("type").trim() {
+                "Mains" | "USB" if read("online").trim() == "1" => return false,
+                "Battery" => has_battery = true,
+                _ => {}
+            }
+        }
+        has_battery
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        match std::process::Command::new("pmset").args(["-g", "batt"]).output() {
+            Ok(output) if output.status.success() => {
+                String::from_utf8_lossy(&output.stdout).contains("'Battery Power'")
+            }
+            _ => {
+                tracing::debug!("Could not query pmset for the power source");
+                false
+            }
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        false
+    }
+}
+
+/// Ask the OS whether low power mode is on
+///
+/// Reads the ACPI platform profile on Linux and `pmset` on macOS. Other
+/// platforms report it off.
+fn low_power_mode_enabled() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/sys/firmware/acpi/platform_profile")
+            .is_ok_and(|profile| profile.trim() == "low-power")
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        match std::process::Command::new("pmset").arg("-g").output() {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|line| line.split_whitespace().eq(["lowpowermode", "1"])),
+            _ => false,
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_constrained() {
+        let config = PowerConfig::default();
+        let mains = PowerStatus::default();
+        let battery = PowerStatus {
+            on_battery: true,
+            ..mains
+        };
+        let low_power = PowerStatus {
+            low_power_mode: true,
+            ..mains
+        };
+
+        assert!(!mains.is_constrained(&config));
+        assert!(battery.is_constrained(&config));
+        assert!(low_power.is_constrained(&config));
+
+        let battery_only = PowerConfig {
+            low_power_mode: false,
+            ..config.clone()
+        };
+        assert!(!low_power.is_constrained(&battery_only));
+
+        let disabled = PowerConfig { enabled: false, ..config };
+        assert!(!battery.is_constrained(&disabled));
+    }
+}
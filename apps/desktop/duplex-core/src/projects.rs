@@ -0,0 +1,297 @@
+//! Short display names for projects
+//!
+//! Paths like `/Users/me/src/acme-monorepo/services/billing` are unwieldy
+//! in menus and reports. Each project is named by the alias set for it (or
+//! a parent directory) in `projects.aliases`, or else by the one suggested
+//! from its git repository when it was last synced:
+//! `acme-monorepo/services/billing`. Uploads carry the name as
+//! `projectName`, and every local surface that lists projects shows it.
+//!
+//! In a monorepo, sessions usually run at the repository root whatever
+//! package they are about. With `projects.resolution` set, a
+//! [`ProjectResolver`] narrows the project to the component the session's
+//! tools touched before anything else looks at it.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{ProjectResolution, ProjectResolutionConfig, ProjectsConfig};
+use crate::db::Database;
+use crate::git::{self, GitContext};
+use crate::parsers::Message;
+use crate::policy::{self, PolicyError};
+use crate::watcher::expand_path;
+
+/// Where a project's name comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasSource {
+    Config,
+    Git,
+}
+
+/// Project names from the config and the database
+#[derive(Debug, Clone, Default)]
+pub struct ProjectNames {
+    /// (path, alias), longest path first
+    configured: Vec<(String, String)>,
+    /// Aliases suggested from git, by project path
+    suggested: HashMap<String, String>,
+}
+
+impl ProjectNames {
+    /// Names set in the config only
+    pub fn from_config(config: &ProjectsConfig) -> Self {
+        let mut configured: Vec<(String, String)> = config
+            .aliases
+            .iter()
+            .map(|(path, alias)| {
+                let path = expand_path(path).to_string_lossy().trim_end_matches('/').to_string();
+                (path, alias.clone())
+            })
+            .collect();
+        configured.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        Self {
+            configured,
+            suggested: HashMap::new(),
+        }
+    }
+
+    /// Names set in the config, then those suggested for synced projects
+    pub fn load(config: &ProjectsConfig, db: &Database) -> rusqlite::Result<Self> {
+        Ok(Self {
+            suggested: db.suggested_aliases()?,
+            ..Self::from_config(config)
+        })
+    }
+
+    /// A project's alias and where it comes from
+    pub fn alias(&self, project_path: &str) -> Option<(String, AliasSource)> {
+        self.configured_alias(project_path)
+            .map(|alias| (alias, AliasSource::Config))
+            .or_else(|| {
+                let alias = self.suggested.get(project_path)?;
+                Some((alias.clone(), AliasSource::Git))
+            })
+    }
+
+    /// The alias set in the config for a project or a directory above it
+    pub fn configured_alias(&self, project_path: &str) -> Option<String> {
+        self.configured.iter().find_map(|(path, alias)| {
+            let rest = project_path.strip_prefix(path.as_str())?;
+            if rest.is_empty() {
+                return Some(alias.clone());
+            }
+            rest.strip_prefix('/').map(|rest| format!("{}/{}", alias, rest))
+        })
+    }
+
+    /// A project's alias, or its path when it has none
+    pub fn display(&self, project_path: &str) -> String {
+        self.alias(project_path)
+            .map(|(alias, _)| alias)
+            .unwrap_or_else(|| project_path.to_string())
+    }
+}
+
+/// Narrows projects in a repository to the component a conversation concerns
+#[derive(Debug, Clone)]
+pub struct ProjectResolver {
+    strategy: ProjectResolution,
+    markers: Vec<String>,
+    rules: GlobSet,
+}
+
+impl ProjectResolver {
+    /// Compile `projects.resolution`; `~/` at the start of a rule is the
+    /// home directory, and `*` stays within one path component
+    pub fn from_config(config: &ProjectResolutionConfig) -> Result<Self, PolicyError> {
+        let mut rules = GlobSetBuilder::new();
+        for rule in &config.rules {
+            let glob = GlobBuilder::new(&policy::expand_home(rule))
+                .literal_separator(true)
+                .build()
+                .map_err(|e| PolicyError::InvalidGlob(rule.clone(), e))?;
+            rules.add(glob);
+        }
+        Ok(Self {
+            strategy: config.strategy,
+            markers: config.markers.clone(),
+            rules: rules
+                .build()
+                .map_err(|e| PolicyError::InvalidGlob(config.rules.join(", "), e))?,
+        })
+    }
+
+    /// Whether projects are taken as recorded
+    pub fn is_off(&self) -> bool {
+        self.strategy == ProjectResolution::Directory
+    }
+
+    /// The component of `project` a conversation with `messages` concerns
+    ///
+    /// Components are looked for below the project's repository root: the
+    /// one holding most of the files the conversation's tools touched, else
+    /// the one holding the project itself. Without one, the project stands.
+    pub fn resolve(&self, project: &Path, messages: &[Message]) -> PathBuf {
+        if self.is_off() {
+            return project.to_path_buf();
+        }
+        let root = git::toplevel(project)
+            .filter(|root| project.starts_with(root))
+            .unwrap_or_else(|| project.to_path_buf());
+
+        let touched = messages
+            .iter()
+            .flat_map(|message| &message.tool_calls)
+            .flat_map(|call| call.paths());
+        self.most_touched(&root, touched)
+            .or_else(|| self.component_of(project, &root))
+            .unwrap_or_else(|| project.to_path_buf())
+    }
+
+    /// The component most of `paths` are in, the first seen on a tie
+    fn most_touched(&self, root: &Path, paths: impl Iterator<Item = PathBuf>) -> Option<PathBuf> {
+        let mut counts: Vec<(PathBuf, usize)> = Vec::new();
+        for path in paths.filter(|path| path.starts_with(root)) {
+            let dir = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or(root) };
+            let Some(component) = self.component_of(dir, root) else {
+                continue;
+            };
+            match counts.iter_mut().find(|(seen, _)| *seen == component) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((component, 1)),
+            }
+        }
+        counts.into_iter().rev().max_by_key(|(_, count)| *count).map(|(component, _)| component)
+    }
+
+    /// The nearest component `dir` is in, below `root`
+    fn component_of(&self, dir: &Path, root: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .take_while(|dir| *dir != root && dir.starts_with(root))
+            .find(|dir| self.is_component(dir))
+            .map(Path::to_path_buf)
+    }
+
+    fn is_component(&self, dir: &Path) -> bool {
+        if self.rules.is_match(dir) {
+            return true;
+        }
+        self.strategy == ProjectResolution::Nearest
+            && (dir.join(".git").is_file() || self.markers.iter().any(|marker| dir.join(marker).is_file()))
+    }
+}
+
+/// Suggest an alias for a project in a git repository: the repository's
+/// name, then the project's path within it
+pub fn suggest(project_path: &Path, git: &GitContext) -> Option<String> {
+    let root = git::toplevel(project_path)?;
+    suggest_from(project_path, &root, git.remote_url.as_deref())
+}
+
+fn suggest_from(project_path: &Path, root: &Path, remote_url: Option<&str>) -> Option<String> {
+    let name = remote_url
+        .and_then(git::repo_name)
+        .or_else(|| Some(root.file_name()?.to_string_lossy().to_string()))?;
+    // git reports the root with symlinks resolved
+    let project_path = project_path.canonicalize().unwrap_or_else(|_| project_path.to_path_buf());
+    let within = project_path.strip_prefix(root).ok().filter(|rest| !rest.as_os_str().is_empty());
+    Some(match within {
+        Some(rest) => {
+            let parts: Vec<_> = rest.iter().map(|part| part.to_string_lossy()).collect();
+            format!("{}/{}", name, parts.join("/"))
+        }
+        None => name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::ToolCall;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_project_names() {
+        let config = ProjectsConfig {
+            aliases: BTreeMap::from([
+                ("/src/acme-monorepo/".to_string(), "acme".to_string()),
+                ("/src/acme-monorepo/services/billing".to_string(), "billing".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let mut names = ProjectNames::from_config(&config);
+        names.suggested.insert("/src/tools".to_string(), "tools".to_string());
+        names.suggested.insert("/src/acme-monorepo/web".to_string(), "acme-monorepo/web".to_string());
+
+        assert_eq!(names.display("/src/acme-monorepo"), "acme");
+        assert_eq!(names.display("/src/acme-monorepo/services/billing"), "billing");
+        assert_eq!(names.display("/src/acme-monorepo/services/billing/api"), "billing/api");
+        // The config wins over a suggestion
+        assert_eq!(names.alias("/src/acme-monorepo/web"), Some(("acme/web".to_string(), AliasSource::Config)));
+        assert_eq!(names.alias("/src/tools"), Some(("tools".to_string(), AliasSource::Git)));
+        assert_eq!(names.display("/src/acme-monorepo-old"), "/src/acme-monorepo-old");
+    }
+
+    #[test]
+    fn test_resolve_components() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("acme");
+        for package in ["packages/billing", "packages/web", "services/api"] {
+            std::fs::create_dir_all(repo.join(package).join("src")).unwrap();
+        }
+        std::fs::write(repo.join("packages/billing/package.json"), "{}").unwrap();
+        std::fs::write(repo.join("packages/web/package.json"), "{}").unwrap();
+        let edits = |files: &[&str]| {
+            let tool_calls = files
+                .iter()
+                .map(|file| ToolCall {
+                    name: "Edit".to_string(),
+                    input: serde_json::json!({ "file_path": repo.join(file) }).to_string(),
+                })
+                .collect();
+            vec![Message {
+                role: "assistant".to_string(),
+                content: String::new(),
+                timestamp: None,
+                utc_offset: None,
+                tool_calls,
+                model: None,
+            }]
+        };
+        let resolver = |strategy, rules: &[&str]| {
+            ProjectResolver::from_config(&ProjectResolutionConfig {
+                strategy,
+                rules: rules.iter().map(|rule| repo.join(rule).to_string_lossy().to_string()).collect(),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        let nearest = resolver(ProjectResolution::Nearest, &["services/*"]);
+        let touched = edits(&["packages/web/src/a.ts", "packages/billing/src/b.ts", "packages/billing/src/c.ts"]);
+        assert_eq!(nearest.resolve(&repo, &touched), repo.join("packages/billing"));
+        assert_eq!(nearest.resolve(&repo, &edits(&["services/api/src/main.go"])), repo.join("services/api"));
+        // Nothing touched in a component, or outside the repository
+        assert_eq!(nearest.resolve(&repo, &edits(&["README.md", "/etc/hosts"])), repo);
+
+        let rules = resolver(ProjectResolution::Rules, &["services/*"]);
+        assert_eq!(rules.resolve(&repo, &touched), repo);
+        let off = resolver(ProjectResolution::Directory, &["services/*"]);
+        assert_eq!(off.resolve(&repo, &edits(&["services/api/src/main.go"])), repo);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("acme-monorepo");
+        let billing = root.join("services").join("billing");
+        std::fs::create_dir_all(&billing).unwrap();
+
+        let remote = Some("git@github.com:acme/monorepo.git");
+        assert_eq!(suggest_from(&billing, &root, remote).as_deref(), Some("monorepo/services/billing"));
+        assert_eq!(suggest_from(&billing, &root, None).as_deref(), Some("acme-monorepo/services/billing"));
+        assert_eq!(suggest_from(&root, &root, None).as_deref(), Some("acme-monorepo"));
+    }
+}
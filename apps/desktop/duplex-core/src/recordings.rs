@@ -0,0 +1,178 @@
+//! Terminal recording correlation
+//!
+//! Links conversations to asciinema (`.cast`) and `script` recordings found
+//! in the configured directories. A recording matches when its time span
+//! overlaps the conversation's (plus some slack) and, if the recording
+//! captured a working directory, that directory is inside the project.
+//! Only the linkage metadata is uploaded, never the recording itself.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::timestamps;
+use crate::watcher::expand_path;
+
+/// Largest header line read from a recording
+const MAX_HEADER_BYTES: u64 = 64 * 1024;
+
+/// A terminal recording found on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    pub path: PathBuf,
+    /// "asciinema" or "script"
+    pub kind: &'static str,
+    /// Unix seconds, sent as RFC 3339
+    #[serde(serialize_with = "timestamps::serialize_rfc3339")]
+    pub started_at: i64,
+    /// Unix seconds, sent as RFC 3339
+    #[serde(serialize_with = "timestamps::serialize_rfc3339")]
+    pub ended_at: i64,
+    /// Working directory at recording time, when captured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+/// Find recordings in the given directories (not recursive)
+pub fn scan(directories: &[String]) -> Vec<Recording> {
+    let mut recordings = Vec::new();
+
+    for dir in directories {
+        let dir = expand_path(dir);
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            tracing::debug!("Recording directory not readable: {:?}", dir);
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() {
+                if let Some(recording) = read_recording(&path) {
+                    recordings.push(recording);
+                }
+            }
+        }
+    }
+
+    recordings
+}
+
+/// Parse a recording's header, returning `None` for other files
+fn read_recording(path: &Path) -> Option<Recording> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut header = String::new();
+    BufReader::new(std::io::Read::take(file, MAX_HEADER_BYTES))
+        .read_line(&mut header)
+        .ok()?;
+
+    let modified_at = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(|t| DateTime::<Utc>::from(t).timestamp())?;
+
+    let is_cast = path.extension().is_some_and(|e| e == "cast");
+    let (kind, started_at, duration, cwd) = if is_cast {
+        let (started_at, duration, cwd) = parse_asciinema_header(&header)?;
+        ("asciinema", started_at, duration, cwd)
+    } else {
+        ("script", parse_script_header(&header)?, None, None)
+    };
+
+    Some(Recording {
+        path: path.to_path_buf(),
+        kind,
+        started_at,
+        // Recordings are appended to until they end
+        ended_at: duration
+            .map(|d| started_at + d)
+            .unwrap_or(modified_at)
+            .max(started_at),
+        cwd,
+    })
+}
+
+/// Parse an asciinema v2/v3 header into (start, duration, cwd)
+///
+/// The cwd is only present when recorded with `--env` including `PWD`.
+fn parse_asciinema_header(header: &str) -> Option<(i64, Option<i64>, Option<String>)> {
+    let header: serde_json::Value = serde_json::from_str(header).ok()?;
+    let started_at = header["timestamp"].as_i64()?;
+    let duration = header["duration"].as_f64().map(|d| d.ceil() as i64);
+    let cwd = header["env"]["PWD"].as_str().map(str::to_string);
+    Some((started_at, duration, cwd))
+}
+
+/// Parse the start time from a `script` typescript header
+///
+/// util-linux writes `Script started on 2024-01-15 10:23:45+01:00 [...]`.
+fn parse_script_header(header: &str) -> Option<i64> {
+    let rest = header.strip_prefix("Script started on ")?;
+    // Older versions omit the offset, which is then taken as local time
+    timestamps::parse(rest.split(" [").next()?).map(|time| time.unix())
+}
+
+/// Recordings that overlap a conversation window in the same project
+pub fn link(
+    recordings: &[Recording],
+    window: (i64, i64),
+    project_path: Option<&Path>,
+    slack_secs: i64,
+) -> Vec<Recording> {
+    let (start, end) = (window.0 - slack_secs, window.1 + slack_secs);
+
+    recordings
+        .iter()
+        .filter(|r| r.started_at <= end && r.ended_at >= start)
+        .filter(|r| match (&r.cwd, project_path) {
+            (Some(cwd), Some(project)) => Path::new(cwd).starts_with(project),
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let cast = r#"{"version": 2, "width": 80, "height": 24, "timestamp": 1700000000, "duration": 61.2, "env": {"SHELL": "/bin/zsh", "PWD": "/work/app"}}"#;
+        assert_eq!(
+            parse_asciinema_header(cast),
+            Some((1700000000, Some(62), Some("/work/app".to_string())))
+        );
+
+        let script = "Script started on 2023-11-14 22:13:20+00:00 [COMMAND=\"zsh\" TERM=\"xterm\"]\n";
+        assert_eq!(parse_script_header(script), Some(1700000000));
+        assert_eq!(parse_script_header("just a log file"), None);
+    }
+
+    #[test]
+    fn test_link_by_window_and_cwd() {
+        let recording = |cwd: Option<&str>, started_at| Recording {
+            path: PathBuf::from("/rec.cast"),
+            kind: "asciinema",
+            started_at,
+            ended_at: started_at + 600,
+            cwd: cwd.map(str::to_string),
+        };
+        let recordings = vec![
+            recording(Some("/work/app/src"), 1000),
+            recording(Some("/work/other"), 1000),
+            recording(None, 1000),
+            recording(None, 5000),
+        ];
+
+        let linked = link(&recordings, (1200, 1300), Some(Path::new("/work/app")), 60);
+        assert_eq!(linked.len(), 2);
+        assert_eq!(linked[0].cwd.as_deref(), Some("/work/app/src"));
+        assert_eq!(linked[1].cwd, None);
+
+        let sent = serde_json::to_value(&linked[1]).unwrap();
+        assert_eq!(sent["startedAt"], "1970-01-01T00:16:40Z");
+        assert_eq!(sent["endedAt"], "1970-01-01T00:26:40Z");
+    }
+}
@@ -0,0 +1,334 @@
+//! Recording the sync engine's event stream, to replay it later
+//!
+//! With `DUPLEX_RECORD_EVENTS` set to a file path, the app writes every file
+//! change the sync engine receives and every change to its queue to that
+//! file, one JSON object per line. Paths are written as
+//! `project-N/session-M` and no content is kept, only each file's length,
+//! so a log can be attached to a bug report as is.
+//!
+//! [`replay`] feeds a log back through a sync engine reading in-memory
+//! files of the recorded lengths, uploading wherever the recording started
+//! an upload. Run against the mock API, the same log gives the same queue
+//! changes every time; to replay one from a bug report:
+//!
+//! ```text
+//! DUPLEX_REPLAY_LOG=events.jsonl cargo test --test sync_e2e replay_log -- --ignored --nocapture
+//! ```
+//!
+//! [`replay`] is only built for tests and with the `test-support` feature.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use thiserror::Error;
+
+use crate::sync::QueueEvent;
+use crate::watcher::FileChangeEvent;
+
+/// Environment variable naming the file to record events to
+pub const RECORD_EVENTS_ENV: &str = "DUPLEX_RECORD_EVENTS";
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Line {line} of the event log: {source}")]
+    Parse { line: usize, source: serde_json::Error },
+}
+
+/// One line of an event log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RecordedEvent {
+    /// The watcher reported a change to a file
+    #[serde(rename_all = "camelCase")]
+    Change {
+        /// Milliseconds since recording started
+        at_ms: u64,
+        /// The file as `project-N/session-M`
+        file: String,
+        parser_name: String,
+        /// File length in bytes, `None` once it was removed
+        len: Option<u64>,
+    },
+    /// The queue changed; paths are written like [`RecordedEvent::Change`]'s
+    #[serde(rename_all = "camelCase")]
+    Queue {
+        at_ms: u64,
+        #[serde(flatten)]
+        event: QueueEvent,
+    },
+}
+
+/// Writes an event log as the sync engine works
+pub struct EventRecorder {
+    out: LineWriter<File>,
+    started: Instant,
+    /// Recorded name of each project directory, numbered from 1
+    projects: HashMap<PathBuf, usize>,
+    /// Recorded name of each file
+    files: HashMap<PathBuf, String>,
+}
+
+impl EventRecorder {
+    /// Start a log at `path`, replacing any log there
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            out: LineWriter::new(File::create(path)?),
+            started: Instant::now(),
+            projects: HashMap::new(),
+            files: HashMap::new(),
+        })
+    }
+
+    /// Start a log at the path in `DUPLEX_RECORD_EVENTS`, if it is set
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var_os(RECORD_EVENTS_ENV).filter(|path| !path.is_empty())?;
+        match Self::create(Path::new(&path)) {
+            Ok(recorder) => {
+                tracing::info!("Recording sync events to {:?}", path);
+                Some(recorder)
+            }
+            Err(e) => {
+                tracing::warn!("Can't record sync events to {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Record a change the watcher reported
+    pub fn file_changed(&mut self, event: &FileChangeEvent) {
+        let event = RecordedEvent::Change {
+            at_ms: self.elapsed_ms(),
+            file: self.name(&event.path),
+            parser_name: event.parser_name.clone(),
+            len: std::fs::metadata(&event.path).ok().map(|metadata| metadata.len()),
+        };
+        self.write(event);
+    }
+
+    /// Record a change to the queue
+    pub fn queue_changed(&mut self, event: &QueueEvent) {
+        let event = RecordedEvent::Queue {
+            at_ms: self.elapsed_ms(),
+            event: QueueEvent {
+                seq: event.seq,
+                change: event.change.clone().map_paths(|path| PathBuf::from(self.name(path))),
+            },
+        };
+        self.write(event);
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// A file's name in the log: its project's number, then its own
+    fn name(&mut self, path: &Path) -> String {
+        if let Some(name) = self.files.get(path) {
+            return name.clone();
+        }
+        let next_project = self.projects.len() + 1;
+        let project = *self
+            .projects
+            .entry(path.parent().map(Path::to_path_buf).unwrap_or_default())
+            .or_insert(next_project);
+        let name = format!("project-{}/session-{}", project, self.files.len() + 1);
+        self.files.insert(path.to_path_buf(), name.clone());
+        name
+    }
+
+    fn write(&mut self, event: RecordedEvent) {
+        let line = serde_json::to_string(&event).expect("recorded events serialize");
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            tracing::warn!("Failed to record sync event: {}", e);
+        }
+    }
+}
+
+/// Read an event log written by [`EventRecorder`]
+pub fn read_log(path: &Path) -> Result<Vec<RecordedEvent>, ReplayError> {
+    let content = std::fs::read_to_string(path)?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|source| ReplayError::Parse { line: index + 1, source })
+        })
+        .collect()
+}
+
+#[cfg(any(test, feature = "test-support"))]
+pub use self::replaying::{replay, synthetic_content, ReplayReport};
+
+#[cfg(any(test, feature = "test-support"))]
+mod replaying {
+    use std::path::{Path, PathBuf};
+
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::RecordedEvent;
+    use crate::sync::{QueueChange, QueueEvent, SyncEngine, SyncError, SyncReport};
+    use crate::testing::{TestFiles, TestParser};
+    use crate::watcher::FileChangeEvent;
+
+    /// What a replay did
+    #[derive(Debug, Default)]
+    pub struct ReplayReport {
+        /// File changes replayed
+        pub changes: usize,
+        /// Uploads over the whole replay
+        pub sync: SyncReport,
+        /// Queue changes, with paths named as in the log
+        pub events: Vec<QueueEvent>,
+    }
+
+    /// Replay a log through `engine`, whose registry must read `files` with
+    /// a [`TestParser`]
+    ///
+    /// Each recorded file becomes an in-memory file of the recorded length.
+    /// The queue is worked wherever the recording started an upload, and
+    /// once more at the end. Removed files can't be told from unreadable
+    /// ones, so their changes fail to queue and are skipped.
+    pub async fn replay(
+        events: &[RecordedEvent],
+        engine: &mut SyncEngine,
+        files: &TestFiles,
+    ) -> Result<ReplayReport, SyncError> {
+        let (_, mut queue_events) = engine.subscribe_queue();
+        let mut report = ReplayReport::default();
+        for event in events {
+            match event {
+                RecordedEvent::Change { file, len, .. } => {
+                    let path = replay_path(file);
+                    match len {
+                        Some(len) => files.write(&path, &synthetic_content(*len)),
+                        None => {
+                            files.remove(&path);
+                        }
+                    }
+                    report.changes += 1;
+                    let change = FileChangeEvent {
+                        path,
+                        parser_name: TestParser::NAME.to_string(),
+                    };
+                    if let Err(e) = engine.handle_file_change(change) {
+                        tracing::debug!("Replayed change to {} not queued: {}", file, e);
+                    }
+                }
+                RecordedEvent::Queue { event, .. } => {
+                    if matches!(event.change, QueueChange::Started { .. }) && engine.queue_len() > 0 {
+                        merge(&mut report.sync, engine.process_all().await?);
+                    }
+                }
+            }
+            drain(&mut queue_events, &mut report.events);
+        }
+        merge(&mut report.sync, engine.process_all().await?);
+        drain(&mut queue_events, &mut report.events);
+        Ok(report)
+    }
+
+    /// Content of a file `len` bytes long, or one line if that is shorter
+    ///
+    /// Each line depends only on its position, so a longer file extends a
+    /// shorter one the way a growing transcript does.
+    pub fn synthetic_content(len: u64) -> String {
+        let mut content = String::new();
+        for index in 1.. {
+            let line = match index % 2 {
+                1 => format!("user: Message {}\n", index),
+                _ => format!("assistant: Reply {}\n", index),
+            };
+            if index > 1 && (content.len() + line.len()) as u64 > len {
+                break;
+            }
+            content.push_str(&line);
+        }
+        content
+    }
+
+    /// Where a recorded file lives among the in-memory files
+    fn replay_path(file: &str) -> PathBuf {
+        let (project, session) = file.split_once('/').unwrap_or(("project", file));
+        TestFiles::path(project, session)
+    }
+
+    /// A replayed path as it was named in the log
+    fn recorded_path(path: &Path) -> PathBuf {
+        let name = path.strip_prefix(TestParser::ROOT).unwrap_or(path);
+        name.with_extension("")
+    }
+
+    fn drain(events: &mut tokio::sync::broadcast::Receiver<QueueEvent>, into: &mut Vec<QueueEvent>) {
+        loop {
+            match events.try_recv() {
+                Ok(event) => into.push(QueueEvent {
+                    seq: event.seq,
+                    change: event.change.map_paths(recorded_path),
+                }),
+                Err(TryRecvError::Lagged(missed)) => tracing::warn!("Replay missed {} queue change(s)", missed),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return,
+            }
+        }
+    }
+
+    fn merge(total: &mut SyncReport, pass: SyncReport) {
+        total.succeeded += pass.succeeded;
+        total.failed.extend(pass.failed);
+        total.skipped += pass.skipped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::QueueChange;
+
+    #[test]
+    fn test_recorded_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("events.jsonl");
+        let mut recorder = EventRecorder::create(&log).unwrap();
+        let session = dir.path().join("a").join("s1.jsonl");
+        std::fs::create_dir_all(session.parent().unwrap()).unwrap();
+        std::fs::write(&session, "0123456789").unwrap();
+
+        let change = |path: &Path| FileChangeEvent {
+            path: path.to_path_buf(),
+            parser_name: "claude-code".to_string(),
+        };
+        recorder.file_changed(&change(&session));
+        recorder.file_changed(&change(&dir.path().join("b").join("s2.jsonl")));
+        recorder.queue_changed(&QueueEvent {
+            seq: 1,
+            change: QueueChange::Started { path: session.clone() },
+        });
+        drop(recorder);
+
+        let events = read_log(&log).unwrap();
+        assert!(matches!(
+            &events[0],
+            RecordedEvent::Change { file, len: Some(10), .. } if file == "project-1/session-1"
+        ));
+        assert!(matches!(&events[1], RecordedEvent::Change { file, len: None, .. } if file == "project-2/session-2"));
+        let RecordedEvent::Queue { event, .. } = &events[2] else {
+            panic!("expected a queue change");
+        };
+        assert_eq!(event.change, QueueChange::Started { path: "project-1/session-1".into() });
+        assert!(!std::fs::read_to_string(&log).unwrap().contains(&*dir.path().to_string_lossy()));
+    }
+
+    #[test]
+    fn test_synthetic_content() {
+        assert_eq!(synthetic_content(0), "user: Message 1\n");
+        let short = synthetic_content(40);
+        let long = synthetic_content(400);
+        assert!(short.len() <= 40 && long.len() <= 400 && long.len() > 350);
+        assert!(long.starts_with(&short));
+    }
+}
@@ -0,0 +1,136 @@
+//! Re-pushing history after server-side reprocessing
+//!
+//! `duplex resync` returns already-synced conversations to `pending`,
+//! whatever their content hash. The running app then uploads them from a
+//! low-priority backlog in small batches, behind live changes; if the app is
+//! not running they go out on its next start.
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::path::Path;
+use thiserror::Error;
+
+use crate::db::{Database, DatabaseError};
+use crate::errors::ErrorCategory;
+
+#[derive(Error, Debug)]
+pub enum ResyncError {
+    #[error("Invalid date '{0}', expected YYYY-MM-DD or an RFC 3339 timestamp")]
+    InvalidDate(String),
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl ResyncError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ResyncError::InvalidDate(_) => ErrorCategory::Config,
+            ResyncError::Database(e) => e.category(),
+            ResyncError::Sqlite(_) => ErrorCategory::Io,
+        }
+    }
+}
+
+/// Which conversations to re-sync; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResyncFilter {
+    /// Conversations started (or last changed) at or after this Unix time
+    pub since: Option<i64>,
+    /// Conversations in this project or a directory below it
+    pub project: Option<String>,
+}
+
+impl ResyncFilter {
+    /// Build a filter from `--since` and `--project` arguments
+    pub fn new(since: Option<&str>, project: Option<&Path>) -> Result<Self, ResyncError> {
+        Ok(Self {
+            since: since.map(parse_since).transpose()?,
+            // Match the absolute paths parsers record
+            project: project.map(|p| {
+                std::fs::canonicalize(p)
+                    .unwrap_or_else(|_| p.to_path_buf())
+                    .to_string_lossy()
+                    .to_string()
+            }),
+        })
+    }
+}
+
+/// Mark matching synced conversations `pending`, returning how many
+pub fn reset(db: &Database, filter: &ResyncFilter) -> Result<usize, ResyncError> {
+    let count = db.reset_for_resync(filter.since, filter.project.as_deref())?;
+    tracing::info!("Marked {} conversation(s) for re-sync", count);
+    Ok(count)
+}
+
+fn parse_since(value: &str) -> Result<i64, ResyncError> {
+    parse_timestamp(value).ok_or_else(|| ResyncError::InvalidDate(value.trim().to_string()))
+}
+
+/// Parse a date (local midnight) or full timestamp into Unix seconds
+pub(crate) fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.timestamp());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest())
+        .map(|midnight| midnight.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SyncState, SyncStatus};
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("2026-02-01T00:00:00Z").unwrap(), 1769904000);
+        assert!(parse_since("2026-02-01").is_ok());
+        assert!(matches!(parse_since("last week"), Err(ResyncError::InvalidDate(_))));
+    }
+
+    #[test]
+    fn test_reset_matching_conversations() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+
+        let sessions = [
+            ("/p/app/a.jsonl", "/work/app", 1_000),
+            ("/p/app/b.jsonl", "/work/app/sub", 2_000),
+            ("/p/other/c.jsonl", "/work/app-other", 2_000),
+            ("ingest://tool/run-1", "/work/app", 2_000),
+        ];
+        for (file_path, project, modified) in sessions {
+            db.upsert_sync_state(&SyncState {
+                file_path: file_path.to_string(),
+                content_hash: "abc123".to_string(),
+                last_synced_at: None,
+                last_modified_at: modified,
+                workflow_id: None,
+                status: SyncStatus::Pending,
+                session_id: None,
+                project_path: Some(project.to_string()),
+                source: Some("claude-code".to_string()),
+                git: None,
+                title: None,
+            })
+            .unwrap();
+            db.mark_complete(file_path, "wf-1").unwrap();
+        }
+
+        let filter = ResyncFilter {
+            since: Some(1_500),
+            project: Some("/work/app".to_string()),
+        };
+        assert_eq!(reset(&db, &filter).unwrap(), 1);
+        let pending: Vec<String> = db.get_pending().unwrap().into_iter().map(|s| s.file_path).collect();
+        assert_eq!(pending, vec!["/p/app/b.jsonl"]);
+
+        // Everything with a file behind it
+        assert_eq!(reset(&db, &ResyncFilter::default()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,262 @@
+//! Removal of old session files once they are synced
+//!
+//! Agent tools keep every session they record, which over months runs to
+//! gigabytes. With `retention.enabled`, a daily job removes session files
+//! unchanged for `retention.afterDays`, but only when everything in them is
+//! on the server: the file must be synced, still hash to what was uploaded,
+//! and each conversation in it must have been uploaded. With the `archive`
+//! action (the default), a gzipped copy is kept in `retention.archiveDir`
+//! before the original goes.
+//!
+//! Removed files are marked retired rather than deleted, so their
+//! conversations stay on the server even with `sync.propagateDeletes` on.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+use crate::config::{self, RetentionAction, RetentionConfig};
+use crate::db::{Database, SyncState, SyncStatus};
+use crate::errors::ErrorCategory;
+use crate::files;
+use crate::parsers::ParserRegistry;
+use crate::sync::compute_hash;
+
+#[derive(Error, Debug)]
+pub enum RetentionError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] config::ConfigError),
+}
+
+impl RetentionError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            RetentionError::Io(_) | RetentionError::Sqlite(_) => ErrorCategory::Io,
+            RetentionError::Config(e) => e.category(),
+        }
+    }
+}
+
+/// What a retention pass removed, or would have
+#[derive(Debug, Default)]
+pub struct RetentionReport {
+    /// Files removed from their tool's directory
+    pub retired: Vec<PathBuf>,
+    /// Their total size on disk
+    pub bytes: u64,
+    /// Old files kept because not everything in them is synced
+    pub unsynced: usize,
+}
+
+/// Remove the session files older than `retention.afterDays` whose
+/// conversations are all synced
+///
+/// With `dry_run` nothing is touched; the report lists what would go.
+pub fn enforce(
+    db: &Database,
+    registry: &ParserRegistry,
+    config: &RetentionConfig,
+    dry_run: bool,
+) -> Result<RetentionReport, RetentionError> {
+    let cutoff = SystemTime::now() - Duration::from_secs(config.after_days.saturating_mul(24 * 60 * 60));
+    let archive_dir = match config.action {
+        RetentionAction::Archive => Some(archive_dir(config)?),
+        RetentionAction::Delete => None,
+    };
+
+    let mut report = RetentionReport::default();
+    for state in db.get_tracked_files()? {
+        let path = PathBuf::from(&state.file_path);
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if !metadata.is_file() || metadata.modified().map_or(true, |modified| modified > cutoff) {
+            continue;
+        }
+        if !is_synced(db, registry, &state, &path)? {
+            report.unsynced += 1;
+            continue;
+        }
+
+        if !dry_run {
+            if let Err(e) = retire(db, &state, &path, archive_dir.as_deref()) {
+                tracing::warn!("Could not remove {:?}: {}", path, e);
+                continue;
+            }
+            tracing::info!("Removed synced session file: {:?}", path);
+        }
+        report.bytes += metadata.len();
+        report.retired.push(path);
+    }
+
+    if !dry_run && !report.retired.is_empty() {
+        tracing::info!("Retention removed {} file(s), {} bytes", report.retired.len(), report.bytes);
+    }
+    Ok(report)
+}
+
+/// Where archived files go
+pub fn archive_dir(config: &RetentionConfig) -> Result<PathBuf, RetentionError> {
+    Ok(match &config.archive_dir {
+        Some(dir) => crate::watcher::expand_path(dir),
+        None => config::get_config_dir()?.join("archive"),
+    })
+}
+
+/// Whether everything in a file is on the server as it is now
+fn is_synced(
+    db: &Database,
+    registry: &ParserRegistry,
+    state: &SyncState,
+    path: &Path,
+) -> Result<bool, RetentionError> {
+    if state.status != SyncStatus::Complete {
+        return Ok(false);
+    }
+    let conversations = db.list_file_conversations(&state.file_path)?;
+    let uploaded = if conversations.is_empty() {
+        state.workflow_id.is_some()
+    } else {
+        conversations
+            .iter()
+            .all(|c| c.status == SyncStatus::Complete && c.workflow_id.is_some())
+    };
+    if !uploaded {
+        return Ok(false);
+    }
+
+    // A change the watcher hasn't picked up yet isn't on the server
+    let Some(parser) = state.source.as_deref().and_then(|name| registry.get(name)) else {
+        return Ok(false);
+    };
+    Ok(parser
+        .read(path)
+        .is_ok_and(|content| compute_hash(&content) == state.content_hash))
+}
+
+/// Archive a file if configured, then remove it
+fn retire(db: &Database, state: &SyncState, path: &Path, archive_dir: Option<&Path>) -> Result<(), RetentionError> {
+    if let Some(dir) = archive_dir {
+        archive(path, &archive_path(dir, state, path))?;
+    }
+
+    // Marked first, so the watcher doesn't take the removal for a deletion
+    db.update_status(&state.file_path, SyncStatus::Retired)?;
+    if let Err(e) = fs::remove_file(path) {
+        db.update_status(&state.file_path, SyncStatus::Complete)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// `<archive>/<tool>/<parent directory>/<file name>.gz`, keeping apart
+/// sessions of the same name from different projects
+fn archive_path(dir: &Path, state: &SyncState, path: &Path) -> PathBuf {
+    let mut archived = dir.join(state.source.as_deref().unwrap_or("unknown"));
+    if let Some(parent) = path.parent().and_then(Path::file_name) {
+        archived.push(parent);
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    archived.join(format!("{}.gz", name))
+}
+
+fn archive(path: &Path, dest: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.flush()?;
+    files::write_private(dest, encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn backdate(path: &Path, days: u64) {
+        let then = SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+        File::options().write(true).open(path).unwrap().set_modified(then).unwrap();
+    }
+
+    fn track(db: &Database, path: &Path, content: &str, workflow_id: Option<&str>) {
+        db.upsert_sync_state(&SyncState {
+            file_path: path.to_string_lossy().to_string(),
+            content_hash: compute_hash(content),
+            last_synced_at: None,
+            last_modified_at: 0,
+            workflow_id: workflow_id.map(str::to_string),
+            status: SyncStatus::Complete,
+            session_id: None,
+            project_path: None,
+            source: Some("claude-code".to_string()),
+            git: None,
+            title: None,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_enforce() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let registry = ParserRegistry::new();
+        let project = dir.path().join("projects").join("-home-me-app");
+        fs::create_dir_all(&project).unwrap();
+
+        let content = "{\"type\":\"user\",\"message\":{\"content\":\"hi\"}}\n";
+        let synced = project.join("synced.jsonl");
+        let recent = project.join("recent.jsonl");
+        let unsynced = project.join("unsynced.jsonl");
+        let changed = project.join("changed.jsonl");
+        for (path, workflow_id) in [
+            (&synced, Some("wf-1")),
+            (&recent, Some("wf-2")),
+            (&unsynced, None),
+            (&changed, Some("wf-3")),
+        ] {
+            fs::write(path, content).unwrap();
+            track(&db, path, content, workflow_id);
+        }
+        fs::write(&changed, format!("{}{}", content, content)).unwrap();
+        for path in [&synced, &unsynced, &changed] {
+            backdate(path, 100);
+        }
+        backdate(&recent, 10);
+
+        let config = RetentionConfig {
+            enabled: true,
+            archive_dir: Some(dir.path().join("archive").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let report = enforce(&db, &registry, &config, true).unwrap();
+        assert_eq!(report.retired, vec![synced.clone()]);
+        assert_eq!(report.unsynced, 2);
+        assert!(synced.exists());
+
+        let report = enforce(&db, &registry, &config, false).unwrap();
+        assert_eq!(report.retired, vec![synced.clone()]);
+        assert!(!synced.exists() && recent.exists() && unsynced.exists() && changed.exists());
+
+        let state = db.get_sync_state(&synced.to_string_lossy()).unwrap().unwrap();
+        assert_eq!(state.status, SyncStatus::Retired);
+        assert_eq!(state.workflow_id.as_deref(), Some("wf-1"));
+
+        let archived = dir.path().join("archive/claude-code/-home-me-app/synced.jsonl.gz");
+        let mut restored = String::new();
+        GzDecoder::new(File::open(archived).unwrap()).read_to_string(&mut restored).unwrap();
+        assert_eq!(restored, content);
+
+        // Retired files aren't looked at again
+        assert!(enforce(&db, &registry, &config, false).unwrap().retired.is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+//! Sync schedule ("quiet hours")
+//!
+//! `sync.schedule` limits when queued uploads run: a daily window in local
+//! time and/or only on unmetered connections. The sync engine checks the
+//! schedule before each upload; anything queued outside it stays `pending`
+//! and goes out once the window opens.
+
+use chrono::{Local, NaiveTime};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::config::ScheduleConfig;
+use crate::errors::ErrorCategory;
+
+/// How long a metered-connection check is reused
+const METERED_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error("Invalid schedule time '{0}', expected HH:MM")]
+    InvalidTime(String),
+    #[error("Schedule needs both start and end")]
+    IncompleteWindow,
+}
+
+impl ScheduleError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        ErrorCategory::Config
+    }
+}
+
+/// Compiled `sync.schedule`
+#[derive(Debug)]
+pub struct Schedule {
+    /// Daily window as (start, end) local times
+    window: Option<(NaiveTime, NaiveTime)>,
+    skip_metered: bool,
+    /// Last metered check and its result
+    metered: Mutex<Option<(Instant, bool)>>,
+    /// Whether the schedule was open at the last check, to log changes once
+    was_open: AtomicBool,
+}
+
+impl Schedule {
+    /// Compile the schedule section of the config
+    pub fn from_config(config: &ScheduleConfig) -> Result<Self, ScheduleError> {
+        let window = match (&config.start, &config.end) {
+            (Some(start), Some(end)) => Some((parse_time(start)?, parse_time(end)?)),
+            (None, None) => None,
+            _ => return Err(ScheduleError::IncompleteWindow),
+        };
+
+        Ok(Self {
+            window,
+            skip_metered: config.skip_metered,
+            metered: Mutex::new(None),
+            was_open: AtomicBool::new(true),
+        })
+    }
+
+    /// Whether uploads may run now
+    pub fn is_open(&self) -> bool {
+        let reason = if !self.in_window(Local::now().time()) {
+            Some("outside the sync window")
+        } else if self.skip_metered && self.is_metered() {
+            Some("on a metered connection")
+        } else {
+            None
+        };
+
+        let open = reason.is_none();
+        if self.was_open.swap(open, Ordering::Relaxed) != open {
+            match reason {
+                Some(reason) => tracing::info!("Holding uploads: {}", reason),
+                None => tracing::info!("Sync schedule open, resuming uploads"),
+            }
+        }
+        open
+    }
+
+    /// Whether `time` falls inside the daily window
+    fn in_window(&self, time: NaiveTime) -> bool {
+        match self.window {
+            None => true,
+            Some((start, end)) if start <= end => start <= time && time < end,
+            // The window spans midnight
+            Some((start, end)) => time >= start || time < end,
+        }
+    }
+
+    fn is_metered(&self) -> bool {
+        let mut cached = self.metered.lock().unwrap();
+        match *cached {
+            Some((checked_at, metered)) if checked_at.elapsed() < METERED_CHECK_INTERVAL => metered,
+            _ => {
+                let metered = connection_is_metered();
+                *cached = Some((Instant::now(), metered));
+                metered
+            }
+        }
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, ScheduleError> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| ScheduleError::InvalidTime(value.to_string()))
+}
+
+/// Ask the OS whether the active connection is metered
+///
+/// Uses NetworkManager on Linux. Other platforms report unmetered.
+fn connection_is_metered() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        // NMMetered: 1 = yes, 3 = guessed yes
+        let output = std::process::Command::new("busctl")
+            .args([
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                let value = String::from_utf8_lossy(&output.stdout);
+                matches!(value.trim(), "u 1" | "u 3")
+            }
+            _ => {
+                tracing::debug!("Could not query NetworkManager for metered state");
+                false
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start: &str, end: &str) -> Schedule {
+        Schedule::from_config(&ScheduleConfig {
+            start: Some(start.to_string()),
+            end: Some(end.to_string()),
+            skip_metered: false,
+        })
+        .unwrap()
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        parse_time(time).unwrap()
+    }
+
+    #[test]
+    fn test_in_window() {
+        let day = schedule("08:00", "20:00");
+        assert!(day.in_window(at("08:00")));
+        assert!(day.in_window(at("19:59")));
+        assert!(!day.in_window(at("20:00")));
+        assert!(!day.in_window(at("03:00")));
+
+        let night = schedule("22:00", "06:00");
+        assert!(night.in_window(at("23:30")));
+        assert!(night.in_window(at("05:00")));
+        assert!(!night.in_window(at("12:00")));
+
+        assert!(Schedule::from_config(&ScheduleConfig::default()).unwrap().is_open());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = ScheduleConfig {
+            start: Some("8am".to_string()),
+            end: Some("20:00".to_string()),
+            skip_metered: false,
+        };
+        assert!(matches!(Schedule::from_config(&config), Err(ScheduleError::InvalidTime(_))));
+
+        let config = ScheduleConfig {
+            start: Some("08:00".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(Schedule::from_config(&config), Err(ScheduleError::IncompleteWindow)));
+    }
+}
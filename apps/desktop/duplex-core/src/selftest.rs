@@ -0,0 +1,195 @@
+//! Startup self-test
+//!
+//! Runs quick probes of the app's dependencies so that missing capabilities
+//! are reported up front (in the tray and `duplex doctor`) instead of only
+//! surfacing as warnings in the logs.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::api::{ClientInfo, CLIENT_HEADER};
+use crate::config::{Config, SecureTokenStorage};
+use crate::db::Database;
+
+/// Timeout for the API reachability probe
+const API_PROBE_TIMEOUT_SECS: u64 = 3;
+
+/// Outcome of a single probe
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    /// Probe name (e.g., "keyring")
+    pub name: &'static str,
+    /// Whether the capability is fully available
+    pub ok: bool,
+    /// Details on success, or the limitation on failure
+    pub detail: String,
+}
+
+impl ProbeResult {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            ok: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn degraded(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            ok: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Results of all startup probes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfTestReport {
+    pub probes: Vec<ProbeResult>,
+}
+
+impl SelfTestReport {
+    /// Descriptions of every degraded capability
+    pub fn limitations(&self) -> Vec<&str> {
+        self.probes
+            .iter()
+            .filter(|p| !p.ok)
+            .map(|p| p.detail.as_str())
+            .collect()
+    }
+
+    /// Whether every probe passed
+    pub fn is_healthy(&self) -> bool {
+        self.probes.iter().all(|p| p.ok)
+    }
+
+    /// One-line summary suitable for the tray
+    pub fn summary(&self) -> Option<String> {
+        if self.is_healthy() {
+            None
+        } else {
+            Some(format!("Running with limitations: {}", self.limitations().join(", ")))
+        }
+    }
+}
+
+static LAST_REPORT: Mutex<Option<SelfTestReport>> = Mutex::new(None);
+
+/// Run all probes
+pub async fn run(api_url: &str, config: &Config) -> SelfTestReport {
+    let report = SelfTestReport {
+        probes: vec![
+            probe_keyring(),
+            probe_database(),
+            probe_api(api_url, config).await,
+            probe_conversation_dirs(config),
+        ],
+    };
+
+    for probe in report.probes.iter().filter(|p| !p.ok) {
+        tracing::warn!("Self-test: {} degraded - {}", probe.name, probe.detail);
+    }
+
+    *LAST_REPORT.lock().unwrap() = Some(report.clone());
+    report
+}
+
+/// Get the report from the most recent self-test, if one has run
+pub fn last_report() -> Option<SelfTestReport> {
+    LAST_REPORT.lock().unwrap().clone()
+}
+
+fn probe_keyring() -> ProbeResult {
+    match SecureTokenStorage::new().check_available() {
+        Ok(()) => ProbeResult::ok("keyring", "available"),
+        Err(e) => {
+            tracing::debug!("Keyring probe failed: {}", e);
+            ProbeResult::degraded("keyring", "keyring unavailable, using file storage")
+        }
+    }
+}
+
+fn probe_database() -> ProbeResult {
+    match Database::open() {
+        Ok(_) => ProbeResult::ok("database", "opened"),
+        Err(e) => {
+            tracing::debug!("Database probe failed: {}", e);
+            ProbeResult::degraded("database", format!("sync history unavailable ({})", e))
+        }
+    }
+}
+
+async fn probe_api(api_url: &str, config: &Config) -> ProbeResult {
+    let client_info = ClientInfo::from_config(config);
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(API_PROBE_TIMEOUT_SECS))
+        .user_agent(config.sync.user_agent.clone().unwrap_or_else(|| client_info.user_agent()))
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => return ProbeResult::degraded("api", format!("HTTP client unavailable ({})", e)),
+    };
+
+    // Any HTTP response means the server is reachable
+    match client.get(api_url).header(CLIENT_HEADER, client_info.header_value()).send().await {
+        Ok(response) => ProbeResult::ok("api", format!("{} ({})", api_url, response.status())),
+        Err(e) => {
+            tracing::debug!("API probe failed: {}", e);
+            ProbeResult::degraded("api", "API unreachable, changes will queue until it is")
+        }
+    }
+}
+
+fn probe_conversation_dirs(config: &Config) -> ProbeResult {
+    let mut found = Vec::new();
+
+    if config.discovery.auto_discover {
+        found.extend(
+            crate::parsers::ClaudeCodeParser::projects_dirs(&config.parsers.claude_code)
+                .iter()
+                .chain(&crate::parsers::CodexParser::sessions_dirs(&config.parsers.codex))
+                .chain(&crate::parsers::GeminiParser::tmp_dirs(&config.parsers.gemini))
+                .map(|dir| dir.to_string_lossy().to_string()),
+        );
+    }
+
+    for path in &config.discovery.additional_paths {
+        let expanded = crate::watcher::expand_path(path);
+        if expanded.exists() {
+            found.push(expanded.to_string_lossy().to_string());
+        }
+    }
+
+    if found.is_empty() {
+        ProbeResult::degraded("conversation_dirs", "no conversation directories found")
+    } else {
+        ProbeResult::ok("conversation_dirs", found.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_summary() {
+        let healthy = SelfTestReport {
+            probes: vec![ProbeResult::ok("keyring", "available")],
+        };
+        assert!(healthy.is_healthy());
+        assert_eq!(healthy.summary(), None);
+
+        let degraded = SelfTestReport {
+            probes: vec![
+                ProbeResult::degraded("keyring", "keyring unavailable, using file storage"),
+                ProbeResult::ok("database", "opened"),
+            ],
+        };
+        assert_eq!(
+            degraded.summary().unwrap(),
+            "Running with limitations: keyring unavailable, using file storage"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Coordinated shutdown
+//!
+//! Background threads are started through [`Shutdown::spawn`] and handed a
+//! cancellation token. On quit or a termination signal the token is cancelled:
+//! watchers stop taking events, the sync engine finishes the upload in flight
+//! but starts no new ones, and [`Shutdown::shutdown`] waits (up to a grace
+//! period) for the threads to exit. Anything still queued stays `pending` in
+//! the database and is picked up again on the next start.
+//!
+//! System sleep suspends uploads the same way without stopping threads.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// How long to wait for threads to finish on quit
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+/// Interval between checks for threads that have finished
+const JOIN_POLL: Duration = Duration::from_millis(50);
+
+/// Shutdown coordinator shared by the app's background threads
+#[derive(Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    /// Set while the system sleeps
+    suspended: AtomicBool,
+    threads: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+}
+
+/// Shared shutdown coordinator
+pub type SharedShutdown = Arc<Shutdown>;
+
+impl Shutdown {
+    pub fn new() -> SharedShutdown {
+        Arc::new(Self::default())
+    }
+
+    /// Token cancelled when shutdown starts
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Whether shutdown has started
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Whether new uploads should wait (shutting down or asleep)
+    pub fn is_paused(&self) -> bool {
+        self.is_shutting_down() || self.suspended.load(Ordering::SeqCst)
+    }
+
+    /// Pause uploads while the system sleeps
+    pub fn suspend(&self) {
+        tracing::info!("System going to sleep, pausing uploads");
+        self.suspended.store(true, Ordering::SeqCst);
+    }
+
+    /// Resume uploads after the system wakes
+    pub fn resume(&self) {
+        tracing::info!("System woke, resuming uploads");
+        self.suspended.store(false, Ordering::SeqCst);
+    }
+
+    /// Run `f` on a named thread that shutdown waits for
+    pub fn spawn<F>(&self, name: &'static str, f: F)
+    where
+        F: FnOnce(CancellationToken) + Send + 'static,
+    {
+        let token = self.token();
+        let spawned = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || f(token));
+
+        match spawned {
+            Ok(handle) => self.threads.lock().unwrap().push((name, handle)),
+            Err(e) => tracing::error!("Failed to start {} thread: {}", name, e),
+        }
+    }
+
+    /// Cancel the token and wait up to `grace` for spawned threads to exit
+    ///
+    /// Returns the names of threads still running when the grace period ran
+    /// out. Safe to call more than once.
+    pub fn shutdown(&self, grace: Duration) -> Vec<&'static str> {
+        if !self.token.is_cancelled() {
+            tracing::info!("Shutting down, waiting up to {:?} for in-flight work", grace);
+            self.token.cancel();
+        }
+
+        let deadline = Instant::now() + grace;
+        let mut threads = self.threads.lock().unwrap();
+        loop {
+            let (finished, running): (Vec<_>, Vec<_>) =
+                threads.drain(..).partition(|(_, handle)| handle.is_finished());
+            for (name, handle) in finished {
+                if handle.join().is_err() {
+                    tracing::error!("{} thread panicked", name);
+                }
+            }
+            *threads = running;
+
+            if threads.is_empty() || Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(JOIN_POLL);
+        }
+
+        let unfinished: Vec<_> = threads.iter().map(|(name, _)| *name).collect();
+        if !unfinished.is_empty() {
+            tracing::warn!("Exiting with work still in flight: {}", unfinished.join(", "));
+        }
+        unfinished
+    }
+}
+
+/// Wait for the OS to ask the process to terminate
+///
+/// SIGTERM, SIGHUP and Ctrl-C on Unix; Ctrl-C, console close, logoff and
+/// system shutdown on Windows.
+pub async fn termination_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut term = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::select! {
+            _ = term.recv() => {}
+            _ = hangup.recv() => {}
+            result = tokio::signal::ctrl_c() => result?,
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows;
+
+        let mut close = windows::ctrl_close()?;
+        let mut logoff = windows::ctrl_logoff()?;
+        let mut system_shutdown = windows::ctrl_shutdown()?;
+        tokio::select! {
+            _ = close.recv() => {}
+            _ = logoff.recv() => {}
+            _ = system_shutdown.recv() => {}
+            result = tokio::signal::ctrl_c() => result?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutdown_waits_for_threads() {
+        let shutdown = Shutdown::new();
+        shutdown.spawn("worker", |token| {
+            while !token.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+        shutdown.spawn("stuck", |_| std::thread::sleep(Duration::from_secs(5)));
+
+        assert!(!shutdown.is_paused());
+        let unfinished = shutdown.shutdown(Duration::from_millis(200));
+        assert_eq!(unfinished, vec!["stuck"]);
+        assert!(shutdown.is_shutting_down());
+    }
+
+    #[test]
+    fn test_suspend_pauses() {
+        let shutdown = Shutdown::new();
+        shutdown.suspend();
+        assert!(shutdown.is_paused());
+        shutdown.resume();
+        assert!(!shutdown.is_paused());
+        assert!(!shutdown.is_shutting_down());
+    }
+}
@@ -0,0 +1,139 @@
+//! Agent activity over time
+//!
+//! Renders the counts from [`Database::activity_histogram`] as a terminal
+//! heatmap for `duplex stats --heatmap`: weekdays against recent weeks for
+//! day buckets, or against the hours of the day for hour buckets.
+//!
+//! [`Database::activity_histogram`]: crate::db::Database::activity_histogram
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use std::collections::BTreeMap;
+
+use crate::db::{ActivityBucket, ActivityCount};
+
+/// Weeks shown by the day heatmap, ending with the latest activity
+const HEATMAP_WEEKS: i64 = 26;
+
+/// Cell shades from no activity to the busiest cell
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Render counts as a weekday-by-week or weekday-by-hour grid
+///
+/// Counts from all projects given are added together.
+pub fn render_heatmap(counts: &[ActivityCount], bucket: ActivityBucket) -> String {
+    let total: usize = counts.iter().map(|c| c.conversations).sum();
+    if total == 0 {
+        return "No activity yet\n".to_string();
+    }
+
+    let mut out = match bucket {
+        ActivityBucket::Day => render_days(counts),
+        ActivityBucket::Hour => render_hours(counts),
+    };
+    out.push_str(&format!(
+        "\n    Less {} More    {} conversation(s)\n",
+        SHADES.iter().map(char::to_string).collect::<Vec<_>>().join(" "),
+        total
+    ));
+    out
+}
+
+/// Weekdays against the last [`HEATMAP_WEEKS`] weeks
+fn render_days(counts: &[ActivityCount]) -> String {
+    let mut by_date: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for count in counts {
+        if let Ok(date) = NaiveDate::parse_from_str(&count.bucket, "%Y-%m-%d") {
+            *by_date.entry(date).or_default() += count.conversations;
+        }
+    }
+    let Some(&last) = by_date.keys().next_back() else {
+        return String::new();
+    };
+
+    let last_monday = last - Duration::days(last.weekday().num_days_from_monday() as i64);
+    let start = last_monday - Duration::weeks(HEATMAP_WEEKS - 1);
+    let mut grid = [[0usize; HEATMAP_WEEKS as usize]; 7];
+    for (date, conversations) in by_date.range(start..) {
+        let week = ((*date - start).num_days() / 7) as usize;
+        grid[date.weekday().num_days_from_monday() as usize][week] += conversations;
+    }
+
+    let mut out = format!("Week of {} to {}\n", start.format("%b %-d, %Y"), last.format("%b %-d, %Y"));
+    out.push_str(&render_grid(&grid));
+    out
+}
+
+/// Weekdays against hours of the day, over all time
+fn render_hours(counts: &[ActivityCount]) -> String {
+    let mut grid = [[0usize; 24]; 7];
+    for count in counts {
+        if let Ok(time) = NaiveDateTime::parse_from_str(&count.bucket, "%Y-%m-%dT%H:%M") {
+            grid[time.weekday().num_days_from_monday() as usize][time.hour() as usize] += count.conversations;
+        }
+    }
+
+    let mut out = format!("    {:<6}{:<6}{:<6}{}\n", 0, 6, 12, 18);
+    out.push_str(&render_grid(&grid));
+    out
+}
+
+fn render_grid<const N: usize>(grid: &[[usize; N]; 7]) -> String {
+    let max = grid.iter().flatten().copied().max().unwrap_or(0);
+    let mut out = String::new();
+    for (weekday, row) in WEEKDAYS.iter().zip(grid) {
+        let cells: String = row.iter().map(|&count| shade(count, max)).collect();
+        out.push_str(&format!("{} {}\n", weekday, cells));
+    }
+    out
+}
+
+/// Shade for `count`, with `max` the darkest
+fn shade(count: usize, max: usize) -> char {
+    if count == 0 || max == 0 {
+        return SHADES[0];
+    }
+    let levels = SHADES.len() - 1;
+    SHADES[(count * levels).div_ceil(max).clamp(1, levels)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(bucket: &str, conversations: usize) -> ActivityCount {
+        ActivityCount {
+            project_path: Some("/work/app".to_string()),
+            bucket: bucket.to_string(),
+            conversations,
+        }
+    }
+
+    #[test]
+    fn test_shade() {
+        assert_eq!(shade(0, 8), '·');
+        assert_eq!(shade(1, 8), '░');
+        assert_eq!(shade(5, 8), '▓');
+        assert_eq!(shade(8, 8), '█');
+    }
+
+    #[test]
+    fn test_render_heatmap() {
+        assert_eq!(render_heatmap(&[], ActivityBucket::Day), "No activity yet\n");
+
+        // Wednesday June 3rd and Monday June 1st, 2026
+        let counts = [count("2026-06-01", 1), count("2026-06-03", 4)];
+        let days = render_heatmap(&counts, ActivityBucket::Day);
+        let lines: Vec<&str> = days.lines().collect();
+        assert_eq!(lines[0], "Week of Dec 8, 2025 to Jun 3, 2026");
+        assert!(lines[1].starts_with("Mon ·") && lines[1].ends_with('░'));
+        assert!(lines[3].ends_with('█'));
+        assert!(days.contains("5 conversation(s)"));
+
+        let counts = [count("2026-06-01T09:00", 2), count("2026-06-08T09:00", 2)];
+        let hours = render_heatmap(&counts, ActivityBucket::Hour);
+        let monday = hours.lines().nth(1).unwrap();
+        assert_eq!(monday.chars().nth(4 + 9), Some('█'));
+    }
+}
@@ -0,0 +1,3488 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::api::{
+    AnnotationUpdate, Append, ApiError, CreateWorkspaceRequest, DuplexApiClient, Encryption, ExtractRequest,
+    ExtractionResponse, Part, RelatedSession, UploadUrlRequest,
+};
+use crate::cache::{conversation_key, ContentCache};
+use crate::config::{
+    self, BackendChange, BackfillConfig, Config, OverlapStrategy, OversizeStrategy, PayloadLimitConfig, PolicyConfig,
+    PowerConfig, RetryConfig, StreamingConfig, TerminalRecordingsConfig,
+};
+use crate::db::{
+    self, BackendSwitch, Database, FileConversation, Oversize, RemoteResult, SyncState, SyncStatus, SyncVersion,
+    SyncedPrefix,
+};
+use crate::encryption::{self, EncryptionError, EncryptionKey};
+use crate::errors::ErrorCategory;
+use crate::git::{self, GitContext};
+use crate::hooks;
+use crate::machine;
+use crate::metrics;
+use crate::parsers::{self, conversation_window, Conversation, ConversationParser, Message, ParserError, ParserRegistry};
+use crate::policy::{self, Policy, ProjectFilter};
+use crate::power;
+use crate::projects::{self, ProjectNames, ProjectResolver};
+use crate::recordings::{self, Recording};
+use crate::replay::EventRecorder;
+use crate::schedule::{Schedule, ScheduleError};
+use crate::shutdown::SharedShutdown;
+use crate::timestamps;
+use crate::usage;
+use crate::watcher::FileChangeEvent;
+
+/// Threshold for inline uploads vs R2 uploads (512KB)
+const INLINE_THRESHOLD: usize = 512 * 1024;
+
+/// Gap allowed between conversations from different tools on the same task
+const RELATED_SESSION_SLACK_SECS: i64 = 15 * 60;
+
+/// Minimum time before identical content from the same file is uploaded again
+const MIN_REUPLOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often held sync state writes are retried while storage is failing
+const STORAGE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most sync state writes held in memory; the oldest are dropped beyond this
+const MAX_DEFERRED_WRITES: usize = 10_000;
+
+/// How often failed uploads are checked for a due retry
+const RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the API is probed while it can't be reached
+const OFFLINE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often the keyring is checked for a missing encryption key
+const KEY_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often the org baseline is fetched again while uploads wait for it
+const BASELINE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often uploads left in `syncing` are checked for
+const STUCK_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wait before retrying remote deletes after one fails
+const DELETE_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Results fetched per pull, spreading a large backlog over several
+const RESULTS_PER_PULL: usize = 50;
+
+/// Wait before fetching an unfinished extraction's results again
+const UNFINISHED_RESULT_REFRESH: Duration = Duration::from_secs(5 * 60);
+
+/// Wait before fetching a finished extraction's results again, for
+/// changes made on the server since
+const FINISHED_RESULT_REFRESH: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Queue changes held for a slow subscriber before it must re-snapshot
+const QUEUE_EVENT_CAPACITY: usize = 1024;
+
+/// Commands a [`SyncHandle`] can have waiting before senders wait too
+const COMMAND_CAPACITY: usize = 256;
+
+/// How often the engine task works the queue between commands
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Outcome of the latest pass that touched any file, for the tray
+static LAST_REPORT: Mutex<Option<SyncReport>> = Mutex::new(None);
+
+/// Outcome of the latest [`SyncEngine::process_all`] pass that touched any file
+pub fn last_report() -> Option<SyncReport> {
+    LAST_REPORT.lock().unwrap().clone()
+}
+
+/// Files queued while the API can't be reached, for the tray
+static OFFLINE_QUEUED: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Files waiting for the network, while the API can't be reached
+pub fn offline_queued() -> Option<usize> {
+    *OFFLINE_QUEUED.lock().unwrap()
+}
+
+/// Progress of the history backfill under way, for the tray and CLI
+static BACKFILL_PROGRESS: Mutex<Option<BackfillProgress>> = Mutex::new(None);
+
+/// Progress of the history backfill, while one is under way
+pub fn backfill_progress() -> Option<BackfillProgress> {
+    *BACKFILL_PROGRESS.lock().unwrap()
+}
+
+/// App state key set once the first-run history backfill has been queued
+const HISTORY_BACKFILLED_KEY: &str = "history_backfilled";
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("Database error: {0}")]
+    Database(#[from] crate::db::DatabaseError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Parser error: {0}")]
+    Parser(#[from] ParserError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No parser found for: {0}")]
+    NoParser(String),
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+    #[error("Authentication error: {0}")]
+    Auth(#[from] crate::auth::AuthError),
+    #[error("Policy error: {0}")]
+    Policy(#[from] crate::policy::PolicyError),
+    #[error("Schedule error: {0}")]
+    Schedule(#[from] ScheduleError),
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+    #[error("Conversation is {size} bytes, over the payload limit of {limit}")]
+    TooLarge { size: usize, limit: usize },
+    #[error("Sync engine has stopped")]
+    Stopped,
+}
+
+impl SyncError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            SyncError::Database(e) => e.category(),
+            SyncError::Sqlite(_) | SyncError::Io(_) | SyncError::Stopped => ErrorCategory::Io,
+            SyncError::Parser(e) => e.category(),
+            SyncError::NoParser(_) => ErrorCategory::Config,
+            SyncError::Api(e) => e.category(),
+            SyncError::Auth(e) => e.category(),
+            SyncError::Policy(_) => ErrorCategory::Config,
+            SyncError::Schedule(e) => e.category(),
+            SyncError::Encryption(e) => e.category(),
+            SyncError::TooLarge { .. } => ErrorCategory::Parse,
+        }
+    }
+
+    /// What a user can do about this error, for the CLI and notifications
+    pub fn user_hint(&self) -> &'static str {
+        match self {
+            SyncError::Api(e) => e.user_hint(),
+            SyncError::Auth(e) => e.user_hint(),
+            SyncError::NoParser(_) => "Enable a parser for this file in parsers.enabled, or remove it from discovery.additionalPaths",
+            SyncError::Policy(_) => "Fix the policy section of config.jsonc; `duplex policy` shows the policy in effect",
+            SyncError::Schedule(_) => "Fix sync.schedule in config.jsonc",
+            SyncError::TooLarge { .. } => {
+                "Raise sync.payloadLimit.maxMb, or set sync.payloadLimit.strategy to truncateOldest or split"
+            }
+            SyncError::Encryption(EncryptionError::NoKey) => {
+                "Run `duplex encryption init`, or `duplex encryption import` with the key from another machine"
+            }
+            SyncError::Encryption(EncryptionError::InvalidKey) => {
+                "Import the key again with `duplex encryption import`, or fix DUPLEX_ENCRYPTION_KEY"
+            }
+            _ => self.category().user_hint(),
+        }
+    }
+
+    /// Whether the API couldn't be reached, so the upload should wait for
+    /// the network rather than count as failed
+    pub fn is_offline(&self) -> bool {
+        matches!(self, SyncError::Api(e) if e.is_offline())
+    }
+
+    /// Whether the same upload may succeed if tried again later; not for
+    /// payloads the server rejected or files their parser can't read
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SyncError::Api(e) if e.is_rejection() => false,
+            _ => !matches!(self.category(), ErrorCategory::Parse | ErrorCategory::Config),
+        }
+    }
+}
+
+/// Item in the sync queue
+#[derive(Debug, Clone)]
+pub struct SyncItem {
+    pub path: PathBuf,
+    pub parser_name: String,
+    pub content_hash: String,
+    /// When the file was last queued; on battery it waits out the debounce
+    /// from here
+    pub queued_at: Instant,
+}
+
+/// Outcome of one [`SyncEngine::process_all`] pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Conversations uploaded
+    pub succeeded: usize,
+    /// Files that failed to sync, with the kind of failure
+    pub failed: Vec<(PathBuf, ErrorCategory)>,
+    /// Files passed over: excluded, locked, unchanged or from a disabled parser
+    pub skipped: usize,
+}
+
+impl SyncReport {
+    /// Whether nothing failed
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Whether the pass touched any file
+    pub fn is_empty(&self) -> bool {
+        self.succeeded == 0 && self.failed.is_empty() && self.skipped == 0
+    }
+
+    /// One line for logs, the tray and notifications
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} synced", self.succeeded);
+        if !self.failed.is_empty() {
+            summary.push_str(&format!(", {} failed", self.failed.len()));
+        }
+        if self.skipped > 0 {
+            summary.push_str(&format!(", {} skipped", self.skipped));
+        }
+        summary
+    }
+}
+
+/// Which part of the queue a file waits in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueLane {
+    /// Live changes, uploaded as soon as possible
+    Live,
+    /// Restored and re-sync files, uploaded in paced batches
+    Backlog,
+}
+
+/// A file waiting to sync, as shown in the activity window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEntry {
+    pub path: PathBuf,
+    pub parser_name: String,
+    pub lane: QueueLane,
+}
+
+impl QueueEntry {
+    fn new(item: &SyncItem, lane: QueueLane) -> Self {
+        Self {
+            path: item.path.clone(),
+            parser_name: item.parser_name.clone(),
+            lane,
+        }
+    }
+}
+
+/// How a file's turn in the queue ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueOutcome {
+    Synced,
+    Skipped,
+    Failed,
+    /// The API couldn't be reached; queued again for when it can
+    Offline,
+}
+
+/// One change to the queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum QueueChange {
+    /// Files added to the end of their lane
+    Added { entries: Vec<QueueEntry> },
+    /// Files moved from the backlog to the end of the live queue
+    Promoted { paths: Vec<PathBuf> },
+    /// Files dropped from the queue without an upload
+    Removed { paths: Vec<PathBuf> },
+    /// A file left the queue and its upload began
+    Started { path: PathBuf },
+    /// A file's upload ended
+    Finished { path: PathBuf, outcome: QueueOutcome },
+    /// `done` of the `total` files queued by a history backfill have left
+    /// the queue
+    Backfill { done: usize, total: usize },
+}
+
+impl QueueChange {
+    /// The same change with every path passed through `map`
+    pub fn map_paths(self, mut map: impl FnMut(&Path) -> PathBuf) -> Self {
+        match self {
+            QueueChange::Added { entries } => QueueChange::Added {
+                entries: entries
+                    .into_iter()
+                    .map(|entry| QueueEntry {
+                        path: map(&entry.path),
+                        ..entry
+                    })
+                    .collect(),
+            },
+            QueueChange::Promoted { paths } => QueueChange::Promoted {
+                paths: paths.iter().map(|path| map(path)).collect(),
+            },
+            QueueChange::Removed { paths } => QueueChange::Removed {
+                paths: paths.iter().map(|path| map(path)).collect(),
+            },
+            QueueChange::Started { path } => QueueChange::Started { path: map(&path) },
+            QueueChange::Finished { path, outcome } => QueueChange::Finished {
+                path: map(&path),
+                outcome,
+            },
+            QueueChange::Backfill { done, total } => QueueChange::Backfill { done, total },
+        }
+    }
+}
+
+/// How far a history backfill has got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackfillProgress {
+    /// Files uploaded, skipped or failed
+    pub done: usize,
+    /// Files the backfill queued
+    pub total: usize,
+}
+
+/// Files queued by [`SyncEngine::backfill_history`] not yet through the queue
+#[derive(Debug)]
+struct HistoryBackfill {
+    total: usize,
+    remaining: HashSet<PathBuf>,
+    /// Queue change the remaining files were last checked at
+    checked_seq: u64,
+    /// Progress as last published
+    published: Option<BackfillProgress>,
+}
+
+/// A queue change numbered in the order it happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEvent {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub change: QueueChange,
+}
+
+/// The queue as it stood after change `seq`
+///
+/// Subscribers apply events with a higher `seq` on top of it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueSnapshot {
+    pub seq: u64,
+    /// Live queue first, then the backlog, each in upload order
+    pub entries: Vec<QueueEntry>,
+    /// Files being uploaded right now
+    pub in_flight: Vec<PathBuf>,
+}
+
+/// Last successful upload of a file
+#[derive(Debug, Clone)]
+struct RecentUpload {
+    at: Instant,
+    /// Hash of the content as uploaded
+    content_hash: String,
+    workflow_id: String,
+}
+
+/// The session being streamed
+#[derive(Debug, Clone)]
+struct LiveSession {
+    path: PathBuf,
+    parser_name: String,
+    /// File length when last checked
+    len: u64,
+    /// When the file last grew
+    changed_at: Instant,
+    /// When the file was last checked
+    checked_at: Instant,
+}
+
+/// A write to the sync state database
+type StateWrite = Box<dyn Fn(&Database) -> rusqlite::Result<()> + Send>;
+
+/// Sync state write held in memory until storage recovers
+struct DeferredWrite {
+    file_path: String,
+    apply: StateWrite,
+}
+
+/// Local metadata sent alongside conversation content
+#[derive(Debug, Default)]
+struct UploadContext {
+    /// Workspace from the policy's mapping or auto-provisioning
+    workspace_id: String,
+    /// Alias of the conversation's project
+    project_name: Option<String>,
+    /// Repository the conversation's project belongs to
+    git: Option<GitContext>,
+    /// Tags added locally with `duplex tag` or the tray
+    tags: Vec<String>,
+    /// Terminal recordings made during the conversation
+    terminal_recordings: Vec<Recording>,
+    /// Conversations from other tools on the same task
+    related_sessions: Vec<RelatedSession>,
+    /// Structured messages for payload v2, when the parser provides them
+    messages: Option<Vec<Message>>,
+    /// The earlier upload this one extends, when sending appended lines only
+    append: Option<SyncedPrefix>,
+    /// The conversation is the streaming session
+    live: bool,
+    /// Another machine's newer version of the session, kept on the server
+    /// beside this one
+    conflicts_with: Option<String>,
+    /// Where this upload falls among the parts of a split conversation
+    part: Option<PartOf>,
+}
+
+/// Position of an upload among the parts of a conversation split to fit
+/// `sync.payloadLimit`
+#[derive(Debug, Clone)]
+struct PartOf {
+    /// From 1
+    index: usize,
+    count: usize,
+    /// Workflow of the first part, for the parts after it
+    first_workflow_id: Option<String>,
+}
+
+/// Engine that manages syncing conversations to the API
+pub struct SyncEngine {
+    /// Client for the Duplex API
+    api: DuplexApiClient,
+    /// Queue of items to sync
+    queue: VecDeque<SyncItem>,
+    /// Files restored at startup and re-sync items, uploaded in paced
+    /// batches once the queue is empty
+    backlog: VecDeque<SyncItem>,
+    /// Database for sync state
+    db: Database,
+    /// Parser registry
+    registry: Arc<ParserRegistry>,
+    /// Commands run after each successful upload
+    on_sync_complete: Vec<String>,
+    /// Local policy section, kept to re-merge when the org baseline or overlay changes
+    local_policy: PolicyConfig,
+    /// Effective (local + org) policy
+    policy: Policy,
+    /// Projects selected with `sync.includeProjects` and `sync.excludeProjects`
+    projects: ProjectFilter,
+    /// Backfill pacing from the effective policy
+    backfill: BackfillConfig,
+    /// When the engine was created; backfill waits a random part of the
+    /// startup delay after this
+    started: Instant,
+    /// This machine's part of the startup delay, from 0 to 1
+    startup_jitter: f64,
+    /// Files in the next backfill batch
+    backfill_batch: usize,
+    /// When the last backfill batch started
+    last_backfill: Option<Instant>,
+    /// Delete conversations remotely when the source tool deletes them
+    propagate_deletes: bool,
+    /// Deleted files may still have a server copy to delete
+    deletes_pending: bool,
+    /// When failed remote deletes may be tried again
+    retry_deletes_at: Option<Instant>,
+    /// Fetch what the server produced for uploaded conversations
+    pull_results: bool,
+    /// What to do with files more than one enabled parser reads
+    overlap: OverlapStrategy,
+    /// Largest upload and what to do with larger ones
+    payload_limit: PayloadLimitConfig,
+    /// Time between scans for changes the watcher missed, `None` when off
+    full_scan_interval: Option<Duration>,
+    /// When the last full scan ran
+    last_full_scan: Option<Instant>,
+    /// The history backfill under way, if any
+    history_backfill: Option<HistoryBackfill>,
+    /// Discovery and parser settings, to find conversation directories
+    /// for full scans
+    history: Config,
+    /// Where to look for terminal recordings to link
+    terminal_recordings: TerminalRecordingsConfig,
+    /// Create workspaces for projects without a mapping
+    auto_provision_workspaces: bool,
+    /// Project aliases set in the config
+    project_names: ProjectNames,
+    /// Narrows projects in monorepos to the component a conversation concerns
+    project_resolver: ProjectResolver,
+    /// When queued uploads may run
+    schedule: Schedule,
+    /// Stops new uploads on quit and during system sleep
+    shutdown: Option<SharedShutdown>,
+    /// Files being uploaded right now
+    in_flight: HashSet<PathBuf>,
+    /// Changes to the queue, for [`Self::subscribe_queue`]
+    queue_events: broadcast::Sender<QueueEvent>,
+    /// Number of the last queue change
+    queue_seq: u64,
+    /// Where file and queue changes are recorded for replay, if anywhere
+    recorder: Option<EventRecorder>,
+    /// Files uploaded within [`MIN_REUPLOAD_INTERVAL`]
+    recent_uploads: HashMap<PathBuf, RecentUpload>,
+    /// State writes that hit a storage failure, oldest first
+    deferred_writes: VecDeque<DeferredWrite>,
+    /// When held writes were last tried
+    last_storage_retry: Option<Instant>,
+    /// Local copies of parsed conversations, when enabled
+    content_cache: Option<ContentCache>,
+    /// How uploads back off on battery
+    power: PowerConfig,
+    /// How failed uploads are retried
+    retry: RetryConfig,
+    /// When failed uploads were last checked for a due retry
+    last_retry_check: Option<Instant>,
+    /// When uploads were last checked for ones stuck in `syncing`
+    last_stuck_check: Option<Instant>,
+    /// Since when the API has been unreachable
+    offline_since: Option<Instant>,
+    /// When the API was last probed while unreachable
+    last_connectivity_check: Option<Instant>,
+    /// Live streaming of the session being worked in
+    streaming: StreamingConfig,
+    /// The most recently changed append-only session, while streaming
+    live_session: Option<LiveSession>,
+    /// API base URL uploads go to
+    backend: String,
+    /// Backend the sync state belongs to, while uploads wait for the user
+    /// to choose what to do with it; see [`BackendChange::Ask`]
+    held_for_backend: Option<String>,
+    /// Whether uploads are sealed before they leave; see [`crate::encryption`]
+    encrypt: bool,
+    /// Key uploads are sealed with, once found
+    encryption_key: Option<EncryptionKey>,
+    /// When the keyring was last checked for a missing key
+    last_key_check: Option<Instant>,
+    /// Org baseline the local policy is merged on top of
+    baseline: Option<PolicyConfig>,
+    /// Verified org overlay merged on top of the local policy
+    overlay: Option<PolicyConfig>,
+    /// Set at sign-in: uploads wait until the org baseline is fetched
+    baseline_pending: bool,
+    /// When fetching the baseline was last tried while uploads wait for it
+    last_baseline_check: Option<Instant>,
+}
+
+impl SyncEngine {
+    /// Create a new sync engine
+    pub fn new(
+        api_url: String,
+        access_token: Option<String>,
+        registry: Arc<ParserRegistry>,
+        config: &Config,
+    ) -> Result<Self, SyncError> {
+        Self::with_database(api_url, access_token, registry, config, Database::open()?)
+    }
+
+    /// Create a sync engine that tracks state in the given database
+    pub fn with_database(
+        api_url: String,
+        access_token: Option<String>,
+        registry: Arc<ParserRegistry>,
+        config: &Config,
+        db: Database,
+    ) -> Result<Self, SyncError> {
+        let (baseline, overlay) = policy::load_cached();
+        let merged = policy::merge(&policy::apply_baseline(baseline.as_ref(), &config.policy), overlay.as_ref());
+        let backend = api_url.trim_end_matches('/').to_string();
+        let held_for_backend = check_backend(&db, &backend, config.sync.on_backend_change)?;
+        Ok(Self {
+            api: DuplexApiClient::new(api_url, access_token, config)?,
+            queue: VecDeque::new(),
+            backlog: VecDeque::new(),
+            db,
+            registry,
+            on_sync_complete: config.hooks.on_sync_complete.clone(),
+            local_policy: config.policy.clone(),
+            policy: Policy::compile(&merged)?,
+            projects: ProjectFilter::from_config(&config.sync)?,
+            backfill_batch: merged.backfill.initial_batch(),
+            backfill: merged.backfill,
+            started: Instant::now(),
+            startup_jitter: rand::random(),
+            last_backfill: None,
+            propagate_deletes: config.sync.propagate_deletes,
+            deletes_pending: true,
+            retry_deletes_at: None,
+            pull_results: config.sync.pull_results,
+            overlap: config.parsers.overlap,
+            payload_limit: config.sync.payload_limit.clone(),
+            full_scan_interval: config.sync.full_scan_interval(),
+            last_full_scan: None,
+            history_backfill: None,
+            history: Config {
+                discovery: config.discovery.clone(),
+                parsers: config.parsers.clone(),
+                ..Default::default()
+            },
+            terminal_recordings: config.terminal_recordings.clone(),
+            auto_provision_workspaces: config.workspaces.auto_provision,
+            project_names: ProjectNames::from_config(&config.projects),
+            project_resolver: ProjectResolver::from_config(&config.projects.resolution)?,
+            schedule: Schedule::from_config(&config.sync.schedule)?,
+            shutdown: None,
+            in_flight: HashSet::new(),
+            queue_events: broadcast::channel(QUEUE_EVENT_CAPACITY).0,
+            queue_seq: 0,
+            recorder: None,
+            recent_uploads: HashMap::new(),
+            deferred_writes: VecDeque::new(),
+            last_storage_retry: None,
+            content_cache: ContentCache::from_config(&config.cache),
+            power: config.power.clone(),
+            retry: config.sync.retry.clone(),
+            last_retry_check: None,
+            last_stuck_check: None,
+            offline_since: None,
+            last_connectivity_check: None,
+            streaming: config.sync.streaming.clone(),
+            live_session: None,
+            backend,
+            held_for_backend,
+            encrypt: config.encryption.enabled,
+            encryption_key: config.encryption.enabled.then(load_encryption_key).flatten(),
+            last_key_check: None,
+            baseline,
+            overlay,
+            baseline_pending: false,
+            last_baseline_check: None,
+        })
+    }
+
+    /// Stop starting uploads once `shutdown` begins or the system sleeps
+    pub fn set_shutdown(&mut self, shutdown: SharedShutdown) {
+        self.shutdown = Some(shutdown);
+    }
+
+    /// Re-queue files left pending or stuck mid-upload by a previous run
+    ///
+    /// Queued items are persisted as `pending` rows as they are queued, so
+    /// this restores the queue as it stood when the app last exited. They
+    /// go to the backlog, paced like re-sync history, so machines starting
+    /// together don't all upload at once.
+    pub fn restore_queue(&mut self) -> Result<usize, SyncError> {
+        self.reset_stuck_uploads()?;
+
+        let items = self.unqueued_pending()?;
+        let restored = items.len();
+        self.push_items(items, QueueLane::Backlog);
+
+        if restored > 0 {
+            tracing::info!("Restored {} queued file(s)", restored);
+        }
+        Ok(restored)
+    }
+
+    /// Queue every pending file, and every file under `dirs` that changed
+    /// since it was last synced, for upload on the next pass
+    ///
+    /// For one-off runs like `duplex sync`, which skip the backfill pacing.
+    /// Returns the number of files queued.
+    pub fn queue_all(&mut self, dirs: &[(PathBuf, String)]) -> Result<usize, SyncError> {
+        self.db.reset_interrupted()?;
+
+        let registry = self.registry.clone();
+        for (dir, parser_name) in dirs {
+            let Some(parser) = registry.get(parser_name) else {
+                continue;
+            };
+            for file in parser.discover(dir) {
+                // One unreadable file shouldn't stop the scan
+                if let Err(e) = self.queue_file(&file.path, parser_name.clone(), false) {
+                    tracing::warn!("Failed to queue {:?}: {}", file.path, e);
+                }
+            }
+        }
+
+        let items = self.unqueued_pending()?;
+        self.push_items(items, QueueLane::Live);
+        self.promote(self.backlog.len());
+        Ok(self.queue.len())
+    }
+
+    /// Queue conversations marked for re-sync behind live changes
+    ///
+    /// They go out in batches whenever the queue is empty, so re-pushing a
+    /// large history never delays new conversations.
+    pub fn queue_resync(&mut self) -> Result<usize, SyncError> {
+        let items = self.unqueued_pending()?;
+        let queued = items.len();
+        self.push_items(items, QueueLane::Backlog);
+
+        if queued > 0 {
+            tracing::info!("Queued {} file(s) for re-sync", queued);
+        }
+        Ok(queued)
+    }
+
+    /// Run a full scan if one is due, see [`Self::full_scan`]
+    pub fn full_scan_if_due(&mut self) -> Result<usize, SyncError> {
+        let Some(interval) = self.full_scan_interval else {
+            return Ok(0);
+        };
+        if self.last_full_scan.is_some_and(|last| last.elapsed() < interval) {
+            return Ok(0);
+        }
+        self.full_scan()
+    }
+
+    /// Queue every discovered conversation file that is new or changed
+    /// since it was last queued, returning how many
+    ///
+    /// Catches what the watcher missed, such as files changed while the
+    /// app wasn't running. Files unmodified since they were last queued
+    /// aren't read again. What is found goes to the backlog, paced like
+    /// re-sync history.
+    pub fn full_scan(&mut self) -> Result<usize, SyncError> {
+        self.last_full_scan = Some(Instant::now());
+
+        let mut queued = 0;
+        for (path, parser_name) in self.history_files() {
+            if self.is_waiting(&path) || !self.changed_since_queued(&path)? {
+                continue;
+            }
+            if self.queue_history_file(&path, parser_name)? {
+                queued += 1;
+            }
+        }
+
+        if queued > 0 {
+            tracing::info!("Full scan found {} new or changed file(s)", queued);
+        }
+        Ok(queued)
+    }
+
+    /// Queue the history backfill on the app's first run, see
+    /// [`Self::backfill_history`]
+    ///
+    /// Until then nothing that existed before install syncs until it changes.
+    pub fn first_run_backfill(&mut self) -> Result<usize, SyncError> {
+        if self.db.get_app_state(HISTORY_BACKFILLED_KEY)?.is_some() {
+            return Ok(0);
+        }
+        let queued = self.backfill_history()?;
+        self.db.set_app_state(HISTORY_BACKFILLED_KEY, &chrono::Utc::now().to_rfc3339())?;
+        Ok(queued)
+    }
+
+    /// Queue every discovered conversation not synced as it is now, oldest
+    /// first, returning how many
+    ///
+    /// They go to the backlog, paced like re-sync history. Progress is
+    /// published as [`QueueChange::Backfill`] and [`backfill_progress`] as
+    /// the files leave the queue.
+    pub fn backfill_history(&mut self) -> Result<usize, SyncError> {
+        let mut files: Vec<(PathBuf, String, SystemTime)> = self
+            .history_files()
+            .into_iter()
+            .map(|(path, parser_name)| {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (path, parser_name, modified)
+            })
+            .collect();
+        files.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut queued = Vec::new();
+        for (path, parser_name, _) in files {
+            if self.is_waiting(&path) {
+                continue;
+            }
+            if self.queue_history_file(&path, parser_name)? {
+                queued.push(path);
+            }
+        }
+
+        let mut backfill = self.history_backfill.take().unwrap_or(HistoryBackfill {
+            total: 0,
+            remaining: HashSet::new(),
+            checked_seq: 0,
+            published: None,
+        });
+        for path in &queued {
+            if backfill.remaining.insert(path.clone()) {
+                backfill.total += 1;
+            }
+        }
+        if backfill.remaining.is_empty() {
+            tracing::info!("History backfill found nothing to sync");
+            return Ok(0);
+        }
+        tracing::info!("History backfill queued {} file(s)", queued.len());
+        self.history_backfill = Some(backfill);
+        self.track_backfill();
+        Ok(queued.len())
+    }
+
+    /// Count backfill files that have left the queue, publishing progress
+    /// when it moves
+    fn track_backfill(&mut self) {
+        let Some(mut backfill) = self.history_backfill.take() else {
+            return;
+        };
+        if backfill.checked_seq == self.queue_seq {
+            self.history_backfill = Some(backfill);
+            return;
+        }
+        let waiting: HashSet<&Path> = self
+            .queue
+            .iter()
+            .chain(&self.backlog)
+            .map(|item| item.path.as_path())
+            .chain(self.in_flight.iter().map(PathBuf::as_path))
+            .collect();
+        backfill.remaining.retain(|path| waiting.contains(path.as_path()));
+        let progress = BackfillProgress {
+            done: backfill.total - backfill.remaining.len(),
+            total: backfill.total,
+        };
+
+        if backfill.published != Some(progress) {
+            backfill.published = Some(progress);
+            self.emit(QueueChange::Backfill {
+                done: progress.done,
+                total: progress.total,
+            });
+        }
+        backfill.checked_seq = self.queue_seq;
+        if backfill.remaining.is_empty() {
+            tracing::info!("History backfill finished: {} file(s)", progress.total);
+            *BACKFILL_PROGRESS.lock().unwrap() = None;
+        } else {
+            *BACKFILL_PROGRESS.lock().unwrap() = Some(progress);
+            self.history_backfill = Some(backfill);
+        }
+    }
+
+    /// Every conversation file enabled parsers discover in their history
+    /// directories
+    fn history_files(&self) -> Vec<(PathBuf, String)> {
+        let registry = self.registry.clone();
+        usage::history_dirs(&registry, &self.history)
+            .into_iter()
+            .filter(|(_, parser)| registry.is_enabled(parser.name()))
+            .flat_map(|(dir, parser)| {
+                let parser_name = parser.name().to_string();
+                parser.discover(&dir).into_iter().map(move |file| (file.path, parser_name.clone()))
+            })
+            .collect()
+    }
+
+    /// Whether a file is queued or being uploaded
+    fn is_waiting(&self, path: &Path) -> bool {
+        self.in_flight.contains(path) || self.queue.iter().chain(&self.backlog).any(|item| item.path == path)
+    }
+
+    /// Queue a file from a history directory in the backlog, if its project
+    /// is selected, returning whether it was queued
+    fn queue_history_file(&mut self, path: &Path, parser_name: String) -> Result<bool, SyncError> {
+        if !self.project_selected(path, &parser_name)? {
+            return Ok(false);
+        }
+        let before = self.backlog.len();
+        // One unreadable file shouldn't stop the scan
+        if let Err(e) = self.queue_file_in(path, parser_name, false, QueueLane::Backlog) {
+            tracing::warn!("Failed to queue {:?}: {}", path, e);
+        }
+        Ok(self.backlog.len() > before)
+    }
+
+    /// Whether a file may have changed since it was last queued, judged by
+    /// its modification time
+    fn changed_since_queued(&self, path: &Path) -> Result<bool, SyncError> {
+        let Some(state) = self.db.get_sync_state(&path.to_string_lossy())? else {
+            return Ok(true);
+        };
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|since| since.as_secs() as i64);
+        // Changed within the second it was queued in is unclear, so compare hashes
+        Ok(state.status == SyncStatus::Deleted || modified.is_none_or(|modified| modified >= state.last_modified_at))
+    }
+
+    /// Pending files with a parser to read them that aren't queued yet
+    fn unqueued_pending(&self) -> Result<Vec<SyncItem>, SyncError> {
+        let mut items: Vec<SyncItem> = Vec::new();
+        for state in self.db.get_pending()? {
+            let path = PathBuf::from(&state.file_path);
+            // Ingested content has no file to re-read
+            let Some(parser_name) = state.source.filter(|name| self.registry.is_enabled(name)) else {
+                continue;
+            };
+            let queued = self.queue.iter().chain(&self.backlog).chain(&items).any(|queued| queued.path == path);
+            if !path.is_file() || queued {
+                continue;
+            }
+
+            items.push(SyncItem {
+                path,
+                parser_name,
+                content_hash: state.content_hash,
+                queued_at: Instant::now(),
+            });
+        }
+        Ok(items)
+    }
+
+    /// Drop a disabled parser's files from the queue and mark them skipped
+    pub fn skip_parser(&mut self, parser_name: &str) -> Result<usize, SyncError> {
+        self.remove_queued(|item| item.parser_name == parser_name);
+        let skipped = self.db.skip_source(parser_name)?;
+        if skipped > 0 {
+            tracing::info!("Skipped {} queued file(s) for disabled parser {}", skipped, parser_name);
+        }
+        Ok(skipped)
+    }
+
+    /// Re-queue a re-enabled parser's skipped files
+    pub fn unskip_parser(&mut self, parser_name: &str) -> Result<usize, SyncError> {
+        self.db.unskip_source(parser_name)?;
+        let items = self.unqueued_pending()?;
+        let requeued = items.len();
+        self.push_items(items, QueueLane::Live);
+        Ok(requeued)
+    }
+
+    /// Whether new uploads should wait for shutdown, system sleep, a locked
+    /// keychain, the schedule, a choice about a changed backend, a missing
+    /// encryption key or the org baseline after sign-in
+    pub fn is_paused(&self) -> bool {
+        self.held_for_backend.is_some()
+            || self.waiting_for_encryption_key()
+            || self.baseline_pending
+            || self.shutdown.as_ref().is_some_and(|s| s.is_paused())
+            || config::keychain_locked()
+            || !self.schedule.is_open()
+    }
+
+    /// Whether uploads are waiting for the API to become reachable again
+    pub fn is_offline(&self) -> bool {
+        self.offline_since.is_some()
+    }
+
+    /// While offline, probe the API and resume uploads once it answers;
+    /// returns whether it is reachable
+    ///
+    /// Probes at most once per [`OFFLINE_CHECK_INTERVAL`] unless `now`.
+    pub async fn check_connectivity(&mut self, now: bool) -> bool {
+        let Some(since) = self.offline_since else {
+            return true;
+        };
+        if !now && self.last_connectivity_check.is_some_and(|last| last.elapsed() < OFFLINE_CHECK_INTERVAL) {
+            return false;
+        }
+        self.last_connectivity_check = Some(Instant::now());
+        if !self.api.is_reachable().await {
+            self.set_offline(true);
+            return false;
+        }
+
+        tracing::info!(
+            "API reachable again after {}s, resuming {} queued item(s)",
+            since.elapsed().as_secs(),
+            self.queue_len()
+        );
+        self.set_offline(false);
+        true
+    }
+
+    /// Record whether the API is reachable, and what waits for it, for the tray
+    fn set_offline(&mut self, offline: bool) {
+        if offline {
+            self.offline_since.get_or_insert_with(Instant::now);
+        } else {
+            self.offline_since = None;
+        }
+        *OFFLINE_QUEUED.lock().unwrap() = offline.then(|| self.queue_len());
+    }
+
+    /// Whether uploads are to be encrypted but there is no key yet
+    pub fn waiting_for_encryption_key(&self) -> bool {
+        self.encrypt && self.encryption_key.is_none()
+    }
+
+    /// While waiting for the encryption key, look for it again and resume
+    /// uploads once it is there
+    ///
+    /// Checks at most once per [`KEY_CHECK_INTERVAL`] unless `now`.
+    pub fn refresh_encryption_key(&mut self, now: bool) {
+        if !self.waiting_for_encryption_key() {
+            return;
+        }
+        if !now && self.last_key_check.is_some_and(|last| last.elapsed() < KEY_CHECK_INTERVAL) {
+            return;
+        }
+        self.last_key_check = Some(Instant::now());
+        if let Some(key) = load_encryption_key() {
+            tracing::info!("Encryption key {} found, resuming uploads", key.fingerprint());
+            self.encryption_key = Some(key);
+        }
+    }
+
+    /// Hold uploads until the org baseline has been fetched, so its
+    /// excludes apply before anything uploads with a new sign-in
+    pub fn hold_for_baseline(&mut self) {
+        self.baseline_pending = true;
+    }
+
+    /// Whether uploads are waiting for the org baseline
+    pub fn waiting_for_baseline(&self) -> bool {
+        self.baseline_pending
+    }
+
+    /// While uploads wait for the org baseline, fetch it and resume them
+    /// once it is in
+    ///
+    /// Tries at most once per [`BASELINE_CHECK_INTERVAL`] unless `now`.
+    pub async fn refresh_baseline_if_pending(&mut self, now: bool) {
+        if !self.baseline_pending {
+            return;
+        }
+        if !now && self.last_baseline_check.is_some_and(|last| last.elapsed() < BASELINE_CHECK_INTERVAL) {
+            return;
+        }
+        self.last_baseline_check = Some(Instant::now());
+        match self.refresh_baseline().await {
+            Ok(()) => tracing::info!("Org baseline fetched, resuming uploads"),
+            Err(SyncError::Api(ApiError::NotAuthenticated)) => tracing::debug!("Org baseline waits for sign-in"),
+            Err(e) => tracing::warn!("Could not fetch org baseline, holding uploads: {}", e),
+        }
+    }
+
+    /// Backend the sync state still belongs to, while uploads to the new one
+    /// wait for `duplex backend --keep` or `--reset`
+    pub fn held_for_backend(&self) -> Option<&str> {
+        self.held_for_backend.as_deref()
+    }
+
+    /// Release the backend hold once the sync state has been switched over,
+    /// from another process such as the CLI
+    pub fn refresh_backend_hold(&mut self) {
+        if self.held_for_backend.is_none() {
+            return;
+        }
+        match self.db.get_backend() {
+            Ok(Some(recorded)) if recorded == self.backend => {
+                tracing::info!("Sync state switched to {}, resuming uploads", self.backend);
+                self.held_for_backend = None;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to read recorded backend: {}", e),
+        }
+    }
+
+    /// Handle a file change event
+    pub fn handle_file_change(&mut self, event: FileChangeEvent) -> Result<(), SyncError> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.file_changed(&event);
+        }
+        // Removals arrive as changes too
+        if is_removed(&event.path) {
+            self.mark_deleted(&event.path)?;
+            return Ok(());
+        }
+        if !self.project_selected(&event.path, &event.parser_name)? {
+            tracing::debug!("Project not selected for sync, skipping: {:?}", event.path);
+            return Ok(());
+        }
+        self.follow_live_session(&event.path, &event.parser_name);
+        self.queue_file(&event.path, event.parser_name, false)
+    }
+
+    /// Stream `path` from now on if it is an append-only session, as the
+    /// latest one changed is the one being worked in
+    fn follow_live_session(&mut self, path: &Path, parser_name: &str) {
+        if !self.streaming.enabled || self.live_session.as_ref().is_some_and(|live| live.path == path) {
+            return;
+        }
+        if !self.registry.get(parser_name).is_some_and(|parser| parser.append_only()) {
+            return;
+        }
+        tracing::info!("Streaming {:?}", path);
+        let now = Instant::now();
+        self.live_session = Some(LiveSession {
+            path: path.to_path_buf(),
+            parser_name: parser_name.to_string(),
+            len: std::fs::metadata(path).map_or(0, |metadata| metadata.len()),
+            changed_at: now,
+            checked_at: now,
+        });
+    }
+
+    /// Queue the streaming session if lines were added since it was last
+    /// checked, returning whether it was queued
+    ///
+    /// Called from the background loop; the session is checked once per
+    /// `sync.streaming.intervalSeconds` instead of waiting for the watcher's
+    /// debounce, and its new lines go up as an append over the client's
+    /// pooled connection. Streaming stops after `idleSeconds` without
+    /// change, and waits while the server doesn't accept appends. On
+    /// battery, the usual debounce still applies.
+    pub fn stream_live_session(&mut self) -> Result<bool, SyncError> {
+        let interval = Duration::from_secs(self.streaming.interval_seconds);
+        let idle = Duration::from_secs(self.streaming.idle_seconds);
+        let Some(live) = self.live_session.as_mut() else {
+            return Ok(false);
+        };
+        if live.changed_at.elapsed() >= idle {
+            tracing::info!("Stopped streaming idle session {:?}", live.path);
+            self.live_session = None;
+            return Ok(false);
+        }
+        if live.checked_at.elapsed() < interval || !self.api.accepts_append() {
+            return Ok(false);
+        }
+        live.checked_at = Instant::now();
+        let len = std::fs::metadata(&live.path).map_or(0, |metadata| metadata.len());
+        if len == live.len {
+            return Ok(false);
+        }
+        live.len = len;
+        live.changed_at = Instant::now();
+        let (path, parser_name) = (live.path.clone(), live.parser_name.clone());
+        self.queue_file(&path, parser_name, false)?;
+        Ok(self.queue.iter().any(|item| item.path == path))
+    }
+
+    /// Mark tracked files the source tool has removed as deleted, returning
+    /// how many
+    ///
+    /// Catches removals made while the app wasn't running; ones made while
+    /// it runs arrive as file changes.
+    pub fn reconcile_deletions(&mut self) -> Result<usize, SyncError> {
+        let mut deleted = 0;
+        for state in self.db.get_tracked_files()? {
+            let path = PathBuf::from(&state.file_path);
+            // Content ingested from a file outside the parsers' directories
+            // is the user's to manage
+            let from_parser = state.source.is_some_and(|source| self.registry.get(&source).is_some());
+            if from_parser && is_removed(&path) && self.mark_deleted(&path)? {
+                deleted += 1;
+            }
+        }
+
+        if deleted > 0 {
+            tracing::info!("{} conversation(s) deleted by their source tool", deleted);
+        }
+        Ok(deleted)
+    }
+
+    /// Drop a removed file from the queue and mark it deleted, returning
+    /// whether it was tracked
+    fn mark_deleted(&mut self, path: &Path) -> Result<bool, SyncError> {
+        self.remove_queued(|queued| queued.path == path);
+
+        let key = path.to_string_lossy();
+        match self.db.get_sync_state(&key)? {
+            Some(state) if !matches!(state.status, SyncStatus::Deleted | SyncStatus::Retired) => {}
+            _ => return Ok(false),
+        }
+        self.persist_status(&key, SyncStatus::Deleted)?;
+        self.deletes_pending = true;
+        tracing::info!("Deleted by its source tool: {:?}", path);
+        Ok(true)
+    }
+
+    /// Delete the server copies of deleted files when `sync.propagateDeletes`
+    /// is on, returning how many were deleted
+    ///
+    /// Does nothing until a file has been deleted; after a failure the rest
+    /// wait [`DELETE_RETRY_INTERVAL`].
+    pub async fn propagate_deletions(&mut self) -> Result<usize, SyncError> {
+        if !self.propagate_deletes || !self.deletes_pending || self.is_paused() {
+            return Ok(0);
+        }
+        if self.retry_deletes_at.is_some_and(|at| Instant::now() < at) {
+            return Ok(0);
+        }
+
+        let mut deleted = 0;
+        let mut failed = false;
+        for state in self.db.get_deleted_uploaded()? {
+            // A file holding several conversations has a workflow for each
+            let mut workflow_ids: Vec<String> = state.workflow_id.into_iter().collect();
+            for conversation in self.db.list_file_conversations(&state.file_path)? {
+                workflow_ids.extend(conversation.workflow_id.filter(|id| !workflow_ids.contains(id)));
+            }
+
+            let mut file_failed = false;
+            for workflow_id in &workflow_ids {
+                match self.api.delete_conversation(workflow_id).await {
+                    Ok(()) => {}
+                    // Already gone
+                    Err(e) if e.is_not_found() => {}
+                    Err(e) => {
+                        tracing::warn!("Could not delete {} remotely: {}", state.file_path, e);
+                        file_failed = true;
+                    }
+                }
+            }
+            if file_failed {
+                failed = true;
+                continue;
+            }
+            let file_path = state.file_path.clone();
+            self.persist(&state.file_path, move |db| {
+                db.clear_workflow_id(&file_path)?;
+                db.delete_file_conversations(&file_path)
+            })?;
+            deleted += 1;
+        }
+
+        self.deletes_pending = failed;
+        self.retry_deletes_at = failed.then(|| Instant::now() + DELETE_RETRY_INTERVAL);
+        if deleted > 0 {
+            tracing::info!("Deleted {} conversation(s) remotely", deleted);
+        }
+        Ok(deleted)
+    }
+
+    /// Fetch what the server produced for uploaded conversations when
+    /// `sync.pullResults` is on, returning how many results were stored
+    ///
+    /// Fetches up to [`RESULTS_PER_PULL`]: those never fetched first, then
+    /// unfinished extractions and, less often, finished ones. Stops at the
+    /// first failure other than a workflow the server no longer has.
+    pub async fn pull_results(&mut self) -> Result<usize, SyncError> {
+        if !self.pull_results || self.is_paused() || self.is_offline() {
+            return Ok(0);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let due = self.db.results_to_pull(
+            now - UNFINISHED_RESULT_REFRESH.as_secs() as i64,
+            now - FINISHED_RESULT_REFRESH.as_secs() as i64,
+            RESULTS_PER_PULL,
+        )?;
+
+        let mut pulled = 0;
+        for workflow_id in due {
+            let result = match self.api.workflow_status(&workflow_id).await {
+                Ok(status) => RemoteResult {
+                    finished: status.is_finished(),
+                    workflow_id,
+                    status: status.status,
+                    summary: status.summary,
+                    tags: status.tags,
+                    output: status.output,
+                    error: status.error,
+                    fetched_at: now,
+                    title: status.title,
+                    edited: false,
+                },
+                // Deleted on the server; recorded so it isn't asked for every pass
+                Err(e) if e.is_not_found() => RemoteResult {
+                    workflow_id,
+                    status: "not_found".to_string(),
+                    finished: true,
+                    summary: None,
+                    tags: Vec::new(),
+                    output: None,
+                    error: None,
+                    fetched_at: now,
+                    title: None,
+                    edited: false,
+                },
+                Err(e) => {
+                    tracing::warn!("Could not pull results for workflow {}: {}", workflow_id, e);
+                    return Err(e.into());
+                }
+            };
+            self.db.store_remote_result(&result)?;
+            pulled += 1;
+        }
+
+        if pulled > 0 {
+            tracing::debug!("Pulled results for {} conversation(s)", pulled);
+        }
+        Ok(pulled)
+    }
+
+    /// Push title and tag edits made here to the server, returning how many
+    /// went up
+    ///
+    /// Edits to workflows the server no longer has are dropped. Stops at the
+    /// first other failure; the rest are tried on the next push.
+    pub async fn push_annotations(&mut self) -> Result<usize, SyncError> {
+        if self.is_paused() || self.is_offline() {
+            return Ok(0);
+        }
+
+        let mut pushed = 0;
+        for edit in self.db.pending_annotation_edits()? {
+            let update = AnnotationUpdate {
+                title: edit.title.as_deref(),
+                tags: edit.tags.as_deref(),
+            };
+            match self.api.update_annotations(&edit.workflow_id, &update).await {
+                Ok(()) => pushed += 1,
+                Err(e) if e.is_not_found() => {
+                    tracing::info!("Dropping edit to workflow {}, which the server no longer has", edit.workflow_id);
+                }
+                Err(e) => {
+                    tracing::warn!("Could not push edit to workflow {}: {}", edit.workflow_id, e);
+                    return Err(e.into());
+                }
+            }
+            self.db.annotation_pushed(&edit)?;
+        }
+
+        if pushed > 0 {
+            tracing::info!("Pushed title and tag edits for {} conversation(s)", pushed);
+        }
+        Ok(pushed)
+    }
+
+    /// Queue a conversation file for upload even if it is unchanged
+    pub fn force_sync(&mut self, path: &Path) -> Result<(), SyncError> {
+        let parser_name = match self
+            .db
+            .get_sync_state(&path.to_string_lossy())?
+            .and_then(|s| s.source)
+        {
+            Some(name) => name,
+            None => self
+                .registry
+                .detect(path)
+                .map(|p| p.name().to_string())
+                .ok_or_else(|| SyncError::NoParser(path.to_string_lossy().to_string()))?,
+        };
+
+        self.queue_file(path, parser_name, true)
+    }
+
+    /// Add a file to the queue, skipping excluded and (unless forced) unchanged files
+    fn queue_file(&mut self, path: &Path, parser_name: String, force: bool) -> Result<(), SyncError> {
+        self.queue_file_in(path, parser_name, force, QueueLane::Live)
+    }
+
+    fn queue_file_in(
+        &mut self,
+        path: &Path,
+        parser_name: String,
+        force: bool,
+        lane: QueueLane,
+    ) -> Result<(), SyncError> {
+        if !self.registry.is_enabled(&parser_name) {
+            tracing::debug!("Parser {} disabled, skipping: {:?}", parser_name, path);
+            return Ok(());
+        }
+
+        if self.db.is_do_not_sync(&path.to_string_lossy())? {
+            tracing::debug!("File marked do-not-sync, skipping: {:?}", path);
+            return Ok(());
+        }
+
+        if self.policy.is_excluded(&path.to_string_lossy(), None) {
+            tracing::debug!("File excluded by policy, skipping: {:?}", path);
+            return Ok(());
+        }
+
+        // Read file content; a locked file is still being written and will change again
+        let content = match self.registry.get(&parser_name) {
+            Some(parser) => parser.read(path),
+            None => parsers::read_file(path, Default::default()),
+        };
+        let content = match content {
+            Err(ParserError::Busy(_)) => {
+                tracing::debug!("File locked, waiting for its next change: {:?}", path);
+                return Ok(());
+            }
+            Err(e) if e.is_unsupported() => return self.mark_unsupported(path, &parser_name, &e),
+            result => result?,
+        };
+
+        // Compute content hash
+        let content_hash = compute_hash(&content);
+
+        // Check if we need to sync (content changed since last sync, or the
+        // file is back after being deleted)
+        if let Some(existing) = self.db.get_sync_state(&path.to_string_lossy())? {
+            if existing.content_hash == content_hash && !force && existing.status != SyncStatus::Deleted {
+                tracing::debug!("File unchanged, skipping: {:?}", path);
+                return Ok(());
+            }
+        }
+
+        // Add to queue
+        let item = SyncItem {
+            path: path.to_path_buf(),
+            parser_name,
+            content_hash,
+            queued_at: Instant::now(),
+        };
+
+        // Update database with pending status
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let state = SyncState {
+            file_path: path.to_string_lossy().to_string(),
+            content_hash: item.content_hash.clone(),
+            last_synced_at: None,
+            last_modified_at: now,
+            workflow_id: None,
+            status: SyncStatus::Pending,
+            session_id: None,
+            project_path: None,
+            source: Some(item.parser_name.clone()),
+            git: None,
+            title: None,
+        };
+        self.persist(&path.to_string_lossy(), move |db| db.upsert_sync_state(&state))?;
+
+        // Replace any queued entry for the same file so it only uploads once
+        self.remove_queued(|queued| queued.path == path);
+        self.push_items(vec![item], lane);
+        tracing::info!("Queued for sync: {:?}", path);
+
+        Ok(())
+    }
+
+    /// Record a file its parser can't read as text, instead of failing it
+    ///
+    /// The file is tried again after its next change.
+    fn mark_unsupported(&mut self, path: &Path, parser_name: &str, error: &ParserError) -> Result<(), SyncError> {
+        tracing::info!("Not syncing unsupported file ({})", error);
+        self.remove_queued(|queued| queued.path == path);
+
+        let key = path.to_string_lossy().to_string();
+        if self.db.get_sync_state(&key)?.is_some() {
+            return self.persist_status(&key, SyncStatus::Unsupported);
+        }
+        let state = SyncState {
+            file_path: key.clone(),
+            content_hash: String::new(),
+            last_synced_at: None,
+            last_modified_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+            workflow_id: None,
+            status: SyncStatus::Unsupported,
+            session_id: None,
+            project_path: None,
+            source: Some(parser_name.to_string()),
+            git: None,
+            title: None,
+        };
+        self.persist(&key, move |db| db.upsert_sync_state(&state))
+    }
+
+    /// Process the next item in the queue
+    pub async fn process_next(&mut self) -> Result<Option<String>, SyncError> {
+        let item = match self.queue.pop_front() {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+
+        // Never upload the same file twice at once; go again once it finishes
+        if !self.in_flight.insert(item.path.clone()) {
+            tracing::debug!("Already uploading, re-queueing: {:?}", item.path);
+            self.emit(QueueChange::Removed {
+                paths: vec![item.path.clone()],
+            });
+            self.push_items(vec![item], QueueLane::Live);
+            return Ok(None);
+        }
+        self.emit(QueueChange::Started { path: item.path.clone() });
+        let result = self.sync_item(&item).await;
+        self.in_flight.remove(&item.path);
+        match &result {
+            // Not the file's fault; it goes again once the network is back
+            Err(e) if e.is_offline() => {
+                self.persist_status(&item.path.to_string_lossy(), SyncStatus::Pending)?;
+                self.emit(QueueChange::Finished {
+                    path: item.path.clone(),
+                    outcome: QueueOutcome::Offline,
+                });
+                self.push_items(vec![item], QueueLane::Live);
+                self.set_offline(true);
+                return result;
+            }
+            Err(e) => {
+                if let Err(retry_error) = self.schedule_retry(&item, e) {
+                    tracing::warn!("Failed to schedule a retry of {:?}: {}", item.path, retry_error);
+                }
+            }
+            Ok(_) => {}
+        }
+        let outcome = match &result {
+            Ok(Some(_)) => QueueOutcome::Synced,
+            Ok(None) => QueueOutcome::Skipped,
+            Err(_) => QueueOutcome::Failed,
+        };
+        self.emit(QueueChange::Finished {
+            path: item.path.clone(),
+            outcome,
+        });
+        result
+    }
+
+    /// The queue now, and its changes from here on
+    ///
+    /// Both are taken under the same borrow, so the first event received
+    /// follows the snapshot. A receiver that falls more than
+    /// [`QUEUE_EVENT_CAPACITY`] events behind gets `Lagged` and should
+    /// subscribe again.
+    pub fn subscribe_queue(&self) -> (QueueSnapshot, broadcast::Receiver<QueueEvent>) {
+        let entries = self
+            .queue
+            .iter()
+            .map(|item| QueueEntry::new(item, QueueLane::Live))
+            .chain(self.backlog.iter().map(|item| QueueEntry::new(item, QueueLane::Backlog)))
+            .collect();
+        let snapshot = QueueSnapshot {
+            seq: self.queue_seq,
+            entries,
+            in_flight: self.in_flight.iter().cloned().collect(),
+        };
+        (snapshot, self.queue_events.subscribe())
+    }
+
+    /// Number and publish a queue change
+    fn emit(&mut self, change: QueueChange) {
+        self.queue_seq += 1;
+        let event = QueueEvent {
+            seq: self.queue_seq,
+            change,
+        };
+        if let Some(recorder) = &mut self.recorder {
+            recorder.queue_changed(&event);
+        }
+        // No receivers is fine; nobody is watching
+        let _ = self.queue_events.send(event);
+    }
+
+    /// Record file and queue changes from now on, see [`crate::replay`]
+    pub fn set_recorder(&mut self, recorder: EventRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Append files to a lane
+    fn push_items(&mut self, items: Vec<SyncItem>, lane: QueueLane) {
+        if items.is_empty() {
+            return;
+        }
+        let entries = items.iter().map(|item| QueueEntry::new(item, lane)).collect();
+        match lane {
+            QueueLane::Live => self.queue.extend(items),
+            QueueLane::Backlog => self.backlog.extend(items),
+        }
+        self.emit(QueueChange::Added { entries });
+    }
+
+    /// Move the first `count` backlog files to the live queue
+    fn promote(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let items: Vec<SyncItem> = self.backlog.drain(..count).collect();
+        let paths = items.iter().map(|item| item.path.clone()).collect();
+        self.queue.extend(items);
+        self.emit(QueueChange::Promoted { paths });
+    }
+
+    /// Drop queued files matching `remove` from both lanes
+    fn remove_queued(&mut self, remove: impl Fn(&SyncItem) -> bool) {
+        let paths: Vec<PathBuf> = self
+            .queue
+            .iter()
+            .chain(&self.backlog)
+            .filter(|item| remove(item))
+            .map(|item| item.path.clone())
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+        self.queue.retain(|item| !remove(item));
+        self.backlog.retain(|item| !remove(item));
+        self.emit(QueueChange::Removed { paths });
+    }
+
+    /// Mark a failed file and schedule its next try, unless the failure
+    /// would repeat or its retries are used up
+    ///
+    /// Retries back off exponentially per `sync.retry`, less up to half
+    /// the delay at random so machines that failed together spread out.
+    fn schedule_retry(&mut self, item: &SyncItem, error: &SyncError) -> Result<(), SyncError> {
+        let key = item.path.to_string_lossy().to_string();
+        let failures = self.db.get_retry_count(&key)? + 1;
+        let next_retry_at = (error.is_retryable() && failures <= self.retry.max_attempts).then(|| {
+            let delay = self.retry.delay(failures).mul_f64(1.0 - rand::random::<f64>() / 2.0);
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64;
+            tracing::info!(
+                "Retry {} of {} for {:?} in {}s",
+                failures,
+                self.retry.max_attempts,
+                item.path,
+                delay.as_secs()
+            );
+            now + delay.as_secs() as i64
+        });
+        if next_retry_at.is_none() {
+            tracing::info!("Not retrying {:?}; it syncs again after its next change", item.path);
+        }
+        let file_path = key.clone();
+        self.persist(&key, move |db| db.mark_failed(&file_path, failures, next_retry_at))
+    }
+
+    /// Queue failed files whose retry is due, returning how many
+    ///
+    /// Checks at most once per [`RETRY_CHECK_INTERVAL`], so the background
+    /// loop can call it on every turn.
+    pub fn queue_due_retries(&mut self) -> Result<usize, SyncError> {
+        if self.last_retry_check.is_some_and(|last| last.elapsed() < RETRY_CHECK_INTERVAL) {
+            return Ok(0);
+        }
+        self.last_retry_check = Some(Instant::now());
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let mut queued = 0;
+        for state in self.db.get_due_retries(now)? {
+            let path = PathBuf::from(&state.file_path);
+            let waiting = self.in_flight.contains(&path) || self.queue.iter().chain(&self.backlog).any(|item| item.path == path);
+            let Some(parser_name) = state.source.filter(|_| !waiting) else {
+                continue;
+            };
+            tracing::info!("Retrying failed upload: {:?}", path);
+            if let Err(e) = self.queue_file(&path, parser_name, true) {
+                // Left for its next change rather than retried every check
+                tracing::warn!("Could not queue retry of {:?}: {}", path, e);
+                let retries = self.db.get_retry_count(&state.file_path)?;
+                self.db.mark_failed(&state.file_path, retries, None)?;
+                continue;
+            }
+            queued += 1;
+        }
+        Ok(queued)
+    }
+
+    /// Return uploads stuck in `syncing` for `retry.stuckAfterSeconds` to
+    /// pending and queue them, returning how many
+    ///
+    /// A crash mid-upload leaves its row in `syncing`, which nothing else
+    /// retries. Checks at most once per [`STUCK_CHECK_INTERVAL`]; uploads
+    /// this engine has started finish before the next check.
+    pub fn recover_stuck_uploads(&mut self) -> Result<usize, SyncError> {
+        let recovered = self.reset_stuck_uploads()?;
+        if recovered > 0 {
+            let items = self.unqueued_pending()?;
+            self.push_items(items, QueueLane::Live);
+        }
+        Ok(recovered)
+    }
+
+    fn reset_stuck_uploads(&mut self) -> Result<usize, SyncError> {
+        if self.last_stuck_check.is_some_and(|last| last.elapsed() < STUCK_CHECK_INTERVAL) {
+            return Ok(0);
+        }
+        self.last_stuck_check = Some(Instant::now());
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let cutoff = now.saturating_sub(self.retry.stuck_after_seconds as i64);
+        let recovered = self.db.recover_stuck_uploads(cutoff)?;
+        if recovered > 0 {
+            tracing::warn!("Recovered {} upload(s) stuck in syncing", recovered);
+        }
+        Ok(recovered)
+    }
+
+    /// Parse and upload one queued file
+    async fn sync_item(&mut self, item: &SyncItem) -> Result<Option<String>, SyncError> {
+        // The file may have been excluded after it was queued
+        if self.db.is_do_not_sync(&item.path.to_string_lossy())? {
+            tracing::info!("Skipping do-not-sync file: {:?}", item.path);
+            return Ok(None);
+        }
+
+        tracing::info!("Syncing: {:?}", item.path);
+
+        // Mark as syncing
+        self.persist_status(&item.path.to_string_lossy(), SyncStatus::Syncing)?;
+
+        // Get parser and parse the file
+        let parser = self
+            .registry
+            .get(&item.parser_name)
+            .ok_or_else(|| SyncError::NoParser(item.parser_name.clone()))?;
+
+        let key = item.path.to_string_lossy();
+        let mut conversations = match parser.parse_all(&item.path) {
+            Err(ParserError::Busy(_)) => {
+                tracing::info!("File locked, will sync after its next change: {:?}", item.path);
+                self.persist_status(&key, SyncStatus::Pending)?;
+                return Ok(None);
+            }
+            Err(e) if e.is_unsupported() => {
+                self.mark_unsupported(&item.path, &item.parser_name, &e)?;
+                return Ok(None);
+            }
+            result => result?,
+        };
+        for conversation in &mut conversations {
+            self.resolve_project(conversation);
+        }
+        if conversations.len() != 1 {
+            return self.sync_conversations(item, conversations).await;
+        }
+        let mut conversation = conversations.remove(0);
+
+        // Project paths are only known after parsing
+        if self.is_excluded(&key, &conversation) {
+            tracing::info!("Conversation excluded by policy: {:?}", item.path);
+            let file_path = key.to_string();
+            self.persist(&key, move |db| db.delete_sync_state(&file_path))?;
+            return Ok(None);
+        }
+
+        match self.overlap {
+            OverlapStrategy::PreferSpecific => {}
+            OverlapStrategy::UploadBoth => {
+                let readings = self.other_readings(&item.path, &item.parser_name);
+                self.sync_other_readings(&key, readings).await?;
+            }
+            OverlapStrategy::MergeMetadata => {
+                merge_metadata(&mut conversation, &self.other_readings(&item.path, &item.parser_name));
+            }
+        }
+
+        self.cache_content(&key, &conversation);
+
+        // A live change and a reconciliation pass can queue the same content
+        // back to back; the first upload covers both
+        let content_hash = compute_hash(&conversation.content);
+        self.recent_uploads
+            .retain(|_, upload| upload.at.elapsed() < MIN_REUPLOAD_INTERVAL);
+        if let Some(recent) = self.recent_uploads.get(&item.path) {
+            if recent.content_hash == content_hash {
+                tracing::info!("Just uploaded unchanged, skipping: {:?}", item.path);
+                let workflow_id = recent.workflow_id.clone();
+                self.persist_complete(&key, &workflow_id)?;
+                return Ok(None);
+            }
+        }
+
+        let workflow_id = self.sync_conversation(&key, None, &conversation).await?;
+        self.recent_uploads.insert(
+            item.path.clone(),
+            RecentUpload {
+                at: Instant::now(),
+                content_hash,
+                workflow_id: workflow_id.clone(),
+            },
+        );
+        Ok(Some(workflow_id))
+    }
+
+    /// Upload the conversations of a file holding several, skipping ones
+    /// unchanged since their last upload
+    ///
+    /// Stops at the first failure; the file is then retried like any other,
+    /// and conversations already uploaded are skipped.
+    async fn sync_conversations(
+        &mut self,
+        item: &SyncItem,
+        conversations: Vec<Conversation>,
+    ) -> Result<Option<String>, SyncError> {
+        let key = item.path.to_string_lossy().to_string();
+        let mut uploaded = None;
+        for conversation in conversations {
+            let Some(session_id) = conversation.session_id.clone() else {
+                tracing::warn!("Conversation without a session ID in {:?}, skipping it", item.path);
+                continue;
+            };
+            if self.is_excluded(&key, &conversation) {
+                tracing::info!("Conversation {} excluded by policy: {:?}", session_id, item.path);
+                continue;
+            }
+            let content_hash = compute_hash(&conversation.content);
+            if let Some(existing) = self.db.get_file_conversation(&key, &session_id)? {
+                if existing.content_hash == content_hash && existing.status == SyncStatus::Complete {
+                    continue;
+                }
+            }
+
+            self.cache_content(&conversation_key(&key, &session_id), &conversation);
+            match self.sync_conversation(&key, Some(&session_id), &conversation).await {
+                Ok(workflow_id) => uploaded = Some(workflow_id),
+                Err(e) => {
+                    self.persist_status(&key, SyncStatus::Error)?;
+                    return Err(e);
+                }
+            }
+        }
+
+        // The file's workflow is its most recently uploaded conversation's
+        let workflow_id = match uploaded.clone() {
+            Some(workflow_id) => Some(workflow_id),
+            None => self.db.get_sync_state(&key)?.and_then(|state| state.workflow_id),
+        };
+        match workflow_id {
+            Some(workflow_id) => self.persist_complete(&key, &workflow_id)?,
+            None => self.persist_status(&key, SyncStatus::Complete)?,
+        }
+        Ok(uploaded)
+    }
+
+    /// How the other enabled parsers that read a file read it, most
+    /// specific first
+    fn other_readings(&self, path: &Path, owner: &str) -> Vec<Conversation> {
+        self.registry
+            .co_owners(path, owner)
+            .into_iter()
+            .filter_map(|parser| match parser.parse(path) {
+                Ok(mut conversation) => {
+                    self.resolve_project(&mut conversation);
+                    Some(conversation)
+                }
+                Err(e) => {
+                    tracing::debug!("Parser {} can't read {:?} after all: {}", parser.name(), path, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Upload other parsers' readings of the file at `key`, each tracked
+    /// like one of several conversations in the file and skipped while
+    /// unchanged
+    async fn sync_other_readings(&mut self, key: &str, readings: Vec<Conversation>) -> Result<(), SyncError> {
+        for conversation in readings {
+            let reading = format!("source:{}", conversation.source);
+            if self.is_excluded(key, &conversation) {
+                continue;
+            }
+            let content_hash = compute_hash(&conversation.content);
+            if let Some(existing) = self.db.get_file_conversation(key, &reading)? {
+                if existing.content_hash == content_hash && existing.status == SyncStatus::Complete {
+                    continue;
+                }
+            }
+            tracing::info!("Also syncing {} as read by {}", key, conversation.source);
+            self.sync_conversation(key, Some(&reading), &conversation).await?;
+        }
+        Ok(())
+    }
+
+    /// Keep a copy of parsed content when the content cache is enabled
+    fn cache_content(&self, key: &str, conversation: &Conversation) {
+        if let Some(cache) = &self.content_cache {
+            if let Err(e) = cache.store(&self.db, key, conversation) {
+                tracing::warn!("Failed to cache content of {}: {}", key, e);
+            }
+        }
+    }
+
+    /// Push conversation content that did not come from a watched file
+    ///
+    /// Used by `duplex ingest` for tools without a parser. Returns `None` if
+    /// identical content was already synced for the same conversation.
+    pub async fn ingest(&mut self, conversation: &Conversation) -> Result<Option<String>, SyncError> {
+        let content_hash = compute_hash(&conversation.content);
+        let key = &ingest_key(conversation, &content_hash);
+
+        if self.is_excluded(key, conversation) {
+            tracing::info!("Ingested conversation excluded by policy: {}", key);
+            return Ok(None);
+        }
+
+        if let Some(existing) = self.db.get_sync_state(key)? {
+            if existing.content_hash == content_hash && existing.status == SyncStatus::Complete {
+                tracing::debug!("Ingested content unchanged, skipping: {}", key);
+                return Ok(None);
+            }
+        }
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let state = SyncState {
+            file_path: key.to_string(),
+            content_hash,
+            last_synced_at: None,
+            last_modified_at: now,
+            workflow_id: None,
+            status: SyncStatus::Syncing,
+            session_id: None,
+            project_path: None,
+            source: Some(conversation.source.clone()),
+            git: None,
+            title: None,
+        };
+        self.persist(key, move |db| db.upsert_sync_state(&state))?;
+
+        self.sync_conversation(key, None, conversation).await.map(Some)
+    }
+
+    /// Whether policy excludes a conversation by its file or project path,
+    /// or its project isn't selected for sync
+    fn is_excluded(&self, key: &str, conversation: &Conversation) -> bool {
+        if conversation.project_path.as_ref().is_some_and(|p| !self.projects.allows(p)) {
+            return true;
+        }
+        let project = conversation
+            .project_path
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string());
+        self.policy.is_excluded(key, project.as_deref())
+    }
+
+    /// Whether a changed file's project is selected for sync, as far as is
+    /// known before parsing: from its location, or from an earlier sync
+    ///
+    /// While projects are narrowed to components, only parsing tells.
+    fn project_selected(&self, path: &Path, parser_name: &str) -> Result<bool, SyncError> {
+        if self.projects.is_empty() || !self.project_resolver.is_off() {
+            return Ok(true);
+        }
+        let project = match self.registry.get(parser_name).and_then(|parser| parser.project_path_of(path)) {
+            Some(project) => Some(project),
+            None => self
+                .db
+                .get_sync_state(&path.to_string_lossy())?
+                .and_then(|state| state.project_path)
+                .map(PathBuf::from),
+        };
+        Ok(project.is_none_or(|project| self.projects.allows(&project)))
+    }
+
+    /// Narrow a conversation's project to the component it concerns, see
+    /// [`ProjectResolver`]
+    fn resolve_project(&self, conversation: &mut Conversation) {
+        let Some(project) = conversation.project_path.as_deref().filter(|_| !self.project_resolver.is_off()) else {
+            return;
+        };
+        let messages = self
+            .registry
+            .get(&conversation.source)
+            .and_then(|parser| parser.parse_messages(&conversation.content))
+            .unwrap_or_default();
+        let resolved = self.project_resolver.resolve(project, &messages);
+        if resolved != project {
+            tracing::debug!("Project {:?} narrowed to {:?}", project, resolved);
+            conversation.project_path = Some(resolved);
+        }
+    }
+
+    /// Fetch the org baseline and overlay and apply them
+    ///
+    /// A 404 means the org publishes no baseline or overlay, so any cached
+    /// one is dropped. The baseline applies even if the overlay then fails.
+    pub async fn refresh_org_policy(&mut self) -> Result<(), SyncError> {
+        self.refresh_baseline().await?;
+        self.overlay = match self.api.org_policy_overlay().await? {
+            Some(signed) => Some(policy::store_overlay(&signed)?),
+            None => {
+                policy::clear_overlay()?;
+                None
+            }
+        };
+        self.apply_policy()?;
+        tracing::info!(
+            "Applied {}",
+            match (&self.baseline, &self.overlay) {
+                (Some(_), Some(_)) => "org baseline and policy overlay",
+                (Some(_), None) => "org baseline (no org overlay)",
+                (None, Some(_)) => "org policy overlay",
+                (None, None) => "local policy (no org overlay)",
+            }
+        );
+        Ok(())
+    }
+
+    /// Org baseline the local policy is merged on top of, as last fetched
+    pub fn org_baseline(&self) -> Option<&PolicyConfig> {
+        self.baseline.as_ref()
+    }
+
+    /// Fetch the org baseline, cache it and apply it, releasing uploads held
+    /// for it
+    async fn refresh_baseline(&mut self) -> Result<(), SyncError> {
+        self.baseline = self.api.org_policy_baseline().await?;
+        match &self.baseline {
+            Some(baseline) => policy::store_baseline(baseline)?,
+            None => policy::clear_baseline()?,
+        }
+        self.apply_policy()?;
+        self.baseline_pending = false;
+        Ok(())
+    }
+
+    /// Recompile the effective policy from the baseline, local policy and overlay
+    fn apply_policy(&mut self) -> Result<(), SyncError> {
+        let local = policy::apply_baseline(self.baseline.as_ref(), &self.local_policy);
+        let merged = policy::merge(&local, self.overlay.as_ref());
+        self.policy = Policy::compile(&merged)?;
+        self.backfill_batch = self
+            .backfill_batch
+            .clamp(merged.backfill.initial_batch(), merged.backfill.max_batch());
+        self.backfill = merged.backfill;
+        Ok(())
+    }
+
+    /// Find terminal recordings made in the conversation's project while it ran
+    fn link_recordings(&self, conversation: &Conversation, window: (i64, i64)) -> Vec<Recording> {
+        if !self.terminal_recordings.enabled {
+            return Vec::new();
+        }
+
+        let found = recordings::scan(&self.terminal_recordings.directories);
+        recordings::link(
+            &found,
+            window,
+            conversation.project_path.as_deref(),
+            self.terminal_recordings.window_minutes as i64 * 60,
+        )
+    }
+
+    /// Workspace a project's conversations upload to
+    ///
+    /// Policy mappings win; otherwise, with auto-provisioning on, the
+    /// workspace provisioned for the project is used, creating it on first
+    /// sight. Provisioning failures fall back to the default workspace and
+    /// are retried on the next sync.
+    async fn resolve_workspace(&self, project: Option<&str>, git: Option<&GitContext>) -> String {
+        let unmapped = project.filter(|p| self.policy.mapped_workspace(p).is_none());
+        let Some(project) = unmapped.filter(|_| self.auto_provision_workspaces) else {
+            return self.policy.workspace_for(project).to_string();
+        };
+
+        match self.db.get_workspace(project) {
+            Ok(Some(workspace)) => return workspace,
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to read provisioned workspace: {}", e),
+        }
+
+        match self.provision_workspace(project, git).await {
+            Ok(workspace) => {
+                if let Err(e) = self.db.set_workspace(project, &workspace) {
+                    tracing::warn!("Failed to store provisioned workspace: {}", e);
+                }
+                tracing::info!("Using workspace {} for {}", workspace, project);
+                workspace
+            }
+            Err(e) => {
+                tracing::warn!("Failed to provision workspace for {}: {}", project, e);
+                policy::DEFAULT_WORKSPACE.to_string()
+            }
+        }
+    }
+
+    /// Create or look up the workspace for a project, named after its repository
+    async fn provision_workspace(&self, project: &str, git: Option<&GitContext>) -> Result<String, SyncError> {
+        let remote_url = git.and_then(|g| g.remote_url.as_deref());
+        let name = remote_url
+            .and_then(git::repo_name)
+            .or_else(|| {
+                Path::new(project)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| project.to_string());
+
+        let workspace = self
+            .api
+            .create_workspace(&CreateWorkspaceRequest {
+                name: &name,
+                project_path: project,
+                git_remote: remote_url,
+            })
+            .await?;
+        Ok(workspace.id)
+    }
+
+    /// Record metadata for a parsed conversation, upload it and update its state
+    ///
+    /// `session` is set for one of several conversations in the file at
+    /// `key`; its state is tracked per session and the file's metadata is
+    /// left alone.
+    async fn sync_conversation(
+        &mut self,
+        key: &str,
+        session: Option<&str>,
+        conversation: &Conversation,
+    ) -> Result<String, SyncError> {
+        let whole_file = session.is_none();
+        // Hashed before redaction, as unchanged conversations are found by it
+        let tracked = session.map(|session_id| (session_id, compute_hash(&conversation.content)));
+        if whole_file {
+            let file_path = key.to_string();
+            let session_id = conversation.session_id.clone();
+            let project_path = conversation
+                .project_path
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string());
+            let source = conversation.source.clone();
+            let title = conversation.title.clone();
+            self.persist(key, move |db| {
+                db.update_metadata(
+                    &file_path,
+                    session_id.as_deref(),
+                    project_path.as_deref(),
+                    &source,
+                    title.as_deref(),
+                )
+            })?;
+        }
+
+        let git = conversation.project_path.as_deref().and_then(git::collect);
+        if let (Some(git), true) = (&git, whole_file) {
+            let (file_path, git) = (key.to_string(), git.clone());
+            self.persist(key, move |db| db.update_git_context(&file_path, &git))?;
+        }
+
+        let registry = Arc::clone(&self.registry);
+        let parser = registry.get(&conversation.source);
+        let messages = parser
+            .and_then(|parser| parser.parse_messages(&conversation.content))
+            .map(timestamps::normalized);
+        let window = messages.as_deref().and_then(conversation_window);
+        if let (Some((started_at, ended_at)), true) = (window, whole_file) {
+            let file_path = key.to_string();
+            self.persist(key, move |db| db.update_time_window(&file_path, started_at, ended_at))?;
+        }
+
+        let project = conversation
+            .project_path
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string());
+        let project_name = match (&conversation.project_path, &project) {
+            (Some(path), Some(project)) => {
+                let suggested = git.as_ref().and_then(|git| projects::suggest(path, git));
+                if let Some(alias) = suggested.clone() {
+                    let project = project.clone();
+                    self.persist(key, move |db| db.set_suggested_alias(&project, &alias))?;
+                }
+                self.project_names.configured_alias(project).or(suggested)
+            }
+            _ => None,
+        };
+        let related_sessions = match (&project, window) {
+            (Some(project), Some(window)) => self
+                .db
+                .find_related(key, &conversation.source, project, window, RELATED_SESSION_SLACK_SECS)?
+                .into_iter()
+                .map(|state| RelatedSession {
+                    session_id: state.session_id,
+                    source: state.source,
+                    workflow_id: state.workflow_id,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        // Once an append-only file has been uploaded, send only what was added
+        let append_only = whole_file && parser.is_some_and(|parser| parser.append_only());
+        let mut append = match append_only {
+            true => self.append_base(key, &conversation.content)?,
+            false => None,
+        };
+
+        let mut context = UploadContext {
+            workspace_id: self.resolve_workspace(project.as_deref(), git.as_ref()).await,
+            project_name,
+            git,
+            tags: self.db.get_tags(key)?,
+            terminal_recordings: window
+                .map(|window| self.link_recordings(conversation, window))
+                .unwrap_or_default(),
+            related_sessions,
+            messages: None,
+            append: None,
+            live: self.live_session.as_ref().is_some_and(|live| live.path == Path::new(key)),
+            conflicts_with: None,
+            part: None,
+        };
+
+        // Once a session has diverged from another machine's, every version
+        // from here goes beside that one
+        let conflict_key = match session {
+            Some(session_id) => conversation_key(key, session_id),
+            None => key.to_string(),
+        };
+        if let Some(conflict) = self.db.get_conflict(&conflict_key)? {
+            link_conflict(conversation, &mut context, conflict.remote_workflow_id);
+        }
+
+        // Upload to API
+        let mut oversize;
+        let (uploaded, result) = loop {
+            let offset = append.as_ref().map_or(0, |base| base.offset as usize);
+            let (upload, messages) = self.prepare_upload(conversation, parser, offset, messages.as_deref());
+            let size = upload.content.len();
+            let limit = self.payload_limit.max_bytes().filter(|limit| size > *limit);
+            if limit.is_some() && append.is_some() {
+                // The strategy applies to the whole conversation
+                append = None;
+                continue;
+            }
+            oversize = limit.map(|_| Oversize {
+                strategy: self.payload_limit.strategy.as_str().to_string(),
+                bytes: size as u64,
+            });
+            context.append = append.clone();
+            let result = match limit {
+                Some(limit) => self.upload_oversized(&upload, parser, limit, &mut context).await,
+                None => {
+                    context.messages = messages;
+                    self.upload_conversation(&upload, &context).await
+                }
+            };
+            let newer = match &result {
+                Err(SyncError::Api(e)) if context.conflicts_with.is_none() => e.newer_version(),
+                _ => None,
+            };
+            match (result, newer) {
+                (Err(SyncError::Api(e)), _) if append.is_some() && e.is_conflict() => {
+                    tracing::info!("Server copy of {} differs from what was synced, uploading it whole", key);
+                    append = None;
+                }
+                (_, Some(newer)) => {
+                    tracing::warn!(
+                        "Machine {} uploaded a newer version of {}, keeping both",
+                        newer.machine_id.as_deref().unwrap_or("unknown"),
+                        key
+                    );
+                    self.db.record_conflict(
+                        &conflict_key,
+                        conversation.session_id.as_deref(),
+                        &newer.workflow_id,
+                        newer.machine_id.as_deref(),
+                        newer.updated_at,
+                    )?;
+                    link_conflict(conversation, &mut context, newer.workflow_id);
+                }
+                (result, None) => break (upload, result),
+            }
+        };
+        if let (Ok(response), true) = (&result, context.conflicts_with.is_some()) {
+            self.db.set_conflict_workflow(&conflict_key, &response.workflow_id)?;
+        }
+        if whole_file {
+            let (file_path, oversize) = (key.to_string(), oversize.clone());
+            self.persist(key, move |db| db.set_oversize(&file_path, oversize.as_ref()))?;
+        }
+        // Only a whole upload can be extended by later ones
+        let append_only = append_only && oversize.is_none();
+        let full_content = &conversation.content;
+        let conversation = &uploaded;
+
+        match result {
+            Ok(response) if response.duplicate => {
+                // Another machine sharing these files got there first
+                self.persist_outcome(key, tracked, Some(&response.workflow_id))?;
+                if append_only {
+                    self.record_synced_prefix(key, full_content, &response.workflow_id)?;
+                }
+                tracing::info!(
+                    "Already synced by machine {}: {} -> workflow {}",
+                    response.synced_by.as_deref().unwrap_or("unknown"),
+                    key,
+                    response.workflow_id
+                );
+                Ok(response.workflow_id)
+            }
+            Ok(response) => {
+                self.persist_outcome(key, tracked, Some(&response.workflow_id))?;
+                if append_only {
+                    self.record_synced_prefix(key, full_content, &response.workflow_id)?;
+                }
+                if whole_file {
+                    self.record_version(key, full_content, &response.workflow_id)?;
+                }
+                tracing::info!(
+                    "Sync complete: {} -> workflow {}",
+                    key,
+                    response.workflow_id
+                );
+                hooks::run_sync_complete(
+                    &self.on_sync_complete,
+                    &hooks::SyncCompleteEvent {
+                        file: key.to_string(),
+                        project: conversation
+                            .project_path
+                            .as_ref()
+                            .map(|p| p.to_string_lossy().to_string()),
+                        workflow_id: response.workflow_id.clone(),
+                        session_id: conversation.session_id.clone(),
+                        source: conversation.source.clone(),
+                    },
+                );
+                Ok(response.workflow_id)
+            }
+            Err(e) => {
+                self.persist_outcome(key, tracked, None)?;
+                match session {
+                    Some(session_id) => self.record_failure(&conversation_key(key, session_id), &e),
+                    None => self.record_failure(key, &e),
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Upload a conversation over `sync.payloadLimit` as its strategy says,
+    /// returning the response to its first part when split
+    async fn upload_oversized(
+        &self,
+        upload: &Conversation,
+        parser: Option<&dyn ConversationParser>,
+        limit: usize,
+        context: &mut UploadContext,
+    ) -> Result<ExtractionResponse, SyncError> {
+        let size = upload.content.len();
+        let parts = match self.payload_limit.strategy {
+            OversizeStrategy::Reject => None,
+            OversizeStrategy::TruncateOldest => truncate_oldest(&upload.content, limit).map(|kept| vec![kept]),
+            OversizeStrategy::Split => split_lines(&upload.content, limit),
+        };
+        // A single line over the limit can't be cut down
+        let parts = parts.ok_or(SyncError::TooLarge { size, limit })?;
+        tracing::info!(
+            "{:?} is {} bytes, over the payload limit; uploading {} part(s) with {}",
+            upload.source_path,
+            size,
+            parts.len(),
+            self.payload_limit.strategy.as_str()
+        );
+
+        let parser = parser.filter(|parser| parser.structured_upload());
+        let count = parts.len();
+        let mut first: Option<ExtractionResponse> = None;
+        for (i, content) in parts.into_iter().enumerate() {
+            let part = Conversation {
+                content: content.to_string(),
+                ..upload.clone()
+            };
+            context.messages = parser
+                .and_then(|parser| parser.parse_messages(&part.content))
+                .map(timestamps::normalized);
+            context.part = (count > 1).then(|| PartOf {
+                index: i + 1,
+                count,
+                first_workflow_id: first.as_ref().map(|response| response.workflow_id.clone()),
+            });
+            let response = self.upload_conversation(&part, context).await;
+            context.part = None;
+            let response = response?;
+            first.get_or_insert(response);
+        }
+        first.ok_or(SyncError::TooLarge { size, limit })
+    }
+
+    /// The conversation as uploaded, from byte `offset` on with secrets
+    /// redacted, and its messages when the parser's uploads are structured
+    ///
+    /// `messages` are those of the whole unredacted content, reused when
+    /// that is what goes up.
+    fn prepare_upload(
+        &self,
+        conversation: &Conversation,
+        parser: Option<&dyn ConversationParser>,
+        offset: usize,
+        messages: Option<&[Message]>,
+    ) -> (Conversation, Option<Vec<Message>>) {
+        let parser = parser.filter(|parser| parser.structured_upload());
+        let (content, messages) = match self.policy.redact(&conversation.content[offset..]) {
+            Cow::Borrowed(content) if offset == 0 => (content.to_string(), messages.map(<[Message]>::to_vec)),
+            // Messages are uploaded in place of the content, so they must
+            // come from the same lines, redacted
+            content => {
+                let messages = parser
+                    .and_then(|parser| parser.parse_messages(&content))
+                    .map(timestamps::normalized);
+                (content.into_owned(), messages)
+            }
+        };
+        let upload = Conversation {
+            content,
+            // Titles are taken from the content, so may hold the same secrets
+            title: conversation.title.as_deref().map(|t| self.policy.redact(t).into_owned()),
+            ..conversation.clone()
+        };
+        (upload, messages.filter(|_| parser.is_some()))
+    }
+
+    /// The upload a changed append-only file can extend: the one holding
+    /// its synced prefix, if the server accepts appends and the file still
+    /// starts with that prefix and has grown past it
+    fn append_base(&self, key: &str, content: &str) -> Result<Option<SyncedPrefix>, SyncError> {
+        // Sealed uploads can't be extended by the server
+        if self.encrypt || !self.api.accepts_append() {
+            return Ok(None);
+        }
+        let Some(prefix) = self.db.get_synced_prefix(key)? else {
+            return Ok(None);
+        };
+        let offset = prefix.offset as usize;
+        let grown = content.len() > offset && content.is_char_boundary(offset);
+        if !grown || compute_hash(&content[..offset]) != prefix.hash {
+            tracing::debug!("{} was truncated or rewritten since its last upload, sending it whole", key);
+            return Ok(None);
+        }
+        Ok(Some(prefix))
+    }
+
+    /// Remember that the server has all of an append-only file's `content`
+    ///
+    /// Content that doesn't end with a newline may have its last line
+    /// rewritten, so the next upload sends the whole file again.
+    fn record_synced_prefix(&mut self, key: &str, content: &str, workflow_id: &str) -> Result<(), SyncError> {
+        let prefix = content.ends_with('\n').then(|| SyncedPrefix {
+            offset: content.len() as u64,
+            lines: content.matches('\n').count() as u64,
+            hash: compute_hash(content),
+            workflow_id: workflow_id.to_string(),
+        });
+        let file_path = key.to_string();
+        self.persist(key, move |db| db.set_synced_prefix(&file_path, prefix.as_ref()))
+    }
+
+    /// Remember the hash and size of a file's content as uploaded, for `duplex diff`
+    fn record_version(&mut self, key: &str, content: &str, workflow_id: &str) -> Result<(), SyncError> {
+        let version = SyncVersion {
+            file_path: key.to_string(),
+            content_hash: compute_hash(content),
+            bytes: content.len() as u64,
+            lines: content.lines().count() as u64,
+            workflow_id: workflow_id.to_string(),
+            synced_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+        };
+        self.persist(key, move |db| db.record_sync_version(&version))
+    }
+
+    /// Record an upload's workflow, or its failure, on the file's state or,
+    /// for one of several conversations in a file, on that conversation's
+    fn persist_outcome(
+        &mut self,
+        key: &str,
+        tracked: Option<(&str, String)>,
+        workflow_id: Option<&str>,
+    ) -> Result<(), SyncError> {
+        let Some((session_id, content_hash)) = tracked else {
+            return match workflow_id {
+                Some(workflow_id) => self.persist_complete(key, workflow_id),
+                None => self.persist_status(key, SyncStatus::Error),
+            };
+        };
+        let state = FileConversation {
+            file_path: key.to_string(),
+            session_id: session_id.to_string(),
+            content_hash,
+            workflow_id: workflow_id.map(str::to_string),
+            status: if workflow_id.is_some() { SyncStatus::Complete } else { SyncStatus::Error },
+        };
+        self.persist(key, move |db| db.upsert_file_conversation(&state))
+    }
+
+    /// Keep the server's answer to a failed upload for `duplex errors`
+    fn record_failure(&self, key: &str, error: &SyncError) {
+        // The body is kept whole rather than in the summary
+        let (summary, status_code, validation_errors, body) = match error {
+            SyncError::Api(e @ ApiError::Status { status, body }) => (
+                format!("Server answered {}", status),
+                Some(status.as_u16()),
+                e.validation_errors(),
+                Some(body.as_str()),
+            ),
+            e => (e.to_string(), None, Vec::new(), None),
+        };
+
+        if validation_errors.is_empty() {
+            tracing::error!("Sync failed: {} - {}", key, error);
+        } else {
+            tracing::error!("Sync rejected: {} - {}", key, validation_errors.join("; "));
+        }
+
+        if let Err(e) = self
+            .db
+            .record_failed_attempt(key, status_code, &summary, &validation_errors, body)
+        {
+            tracing::warn!("Failed to record failed upload: {}", e);
+        }
+    }
+
+    /// Upload a conversation to the API
+    /// Routes to R2 for large files or inline for smaller ones
+    async fn upload_conversation(
+        &self,
+        conversation: &Conversation,
+        context: &UploadContext,
+    ) -> Result<ExtractionResponse, SyncError> {
+        let key = match (self.encrypt, &self.encryption_key) {
+            (true, None) => return Err(EncryptionError::NoKey.into()),
+            (true, key) => key.as_ref(),
+            (false, _) => None,
+        };
+        let sealed = key.map(|key| key.seal(conversation.content.as_bytes())).transpose()?;
+        let fingerprint = key.map(EncryptionKey::fingerprint);
+        let content_hash = match key {
+            Some(key) => key.content_hash(&conversation.content),
+            None => compute_hash(&conversation.content),
+        };
+        // Structured messages (payload v2) once the server accepts them;
+        // the raw or sealed content otherwise
+        let messages = context
+            .messages
+            .as_deref()
+            .filter(|_| sealed.is_none() && self.api.payload_version() >= 2);
+        let structured = messages.and_then(|messages| serde_json::to_string(messages).ok());
+        let body = sealed
+            .as_deref()
+            .or(structured.as_deref())
+            .unwrap_or(&conversation.content);
+
+        let mut request = ExtractRequest {
+            version: if structured.is_some() { 2 } else { 1 },
+            content: None,
+            messages: None,
+            r2_key: None,
+            source_path: conversation.source_path.to_string_lossy().to_string(),
+            source: &conversation.source,
+            session_id: conversation.session_id.as_deref(),
+            title: conversation.title.as_deref().filter(|_| sealed.is_none()),
+            project_name: context.project_name.as_deref().filter(|_| sealed.is_none()),
+            content_hash: &content_hash,
+            machine_id: machine::machine_id(),
+            content_type: conversation.content_type,
+            workspace_id: &context.workspace_id,
+            git: context.git.as_ref(),
+            tags: &context.tags,
+            terminal_recordings: &context.terminal_recordings,
+            related_sessions: &context.related_sessions,
+            append: context.append.as_ref().map(|base| Append {
+                workflow_id: &base.workflow_id,
+                offset: base.offset,
+                lines: base.lines,
+                base_hash: &base.hash,
+                live: context.live,
+            }),
+            encryption: fingerprint.as_deref().map(|key_fingerprint| Encryption {
+                algorithm: encryption::ALGORITHM,
+                key_fingerprint,
+            }),
+            conflicts_with: context.conflicts_with.as_deref(),
+            part: context.part.as_ref().map(|part| Part {
+                index: part.index,
+                count: part.count,
+                first_workflow_id: part.first_workflow_id.as_deref(),
+            }),
+        };
+
+        // Check content size to determine upload method
+        if body.len() <= INLINE_THRESHOLD {
+            match structured {
+                Some(_) => request.messages = messages,
+                None => request.content = Some(body),
+            }
+            return Ok(self.api.extract(&request).await?);
+        }
+
+        tracing::info!(
+            "Content size {} exceeds threshold, using R2 upload",
+            body.len()
+        );
+
+        // Step 1: Get presigned upload URL from API
+        let filename = conversation
+            .source_path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "conversation".to_string());
+        let upload_info = self
+            .api
+            .upload_url(&UploadUrlRequest {
+                filename: &filename,
+                content_hash: &content_hash,
+                session_id: conversation.session_id.as_deref(),
+                machine_id: machine::machine_id(),
+                source: &conversation.source,
+                workspace_id: &context.workspace_id,
+            })
+            .await?;
+        if let Some(mut existing) = upload_info.duplicate {
+            existing.duplicate = true;
+            return Ok(existing);
+        }
+        tracing::debug!("Got presigned URL for R2 key: {}", upload_info.r2_key);
+
+        // Step 2: Upload content directly to R2 via presigned URL
+        self.api
+            .put_object(&upload_info.upload_url, body.to_string())
+            .await?;
+        tracing::debug!("Uploaded content to R2");
+
+        // Step 3: Trigger extraction with R2 key
+        request.r2_key = Some(&upload_info.r2_key);
+        Ok(self.api.extract(&request).await?)
+    }
+
+    /// Process all items in the queue
+    ///
+    /// Stops early while paused; remaining items stay queued and `pending`.
+    pub async fn process_all(&mut self) -> Result<SyncReport, SyncError> {
+        // Asked for now, so don't wait for the next connectivity check
+        self.check_connectivity(true).await;
+        self.refresh_encryption_key(true);
+        self.refresh_baseline_if_pending(true).await;
+        self.process(false).await
+    }
+
+    /// Process the queue as the background loop does: on battery or in low
+    /// power mode, backfill waits for mains power (with `power.deferBackfill`)
+    /// and changed files upload once they have been quiet for
+    /// `power.batteryDebounceSeconds`
+    pub async fn process_due(&mut self) -> Result<SyncReport, SyncError> {
+        let constrained = power::is_constrained(&self.power);
+        self.process(constrained).await
+    }
+
+    async fn process(&mut self, constrained: bool) -> Result<SyncReport, SyncError> {
+        self.retry_deferred_writes();
+        let defer_backfill = constrained && self.power.defer_backfill;
+        let backfilling =
+            self.queue.is_empty() && !self.backlog.is_empty() && !defer_backfill && self.backfill_due();
+        if backfilling {
+            self.promote(self.backlog.len().min(self.backfill_batch));
+            self.last_backfill = Some(Instant::now());
+        }
+        let mut report = SyncReport::default();
+        while let Some(path) = self.queue.front().map(|item| item.path.clone()) {
+            if self.is_paused() {
+                tracing::info!("Uploads paused with {} item(s) queued", self.queue.len());
+                break;
+            }
+            if self.is_offline() {
+                break;
+            }
+            // Files are queued in order, so everything behind a recent one is too
+            if constrained && self.queue.front().is_some_and(|item| !self.debounced(item)) {
+                tracing::debug!("On battery, holding {} item(s) until changes settle", self.queue.len());
+                break;
+            }
+
+            match self.process_next().await {
+                Ok(Some(_)) => report.succeeded += 1,
+                Ok(None) => report.skipped += 1,
+                Err(e) if e.is_offline() => {
+                    tracing::warn!("API unreachable, holding {} item(s) until it is back: {}", self.queue_len(), e);
+                }
+                Err(e) => {
+                    tracing::error!("Error processing sync item ({}): {}", e.category(), e);
+                    metrics::record_error(e.category());
+                    report.failed.push((path, e.category()));
+                    // Continue with next item
+                }
+            }
+        }
+        if backfilling {
+            self.ramp_backfill(!report.is_success());
+        }
+        if !report.is_empty() {
+            *LAST_REPORT.lock().unwrap() = Some(report.clone());
+        }
+        Ok(report)
+    }
+
+    /// Whether a queued file has been quiet long enough to upload on battery
+    fn debounced(&self, item: &SyncItem) -> bool {
+        item.queued_at.elapsed() >= Duration::from_secs(self.power.battery_debounce_seconds)
+    }
+
+    /// Whether the startup delay and the gap since the last backfill batch
+    /// have passed
+    fn backfill_due(&self) -> bool {
+        let delay = self.backfill.startup_delay().mul_f64(self.startup_jitter);
+        self.started.elapsed() >= delay
+            && self.last_backfill.is_none_or(|last| last.elapsed() >= self.backfill.batch_interval())
+    }
+
+    /// Double the next backfill batch after a clean one; start over after errors
+    fn ramp_backfill(&mut self, failed: bool) {
+        self.backfill_batch = if failed {
+            self.backfill.initial_batch()
+        } else {
+            (self.backfill_batch * 2).min(self.backfill.max_batch())
+        };
+    }
+
+    /// Write sync state, holding the write in memory if storage is failing
+    ///
+    /// A write that fails on a full disk or lost permissions is kept and
+    /// retried by [`Self::retry_deferred_writes`], so the file stays tracked.
+    /// Later writes queue behind held ones so they land in order.
+    fn persist(
+        &mut self,
+        file_path: &str,
+        write: impl Fn(&Database) -> rusqlite::Result<()> + Send + 'static,
+    ) -> Result<(), SyncError> {
+        if self.deferred_writes.is_empty() {
+            match write(&self.db) {
+                Err(e) if db::is_storage_failure(&e) => {
+                    tracing::warn!("Failed to save sync state for {}: {}", file_path, e);
+                    metrics::record_error(ErrorCategory::Io);
+                    db::set_storage_failing(true);
+                    self.last_storage_retry = Some(Instant::now());
+                }
+                result => return Ok(result?),
+            }
+        }
+
+        if self.deferred_writes.len() >= MAX_DEFERRED_WRITES {
+            if let Some(dropped) = self.deferred_writes.pop_front() {
+                tracing::warn!("Too many unsaved changes, dropping sync state for {}", dropped.file_path);
+            }
+        }
+        self.deferred_writes.push_back(DeferredWrite {
+            file_path: file_path.to_string(),
+            apply: Box::new(write),
+        });
+        Ok(())
+    }
+
+    fn persist_status(&mut self, file_path: &str, status: SyncStatus) -> Result<(), SyncError> {
+        let key = file_path.to_string();
+        self.persist(file_path, move |db| match &status {
+            SyncStatus::Syncing => db.mark_syncing(&key),
+            status => db.update_status(&key, status.clone()),
+        })
+    }
+
+    fn persist_complete(&mut self, file_path: &str, workflow_id: &str) -> Result<(), SyncError> {
+        let (key, workflow_id) = (file_path.to_string(), workflow_id.to_string());
+        self.persist(file_path, move |db| db.mark_complete(&key, &workflow_id))
+    }
+
+    /// Retry held sync state writes, at most every [`STORAGE_RETRY_INTERVAL`]
+    ///
+    /// Returns the number of writes still held.
+    pub fn retry_deferred_writes(&mut self) -> usize {
+        let due = self
+            .last_storage_retry
+            .is_none_or(|at| at.elapsed() >= STORAGE_RETRY_INTERVAL);
+        if self.deferred_writes.is_empty() || !due {
+            return self.deferred_writes.len();
+        }
+        self.last_storage_retry = Some(Instant::now());
+
+        while let Some(write) = self.deferred_writes.front() {
+            match (write.apply)(&self.db) {
+                Ok(()) => {}
+                Err(e) if db::is_storage_failure(&e) => {
+                    tracing::debug!(
+                        "Storage still failing with {} change(s) unsaved: {}",
+                        self.deferred_writes.len(),
+                        e
+                    );
+                    return self.deferred_writes.len();
+                }
+                // Retrying won't fix anything but storage
+                Err(e) => tracing::error!("Dropping sync state for {}: {}", write.file_path, e),
+            }
+            self.deferred_writes.pop_front();
+        }
+
+        db::set_storage_failing(false);
+        0
+    }
+
+    /// Get the number of items in the queue, including the re-sync backlog
+    pub fn queue_len(&self) -> usize {
+        self.queue.len() + self.backlog.len()
+    }
+
+    /// Get sync status counts from the database
+    pub fn get_status_counts(&self) -> Result<crate::db::StatusCounts, SyncError> {
+        Ok(self.db.get_status_counts()?)
+    }
+
+    /// Web URL of the conversation uploaded most recently, if any
+    pub fn latest_conversation_url(&self) -> Result<Option<String>, SyncError> {
+        Ok(self
+            .db
+            .latest_synced()?
+            .and_then(|state| state.workflow_id)
+            .map(|workflow_id| self.api.conversation_url(&workflow_id)))
+    }
+}
+
+/// Whether a file is gone but its directory is still there
+///
+/// A missing directory more likely means an unmounted drive or a moved
+/// projects folder than a deleted conversation, so it doesn't count.
+fn is_removed(path: &Path) -> bool {
+    !path.exists() && path.parent().is_some_and(Path::is_dir)
+}
+
+/// Database key for ingested content
+///
+/// Re-ingesting with the same session ID (or from the same file) updates one
+/// record; otherwise each distinct piece of content is its own conversation.
+fn ingest_key(conversation: &Conversation, content_hash: &str) -> String {
+    if let Some(session_id) = &conversation.session_id {
+        format!("ingest://{}/{}", conversation.source, session_id)
+    } else if conversation.source_path.is_file() {
+        conversation.source_path.to_string_lossy().to_string()
+    } else {
+        format!("ingest://{}/{}", conversation.source, &content_hash[..16])
+    }
+}
+
+/// Compare `backend` with the one the sync state was recorded against.
+/// Workflow IDs from another backend mean nothing to this one, so a change
+/// switches the state over as `on_change` says, or returns the previous
+/// backend when the user should choose first
+fn check_backend(db: &Database, backend: &str, on_change: BackendChange) -> Result<Option<String>, SyncError> {
+    let Some(recorded) = db.get_backend()? else {
+        db.set_backend(backend)?;
+        return Ok(None);
+    };
+    if recorded == backend {
+        return Ok(None);
+    }
+
+    let keep = match on_change {
+        // Going back to a backend seen before needs no choice
+        _ if db.has_backend_state(backend)? => false,
+        BackendChange::Keep => true,
+        BackendChange::Reset => false,
+        BackendChange::Ask => {
+            tracing::warn!(
+                "API URL changed from {} to {}; uploads are held until `duplex backend --keep` or `--reset`",
+                recorded,
+                backend
+            );
+            return Ok(Some(recorded));
+        }
+    };
+    match db.switch_backend(&recorded, backend, keep)? {
+        BackendSwitch::Restored => tracing::info!("Restored sync state for {}", backend),
+        BackendSwitch::Kept => tracing::info!("Kept uploaded conversations as synced on {}", backend),
+        BackendSwitch::Reset => tracing::info!("Queued uploaded conversations to sync again to {}", backend),
+    }
+    Ok(None)
+}
+
+/// The key to seal uploads with, if there is one yet
+fn load_encryption_key() -> Option<EncryptionKey> {
+    match encryption::load_key() {
+        Ok(key) => Some(key),
+        Err(EncryptionError::NoKey) => {
+            tracing::warn!("Encryption is on but there is no key; uploads wait for `duplex encryption init` or `import`");
+            None
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load encryption key, uploads wait: {}", e);
+            None
+        }
+    }
+}
+
+/// Take what a parser's reading of a file lacks from other parsers' readings
+/// of it, the first that has each wins
+fn merge_metadata(conversation: &mut Conversation, others: &[Conversation]) {
+    for other in others {
+        conversation.session_id = conversation.session_id.take().or_else(|| other.session_id.clone());
+        conversation.project_path = conversation.project_path.take().or_else(|| other.project_path.clone());
+        conversation.title = conversation.title.take().or_else(|| other.title.clone());
+    }
+}
+
+/// Upload beside another machine's version of the session, linked to it
+fn link_conflict(conversation: &Conversation, context: &mut UploadContext, workflow_id: String) {
+    context.related_sessions.push(RelatedSession {
+        session_id: conversation.session_id.clone(),
+        source: Some(conversation.source.clone()),
+        workflow_id: Some(workflow_id.clone()),
+    });
+    context.conflicts_with = Some(workflow_id);
+}
+
+/// The end of `content` that fits in `limit` bytes, starting at a line;
+/// `None` when its last line alone is over
+fn truncate_oldest(content: &str, limit: usize) -> Option<&str> {
+    let start = content.len().saturating_sub(limit);
+    if start == 0 {
+        return Some(content);
+    }
+    let cut = match content.as_bytes()[start - 1] {
+        b'\n' => start,
+        _ => start + content.as_bytes()[start..].iter().position(|&b| b == b'\n')? + 1,
+    };
+    Some(&content[cut..]).filter(|kept| !kept.is_empty())
+}
+
+/// `content` in consecutive runs of whole lines, each at most `limit`
+/// bytes; `None` when a line alone is over
+fn split_lines(content: &str, limit: usize) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for line in content.split_inclusive('\n') {
+        if line.len() > limit {
+            return None;
+        }
+        if end + line.len() - start > limit {
+            parts.push(&content[start..end]);
+            start = end;
+        }
+        end += line.len();
+    }
+    if end > start {
+        parts.push(&content[start..end]);
+    }
+    Some(parts)
+}
+
+/// Compute SHA-256 hash of content
+pub(crate) fn compute_hash(content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// What the engine task is doing, from [`SyncHandle::status`]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineStatus {
+    /// Files waiting to upload
+    pub queued: usize,
+    /// Files being uploaded right now
+    pub in_flight: usize,
+    /// Whether uploads are held back; see [`SyncEngine::is_paused`]
+    pub paused: bool,
+    /// Whether uploads wait for the API to become reachable
+    pub offline: bool,
+}
+
+/// A request to the engine task, answered in the order sent
+enum Command {
+    /// Queue a changed file
+    Enqueue(FileChangeEvent),
+    /// Upload everything queued, ignoring the battery and startup pacing
+    SyncNow(oneshot::Sender<Result<SyncReport, SyncError>>),
+    Status(oneshot::Sender<EngineStatus>),
+    PropagateDeletions(oneshot::Sender<Result<usize, SyncError>>),
+    PullResults(oneshot::Sender<Result<usize, SyncError>>),
+    PushAnnotations(oneshot::Sender<Result<usize, SyncError>>),
+    RefreshOrgPolicy(oneshot::Sender<Result<(), SyncError>>),
+    /// Run a closure against the engine between passes
+    Call(Box<dyn FnOnce(&mut SyncEngine) + Send>),
+}
+
+/// Cloneable handle to a [`SyncTask`]
+///
+/// Every method fails with [`SyncError::Stopped`] once the task has exited.
+/// The `blocking_` variants are for threads outside the runtime and panic
+/// if called from async code.
+#[derive(Clone)]
+pub struct SyncHandle {
+    commands: mpsc::Sender<Command>,
+}
+
+impl SyncHandle {
+    /// Queue a changed file
+    pub async fn enqueue(&self, event: FileChangeEvent) -> Result<(), SyncError> {
+        self.commands.send(Command::Enqueue(event)).await.map_err(|_| SyncError::Stopped)
+    }
+
+    /// Queue a changed file from outside the runtime
+    pub fn blocking_enqueue(&self, event: FileChangeEvent) -> Result<(), SyncError> {
+        self.commands.blocking_send(Command::Enqueue(event)).map_err(|_| SyncError::Stopped)
+    }
+
+    /// Upload everything queued now; see [`SyncEngine::process_all`]
+    pub async fn sync_now(&self) -> Result<SyncReport, SyncError> {
+        self.request(Command::SyncNow).await?
+    }
+
+    /// Queue length and whether uploads are held back
+    pub async fn status(&self) -> Result<EngineStatus, SyncError> {
+        self.request(Command::Status).await
+    }
+
+    /// See [`SyncEngine::propagate_deletions`]
+    pub async fn propagate_deletions(&self) -> Result<usize, SyncError> {
+        self.request(Command::PropagateDeletions).await?
+    }
+
+    /// See [`SyncEngine::pull_results`]
+    pub async fn pull_results(&self) -> Result<usize, SyncError> {
+        self.request(Command::PullResults).await?
+    }
+
+    /// See [`SyncEngine::push_annotations`]
+    pub async fn push_annotations(&self) -> Result<usize, SyncError> {
+        self.request(Command::PushAnnotations).await?
+    }
+
+    /// See [`SyncEngine::refresh_org_policy`]
+    pub async fn refresh_org_policy(&self) -> Result<(), SyncError> {
+        self.request(Command::RefreshOrgPolicy).await?
+    }
+
+    /// Run `f` against the engine and return its result
+    ///
+    /// For the engine's quick, synchronous operations; `f` holds up every
+    /// other command while it runs.
+    pub async fn call<R, F>(&self, f: F) -> Result<R, SyncError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut SyncEngine) -> R + Send + 'static,
+    {
+        self.request(|reply| Self::call_command(f, reply)).await
+    }
+
+    /// [`Self::call`] from outside the runtime
+    pub fn blocking_call<R, F>(&self, f: F) -> Result<R, SyncError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut SyncEngine) -> R + Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .blocking_send(Self::call_command(f, reply))
+            .map_err(|_| SyncError::Stopped)?;
+        response.blocking_recv().map_err(|_| SyncError::Stopped)
+    }
+
+    fn call_command<R, F>(f: F, reply: oneshot::Sender<R>) -> Command
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut SyncEngine) -> R + Send + 'static,
+    {
+        Command::Call(Box::new(move |engine| {
+            let _ = reply.send(f(engine));
+        }))
+    }
+
+    /// Send a command carrying a reply channel and wait for the answer
+    async fn request<R>(&self, command: impl FnOnce(oneshot::Sender<R>) -> Command) -> Result<R, SyncError> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).await.map_err(|_| SyncError::Stopped)?;
+        response.await.map_err(|_| SyncError::Stopped)
+    }
+}
+
+/// A [`SyncEngine`] owned by a task and driven through [`SyncHandle`]s
+///
+/// Between commands the task does what the app's sync loop used to: save
+/// held-back state, queue due retries and live session lines, and work the
+/// queue when uploads aren't paused.
+pub struct SyncTask {
+    engine: SyncEngine,
+    commands: mpsc::Receiver<Command>,
+}
+
+impl SyncEngine {
+    /// Hand the engine to a task, returning a handle to command it with
+    ///
+    /// Nothing happens until [`SyncTask::run`] is polled.
+    pub fn into_task(self) -> (SyncHandle, SyncTask) {
+        let (sender, commands) = mpsc::channel(COMMAND_CAPACITY);
+        let task = SyncTask { engine: self, commands };
+        (SyncHandle { commands: sender }, task)
+    }
+}
+
+impl SyncTask {
+    /// Serve commands and work the queue until `shutdown` is cancelled or
+    /// every handle is dropped
+    ///
+    /// A pass already under way when `shutdown` is cancelled finishes its
+    /// current upload first. The future isn't `Send`, as the engine's
+    /// database connection can't be shared: run it with `block_on` on a
+    /// thread of its own, or in a `LocalSet`.
+    pub async fn run(mut self, shutdown: CancellationToken) {
+        let mut tick = tokio::time::interval(TICK_INTERVAL);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                command = self.commands.recv() => match command {
+                    Some(command) => self.handle(command).await,
+                    None => break,
+                },
+                _ = tick.tick() => self.work_queue().await,
+            }
+        }
+        tracing::info!("Stopped taking file changes");
+    }
+
+    async fn handle(&mut self, command: Command) {
+        let engine = &mut self.engine;
+        match command {
+            Command::Enqueue(event) => {
+                tracing::info!("File changed: {:?} (parser: {})", event.path, event.parser_name);
+                if let Err(e) = engine.handle_file_change(event) {
+                    tracing::error!("Failed to queue file for sync: {}", e);
+                }
+            }
+            Command::SyncNow(reply) => {
+                let _ = reply.send(engine.process_all().await);
+            }
+            Command::Status(reply) => {
+                let _ = reply.send(EngineStatus {
+                    queued: engine.queue_len(),
+                    in_flight: engine.in_flight.len(),
+                    paused: engine.is_paused(),
+                    offline: engine.is_offline(),
+                });
+            }
+            Command::PropagateDeletions(reply) => {
+                let _ = reply.send(engine.propagate_deletions().await);
+            }
+            Command::PullResults(reply) => {
+                let _ = reply.send(engine.pull_results().await);
+            }
+            Command::PushAnnotations(reply) => {
+                let _ = reply.send(engine.push_annotations().await);
+            }
+            Command::RefreshOrgPolicy(reply) => {
+                let _ = reply.send(engine.refresh_org_policy().await);
+            }
+            Command::Call(f) => f(engine),
+        }
+    }
+
+    async fn work_queue(&mut self) {
+        let engine = &mut self.engine;
+        engine.refresh_backend_hold();
+        engine.refresh_encryption_key(false);
+        engine.refresh_baseline_if_pending(false).await;
+
+        // Save state held back by a full disk or failed write once it can be
+        engine.retry_deferred_writes();
+
+        // Retry failed uploads whose backoff has passed
+        if let Err(e) = engine.queue_due_retries() {
+            tracing::error!("Failed to queue retries: {}", e);
+        }
+
+        // Uploads a crashed process left behind
+        if let Err(e) = engine.recover_stuck_uploads() {
+            tracing::error!("Failed to recover stuck uploads: {}", e);
+        }
+
+        // Files changed while the app wasn't running, or that the watcher missed
+        if let Err(e) = engine.full_scan_if_due() {
+            tracing::error!("Full scan failed: {}", e);
+        }
+        engine.track_backfill();
+
+        // Pick up new lines in the session being worked in without the debounce
+        if let Err(e) = engine.stream_live_session() {
+            tracing::error!("Failed to queue streaming session: {}", e);
+        }
+
+        // Wait out a lost network without failing everything queued
+        if engine.is_offline() && !engine.check_connectivity(false).await {
+            return;
+        }
+
+        // Process the queue, including items restored at startup or held back
+        // by sleep or the sync schedule
+        if engine.queue_len() > 0 && !engine.is_paused() {
+            match engine.process_due().await {
+                Ok(report) if !report.is_success() => tracing::warn!("Sync pass finished: {}", report.summary()),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to process sync queue: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::ContentType;
+
+    #[test]
+    fn test_compute_hash() {
+        let hash1 = compute_hash("hello world");
+        let hash2 = compute_hash("hello world");
+        let hash3 = compute_hash("different content");
+
+        assert_eq!(hash1, hash2);
+        assert_ne!(hash1, hash3);
+        assert_eq!(hash1.len(), 64); // SHA-256 produces 64 hex chars
+    }
+
+    #[test]
+    fn test_fit_to_limit() {
+        let content = "aaaa\nbbbb\ncccc\n";
+        assert_eq!(truncate_oldest(content, 100), Some(content));
+        assert_eq!(truncate_oldest(content, 10), Some("bbbb\ncccc\n"));
+        assert_eq!(truncate_oldest(content, 9), Some("cccc\n"));
+        assert_eq!(truncate_oldest(content, 4), None);
+
+        assert_eq!(split_lines(content, 10), Some(vec!["aaaa\nbbbb\n", "cccc\n"]));
+        assert_eq!(split_lines(content, 5), Some(vec!["aaaa\n", "bbbb\n", "cccc\n"]));
+        assert_eq!(split_lines("aaaa\nbb", 6), Some(vec!["aaaa\n", "bb"]));
+        assert_eq!(split_lines(content, 4), None);
+    }
+
+    #[test]
+    fn test_ingest_key() {
+        let mut conversation = Conversation {
+            source_path: PathBuf::from("-"),
+            source: "my-tool".to_string(),
+            session_id: None,
+            project_path: None,
+            content: "hello".to_string(),
+            content_type: ContentType::Conversation,
+            title: None,
+        };
+        let hash = compute_hash(&conversation.content);
+        assert_eq!(ingest_key(&conversation, &hash), format!("ingest://my-tool/{}", &hash[..16]));
+
+        conversation.session_id = Some("run-42".to_string());
+        assert_eq!(ingest_key(&conversation, &hash), "ingest://my-tool/run-42");
+    }
+
+    #[test]
+    fn test_state_writes_held_while_storage_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let mut engine = SyncEngine::with_database(
+            "http://127.0.0.1:9".to_string(),
+            None,
+            Arc::new(ParserRegistry::new()),
+            &Config::default(),
+            db,
+        )
+        .unwrap();
+
+        let session = dir.path().join("session.jsonl");
+        std::fs::write(&session, "{\"type\":\"user\"}\n").unwrap();
+        let key = session.to_string_lossy().to_string();
+
+        engine.db.set_read_only(true).unwrap();
+        engine.queue_file(&session, "claude-code".to_string(), false).unwrap();
+        engine.persist_complete(&key, "wf-1").unwrap();
+        assert_eq!(engine.queue_len(), 1);
+        assert!(engine.db.get_sync_state(&key).unwrap().is_none());
+
+        // Still failing: nothing is lost
+        engine.last_storage_retry = None;
+        assert_eq!(engine.retry_deferred_writes(), 2);
+
+        engine.db.set_read_only(false).unwrap();
+        engine.last_storage_retry = None;
+        assert_eq!(engine.retry_deferred_writes(), 0);
+        let state = engine.db.get_sync_state(&key).unwrap().unwrap();
+        assert_eq!(state.status, SyncStatus::Complete);
+        assert_eq!(state.workflow_id.as_deref(), Some("wf-1"));
+    }
+
+    #[test]
+    fn test_resync_queues_behind_live_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let mut engine = SyncEngine::with_database(
+            "http://127.0.0.1:9".to_string(),
+            None,
+            Arc::new(ParserRegistry::new()),
+            &Config::default(),
+            db,
+        )
+        .unwrap();
+
+        let sessions: Vec<PathBuf> = ["a.jsonl", "b.jsonl"].iter().map(|name| dir.path().join(name)).collect();
+        for session in &sessions {
+            std::fs::write(session, "{\"type\":\"user\"}\n").unwrap();
+            engine.queue_file(session, "claude-code".to_string(), false).unwrap();
+        }
+        engine.queue.clear();
+
+        assert_eq!(engine.queue_resync().unwrap(), 2);
+        assert_eq!(engine.queue_resync().unwrap(), 0);
+        assert!(engine.queue.is_empty());
+        assert_eq!(engine.queue_len(), 2);
+
+        // A live change moves the file to the front of the line
+        engine.queue_file(&sessions[1], "claude-code".to_string(), true).unwrap();
+        assert_eq!(engine.queue.len(), 1);
+        assert_eq!(engine.backlog.len(), 1);
+        assert_eq!(engine.backlog[0].path, sessions[0]);
+    }
+
+    #[test]
+    fn test_full_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let mut config = Config::default();
+        for base_dir in [
+            &mut config.parsers.claude_code.base_dir,
+            &mut config.parsers.codex.base_dir,
+            &mut config.parsers.gemini.base_dir,
+        ] {
+            *base_dir = Some(dir.path().to_string_lossy().to_string());
+        }
+        let mut engine = SyncEngine::with_database(
+            "http://127.0.0.1:9".to_string(),
+            None,
+            Arc::new(ParserRegistry::new()),
+            &config,
+            db,
+        )
+        .unwrap();
+
+        let project = dir.path().join("projects").join("-work-app");
+        std::fs::create_dir_all(&project).unwrap();
+        let session = project.join("123e4567-e89b-12d3-a456-426614174000.jsonl");
+        std::fs::write(&session, "{\"type\":\"user\"}\n").unwrap();
+
+        // New files wait in the backlog, and are found once
+        assert_eq!(engine.full_scan_if_due().unwrap(), 1);
+        assert_eq!(engine.backlog.len(), 1);
+        assert_eq!(engine.full_scan().unwrap(), 0);
+        assert_eq!(engine.full_scan_if_due().unwrap(), 0);
+
+        engine.backlog.clear();
+        assert_eq!(engine.full_scan().unwrap(), 0);
+        std::fs::write(&session, "{\"type\":\"user\"}\n{\"type\":\"assistant\"}\n").unwrap();
+        assert_eq!(engine.full_scan().unwrap(), 1);
+        assert!(engine.queue.is_empty());
+    }
+
+    #[test]
+    fn test_backfill_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let mut config = Config::default();
+        config.parsers.claude_code.base_dir = Some(dir.path().to_string_lossy().to_string());
+        let mut engine = SyncEngine::with_database(
+            "http://127.0.0.1:9".to_string(),
+            None,
+            Arc::new(ParserRegistry::new()),
+            &config,
+            db,
+        )
+        .unwrap();
+
+        let project = dir.path().join("projects").join("-work-app");
+        std::fs::create_dir_all(&project).unwrap();
+        let newer = project.join("123e4567-e89b-12d3-a456-426614174000.jsonl");
+        let older = project.join("123e4567-e89b-12d3-a456-426614174001.jsonl");
+        for (path, age) in [(&newer, 60), (&older, 3600)] {
+            std::fs::write(path, "{\"type\":\"user\"}\n").unwrap();
+            let file = std::fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+        }
+
+        // Oldest first, in the backlog
+        assert_eq!(engine.first_run_backfill().unwrap(), 2);
+        let paths: Vec<_> = engine.backlog.iter().map(|item| item.path.clone()).collect();
+        assert_eq!(paths, vec![older.clone(), newer.clone()]);
+        assert!(engine.queue.is_empty());
+
+        engine.remove_queued(|item| item.path == older);
+        engine.track_backfill();
+        let backfill = engine.history_backfill.as_ref().unwrap();
+        assert_eq!(backfill.published, Some(BackfillProgress { done: 1, total: 2 }));
+
+        engine.backlog.clear();
+        engine.queue_seq += 1;
+        engine.track_backfill();
+        assert!(engine.history_backfill.is_none());
+
+        // Only once on its own; on request it finds nothing new
+        assert_eq!(engine.first_run_backfill().unwrap(), 0);
+        assert_eq!(engine.backfill_history().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_merge_metadata() {
+        let reading = |source: &str, session_id: Option<&str>, title: Option<&str>| Conversation {
+            source_path: PathBuf::from("/logs/a.jsonl"),
+            source: source.to_string(),
+            session_id: session_id.map(str::to_string),
+            project_path: None,
+            title: title.map(str::to_string),
+            content: String::new(),
+            content_type: ContentType::Conversation,
+        };
+        let mut conversation = reading("acme", None, Some("Fix the build"));
+        let mut generic = reading("generic-jsonl", Some("s1"), Some("Untitled"));
+        generic.project_path = Some(PathBuf::from("/work/app"));
+
+        merge_metadata(&mut conversation, &[generic]);
+        assert_eq!(conversation.source, "acme");
+        assert_eq!(conversation.session_id.as_deref(), Some("s1"));
+        assert_eq!(conversation.project_path, Some(PathBuf::from("/work/app")));
+        assert_eq!(conversation.title.as_deref(), Some("Fix the build"));
+    }
+
+    #[test]
+    fn test_backfill_pacing() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let mut config = Config::default();
+        config.policy.backfill = BackfillConfig {
+            startup_delay_seconds: Some(3600),
+            max_batch: Some(4),
+            ..Default::default()
+        };
+        let mut engine = SyncEngine::with_database(
+            "http://127.0.0.1:9".to_string(),
+            None,
+            Arc::new(ParserRegistry::new()),
+            &config,
+            db,
+        )
+        .unwrap();
+
+        let session = dir.path().join("a.jsonl");
+        std::fs::write(&session, "{\"type\":\"user\"}\n").unwrap();
+        engine.queue_file(&session, "claude-code".to_string(), false).unwrap();
+        engine.queue.clear();
+
+        // Files left from the last run wait in the backlog
+        assert_eq!(engine.restore_queue().unwrap(), 1);
+        assert!(engine.queue.is_empty());
+        assert_eq!(engine.backlog.len(), 1);
+
+        engine.startup_jitter = 1.0;
+        assert!(!engine.backfill_due());
+        engine.startup_jitter = 0.0;
+        assert!(engine.backfill_due());
+        engine.last_backfill = Some(Instant::now());
+        assert!(!engine.backfill_due());
+
+        assert_eq!(engine.backfill_batch, 1);
+        for expected in [2, 4, 4] {
+            engine.ramp_backfill(false);
+            assert_eq!(engine.backfill_batch, expected);
+        }
+        engine.ramp_backfill(true);
+        assert_eq!(engine.backfill_batch, 1);
+    }
+}
@@ -0,0 +1,280 @@
+//! Aggregate usage stats for teams
+//!
+//! Opt-in with `teamStats.enabled`, a consent separate from syncing: orgs
+//! can see adoption without collecting transcripts. For each finished UTC
+//! day, sessions are counted per tool on this machine and only those
+//! numbers are sent, with the sessions' average length. No content, titles,
+//! paths or session IDs are included, and reports go to `/team-stats` at
+//! `teamStats.url` rather than with uploads.
+//!
+//! Before anything is sent, counts and lengths are blurred with Laplace
+//! noise scaled by `teamStats.epsilon`, session lengths are capped and each
+//! day is reported once, so what a team sees can't single out one session.
+//! Every day has a row for every tool the app has a parser for, zeros
+//! included, so which rows are sent says nothing either.
+
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, Utc};
+use rand::Rng;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{ApiError, DuplexApiClient};
+use crate::config::TeamStatsConfig;
+use crate::db::{DailySessions, Database};
+use crate::errors::ErrorCategory;
+use crate::parsers::ParserRegistry;
+
+/// Report format version
+const REPORT_VERSION: u32 = 1;
+
+/// Longest a session counts as, bounding how far one session moves a total
+const MAX_SESSION_SECONDS: i64 = 4 * 60 * 60;
+
+/// Days sent at most in one report, e.g. the first after opting in
+const MAX_DAYS: i64 = 7;
+
+/// App state key holding the last day reported, `YYYY-MM-DD`
+const REPORTED_THROUGH_KEY: &str = "team_stats_reported_through";
+
+#[derive(Error, Debug)]
+pub enum TeamStatsError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("API error: {0}")]
+    Api(#[from] ApiError),
+}
+
+impl TeamStatsError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            TeamStatsError::Sqlite(_) => ErrorCategory::Io,
+            TeamStatsError::Api(e) => e.category(),
+        }
+    }
+}
+
+/// What is sent to `/team-stats`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamReport {
+    pub version: u32,
+    /// Privacy budget the numbers were blurred with
+    pub epsilon: f64,
+    pub days: Vec<DayStats>,
+}
+
+/// One tool's use on one UTC day, blurred
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayStats {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// e.g. `claude-code`
+    pub tool: String,
+    pub sessions: u64,
+    /// Average session length, to a tenth of a minute
+    pub average_minutes: f64,
+}
+
+/// The next report due on `today`: the finished days not reported yet,
+/// with the last of them
+///
+/// `None` once every day before `today` has been reported.
+pub fn next_report(
+    db: &Database,
+    config: &TeamStatsConfig,
+    today: NaiveDate,
+) -> Result<Option<(TeamReport, NaiveDate)>, TeamStatsError> {
+    let through = today - Duration::days(1);
+    let earliest = today - Duration::days(MAX_DAYS);
+    let reported = db
+        .get_app_state(REPORTED_THROUGH_KEY)?
+        .and_then(|date| date.parse::<NaiveDate>().ok());
+    let from = match reported {
+        Some(last) => (last + Duration::days(1)).max(earliest),
+        None => earliest,
+    };
+    if from > through {
+        return Ok(None);
+    }
+
+    let sessions = db.daily_sessions(midnight(from), midnight(today), MAX_SESSION_SECONDS)?;
+    let tools: Vec<String> = ParserRegistry::new().all().map(|parser| parser.name().to_string()).collect();
+    let report = TeamReport {
+        version: REPORT_VERSION,
+        epsilon: config.epsilon,
+        days: blur(&sessions, &tools, from, through, config.epsilon, &mut rand::thread_rng()),
+    };
+    Ok(Some((report, through)))
+}
+
+/// Send the days not reported yet, returning how many day and tool rows
+/// went out
+pub async fn report(
+    db: &Database,
+    api: &DuplexApiClient,
+    config: &TeamStatsConfig,
+) -> Result<usize, TeamStatsError> {
+    let Some((report, through)) = next_report(db, config, Utc::now().date_naive())? else {
+        return Ok(0);
+    };
+    if !report.days.is_empty() {
+        api.send_team_stats(&report).await?;
+        tracing::info!("Sent team stats through {}", through);
+    }
+    db.set_app_state(REPORTED_THROUGH_KEY, &through.to_string())?;
+    Ok(report.days.len())
+}
+
+/// Unix seconds at the start of `date`, UTC
+fn midnight(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+/// A row for each day from `from` through `through` and each of `tools`,
+/// with Laplace noise added to each count and total length
+///
+/// Half the budget goes to each. One session moves a count by at most 1
+/// and a total by at most [`MAX_SESSION_SECONDS`], which sets the scales.
+/// Sessions of tools not in `tools` are left out, as their rows would show
+/// the tool was used.
+fn blur(
+    sessions: &[DailySessions],
+    tools: &[String],
+    from: NaiveDate,
+    through: NaiveDate,
+    epsilon: f64,
+    rng: &mut impl Rng,
+) -> Vec<DayStats> {
+    let epsilon = epsilon.max(f64::MIN_POSITIVE) / 2.0;
+    let counted: HashMap<(&str, &str), &DailySessions> = sessions
+        .iter()
+        .map(|day| ((day.date.as_str(), day.tool.as_str()), day))
+        .collect();
+
+    let mut days = Vec::new();
+    for date in from.iter_days().take_while(|date| *date <= through) {
+        let date = date.to_string();
+        for tool in tools {
+            let (sessions, seconds) = counted
+                .get(&(date.as_str(), tool.as_str()))
+                .map_or((0, 0), |day| (day.sessions, day.total_seconds));
+            let count = (sessions as f64 + laplace(1.0 / epsilon, rng)).round().max(0.0);
+            let total = seconds as f64 + laplace(MAX_SESSION_SECONDS as f64 / epsilon, rng);
+            let average = (total / count.max(1.0)).clamp(0.0, MAX_SESSION_SECONDS as f64);
+            days.push(DayStats {
+                date: date.clone(),
+                tool: tool.clone(),
+                sessions: count as u64,
+                average_minutes: (average / 6.0).round() / 10.0,
+            });
+        }
+    }
+    days
+}
+
+/// A draw from the Laplace distribution centred on 0
+fn laplace(scale: f64, rng: &mut impl Rng) -> f64 {
+    let u: f64 = rng.gen_range(-0.5..0.5);
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f64::MIN_POSITIVE).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SyncState, SyncStatus};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn day(date: &str, sessions: u64, total_seconds: u64) -> DailySessions {
+        DailySessions {
+            date: date.to_string(),
+            tool: "claude-code".to_string(),
+            sessions,
+            total_seconds,
+        }
+    }
+
+    #[test]
+    fn test_blur() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let sessions = [day("2026-06-01", 2, 3000), day("2026-06-02", 1, 0)];
+        let tools = ["claude-code".to_string(), "codex".to_string()];
+        let (from, through) = ("2026-06-01".parse().unwrap(), "2026-06-03".parse().unwrap());
+
+        // A budget this large adds no noise worth the name
+        let exact = blur(&sessions, &tools, from, through, 1e12, &mut rng);
+        let rows: Vec<_> = exact
+            .iter()
+            .map(|d| (d.date.as_str(), d.tool.as_str(), d.sessions, d.average_minutes))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("2026-06-01", "claude-code", 2, 25.0),
+                ("2026-06-01", "codex", 0, 0.0),
+                ("2026-06-02", "claude-code", 1, 0.0),
+                ("2026-06-02", "codex", 0, 0.0),
+                ("2026-06-03", "claude-code", 0, 0.0),
+                ("2026-06-03", "codex", 0, 0.0),
+            ]
+        );
+
+        // Unused days and tools are sent all the same
+        let blurred = blur(&sessions, &tools, from, through, 0.1, &mut rng);
+        assert_eq!(blurred.len(), 6);
+        assert_eq!(blurred[0].date, "2026-06-01");
+        assert!(blurred.iter().all(|d| (0.0..=240.0).contains(&d.average_minutes)));
+        assert_eq!(blur(&[], &tools, from, through, 0.1, &mut rng).len(), 6);
+    }
+
+    #[test]
+    fn test_next_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        let today: NaiveDate = "2026-06-10".parse().unwrap();
+        for (file_path, date) in [("/a.jsonl", "2026-06-01"), ("/b.jsonl", "2026-06-08"), ("/c.jsonl", "2026-06-10")] {
+            db.upsert_sync_state(&SyncState {
+                file_path: file_path.to_string(),
+                content_hash: "abc".to_string(),
+                last_synced_at: None,
+                last_modified_at: midnight(date.parse().unwrap()) + 3600,
+                workflow_id: None,
+                status: SyncStatus::Complete,
+                session_id: None,
+                project_path: None,
+                source: Some("codex".to_string()),
+                git: None,
+                title: None,
+            })
+            .unwrap();
+        }
+        let config = TeamStatsConfig {
+            epsilon: 1e12,
+            ..Default::default()
+        };
+
+        // The last week, up to yesterday; today isn't over
+        let (report, through) = next_report(&db, &config, today).unwrap().unwrap();
+        assert_eq!(through.to_string(), "2026-06-09");
+        let used: Vec<_> = report
+            .days
+            .iter()
+            .filter(|d| d.sessions > 0)
+            .map(|d| (d.date.as_str(), d.tool.as_str()))
+            .collect();
+        assert_eq!(used, vec![("2026-06-08", "codex")]);
+        let tools = ParserRegistry::new().all().count();
+        assert_eq!(report.days.len(), 7 * tools);
+        assert_eq!(report.days[0].date, "2026-06-03");
+        assert!(!serde_json::to_string(&report).unwrap().contains(".jsonl"));
+
+        // Each day goes out once
+        db.set_app_state(REPORTED_THROUGH_KEY, "2026-06-09").unwrap();
+        assert!(next_report(&db, &config, today).unwrap().is_none());
+        let (report, _) = next_report(&db, &config, "2026-06-11".parse().unwrap()).unwrap().unwrap();
+        assert_eq!(report.days[0].date, "2026-06-10");
+    }
+}
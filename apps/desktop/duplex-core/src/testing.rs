@@ -0,0 +1,227 @@
+//! Deterministic stand-ins for the filesystem side of sync
+//!
+//! [`TestParser`] reads conversations from an in-memory [`TestFiles`] store
+//! and [`FakeWatcher`] reports changes only when told to, so tests can drive
+//! the queue, sync engine and database without a real home directory or
+//! the timing of OS file events. Only built for tests and with the
+//! `test-support` feature.
+//!
+//! Files live under [`TestParser::ROOT`], which must not exist on disk: the
+//! engine treats a change to a missing file in an existing directory as a
+//! deletion.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::parsers::{
+    conversation_title, ContentType, Conversation, ConversationFile, ConversationParser, Message, ParserError,
+};
+use crate::watcher::FileChangeEvent;
+
+/// In-memory files shared between a test and its [`TestParser`]
+#[derive(Debug, Clone, Default)]
+pub struct TestFiles {
+    files: Arc<Mutex<BTreeMap<PathBuf, String>>>,
+}
+
+impl TestFiles {
+    /// Path of a session file in a project
+    pub fn path(project: &str, session_id: &str) -> PathBuf {
+        Path::new(TestParser::ROOT).join(project).join(format!("{}.log", session_id))
+    }
+
+    /// Replace a file's content
+    pub fn write(&self, path: &Path, content: &str) {
+        self.files.lock().unwrap().insert(path.to_path_buf(), content.to_string());
+    }
+
+    /// Add a `role: text` line to a file, creating it if needed
+    pub fn append(&self, path: &Path, role: &str, text: &str) {
+        let mut files = self.files.lock().unwrap();
+        let content = files.entry(path.to_path_buf()).or_default();
+        content.push_str(&format!("{}: {}\n", role, text));
+    }
+
+    /// Remove a file, returning whether it existed
+    pub fn remove(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().remove(path).is_some()
+    }
+
+    /// A file's content, if it exists
+    pub fn read(&self, path: &Path) -> Option<String> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+
+    /// Files at or below `dir`, in path order
+    pub fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        self.files
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.starts_with(dir))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Parser for [`TestFiles`], named `test`
+///
+/// Each file is one conversation of `role: text` lines; its session ID is
+/// the file stem and its project the directory holding it. Files only grow
+/// by whole lines, like a real transcript.
+#[derive(Debug, Clone, Default)]
+pub struct TestParser {
+    files: TestFiles,
+}
+
+impl TestParser {
+    pub const NAME: &'static str = "test";
+
+    /// Where test files live
+    pub const ROOT: &'static str = "/duplex-test";
+
+    /// Parser over `files`
+    pub fn new(files: TestFiles) -> Self {
+        Self { files }
+    }
+
+    fn not_found(file: &Path) -> ParserError {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("no test file {:?}", file)).into()
+    }
+}
+
+impl ConversationParser for TestParser {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn detect(&self, path: &Path) -> bool {
+        path.starts_with(Self::ROOT)
+    }
+
+    fn discover(&self, path: &Path) -> Vec<ConversationFile> {
+        self.files
+            .list(path)
+            .into_iter()
+            .map(|file| ConversationFile {
+                session_id: file.file_stem().map(|stem| stem.to_string_lossy().to_string()),
+                project_path: file.parent().map(Path::to_path_buf),
+                path: file,
+            })
+            .collect()
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        let content = self.read(file)?;
+        let messages = self.parse_messages(&content).unwrap_or_default();
+        Ok(Conversation {
+            source_path: file.to_path_buf(),
+            source: Self::NAME.to_string(),
+            session_id: file.file_stem().map(|stem| stem.to_string_lossy().to_string()),
+            project_path: file.parent().map(Path::to_path_buf),
+            title: conversation_title(&messages, None),
+            content,
+            content_type: ContentType::Conversation,
+        })
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        vec!["*.log"]
+    }
+
+    fn read(&self, file: &Path) -> Result<String, ParserError> {
+        self.files.read(file).ok_or_else(|| Self::not_found(file))
+    }
+
+    fn parse_messages(&self, content: &str) -> Option<Vec<Message>> {
+        let messages = content
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .map(|(role, text)| Message {
+                role: role.to_string(),
+                content: text.to_string(),
+                timestamp: None,
+                utc_offset: None,
+                tool_calls: Vec::new(),
+                model: None,
+            })
+            .collect();
+        Some(messages)
+    }
+
+    fn append_only(&self) -> bool {
+        true
+    }
+}
+
+/// Watcher whose events are raised by hand
+///
+/// Mirrors [`FileWatcher`](crate::watcher::FileWatcher): directories are
+/// watched for a parser, and [`Self::touch`] reports a change to a file in
+/// one of them as that parser's, with no debounce.
+pub struct FakeWatcher {
+    /// Watched directories and their parsers
+    watched_dirs: BTreeMap<PathBuf, String>,
+    event_tx: Sender<FileChangeEvent>,
+    event_rx: Receiver<FileChangeEvent>,
+}
+
+impl FakeWatcher {
+    pub fn new() -> Self {
+        let (event_tx, event_rx) = channel();
+        Self {
+            watched_dirs: BTreeMap::new(),
+            event_tx,
+            event_rx,
+        }
+    }
+
+    /// Watch a directory tree with the given parser
+    pub fn watch(&mut self, path: &Path, parser_name: &str) {
+        self.watched_dirs.insert(path.to_path_buf(), parser_name.to_string());
+    }
+
+    /// Stop watching a directory
+    pub fn unwatch(&mut self, path: &Path) {
+        self.watched_dirs.remove(path);
+    }
+
+    /// Report a change to `path`, returning whether a watched directory
+    /// covers it
+    ///
+    /// The most specific watched directory wins, as with the real watcher.
+    pub fn touch(&self, path: &Path) -> bool {
+        let parser_name = self
+            .watched_dirs
+            .iter()
+            .filter(|(dir, _)| path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map(|(_, parser_name)| parser_name.clone());
+        let Some(parser_name) = parser_name else {
+            return false;
+        };
+        let _ = self.event_tx.send(FileChangeEvent {
+            path: path.to_path_buf(),
+            parser_name,
+        });
+        true
+    }
+
+    /// Get the number of watched directories
+    pub fn watched_count(&self) -> usize {
+        self.watched_dirs.len()
+    }
+
+    /// Try to receive a file change event (non-blocking)
+    pub fn try_recv(&self) -> Option<FileChangeEvent> {
+        self.event_rx.try_recv().ok()
+    }
+}
+
+impl Default for FakeWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,157 @@
+//! Timestamp parsing, normalization and display
+//!
+//! Times are kept as Unix seconds in the database and sent as RFC 3339 in
+//! UTC, so conversations from machines in different timezones order the
+//! same way everywhere. Tools write their own formats: Claude Code uses
+//! RFC 3339 in UTC, others local time with an offset, local time without
+//! one, or epoch milliseconds. The offset a tool wrote is kept alongside
+//! the UTC time so the local time of day on the writing machine isn't lost.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+
+use crate::parsers::Message;
+
+/// Epoch values above this are taken as milliseconds (it is in 1973 as ms,
+/// and in the year 5138 as seconds)
+const EPOCH_MILLIS_FROM: i64 = 100_000_000_000;
+
+/// A timestamp as a source tool wrote it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceTime {
+    pub utc: DateTime<Utc>,
+    /// The UTC offset written with the time; `None` for local times without
+    /// one and for epoch values
+    pub offset: Option<FixedOffset>,
+}
+
+impl SourceTime {
+    /// Unix seconds
+    pub fn unix(&self) -> i64 {
+        self.utc.timestamp()
+    }
+}
+
+/// Parse a timestamp written by a source tool
+///
+/// Accepts RFC 3339, `YYYY-MM-DD HH:MM:SS` with an optional offset and
+/// fractional seconds, and epoch seconds or milliseconds. Times without an
+/// offset are taken as local time on this machine.
+pub fn parse(value: &str) -> Option<SourceTime> {
+    let value = value.trim();
+    let with_offset = DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%:z"))
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%z"));
+    if let Ok(time) = with_offset {
+        return Some(SourceTime {
+            utc: time.with_timezone(&Utc),
+            offset: Some(*time.offset()),
+        });
+    }
+
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        let epoch: i64 = value.parse().ok()?;
+        let utc = if epoch >= EPOCH_MILLIS_FROM {
+            DateTime::from_timestamp_millis(epoch)?
+        } else {
+            DateTime::from_timestamp(epoch, 0)?
+        };
+        return Some(SourceTime { utc, offset: None });
+    }
+
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some(SourceTime {
+        utc: local.with_timezone(&Utc),
+        offset: None,
+    })
+}
+
+/// Format Unix seconds as RFC 3339 in UTC, e.g. `2025-01-01T10:00:00Z`
+pub fn to_rfc3339(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Serialize Unix seconds with [`to_rfc3339`], for `#[serde(serialize_with)]`
+pub fn serialize_rfc3339<S: serde::Serializer>(timestamp: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_rfc3339(*timestamp))
+}
+
+/// Format Unix seconds in local time for display, e.g. `2025-01-01 11:00`
+pub fn format_local(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Rewrite message timestamps as RFC 3339 in UTC, recording the offset the
+/// tool wrote in [`Message::utc_offset`]
+///
+/// Timestamps that can't be parsed are left as they are.
+pub fn normalized(mut messages: Vec<Message>) -> Vec<Message> {
+    for message in &mut messages {
+        let Some(time) = message.timestamp.as_deref().and_then(parse) else {
+            continue;
+        };
+        message.timestamp = Some(time.utc.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        message.utc_offset = time.offset.map(|offset| offset.to_string());
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let utc = parse("2025-01-01T10:00:00Z").unwrap();
+        assert_eq!(utc.unix(), 1_735_725_600);
+        assert_eq!(utc.offset, FixedOffset::east_opt(0));
+
+        // The same instant written in another timezone
+        let tokyo = parse("2025-01-01T19:00:00.250+09:00").unwrap();
+        assert_eq!(tokyo.unix(), utc.unix());
+        assert_eq!(tokyo.offset.unwrap().to_string(), "+09:00");
+        assert_eq!(parse("2025-01-01 05:00:00-05:00").unwrap().unix(), utc.unix());
+
+        for epoch in ["1735725600", "1735725600000"] {
+            assert_eq!(parse(epoch), Some(SourceTime { utc: utc.utc, offset: None }));
+        }
+
+        let naive = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let local = Local.from_local_datetime(&naive).earliest().unwrap().timestamp();
+        assert_eq!(parse("2025-01-01T10:00:00").unwrap().unix(), local);
+        assert_eq!(parse("2025-01-01 10:00:00").unwrap().offset, None);
+
+        assert_eq!(parse("yesterday"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_normalized() {
+        let message = |timestamp: &str| Message {
+            role: "user".to_string(),
+            content: String::new(),
+            timestamp: Some(timestamp.to_string()),
+            utc_offset: None,
+            tool_calls: vec![],
+            model: None,
+        };
+        let messages = normalized(vec![
+            message("2025-01-01T19:00:00.250+09:00"),
+            message("1735725600000"),
+            message("not a time"),
+        ]);
+        assert_eq!(messages[0].timestamp.as_deref(), Some("2025-01-01T10:00:00.250Z"));
+        assert_eq!(messages[0].utc_offset.as_deref(), Some("+09:00"));
+        assert_eq!(messages[1].timestamp.as_deref(), Some("2025-01-01T10:00:00Z"));
+        assert_eq!(messages[1].utc_offset, None);
+        assert_eq!(messages[2].timestamp.as_deref(), Some("not a time"));
+
+        assert_eq!(to_rfc3339(1_735_725_600), "2025-01-01T10:00:00Z");
+    }
+}
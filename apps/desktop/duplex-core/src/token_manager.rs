@@ -6,9 +6,11 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
 use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
 
 use crate::auth::{get_client_id, refresh_token, AuthError};
 use crate::config::SecureTokenStorage;
+use crate::metrics;
 
 /// Interval for checking token expiry (30 seconds)
 const CHECK_INTERVAL_SECS: u64 = 30;
@@ -55,8 +57,8 @@ impl TokenManager {
     /// Start the background refresh task
     ///
     /// This spawns a tokio task that periodically checks token expiry
-    /// and refreshes tokens before they expire.
-    pub fn start_background_refresh(&self) -> tokio::task::JoinHandle<()> {
+    /// and refreshes tokens before they expire, until `shutdown` is cancelled.
+    pub fn start_background_refresh(&self, shutdown: CancellationToken) -> tokio::task::JoinHandle<()> {
         let storage = self.storage.clone();
         let running = self.running.clone();
 
@@ -70,7 +72,13 @@ impl TokenManager {
             let mut check_interval = interval(Duration::from_secs(CHECK_INTERVAL_SECS));
 
             loop {
-                check_interval.tick().await;
+                tokio::select! {
+                    _ = check_interval.tick() => {}
+                    _ = shutdown.cancelled() => {
+                        tracing::info!("Token manager stopping");
+                        break;
+                    }
+                }
 
                 // Check if we should stop
                 {
@@ -99,6 +107,7 @@ impl TokenManager {
                                 }
                                 Err(e) => {
                                     tracing::error!("Failed to refresh token: {}", e);
+                                    metrics::record_error(e.category());
                                     // Don't clear tokens on refresh failure - they might still work
                                     // or the user might want to try again
                                 }
@@ -0,0 +1,275 @@
+//! Removing the app's local footprint
+//!
+//! `duplex uninstall` removes any login item that starts the app and, when
+//! asked, deletes the sync database, config and credentials. Uploaded
+//! conversations stay on the server; [`remote_summary`] describes what is
+//! left there so it can be deleted from the web app.
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::config::{self, SecureTokenStorage};
+use crate::db::Database;
+
+/// Name the app registers login items under
+const APP_NAME: &str = "Duplex Stream";
+
+/// Bundle identifier, used for the LaunchAgent label
+#[cfg(target_os = "macos")]
+const APP_IDENTIFIER: &str = "app.duplex.desktop";
+
+/// Registry key holding per-user login items
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+#[derive(Error, Debug)]
+pub enum UninstallError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Config error: {0}")]
+    Config(#[from] config::ConfigError),
+    #[error("Database error: {0}")]
+    Database(#[from] crate::db::DatabaseError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Failed to remove login item {0}")]
+    LoginItem(String),
+}
+
+/// A registration that starts the app at login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginItem {
+    /// LaunchAgent plist, XDG autostart entry or systemd user unit
+    File(PathBuf),
+    /// Value under the per-user `Run` registry key
+    RegistryValue(String),
+}
+
+impl std::fmt::Display for LoginItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoginItem::File(path) => write!(f, "{}", path.display()),
+            LoginItem::RegistryValue(name) => write!(f, "registry Run value \"{}\"", name),
+        }
+    }
+}
+
+/// Local state that can be purged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalData {
+    /// Sync history, tags and provisioned workspaces
+    Database,
+    /// Config file, cached org policy and runtime port files
+    Config,
+    /// Keyring tokens and credential files
+    Credentials,
+}
+
+impl LocalData {
+    pub const ALL: [LocalData; 3] = [LocalData::Database, LocalData::Config, LocalData::Credentials];
+
+    /// Human-readable name
+    pub fn label(&self) -> &'static str {
+        match self {
+            LocalData::Database => "sync database",
+            LocalData::Config => "config",
+            LocalData::Credentials => "credentials",
+        }
+    }
+
+    /// Files holding this data that exist on disk
+    pub fn paths(&self) -> Result<Vec<PathBuf>, UninstallError> {
+        let candidates = match self {
+            LocalData::Database => {
+                let db = config::get_database_path()?;
+                vec![db.with_extension("db-wal"), db.with_extension("db-shm"), db]
+            }
+            LocalData::Config => vec![
+                config::get_config_path()?,
+                config::get_schema_path()?,
+                config::get_org_policy_path()?,
+                config::get_org_baseline_path()?,
+                config::get_control_port_path()?,
+                config::get_editor_port_path()?,
+            ],
+            LocalData::Credentials => {
+                let mut paths = vec![config::get_credentials_path()?];
+                paths.extend(config::get_token_file_path().ok());
+                paths.extend(config::get_control_key_path().ok());
+                paths.extend(config::get_local_api_token_path().ok());
+                paths.extend(config::get_editor_token_path().ok());
+                paths
+            }
+        };
+        Ok(candidates.into_iter().filter(|p| p.exists()).collect())
+    }
+
+    /// Delete this data, returning the files removed
+    pub fn purge(&self) -> Result<Vec<PathBuf>, UninstallError> {
+        if *self == LocalData::Credentials {
+            SecureTokenStorage::new().clear_tokens()?;
+        }
+
+        let paths = self.paths()?;
+        for path in &paths {
+            std::fs::remove_file(path)?;
+            tracing::info!("Deleted {:?}", path);
+        }
+
+        // Leave the config directory only if something else still lives there
+        if let Ok(dir) = config::get_config_dir() {
+            let _ = std::fs::remove_dir(dir);
+        }
+        Ok(paths)
+    }
+}
+
+/// What remains on the server after uninstalling
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RemoteSummary {
+    /// Conversations uploaded from this machine
+    pub conversations: usize,
+    /// Workspaces auto-provisioned from this machine
+    pub workspaces: Vec<String>,
+}
+
+/// Summarize uploads recorded in the local database
+///
+/// Returns an empty summary when there is no database, without creating one.
+pub fn remote_summary() -> Result<RemoteSummary, UninstallError> {
+    if !config::get_database_path()?.exists() {
+        return Ok(RemoteSummary::default());
+    }
+    summarize(&Database::open()?)
+}
+
+fn summarize(db: &Database) -> Result<RemoteSummary, UninstallError> {
+    Ok(RemoteSummary {
+        conversations: db.count_uploaded()?,
+        workspaces: db.list_workspaces()?,
+    })
+}
+
+/// Login items registered on this machine
+pub fn login_items() -> Vec<LoginItem> {
+    #[cfg(target_os = "windows")]
+    {
+        let registered = std::process::Command::new("reg")
+            .args(["query", RUN_KEY, "/v", APP_NAME])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if registered {
+            return vec![LoginItem::RegistryValue(APP_NAME.to_string())];
+        }
+        Vec::new()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        login_item_paths()
+            .into_iter()
+            .filter(|p| p.exists())
+            .map(LoginItem::File)
+            .collect()
+    }
+}
+
+/// Where a login item for the app may have been written
+#[cfg(not(target_os = "windows"))]
+fn login_item_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    #[cfg(target_os = "macos")]
+    if let Some(home) = dirs::home_dir() {
+        let agents = home.join("Library").join("LaunchAgents");
+        paths.push(agents.join(format!("{}.plist", APP_IDENTIFIER)));
+        paths.push(agents.join(format!("{}.plist", APP_NAME)));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    if let Some(config_dir) = dirs::config_dir() {
+        let autostart = config_dir.join("autostart");
+        paths.push(autostart.join(format!("{}.desktop", APP_NAME)));
+        paths.push(autostart.join("duplex.desktop"));
+        paths.push(config_dir.join("systemd").join("user").join("duplex.service"));
+    }
+
+    paths
+}
+
+/// Unregister a login item so the app no longer starts at login
+pub fn remove_login_item(item: &LoginItem) -> Result<(), UninstallError> {
+    match item {
+        LoginItem::File(path) => {
+            // Stop the service manager tracking it before the file goes away
+            #[cfg(target_os = "macos")]
+            let _ = std::process::Command::new("launchctl")
+                .arg("unload")
+                .arg(path)
+                .output();
+
+            #[cfg(not(target_os = "macos"))]
+            if path.extension().is_some_and(|ext| ext == "service") {
+                let _ = std::process::Command::new("systemctl")
+                    .args(["--user", "disable", "duplex.service"])
+                    .output();
+            }
+
+            std::fs::remove_file(path)?;
+        }
+        LoginItem::RegistryValue(name) => {
+            #[cfg(target_os = "windows")]
+            {
+                let removed = std::process::Command::new("reg")
+                    .args(["delete", RUN_KEY, "/v", name, "/f"])
+                    .output()
+                    .is_ok_and(|output| output.status.success());
+                if !removed {
+                    return Err(UninstallError::LoginItem(item.to_string()));
+                }
+            }
+
+            #[cfg(not(target_os = "windows"))]
+            return Err(UninstallError::LoginItem(name.clone()));
+        }
+    }
+
+    tracing::info!("Removed login item {}", item);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SyncState, SyncStatus};
+
+    #[test]
+    fn test_summarize_uploads() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open_at(&dir.path().join("test.db")).unwrap();
+        assert_eq!(summarize(&db).unwrap(), RemoteSummary::default());
+
+        for file_path in ["/a.jsonl", "/b.jsonl"] {
+            db.upsert_sync_state(&SyncState {
+                file_path: file_path.to_string(),
+                content_hash: "abc123".to_string(),
+                last_synced_at: None,
+                last_modified_at: 1234567890,
+                workflow_id: None,
+                status: SyncStatus::Pending,
+                session_id: None,
+                project_path: None,
+                source: Some("claude-code".to_string()),
+                git: None,
+                title: None,
+            })
+            .unwrap();
+        }
+        db.mark_complete("/a.jsonl", "wf-1").unwrap();
+        db.set_workspace("/work/app", "ws-1").unwrap();
+
+        let summary = summarize(&db).unwrap();
+        assert_eq!(summary.conversations, 1);
+        assert_eq!(summary.workspaces, vec!["ws-1"]);
+    }
+}
@@ -0,0 +1,361 @@
+//! Disk usage of agent history
+//!
+//! Measures the conversation files every parser can find, grouped by tool
+//! and by project, along with how much of it has already been synced. Used
+//! by `duplex usage` and the tray's storage report to help clean up old
+//! agent logs.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use crate::config::Config;
+use crate::db::{Database, SyncStatus};
+use crate::export::escape_html;
+use crate::parsers::{ClaudeCodeParser, CodexParser, ConversationParser, GeminiParser, ParserRegistry};
+use crate::projects::ProjectNames;
+use crate::watcher::expand_path;
+
+/// Size and sync state of one conversation file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileUsage {
+    pub path: PathBuf,
+    /// Parser that found the file
+    pub tool: String,
+    pub session_id: Option<String>,
+    pub project_path: Option<PathBuf>,
+    pub bytes: u64,
+    /// Unix seconds
+    pub modified_at: Option<i64>,
+    /// Whether the file has been uploaded
+    pub synced: bool,
+}
+
+/// Totals for a tool or project
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageGroup {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub synced_bytes: u64,
+}
+
+impl UsageGroup {
+    fn add(&mut self, file: &FileUsage) {
+        self.files += 1;
+        self.bytes += file.bytes;
+        if file.synced {
+            self.synced_bytes += file.bytes;
+        }
+    }
+}
+
+/// Disk usage report, groups and files sorted largest first
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReport {
+    pub total: UsageGroup,
+    pub by_tool: Vec<UsageGroup>,
+    pub by_project: Vec<UsageGroup>,
+    pub largest: Vec<FileUsage>,
+}
+
+/// Measure agent history in every known conversation directory
+pub fn scan(registry: &ParserRegistry, config: &Config, db: &Database, limit: usize) -> UsageReport {
+    let mut files = Vec::new();
+
+    for (dir, parser) in history_dirs(registry, config) {
+        for file in parser.discover(&dir) {
+            let Ok(metadata) = std::fs::metadata(&file.path) else {
+                continue;
+            };
+            let synced = db
+                .get_sync_state(&file.path.to_string_lossy())
+                .ok()
+                .flatten()
+                .is_some_and(|state| state.status == SyncStatus::Complete);
+
+            files.push(FileUsage {
+                tool: parser.name().to_string(),
+                session_id: file.session_id,
+                project_path: file.project_path,
+                bytes: metadata.len(),
+                modified_at: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64),
+                synced,
+                path: file.path,
+            });
+        }
+    }
+
+    let names =
+        ProjectNames::load(&config.projects, db).unwrap_or_else(|_| ProjectNames::from_config(&config.projects));
+    summarize(files, limit, &names)
+}
+
+/// Directories holding agent history, with the parser that understands each
+///
+/// Claude Code's todo and plan directories are included whether or not
+/// artifact sync is enabled; they take up space either way.
+pub fn history_dirs<'a>(registry: &'a ParserRegistry, config: &Config) -> Vec<(PathBuf, &'a dyn ConversationParser)> {
+    let mut dirs = Vec::new();
+
+    if config.discovery.auto_discover {
+        if let Some(parser) = registry.get("claude-code") {
+            dirs.extend(ClaudeCodeParser::projects_dirs(&config.parsers.claude_code).into_iter().map(|dir| (dir, parser)));
+        }
+        if let Some(parser) = registry.get("claude-code-artifacts") {
+            for claude_dir in ClaudeCodeParser::config_roots(&config.parsers.claude_code) {
+                dirs.push((claude_dir.join("todos"), parser));
+                dirs.push((claude_dir.join("plans"), parser));
+            }
+        }
+        if let Some(parser) = registry.get("codex") {
+            dirs.extend(CodexParser::sessions_dirs(&config.parsers.codex).into_iter().map(|dir| (dir, parser)));
+        }
+        if let Some(parser) = registry.get("gemini") {
+            dirs.extend(GeminiParser::tmp_dirs(&config.parsers.gemini).into_iter().map(|dir| (dir, parser)));
+        }
+    }
+
+    for parser in registry.all() {
+        dirs.extend(parser.watch_dirs().into_iter().map(|dir| (dir, parser)));
+    }
+
+    for path in &config.discovery.additional_paths {
+        let path = expand_path(path);
+        if let Some(parser) = registry.detect(&path) {
+            dirs.push((path, parser));
+        }
+    }
+
+    dirs.retain(|(dir, _)| dir.exists());
+    dirs
+}
+
+/// Group files by tool and project and keep the `limit` largest
+fn summarize(mut files: Vec<FileUsage>, limit: usize, names: &ProjectNames) -> UsageReport {
+    let mut total = UsageGroup {
+        name: "total".to_string(),
+        ..Default::default()
+    };
+    let mut by_tool: BTreeMap<String, UsageGroup> = BTreeMap::new();
+    let mut by_project: BTreeMap<String, UsageGroup> = BTreeMap::new();
+
+    for file in &files {
+        total.add(file);
+        by_tool
+            .entry(file.tool.clone())
+            .or_insert_with_key(|name| UsageGroup { name: name.clone(), ..Default::default() })
+            .add(file);
+        let project = file
+            .project_path
+            .as_ref()
+            .map(|p| names.display(&p.to_string_lossy()))
+            .unwrap_or_else(|| "(no project)".to_string());
+        by_project
+            .entry(project)
+            .or_insert_with_key(|name| UsageGroup { name: name.clone(), ..Default::default() })
+            .add(file);
+    }
+
+    let sorted = |groups: BTreeMap<String, UsageGroup>| {
+        let mut groups: Vec<UsageGroup> = groups.into_values().collect();
+        groups.sort_by_key(|g| std::cmp::Reverse(g.bytes));
+        groups
+    };
+
+    files.sort_by_key(|f| std::cmp::Reverse(f.bytes));
+    files.truncate(limit);
+
+    UsageReport {
+        total,
+        by_tool: sorted(by_tool),
+        by_project: sorted(by_project),
+        largest: files,
+    }
+}
+
+/// Human-readable byte count
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn synced_percent(group: &UsageGroup) -> u64 {
+    (group.synced_bytes * 100).checked_div(group.bytes).unwrap_or(100)
+}
+
+/// Render the report as plain text for the terminal
+pub fn render_text(report: &UsageReport) -> String {
+    let mut out = format!(
+        "Agent history: {} in {} files, {}% synced\n",
+        format_bytes(report.total.bytes),
+        report.total.files,
+        synced_percent(&report.total)
+    );
+
+    for (title, groups) in [("By tool", &report.by_tool), ("By project", &report.by_project)] {
+        out.push_str(&format!("\n{}:\n", title));
+        for group in groups {
+            out.push_str(&format!(
+                "  {:>10}  {:>5} files  {:>3}% synced  {}\n",
+                format_bytes(group.bytes),
+                group.files,
+                synced_percent(group),
+                group.name
+            ));
+        }
+    }
+
+    out.push_str("\nLargest conversations:\n");
+    for file in &report.largest {
+        out.push_str(&format!(
+            "  {:>10}  {}  {}\n",
+            format_bytes(file.bytes),
+            if file.synced { "synced  " } else { "unsynced" },
+            file.path.display()
+        ));
+    }
+
+    out
+}
+
+/// Render the report as a standalone HTML page
+pub fn render_html(report: &UsageReport) -> String {
+    let mut body = format!(
+        "<h1>Agent history</h1>\n<p>{} in {} files, {}% synced</p>\n",
+        format_bytes(report.total.bytes),
+        report.total.files,
+        synced_percent(&report.total)
+    );
+
+    for (title, groups) in [("By tool", &report.by_tool), ("By project", &report.by_project)] {
+        body.push_str(&format!(
+            "<h2>{}</h2>\n<table>\n<tr><th>Size</th><th>Files</th><th>Synced</th><th>Name</th></tr>\n",
+            title
+        ));
+        for group in groups {
+            body.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}%</td><td><code>{}</code></td></tr>\n",
+                format_bytes(group.bytes),
+                group.files,
+                synced_percent(group),
+                escape_html(&group.name)
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+
+    body.push_str("<h2>Largest conversations</h2>\n<table>\n<tr><th>Size</th><th>Synced</th><th>File</th></tr>\n");
+    for file in &report.largest {
+        body.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td><code>{}</code></td></tr>\n",
+            format_bytes(file.bytes),
+            if file.synced { "yes" } else { "no" },
+            escape_html(&file.path.to_string_lossy())
+        ));
+    }
+    body.push_str("</table>\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Agent history usage</title>
+<style>
+body {{ font-family: system-ui; max-width: 960px; margin: 40px auto; padding: 0 20px; color: #1f2328; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ text-align: left; padding: 4px 8px; border-bottom: 1px solid #d0d7de; }}
+td:first-child {{ white-space: nowrap; }}
+code {{ word-break: break-all; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        body
+    )
+}
+
+/// Write the HTML report to the config directory for opening in a browser
+pub fn write_html_report(report: &UsageReport) -> Result<PathBuf, std::io::Error> {
+    let dir = crate::config::get_config_dir()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, e.to_string()))?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("usage-report.html");
+    std::fs::write(&path, render_html(report))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectsConfig;
+
+    fn file(path: &str, tool: &str, project: Option<&str>, bytes: u64, synced: bool) -> FileUsage {
+        FileUsage {
+            path: PathBuf::from(path),
+            tool: tool.to_string(),
+            session_id: None,
+            project_path: project.map(PathBuf::from),
+            bytes,
+            modified_at: None,
+            synced,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let report = summarize(
+            vec![
+                file("/a.jsonl", "claude-code", Some("/work/app"), 300, true),
+                file("/b.jsonl", "claude-code", Some("/work/api"), 900, false),
+                file("/todo.json", "claude-code-artifacts", None, 100, true),
+            ],
+            2,
+            &ProjectNames::from_config(&ProjectsConfig {
+                aliases: [("/work/app".to_string(), "app".to_string())].into(),
+                ..Default::default()
+            }),
+        );
+
+        assert_eq!(report.total.bytes, 1300);
+        assert_eq!(report.total.synced_bytes, 400);
+        assert_eq!(synced_percent(&report.total), 30);
+
+        assert_eq!(report.by_tool[0].name, "claude-code");
+        assert_eq!(report.by_tool[0].files, 2);
+        assert_eq!(report.by_project[0].name, "/work/api");
+        assert_eq!(report.by_project[1].name, "app");
+        assert_eq!(report.by_project[2].name, "(no project)");
+
+        assert_eq!(report.largest.len(), 2);
+        assert_eq!(report.largest[0].path, PathBuf::from("/b.jsonl"));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+}
@@ -0,0 +1,610 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_debouncer_mini::{new_debouncer, DebouncedEventKind, Debouncer};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::metrics;
+use crate::parsers::{ClaudeCodeParser, CodexParser, ConversationParser, GeminiParser, ParserRegistry, MEMORY_FILES};
+use crate::policy::{PolicyError, ProjectFilter};
+
+/// Most files with changes waiting for the sync engine
+///
+/// A runaway tool rewriting thousands of files would otherwise queue
+/// events faster than the engine drains them.
+const EVENT_CAPACITY: usize = 4096;
+
+/// Advice when the OS runs out of file watches
+const WATCH_LIMIT_HINT: &str = if cfg!(target_os = "linux") {
+    "Increase inotify limits with `sudo sysctl fs.inotify.max_user_watches=524288` (add it to /etc/sysctl.conf to keep it)"
+} else {
+    "The system ran out of file watches; remove large directories from discovery.additionalPaths"
+};
+
+#[derive(Error, Debug)]
+pub enum WatcherError {
+    #[error("Notify error: {0}")]
+    Notify(#[from] notify::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Path not found: {0}")]
+    PathNotFound(PathBuf),
+    #[error("Invalid watch pattern: {0}")]
+    Pattern(#[from] globset::Error),
+    #[error("Invalid project pattern: {0}")]
+    Projects(#[from] PolicyError),
+}
+
+impl WatcherError {
+    /// What a user can do about this error
+    pub fn user_hint(&self) -> &'static str {
+        let io = match self {
+            WatcherError::Notify(notify::Error {
+                kind: notify::ErrorKind::MaxFilesWatch,
+                ..
+            }) => return WATCH_LIMIT_HINT,
+            WatcherError::Notify(notify::Error {
+                kind: notify::ErrorKind::Io(e),
+                ..
+            })
+            | WatcherError::Io(e) => e,
+            WatcherError::Notify(_) => return "Check that the watched directories exist and are readable",
+            WatcherError::PathNotFound(_) => return "Check discovery.additionalPaths in config.jsonc",
+            WatcherError::Pattern(_) => return "Fix watchPatterns in parsers.external in config.jsonc",
+            WatcherError::Projects(_) => return "Fix sync.includeProjects or sync.excludeProjects in config.jsonc",
+        };
+        // inotify reports running out of watches as a full disk
+        if cfg!(target_os = "linux") && io.raw_os_error() == Some(28) {
+            WATCH_LIMIT_HINT
+        } else {
+            "Check that the watched directories exist and are readable"
+        }
+    }
+}
+
+/// Event emitted when a file is ready to sync
+#[derive(Debug, Clone)]
+pub struct FileChangeEvent {
+    /// Path to the changed file
+    pub path: PathBuf,
+    /// Name of the parser that handles this file
+    pub parser_name: String,
+}
+
+/// File changes waiting for the sync engine, at most one per file
+///
+/// While the engine keeps up each change passes straight through. When it
+/// falls behind, a change to a file already waiting is merged into the
+/// waiting one, since the engine reads the file as it is when it gets to
+/// it; once `capacity` files are waiting, changes to other files are
+/// dropped and sync on their next change. Both are counted in
+/// [`metrics::change_counts`].
+pub struct EventQueue {
+    pending: Mutex<PendingEvents>,
+    ready: Condvar,
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct PendingEvents {
+    events: VecDeque<FileChangeEvent>,
+    paths: HashSet<PathBuf>,
+    /// Whether the queue has been full since it last had room, so a storm
+    /// logs once
+    full: bool,
+}
+
+impl EventQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            pending: Mutex::default(),
+            ready: Condvar::new(),
+            capacity,
+        }
+    }
+
+    fn push(&self, event: FileChangeEvent) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.paths.contains(&event.path) {
+            metrics::record_coalesced_change();
+            return;
+        }
+        if pending.events.len() >= self.capacity {
+            if !pending.full {
+                tracing::warn!(
+                    "{} files are waiting to sync; dropping changes to other files until they drain",
+                    self.capacity
+                );
+                pending.full = true;
+            }
+            metrics::record_dropped_change();
+            return;
+        }
+
+        pending.full = false;
+        pending.paths.insert(event.path.clone());
+        pending.events.push_back(event);
+        self.ready.notify_one();
+    }
+
+    /// Take the oldest waiting change without blocking
+    pub fn try_recv(&self) -> Result<FileChangeEvent, TryRecvError> {
+        let mut pending = self.pending.lock().unwrap();
+        Self::pop(&mut pending).ok_or(TryRecvError::Empty)
+    }
+
+    /// Take the oldest waiting change, waiting up to `timeout` for one
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<FileChangeEvent, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut pending = self.pending.lock().unwrap();
+        loop {
+            if let Some(event) = Self::pop(&mut pending) {
+                return Ok(event);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            pending = self.ready.wait_timeout(pending, remaining).unwrap().0;
+        }
+    }
+
+    /// Number of files with changes waiting
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().events.len()
+    }
+
+    /// Whether no changes are waiting
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn pop(pending: &mut PendingEvents) -> Option<FileChangeEvent> {
+        let event = pending.events.pop_front()?;
+        pending.paths.remove(&event.path);
+        Some(event)
+    }
+}
+
+/// A watched directory and the files in it that matter
+struct WatchedDir {
+    parser_name: String,
+    /// Matched against file names
+    patterns: GlobSet,
+    recursive: bool,
+}
+
+/// Manages file watching for conversation files
+pub struct FileWatcher {
+    /// The debouncer that wraps the watcher
+    debouncer: Debouncer<RecommendedWatcher>,
+    /// Map of watched directories to their parsers and patterns
+    watched_dirs: Arc<Mutex<HashMap<PathBuf, WatchedDir>>>,
+    /// Projects whose changes are reported
+    projects: Arc<Mutex<ProjectFilter>>,
+    /// File change events waiting to be taken
+    events: Arc<EventQueue>,
+}
+
+impl FileWatcher {
+    /// Create a new file watcher with the given debounce duration
+    pub fn new(debounce_duration: Duration) -> Result<Self, WatcherError> {
+        let events = Arc::new(EventQueue::new(EVENT_CAPACITY));
+        let watched_dirs: Arc<Mutex<HashMap<PathBuf, WatchedDir>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+
+        let watched_dirs_clone = watched_dirs.clone();
+        let projects: Arc<Mutex<ProjectFilter>> = Arc::default();
+        let projects_clone = projects.clone();
+        let events_clone = events.clone();
+
+        // Create the debouncer with our event handler
+        let debouncer = new_debouncer(
+            debounce_duration,
+            move |res: Result<Vec<notify_debouncer_mini::DebouncedEvent>, notify::Error>| {
+                match res {
+                    Ok(events) => {
+                        for event in events {
+                            if event.kind == DebouncedEventKind::Any {
+                                let path = &event.path;
+
+                                // Check if this file is one a watched directory cares about
+                                if let Some(parser_name) = find_parser_for_path(path, &watched_dirs_clone)
+                                    .filter(|parser_name| project_allowed(path, parser_name, &projects_clone))
+                                {
+                                    events_clone.push(FileChangeEvent {
+                                        path: path.clone(),
+                                        parser_name,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Watch error: {:?}", e);
+                    }
+                }
+            },
+        )?;
+
+        Ok(Self {
+            debouncer,
+            watched_dirs,
+            projects,
+            events,
+        })
+    }
+
+    /// Watch a directory tree for `.jsonl` files with the given parser
+    pub fn watch(&mut self, path: &Path, parser_name: &str) -> Result<(), WatcherError> {
+        self.watch_matching(path, parser_name, &["*.jsonl"], true)
+    }
+
+    /// Watch a directory for files whose names match one of `patterns`
+    pub fn watch_matching(
+        &mut self,
+        path: &Path,
+        parser_name: &str,
+        patterns: &[&str],
+        recursive: bool,
+    ) -> Result<(), WatcherError> {
+        if !path.exists() {
+            return Err(WatcherError::PathNotFound(path.to_path_buf()));
+        }
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern)?);
+        }
+        let patterns = builder.build()?;
+
+        // Add to watcher
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        self.debouncer.watcher().watch(path, mode)?;
+
+        // Track the directory and its parser
+        let mut dirs = self.watched_dirs.lock().unwrap();
+        dirs.insert(
+            path.to_path_buf(),
+            WatchedDir {
+                parser_name: parser_name.to_string(),
+                patterns,
+                recursive,
+            },
+        );
+
+        tracing::info!("Watching {:?} with parser '{}'", path, parser_name);
+        Ok(())
+    }
+
+    /// Only report changes in projects `filter` allows
+    pub fn set_project_filter(&self, filter: ProjectFilter) {
+        *self.projects.lock().unwrap() = filter;
+    }
+
+    /// Stop watching a directory
+    pub fn unwatch(&mut self, path: &Path) -> Result<(), WatcherError> {
+        self.debouncer.watcher().unwatch(path)?;
+
+        let mut dirs = self.watched_dirs.lock().unwrap();
+        dirs.remove(path);
+
+        tracing::info!("Stopped watching {:?}", path);
+        Ok(())
+    }
+
+    /// Stop watching every directory handled by a parser, returning how many
+    pub fn unwatch_parser(&mut self, parser_name: &str) -> Result<usize, WatcherError> {
+        let paths: Vec<PathBuf> = self
+            .watched_dirs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, dir)| dir.parser_name == parser_name)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in &paths {
+            self.unwatch(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Get the number of watched directories
+    pub fn watched_count(&self) -> usize {
+        self.watched_dirs.lock().unwrap().len()
+    }
+
+    /// Get the queue of file change events
+    pub fn events(&self) -> &EventQueue {
+        &self.events
+    }
+
+    /// Try to receive a file change event (non-blocking)
+    pub fn try_recv(&self) -> Option<FileChangeEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+/// Whether a changed file's project may sync, as far as its location tells
+///
+/// Only Claude Code names the project in its layout; other files are
+/// checked by the sync engine once parsed.
+fn project_allowed(path: &Path, parser_name: &str, projects: &Mutex<ProjectFilter>) -> bool {
+    let projects = projects.lock().unwrap();
+    if projects.is_empty() || parser_name != "claude-code" {
+        return true;
+    }
+    ClaudeCodeParser::project_path_of_file(path).is_none_or(|project| projects.allows(&project))
+}
+
+/// Find the parser name for a given file path
+///
+/// The most specific watched directory whose patterns match the file wins.
+fn find_parser_for_path(path: &Path, watched_dirs: &Arc<Mutex<HashMap<PathBuf, WatchedDir>>>) -> Option<String> {
+    let dirs = watched_dirs.lock().unwrap();
+    let filename = path.file_name()?;
+
+    dirs.iter()
+        .filter(|(watched_path, dir)| {
+            if dir.recursive {
+                path.starts_with(watched_path)
+            } else {
+                path.parent() == Some(watched_path.as_path())
+            }
+        })
+        .filter(|(_, dir)| dir.patterns.is_match(filename))
+        .max_by_key(|(watched_path, _)| watched_path.components().count())
+        .map(|(_, dir)| dir.parser_name.clone())
+}
+
+/// Discover and watch all known conversation directories for enabled parsers
+pub fn discover_and_watch(
+    watcher: &mut FileWatcher,
+    registry: &ParserRegistry,
+    config: &crate::config::Config,
+) -> Result<usize, WatcherError> {
+    watcher.set_project_filter(ProjectFilter::from_config(&config.sync)?);
+
+    let mut count = 0;
+    for parser in registry.all().filter(|p| registry.is_enabled(p.name())) {
+        count += watch_parser(watcher, registry, config, parser.name())?;
+    }
+
+    for path_str in &config.discovery.additional_paths {
+        let path = expand_path(path_str);
+        if !path.exists() {
+            tracing::warn!("Configured path does not exist: {:?}", path);
+        } else if registry.detect(&path).is_none() {
+            tracing::warn!("No parser found for path: {:?}", path);
+        }
+    }
+
+    tracing::info!("Discovered and watching {} directories", count);
+    Ok(count)
+}
+
+/// Watch the directories one parser handles, returning how many
+///
+/// Used at startup and when a parser is enabled at runtime.
+pub fn watch_parser(
+    watcher: &mut FileWatcher,
+    registry: &ParserRegistry,
+    config: &crate::config::Config,
+    parser_name: &str,
+) -> Result<usize, WatcherError> {
+    let mut count = 0;
+
+    // Auto-discover known locations if enabled
+    if config.discovery.auto_discover && parser_name == "claude-code" {
+        // Claude Code projects directory under each config root
+        let projects_dirs = ClaudeCodeParser::projects_dirs(&config.parsers.claude_code);
+        if projects_dirs.is_empty() {
+            tracing::debug!("No Claude Code projects directory found");
+        }
+        for claude_projects in projects_dirs {
+            watcher.watch(&claude_projects, parser_name)?;
+            count += 1;
+        }
+    }
+
+    if config.discovery.auto_discover && parser_name == "codex" {
+        if let Some(parser) = registry.get(parser_name) {
+            // Sessions are nested in dated directories
+            for sessions_dir in CodexParser::sessions_dirs(&config.parsers.codex) {
+                watcher.watch_matching(&sessions_dir, parser_name, &parser.watch_patterns(), true)?;
+                count += 1;
+            }
+        }
+    }
+
+    if config.discovery.auto_discover && parser_name == "gemini" {
+        if let Some(parser) = registry.get(parser_name) {
+            // Sessions are in each project's `chats` directory
+            for tmp_dir in GeminiParser::tmp_dirs(&config.parsers.gemini) {
+                watcher.watch_matching(&tmp_dir, parser_name, &parser.watch_patterns(), true)?;
+                count += 1;
+            }
+        }
+    }
+
+    if parser_name == "claude-code-artifacts" {
+        count += watch_artifacts(watcher, registry, &config.artifacts, &config.parsers.claude_code)?;
+    }
+
+    // Directories named by the parser itself, e.g. in a parser definition
+    if let Some(parser) = registry.get(parser_name) {
+        for dir in parser.watch_dirs().into_iter().filter(|dir| dir.exists()) {
+            watcher.watch_matching(&dir, parser_name, &parser.watch_patterns(), true)?;
+            count += 1;
+        }
+    }
+
+    // Watch additional configured paths this parser handles
+    for path in config.discovery.additional_paths.iter().map(|p| expand_path(p)) {
+        if !path.exists() {
+            continue;
+        }
+        if let Some(parser) = registry.detect(&path).filter(|p| p.name() == parser_name) {
+            watcher.watch_matching(&path, parser.name(), &parser.watch_patterns(), true)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Watch Claude Code's todo, plan and memory files
+fn watch_artifacts(
+    watcher: &mut FileWatcher,
+    registry: &ParserRegistry,
+    config: &crate::config::ArtifactsConfig,
+    claude_code: &crate::config::ClaudeCodeConfig,
+) -> Result<usize, WatcherError> {
+    let Some(parser) = registry.get("claude-code-artifacts") else {
+        return Ok(0);
+    };
+
+    let mut targets: Vec<(PathBuf, &[&str])> = Vec::new();
+    for claude_dir in ClaudeCodeParser::config_roots(claude_code) {
+        if config.todos {
+            targets.push((claude_dir.join("todos"), &["*.json"]));
+        }
+        if config.plans {
+            targets.push((claude_dir.join("plans"), &["*.md"]));
+        }
+        if config.memory {
+            targets.push((claude_dir, MEMORY_FILES));
+        }
+    }
+    if config.memory {
+        targets.extend(
+            ClaudeCodeParser::known_projects(claude_code)
+                .into_iter()
+                .map(|project| (project, MEMORY_FILES)),
+        );
+    }
+
+    let mut count = 0;
+    for (dir, patterns) in targets {
+        if dir.exists() {
+            watcher.watch_matching(&dir, parser.name(), patterns, false)?;
+            count += 1;
+        } else {
+            tracing::debug!("Claude Code artifact directory not found: {:?}", dir);
+        }
+    }
+    Ok(count)
+}
+
+/// Expand ~ to home directory
+pub fn expand_path(path: &str) -> PathBuf {
+    if path.starts_with("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(&path[2..]);
+        }
+    }
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_user_hint() {
+        let limit = WatcherError::Notify(notify::Error::new(notify::ErrorKind::MaxFilesWatch));
+        assert_eq!(limit.user_hint(), WATCH_LIMIT_HINT);
+
+        // inotify_add_watch fails with ENOSPC when the watch limit is reached
+        #[cfg(target_os = "linux")]
+        assert_eq!(WatcherError::Io(std::io::Error::from_raw_os_error(28)).user_hint(), WATCH_LIMIT_HINT);
+
+        let missing = WatcherError::PathNotFound(PathBuf::from("/nowhere"));
+        assert!(missing.user_hint().contains("discovery.additionalPaths"));
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let expanded = expand_path("~/test/path");
+        assert!(expanded.to_string_lossy().contains("test/path"));
+        assert!(!expanded.to_string_lossy().starts_with("~"));
+
+        let absolute = expand_path("/absolute/path");
+        assert_eq!(absolute, PathBuf::from("/absolute/path"));
+    }
+
+    #[test]
+    fn test_watcher_creation() {
+        let watcher = FileWatcher::new(Duration::from_secs(1));
+        assert!(watcher.is_ok());
+    }
+
+    #[test]
+    fn test_watch_directory() {
+        let dir = tempdir().unwrap();
+        let mut watcher = FileWatcher::new(Duration::from_secs(1)).unwrap();
+
+        let result = watcher.watch(dir.path(), "test-parser");
+        assert!(result.is_ok());
+        assert_eq!(watcher.watched_count(), 1);
+
+        assert_eq!(watcher.unwatch_parser("other-parser").unwrap(), 0);
+        assert_eq!(watcher.unwatch_parser("test-parser").unwrap(), 1);
+        assert_eq!(watcher.watched_count(), 0);
+    }
+
+    #[test]
+    fn test_event_queue() {
+        let event = |name: &str| FileChangeEvent {
+            path: PathBuf::from("/sessions").join(name),
+            parser_name: "claude-code".to_string(),
+        };
+        let queue = EventQueue::new(2);
+        let before = metrics::change_counts();
+
+        queue.push(event("a.jsonl"));
+        queue.push(event("b.jsonl"));
+        queue.push(event("a.jsonl"));
+        queue.push(event("c.jsonl"));
+        assert_eq!(queue.len(), 2);
+        let after = metrics::change_counts();
+        assert!(after.coalesced > before.coalesced && after.dropped > before.dropped);
+
+        assert_eq!(queue.try_recv().unwrap().path, PathBuf::from("/sessions/a.jsonl"));
+        queue.push(event("a.jsonl"));
+        assert_eq!(queue.try_recv().unwrap().path, PathBuf::from("/sessions/b.jsonl"));
+        assert_eq!(queue.recv_timeout(Duration::ZERO).unwrap().path, PathBuf::from("/sessions/a.jsonl"));
+        assert!(queue.is_empty());
+        assert!(matches!(queue.recv_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Timeout)));
+    }
+
+    #[test]
+    fn test_find_parser_for_path() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+
+        let mut watcher = FileWatcher::new(Duration::from_secs(1)).unwrap();
+        watcher.watch(dir.path(), "sessions").unwrap();
+        watcher
+            .watch_matching(&project, "memory", &["CLAUDE.md"], false)
+            .unwrap();
+
+        let find = |path: PathBuf| find_parser_for_path(&path, &watcher.watched_dirs);
+        assert_eq!(find(project.join("a.jsonl")).as_deref(), Some("sessions"));
+        assert_eq!(find(project.join("CLAUDE.md")).as_deref(), Some("memory"));
+        assert_eq!(find(project.join("sub").join("CLAUDE.md")), None);
+        assert_eq!(find(project.join("notes.txt")), None);
+    }
+}
@@ -0,0 +1,345 @@
+//! Worklogs of agent-assisted work
+//!
+//! `duplex worklog` gathers the conversations synced while a git branch was
+//! checked out, or within a date range, and renders them as a Markdown log
+//! grouped by day: what each conversation was about, which tool and project
+//! it ran in, and how much prompting it took. Everything comes from the local
+//! sync database and conversation files; nothing is fetched from the server.
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::cache;
+use crate::db::{ConversationSpan, Database, DatabaseError};
+use crate::errors::ErrorCategory;
+use crate::git;
+use crate::parsers::{Message, ParserRegistry};
+use crate::projects::ProjectNames;
+use crate::resync;
+
+/// Longest first prompt quoted in an entry, in characters
+const MAX_PROMPT_CHARS: usize = 280;
+
+/// Longest title derived from a first prompt, in characters
+const MAX_TITLE_CHARS: usize = 80;
+
+#[derive(Error, Debug)]
+pub enum WorklogError {
+    #[error("Invalid date '{0}', expected YYYY-MM-DD or an RFC 3339 timestamp")]
+    InvalidDate(String),
+    #[error("Give a branch, a date range or both")]
+    NoFilter,
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl WorklogError {
+    /// Classify this error for reporting
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            WorklogError::InvalidDate(_) | WorklogError::NoFilter => ErrorCategory::Config,
+            WorklogError::Database(e) => e.category(),
+            WorklogError::Sqlite(_) => ErrorCategory::Io,
+        }
+    }
+}
+
+/// Which conversations a worklog covers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorklogFilter {
+    /// Git branch checked out when the conversation was synced
+    pub branch: Option<String>,
+    /// Conversations still active at or after this Unix time
+    pub since: Option<i64>,
+    /// Conversations started before this Unix time
+    pub until: Option<i64>,
+}
+
+impl WorklogFilter {
+    /// Build a filter from `--branch`, `--since` and `--until` arguments
+    ///
+    /// A plain `--until` date includes the whole of that day.
+    pub fn new(branch: Option<&str>, since: Option<&str>, until: Option<&str>) -> Result<Self, WorklogError> {
+        if branch.is_none() && since.is_none() && until.is_none() {
+            return Err(WorklogError::NoFilter);
+        }
+        let invalid = |value: &str| WorklogError::InvalidDate(value.trim().to_string());
+        Ok(Self {
+            branch: branch.map(String::from),
+            since: since.map(|v| resync::parse_timestamp(v).ok_or_else(|| invalid(v))).transpose()?,
+            until: until.map(|v| parse_until(v).ok_or_else(|| invalid(v))).transpose()?,
+        })
+    }
+}
+
+/// End of a range: the midnight after a plain date, or a timestamp as given
+fn parse_until(value: &str) -> Option<i64> {
+    match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        Ok(date) => resync::parse_timestamp(&date.succ_opt()?.to_string()),
+        Err(_) => resync::parse_timestamp(value),
+    }
+}
+
+/// One conversation in a worklog
+#[derive(Debug, Clone)]
+pub struct WorklogEntry {
+    pub conversation: ConversationSpan,
+    /// Display name of the project, see [`crate::projects`]
+    pub project: Option<String>,
+    /// Messages from the user, when the conversation could be read
+    pub prompts: Option<usize>,
+    /// Tool calls made by the agent, when the conversation could be read
+    pub tool_calls: Option<usize>,
+    pub first_prompt: Option<String>,
+}
+
+/// Gather the conversations matching `filter`, oldest first
+///
+/// Conversations are read from the content cache or their file for prompt
+/// counts; ones that can no longer be read are listed from their sync state
+/// alone.
+pub fn collect(
+    registry: &ParserRegistry,
+    db: &Database,
+    names: &ProjectNames,
+    filter: &WorklogFilter,
+) -> Result<Vec<WorklogEntry>, WorklogError> {
+    let conversations = db.list_worklog(filter.branch.as_deref(), filter.since, filter.until)?;
+
+    Ok(conversations
+        .into_iter()
+        .map(|conversation| {
+            let messages = read_messages(registry, db, &conversation);
+            let prompts: Option<Vec<&Message>> =
+                messages.as_ref().map(|m| m.iter().filter(|m| m.role == "user").collect());
+            WorklogEntry {
+                project: conversation.state.project_path.as_deref().map(|path| names.display(path)),
+                prompts: prompts.as_ref().map(Vec::len),
+                tool_calls: messages.as_ref().map(|m| m.iter().map(|m| m.tool_calls.len()).sum()),
+                first_prompt: prompts
+                    .as_ref()
+                    .and_then(|p| p.iter().map(|m| m.content.trim()).find(|c| !c.is_empty()))
+                    .map(String::from),
+                conversation,
+            }
+        })
+        .collect())
+}
+
+/// Messages of a conversation, from the content cache or its file
+fn read_messages(registry: &ParserRegistry, db: &Database, conversation: &ConversationSpan) -> Option<Vec<Message>> {
+    let path = PathBuf::from(&conversation.state.file_path);
+    let parser = conversation
+        .state
+        .source
+        .as_deref()
+        .and_then(|name| registry.get(name))
+        .or_else(|| registry.detect(&path))?;
+
+    let content = match cache::load(db, &path, None) {
+        Ok(Some(cached)) => cached.content,
+        _ => parser.parse(&path).ok()?.content,
+    };
+    parser.parse_messages(&content)
+}
+
+/// Render a worklog as Markdown, one section per day
+pub fn render_markdown(filter: &WorklogFilter, entries: &[WorklogEntry]) -> String {
+    let mut out = match &filter.branch {
+        Some(branch) => format!("# Worklog: {}\n\n", branch),
+        None => "# Worklog\n\n".to_string(),
+    };
+
+    let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+        out.push_str("_No conversations found._\n");
+        return out;
+    };
+
+    let prompts: usize = entries.iter().filter_map(|e| e.prompts).sum();
+    out.push_str(&format!(
+        "_{} – {} · {} conversation(s) · {} prompt(s)_\n",
+        local(first.conversation.started_at).format("%Y-%m-%d"),
+        local(last.conversation.ended_at).format("%Y-%m-%d"),
+        entries.len(),
+        prompts
+    ));
+
+    let mut day = None;
+    for entry in entries {
+        let started = local(entry.conversation.started_at);
+        if day != Some(started.date_naive()) {
+            day = Some(started.date_naive());
+            out.push_str(&format!("\n## {}\n", started.format("%A, %Y-%m-%d")));
+        }
+        render_entry(&mut out, filter, entry);
+    }
+    out
+}
+
+fn render_entry(out: &mut String, filter: &WorklogFilter, entry: &WorklogEntry) {
+    let state = &entry.conversation.state;
+    out.push_str(&format!("\n### {}\n\n", title(entry)));
+
+    let mut details = vec![format!(
+        "{}–{}",
+        local(entry.conversation.started_at).format("%H:%M"),
+        local(entry.conversation.ended_at).format("%H:%M")
+    )];
+    if let Some(source) = &state.source {
+        details.push(source.clone());
+    }
+    if let Some(project) = &entry.project {
+        details.push(format!("`{}`", project));
+    }
+    out.push_str(&format!("- {}\n", details.join(" · ")));
+
+    if let Some(git) = &state.git {
+        let mut line = Vec::new();
+        if let Some(repo) = git.remote_url.as_deref().and_then(git::repo_name) {
+            line.push(format!("`{}`", repo));
+        }
+        // Every entry shares the branch when filtering by one
+        if let (Some(branch), None) = (&git.branch, &filter.branch) {
+            line.push(format!("branch `{}`", branch));
+        }
+        if let Some(commit) = &git.commit {
+            line.push(format!("at `{}`", &commit[..commit.len().min(7)]));
+        }
+        if !line.is_empty() {
+            out.push_str(&format!("- {}\n", line.join(" ")));
+        }
+    }
+
+    if let (Some(prompts), Some(tool_calls)) = (entry.prompts, entry.tool_calls) {
+        out.push_str(&format!("- {} prompt(s), {} tool call(s)\n", prompts, tool_calls));
+    }
+    if let Some(session_id) = &state.session_id {
+        out.push_str(&format!("- Session `{}`\n", session_id));
+    }
+
+    if let Some(prompt) = &entry.first_prompt {
+        out.push_str(&format!("\n> {}\n", one_line(prompt, MAX_PROMPT_CHARS)));
+    }
+}
+
+/// The parser's title, else the start of the first prompt, else the session
+fn title(entry: &WorklogEntry) -> String {
+    let state = &entry.conversation.state;
+    state
+        .title
+        .clone()
+        .or_else(|| entry.first_prompt.as_deref().map(|p| one_line(p, MAX_TITLE_CHARS)))
+        .or_else(|| state.session_id.clone())
+        .unwrap_or_else(|| {
+            Path::new(&state.file_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| state.file_path.clone())
+        })
+}
+
+/// Collapse whitespace and cut to `max` characters
+fn one_line(text: &str, max: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max {
+        return text;
+    }
+    let cut: String = text.chars().take(max - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+fn local(timestamp: i64) -> DateTime<Local> {
+    Local.timestamp_opt(timestamp, 0).single().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SyncState, SyncStatus};
+    use crate::git::GitContext;
+
+    fn entry(session_id: &str, started_at: i64, title: Option<&str>, first_prompt: Option<&str>) -> WorklogEntry {
+        WorklogEntry {
+            conversation: ConversationSpan {
+                state: SyncState {
+                    file_path: format!("/p/{}.jsonl", session_id),
+                    content_hash: "abc123".to_string(),
+                    last_synced_at: None,
+                    last_modified_at: started_at,
+                    workflow_id: None,
+                    status: SyncStatus::Complete,
+                    session_id: Some(session_id.to_string()),
+                    project_path: Some("/work/app".to_string()),
+                    source: Some("claude-code".to_string()),
+                    git: Some(GitContext {
+                        remote_url: Some("git@github.com:org/app.git".to_string()),
+                        branch: Some("feature/x".to_string()),
+                        commit: Some("deadbeefcafe".to_string()),
+                    }),
+                    title: title.map(String::from),
+                },
+                started_at,
+                ended_at: started_at + 600,
+            },
+            project: Some("acme/app".to_string()),
+            prompts: Some(2),
+            tool_calls: Some(5),
+            first_prompt: first_prompt.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        assert!(matches!(WorklogFilter::new(None, None, None), Err(WorklogError::NoFilter)));
+        assert!(matches!(
+            WorklogFilter::new(None, Some("yesterday"), None),
+            Err(WorklogError::InvalidDate(_))
+        ));
+
+        let filter = WorklogFilter::new(Some("feature/x"), Some("2026-02-01"), Some("2026-02-01")).unwrap();
+        assert_eq!(filter.branch.as_deref(), Some("feature/x"));
+        let (since, until) = (filter.since.unwrap(), filter.until.unwrap());
+        assert!((23 * 3600..=25 * 3600).contains(&(until - since)));
+
+        let filter = WorklogFilter::new(None, None, Some("2026-02-01T00:00:00Z")).unwrap();
+        assert_eq!(filter.until, Some(1769904000));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let filter = WorklogFilter {
+            branch: Some("feature/x".to_string()),
+            ..Default::default()
+        };
+        assert!(render_markdown(&filter, &[]).contains("_No conversations found._"));
+
+        let day = 1769904000 + 12 * 3600;
+        let entries = [
+            entry("s1", day, Some("Add login form"), Some("Add a login\nform to the app")),
+            entry("s2", day + 3600, None, Some("Fix the failing auth tests")),
+            entry("s3", day + 86_400, None, None),
+        ];
+        let markdown = render_markdown(&filter, &entries);
+
+        assert!(markdown.starts_with("# Worklog: feature/x\n"));
+        assert!(markdown.contains("3 conversation(s) · 6 prompt(s)"));
+        assert_eq!(markdown.matches("\n## ").count(), 2);
+        assert!(markdown.contains("### Add login form\n"));
+        assert!(markdown.contains("> Add a login form to the app\n"));
+        assert!(markdown.contains("### Fix the failing auth tests\n"));
+        assert!(markdown.contains("### s3\n"));
+        assert!(markdown.contains(" · claude-code · `acme/app`\n"));
+        assert!(markdown.contains("- `app` at `deadbee`\n"));
+        assert!(!markdown.contains("branch `feature/x`"));
+        assert!(markdown.contains("- 2 prompt(s), 5 tool call(s)\n"));
+    }
+
+    #[test]
+    fn test_one_line() {
+        assert_eq!(one_line("  a\n b  ", 10), "a b");
+        assert_eq!(one_line("abcdefghij", 5), "abcd…");
+    }
+}
@@ -0,0 +1,432 @@
+//! Shared harness for end-to-end sync tests
+//!
+//! `MockApi` is an in-process HTTP server standing in for the Duplex
+//! backend: it records every request and answers the extraction, upload and
+//! workspace routes the sync engine uses. `Fixture` lays out a temporary
+//! Claude Code projects directory and sync database so tests never touch the
+//! user's real config.
+
+#![allow(dead_code)]
+
+use duplex_core::config::Config;
+use duplex_core::db::{Database, SyncState};
+use duplex_core::parsers::ParserRegistry;
+use duplex_core::sync::SyncEngine;
+use duplex_core::watcher::FileChangeEvent;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+/// A request received by the mock API
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub user_agent: Option<String>,
+    /// `X-Duplex-Client` header
+    pub client: Option<String>,
+    /// `X-Duplex-Signature` and `X-Duplex-Timestamp` headers
+    pub signature: Option<(String, String)>,
+    pub body: Bytes,
+}
+
+impl RecordedRequest {
+    /// Request body parsed as JSON (`Null` if it isn't JSON)
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<RecordedRequest>,
+    /// Responses to answer the next requests with, regardless of route
+    failures: VecDeque<(StatusCode, Value)>,
+    next_id: u32,
+    /// Workflow and machine of the first upload of each session
+    synced: HashMap<String, (String, String)>,
+    /// Payload version advertised in extraction responses
+    max_payload_version: Option<u32>,
+    /// Whether extraction responses advertise accepting appends
+    accepts_append: bool,
+    /// Workspaces created with `POST /workspaces`
+    workspaces: Vec<Value>,
+    /// Policy served at `GET /org/policy-baseline`
+    baseline: Option<Value>,
+}
+
+/// In-process stand-in for the Duplex backend
+///
+/// Routes:
+/// - `POST /extraction/conversations/extract` - `{ workflowId, status }`,
+///   plus `maxPayloadVersion` once set with [`MockApi::accept_payload_version`]
+///   and `acceptsAppend` once set with [`MockApi::accept_appends`]
+/// - `POST /extraction/upload-url` - a presigned URL pointing back at `/r2/`
+///
+/// Both dedup on `sessionId` like the real server: a session first uploaded
+/// by another machine is answered as a duplicate of that upload.
+/// - `PUT /r2/*` - accepts the object
+/// - `GET /extraction/workflows/*` - a finished extraction with a summary and
+///   tags, for workflows it started; 404 for others
+/// - `PATCH /extraction/workflows/*` - accepts title and tag edits to
+///   workflows it started; 404 for others
+/// - `POST /workspaces` - `{ id }`
+/// - `GET /workspaces` - the workspaces created so far
+/// - `GET /org/policy-baseline` - the baseline set with
+///   [`MockApi::publish_baseline`], 404 until then
+/// - `DELETE /extraction/conversations/*` - accepts the delete
+/// - anything else - 404
+pub struct MockApi {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockApi {
+    /// Start the server on an ephemeral localhost port
+    pub async fn start() -> Self {
+        Self::start_at("127.0.0.1:0").await
+    }
+
+    /// Start the server on `addr`, e.g. where an earlier one stopped
+    pub async fn start_at(addr: &str) -> Self {
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = state.clone();
+        let base_url = url.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let state = server_state.clone();
+                let base_url = base_url.clone();
+
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let state = state.clone();
+                        let base_url = base_url.clone();
+                        async move { Ok::<_, hyper::Error>(handle(req, &state, &base_url).await) }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        Self { url, state }
+    }
+
+    /// Answer the next request with `status` instead of routing it
+    pub fn fail_next(&self, status: StatusCode) {
+        let body = json!({ "error": "injected failure" });
+        self.state.lock().unwrap().failures.push_back((status, body));
+    }
+
+    /// Reject the next request as invalid, answering 422 with `body`
+    pub fn reject_next(&self, body: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .push_back((StatusCode::UNPROCESSABLE_ENTITY, body));
+    }
+
+    /// Answer the next request 409, as for a session another machine has
+    /// uploaded a newer version of, as `workflow_id`
+    pub fn newer_elsewhere(&self, workflow_id: &str) {
+        let body = json!({
+            "error": "A newer version of this session exists",
+            "newerVersion": { "workflowId": workflow_id, "machineId": "other-machine", "updatedAt": 1_700_000_000 },
+        });
+        self.state.lock().unwrap().failures.push_back((StatusCode::CONFLICT, body));
+    }
+
+    /// Record `session_id` as already uploaded by another machine
+    pub fn synced_elsewhere(&self, session_id: &str, workflow_id: &str) {
+        self.state.lock().unwrap().synced.insert(
+            session_id.to_string(),
+            (workflow_id.to_string(), "other-machine".to_string()),
+        );
+    }
+
+    /// Advertise `version` as the newest accepted upload payload
+    pub fn accept_payload_version(&self, version: u32) {
+        self.state.lock().unwrap().max_payload_version = Some(version);
+    }
+
+    /// Advertise accepting uploads that extend an earlier one
+    pub fn accept_appends(&self) {
+        self.state.lock().unwrap().accepts_append = true;
+    }
+
+    /// Serve `policy` as the org policy baseline
+    pub fn publish_baseline(&self, policy: Value) {
+        self.state.lock().unwrap().baseline = Some(policy);
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Requests received for a path
+    pub fn requests_to(&self, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.path == path)
+            .collect()
+    }
+}
+
+async fn handle(
+    req: Request<hyper::body::Incoming>,
+    state: &Mutex<State>,
+    base_url: &str,
+) -> Response<Full<Bytes>> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).map(String::from);
+    let authorization = header("authorization");
+    let user_agent = header("user-agent");
+    let client = header("x-duplex-client");
+    let signature = header("x-duplex-signature").zip(header("x-duplex-timestamp"));
+    let body = req
+        .into_body()
+        .collect()
+        .await
+        .map(|b| b.to_bytes())
+        .unwrap_or_default();
+
+    let mut state = state.lock().unwrap();
+    state.requests.push(RecordedRequest {
+        method: method.clone(),
+        path: path.clone(),
+        authorization,
+        user_agent,
+        client,
+        signature,
+        body,
+    });
+
+    if let Some((status, body)) = state.failures.pop_front() {
+        return respond(status, body);
+    }
+
+    state.next_id += 1;
+    let id = state.next_id;
+    let request = state.requests.last().unwrap().json();
+    let duplicate = request["sessionId"].as_str().and_then(|session_id| {
+        let (workflow_id, machine_id) = state.synced.get(session_id)?;
+        (request["machineId"] != machine_id.as_str()).then(|| {
+            json!({
+                "workflowId": workflow_id,
+                "status": "complete",
+                "duplicate": true,
+                "syncedBy": machine_id,
+            })
+        })
+    });
+
+    match (&method, path.as_str()) {
+        (&Method::POST, "/extraction/conversations/extract") => {
+            if let Some(duplicate) = duplicate {
+                return respond(StatusCode::OK, duplicate);
+            }
+            let workflow_id = format!("wf-{}", id);
+            if let (Some(session_id), Some(machine_id)) =
+                (request["sessionId"].as_str(), request["machineId"].as_str())
+            {
+                state
+                    .synced
+                    .entry(session_id.to_string())
+                    .or_insert((workflow_id.clone(), machine_id.to_string()));
+            }
+            respond(
+                StatusCode::OK,
+                json!({
+                    "workflowId": workflow_id,
+                    "status": "started",
+                    "maxPayloadVersion": state.max_payload_version,
+                    "acceptsAppend": state.accepts_append,
+                }),
+            )
+        }
+        (&Method::POST, "/extraction/upload-url") if duplicate.is_some() => {
+            respond(StatusCode::OK, json!({ "duplicate": duplicate }))
+        }
+        (&Method::POST, "/extraction/upload-url") => respond(
+            StatusCode::OK,
+            json!({
+                "uploadUrl": format!("{}/r2/conversations/{}", base_url, id),
+                "r2Key": format!("conversations/{}", id),
+            }),
+        ),
+        (&Method::PUT, p) if p.starts_with("/r2/") => respond(StatusCode::OK, Value::Null),
+        (&Method::GET, p) if p.starts_with("/extraction/workflows/") => {
+            let workflow_id = &p["/extraction/workflows/".len()..];
+            match state.synced.values().any(|(id, _)| id == workflow_id) {
+                true => respond(
+                    StatusCode::OK,
+                    json!({
+                        "workflowId": workflow_id,
+                        "status": "complete",
+                        "summary": format!("Summary of {}", workflow_id),
+                        "tags": ["demo"],
+                        "output": { "decisions": [] },
+                    }),
+                ),
+                false => respond(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+            }
+        }
+        (&Method::PATCH, p) if p.starts_with("/extraction/workflows/") => {
+            let workflow_id = &p["/extraction/workflows/".len()..];
+            match state.synced.values().any(|(id, _)| id == workflow_id) {
+                true => respond(StatusCode::OK, Value::Null),
+                false => respond(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+            }
+        }
+        (&Method::DELETE, p) if p.starts_with("/extraction/conversations/") => respond(StatusCode::OK, Value::Null),
+        (&Method::POST, "/workspaces") => {
+            let workspace = json!({ "id": format!("ws-{}", id), "name": request["name"] });
+            state.workspaces.push(workspace.clone());
+            respond(StatusCode::OK, workspace)
+        }
+        (&Method::GET, "/workspaces") => respond(StatusCode::OK, Value::Array(state.workspaces.clone())),
+        (&Method::GET, "/org/policy-baseline") if state.baseline.is_some() => {
+            respond(StatusCode::OK, state.baseline.clone().unwrap_or_default())
+        }
+        _ => respond(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+    }
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+/// Temporary Claude Code projects directory and sync database
+pub struct Fixture {
+    pub dir: TempDir,
+    pub projects_dir: PathBuf,
+    pub db_path: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let projects_dir = dir.path().join("projects");
+        std::fs::create_dir_all(&projects_dir).unwrap();
+        let db_path = dir.path().join("sync.db");
+
+        Self {
+            dir,
+            projects_dir,
+            db_path,
+        }
+    }
+
+    /// Default config, with every tool's history looked for in the fixture
+    /// directory rather than the user's home
+    pub fn config(&self) -> Config {
+        let base_dir = Some(self.dir.path().to_string_lossy().to_string());
+        let mut config = Config::default();
+        config.parsers.claude_code.base_dir = base_dir.clone();
+        config.parsers.codex.base_dir = base_dir.clone();
+        config.parsers.gemini.base_dir = base_dir;
+        config
+    }
+
+    /// Directory for a project, encoded the way Claude Code does
+    pub fn project_dir(&self, project: &str) -> PathBuf {
+        let dir = self.projects_dir.join(project.replace('/', "-"));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a Claude Code session file with one user and one assistant turn
+    pub fn write_session(&self, project: &str, session_id: &str, prompt: &str) -> PathBuf {
+        let lines = [
+            session_line("user", prompt, "2024-05-01T10:00:00Z"),
+            session_line("assistant", "Done.", "2024-05-01T10:01:00Z"),
+        ];
+        self.write_lines(project, session_id, &lines)
+    }
+
+    /// Write a Claude Code session file from raw JSONL records
+    pub fn write_lines(&self, project: &str, session_id: &str, lines: &[Value]) -> PathBuf {
+        let path = self
+            .project_dir(project)
+            .join(format!("{}.jsonl", session_id));
+        let content: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Sync engine pointed at the mock API and the fixture database
+    pub fn engine(&self, api: &MockApi, config: &Config) -> SyncEngine {
+        self.engine_with_registry(api, config, Arc::new(ParserRegistry::new()))
+    }
+
+    /// Sync engine sharing `registry`, for tests that toggle parsers
+    pub fn engine_with_registry(
+        &self,
+        api: &MockApi,
+        config: &Config,
+        registry: Arc<ParserRegistry>,
+    ) -> SyncEngine {
+        SyncEngine::with_database(
+            api.url.clone(),
+            Some("test-token".to_string()),
+            registry,
+            config,
+            self.db(),
+        )
+        .unwrap()
+    }
+
+    /// A second connection to the fixture database, for assertions
+    pub fn db(&self) -> Database {
+        Database::open_at(&self.db_path).unwrap()
+    }
+
+    /// Sync state recorded for a file
+    pub fn state(&self, path: &Path) -> SyncState {
+        self.db()
+            .get_sync_state(&path.to_string_lossy())
+            .unwrap()
+            .expect("file has no sync state")
+    }
+}
+
+/// Watcher event for a Claude Code session file
+pub fn session_changed(path: &Path) -> FileChangeEvent {
+    FileChangeEvent {
+        path: path.to_path_buf(),
+        parser_name: "claude-code".to_string(),
+    }
+}
+
+/// A Claude Code JSONL record
+pub fn session_line(role: &str, text: &str, timestamp: &str) -> Value {
+    json!({
+        "type": role,
+        "timestamp": timestamp,
+        "message": { "role": role, "content": text },
+    })
+}
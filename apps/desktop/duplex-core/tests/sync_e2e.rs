@@ -0,0 +1,1333 @@
+//! End-to-end sync tests against the mock API
+
+mod common;
+
+use common::{session_changed, session_line, Fixture, MockApi};
+use duplex_core::api::{CreateWorkspaceRequest, DuplexApiClient};
+use duplex_core::config::{
+    self, BackendChange, Config, HmacSigningConfig, OverlapStrategy, OversizeStrategy, SigningConfig,
+};
+use duplex_core::db::{BackendSwitch, SyncStatus};
+use duplex_core::diff;
+use duplex_core::encryption::EncryptionKey;
+use duplex_core::errors::ErrorCategory;
+use duplex_core::export::{self, ExportFormat};
+use duplex_core::power;
+use duplex_core::replay::{self, EventRecorder, RecordedEvent};
+use duplex_core::parsers::{
+    ContentType, Conversation, ConversationFile, ConversationParser, ParserError, ParserRegistry,
+};
+use duplex_core::policy::{Policy, ProjectFilter};
+use duplex_core::shutdown::Shutdown;
+use duplex_core::testing::{FakeWatcher, TestFiles, TestParser};
+use duplex_core::sync::{QueueChange, QueueLane, QueueOutcome, SyncEngine, SyncError};
+use duplex_core::watcher::{FileChangeEvent, FileWatcher};
+use hyper::{Method, StatusCode};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const SESSION_ID: &str = "a1b2c3d4-e5f6-7890-abcd-ef1234567890";
+
+#[tokio::test]
+async fn test_watched_session_syncs_to_complete() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let project_dir = fixture.project_dir("/work/demo");
+    let mut watcher = FileWatcher::new(Duration::from_millis(100)).unwrap();
+    watcher.watch(&fixture.projects_dir, "claude-code").unwrap();
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    let event = loop {
+        let event = watcher
+            .events()
+            .recv_timeout(Duration::from_secs(5))
+            .expect("no watcher event for the session file");
+        if event.path == path {
+            break event;
+        }
+    };
+    assert_eq!(event.parser_name, "claude-code");
+    assert!(event.path.starts_with(&project_dir));
+
+    engine.handle_file_change(event).unwrap();
+    assert_eq!(fixture.state(&path).status, SyncStatus::Pending);
+
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 1);
+    assert_eq!(
+        extracts[0].authorization.as_deref(),
+        Some("Bearer test-token")
+    );
+    let body = extracts[0].json();
+    assert_eq!(body["source"], "claude-code");
+    assert_eq!(body["sourcePath"], path.to_string_lossy().as_ref());
+    assert!(body["content"].as_str().unwrap().contains("Add a README"));
+
+    let state = fixture.state(&path);
+    assert_eq!(state.status, SyncStatus::Complete);
+    assert_eq!(state.session_id.as_deref(), Some(SESSION_ID));
+    assert_eq!(state.project_path.as_deref(), Some("/work/demo"));
+    assert!(state.workflow_id.is_some());
+
+    // Unchanged content is not queued again
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.queue_len(), 0);
+}
+
+#[tokio::test]
+async fn test_payload_limit_strategies() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let big = |session_id: &str| {
+        let lines: Vec<_> = ["one", "two", "three"]
+            .iter()
+            .map(|turn| session_line("user", &format!("{} {}", turn, "x".repeat(600 * 1024)), "2024-05-01T10:00:00Z"))
+            .collect();
+        fixture.write_lines("/work/big", session_id, &lines)
+    };
+    let sync = |strategy: OversizeStrategy, path: &Path| {
+        let mut config = Config::default();
+        config.sync.payload_limit.max_mb = 1;
+        config.sync.payload_limit.strategy = strategy;
+        let mut engine = fixture.engine(&api, &config);
+        engine.handle_file_change(session_changed(path)).unwrap();
+        async move { engine.process_all().await.unwrap() }
+    };
+    let oversize = |path: &Path| fixture.db().get_oversize(&path.to_string_lossy()).unwrap().unwrap();
+
+    let rejected = big("s-reject");
+    assert_eq!(sync(OversizeStrategy::Reject, &rejected).await.failed.len(), 1);
+    assert_eq!(fixture.state(&rejected).status, SyncStatus::Error);
+    assert_eq!(oversize(&rejected).strategy, "reject");
+    assert!(api.requests().is_empty());
+
+    let truncated = big("s-truncate");
+    assert_eq!(sync(OversizeStrategy::TruncateOldest, &truncated).await.succeeded, 1);
+    assert_eq!(oversize(&truncated).strategy, "truncateOldest");
+    assert!(oversize(&truncated).bytes > 1024 * 1024);
+    let put = &api.requests()[1];
+    assert!(put.path.starts_with("/r2/") && put.body.len() < 1024 * 1024);
+    assert!(String::from_utf8_lossy(&put.body).contains("three"));
+
+    let split = big("s-split");
+    assert_eq!(sync(OversizeStrategy::Split, &split).await.succeeded, 1);
+    assert_eq!(oversize(&split).strategy, "split");
+    let parts: Vec<_> = api.requests_to("/extraction/conversations/extract")[1..]
+        .iter()
+        .map(|request| request.json()["part"].clone())
+        .collect();
+    assert_eq!(parts.len(), 3);
+    assert_eq!(parts[0], json!({ "index": 1, "count": 3 }));
+    assert_eq!(parts[2]["firstWorkflowId"], fixture.state(&split).workflow_id.unwrap().as_str());
+}
+
+#[tokio::test]
+async fn test_large_session_uploads_via_presigned_url() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/big", SESSION_ID, &"x".repeat(600 * 1024));
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+
+    let puts: Vec<_> = api
+        .requests()
+        .into_iter()
+        .filter(|r| r.path.starts_with("/r2/"))
+        .collect();
+    assert_eq!(puts.len(), 1);
+    assert!(puts[0].body.len() > 600 * 1024);
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 1);
+    let body = extracts[0].json();
+    assert!(body.get("content").is_none());
+    assert!(body["r2Key"]
+        .as_str()
+        .unwrap()
+        .starts_with("conversations/"));
+
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_server_error_marks_file_errored() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Fix the build");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(engine.process_next().await.is_err());
+    assert_eq!(fixture.state(&path).status, SyncStatus::Error);
+
+    // The next write to the session queues it again
+    fixture.write_session("/work/demo", SESSION_ID, "Fix the build, then run tests");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_queue_subscription() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let first = fixture.write_session("/work/demo", SESSION_ID, "Fix the build");
+    engine.handle_file_change(session_changed(&first)).unwrap();
+
+    let (snapshot, mut events) = engine.subscribe_queue();
+    assert_eq!(snapshot.entries.len(), 1);
+    assert_eq!(snapshot.entries[0].lane, QueueLane::Live);
+
+    let second = fixture.write_session(
+        "/work/demo",
+        "b1b2c3d4-e5f6-7890-abcd-ef1234567890",
+        "Add a LICENSE",
+    );
+    engine.handle_file_change(session_changed(&second)).unwrap();
+    engine.process_all().await.unwrap();
+
+    let mut changes = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        assert_eq!(event.seq, snapshot.seq + changes.len() as u64 + 1);
+        changes.push(event.change);
+    }
+    assert!(matches!(&changes[0], QueueChange::Added { entries } if entries[0].path == second));
+    assert_eq!(changes[1], QueueChange::Started { path: first.clone() });
+    assert_eq!(
+        changes[2],
+        QueueChange::Finished {
+            path: first,
+            outcome: QueueOutcome::Synced,
+        }
+    );
+    assert_eq!(changes.len(), 5);
+}
+
+#[tokio::test]
+async fn test_sync_report_lists_failures() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let first = fixture.write_session("/work/demo", SESSION_ID, "Fix the build");
+    let second = fixture.write_session(
+        "/work/demo",
+        "b1b2c3d4-e5f6-7890-abcd-ef1234567890",
+        "Add a LICENSE",
+    );
+    let dirs = [(fixture.projects_dir.clone(), "claude-code".to_string())];
+    assert_eq!(engine.queue_all(&dirs).unwrap(), 2);
+
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    let report = engine.process_all().await.unwrap();
+    assert_eq!(report.succeeded, 1);
+    assert_eq!(report.skipped, 0);
+    assert!(!report.is_success());
+    let (failed, category) = &report.failed[0];
+    assert!(*failed == first || *failed == second);
+    assert_eq!(*category, ErrorCategory::Server);
+    assert_eq!(report.summary(), "1 synced, 1 failed");
+
+    // Unchanged files aren't queued again
+    assert_eq!(engine.queue_all(&dirs).unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_binary_file_is_marked_unsupported() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.project_dir("/work/demo").join(format!("{}.jsonl", SESSION_ID));
+    std::fs::write(&path, b"SQLite format 3\0\x10\0\x01\x01").unwrap();
+    engine.handle_file_change(session_changed(&path)).unwrap();
+
+    assert_eq!(engine.queue_len(), 0);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Unsupported);
+    assert_eq!(fixture.db().get_status_counts().unwrap().error, 0);
+
+    // Rewritten as text, it syncs
+    fixture.write_session("/work/demo", SESSION_ID, "Fix the build");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_rejected_payload_is_kept_for_debugging() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Fix the build");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+
+    api.reject_next(json!({ "issues": [{ "path": ["contentHash"], "message": "Required" }] }));
+    assert!(engine.process_next().await.is_err());
+
+    let failures = fixture.db().list_failed_attempts(10).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].file_path, fixture.state(&path).file_path);
+    assert_eq!(failures[0].status_code, Some(422));
+    assert_eq!(failures[0].validation_errors, vec!["contentHash: Required"]);
+    assert!(failures[0].response_body.as_deref().unwrap().contains("issues"));
+
+    // Resolved once the session syncs
+    fixture.write_session("/work/demo", SESSION_ID, "Fix the build, then run tests");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+    assert!(fixture.db().list_failed_attempts(10).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_shutdown_leaves_queue_for_next_start() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let shutdown = Shutdown::new();
+
+    let first = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    let second = fixture.write_session(
+        "/work/demo",
+        "b1b2c3d4-e5f6-7890-abcd-ef1234567890",
+        "Add a LICENSE",
+    );
+    {
+        let mut engine = fixture.engine(&api, &Config::default());
+        engine.set_shutdown(shutdown.clone());
+        engine.handle_file_change(session_changed(&first)).unwrap();
+        engine.handle_file_change(session_changed(&second)).unwrap();
+
+        shutdown.shutdown(Duration::ZERO);
+        assert_eq!(engine.process_all().await.unwrap().succeeded, 0);
+        assert_eq!(engine.queue_len(), 2);
+    }
+    assert!(api.requests().is_empty());
+
+    // An upload cut off mid-flight is retried too, once it counts as stuck
+    fixture.db().mark_syncing(&first.to_string_lossy()).unwrap();
+
+    // Restored files are backfill; let them all go out at once
+    let mut config = Config::default();
+    config.sync.retry.stuck_after_seconds = 0;
+    config.policy.backfill.startup_delay_seconds = Some(0);
+    config.policy.backfill.initial_batch = Some(2);
+    let mut engine = fixture.engine(&api, &config);
+    assert_eq!(engine.restore_queue().unwrap(), 2);
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 2);
+    assert_eq!(fixture.state(&first).status, SyncStatus::Complete);
+    assert_eq!(fixture.state(&second).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_signed_requests() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let secret_file = fixture.dir.path().join("gateway.secret");
+    std::fs::write(&secret_file, "s3cret\n").unwrap();
+    let mut config = Config::default();
+    let hmac = HmacSigningConfig {
+        key_id: "laptops".to_string(),
+        secret_file: secret_file.to_string_lossy().to_string(),
+    };
+    config.sync.signing.insert(api.url.clone(), SigningConfig { hmac: Some(hmac), client_cert: None });
+    let mut engine = fixture.engine(&api, &config);
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let extract = &api.requests_to("/extraction/conversations/extract")[0];
+    let (signature, timestamp) = extract.signature.clone().expect("extract request wasn't signed");
+    let content_hash = hex::encode(Sha256::digest(&extract.body));
+    let signed = format!("POST\n/extraction/conversations/extract\n{}\n{}", timestamp, content_hash);
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"s3cret");
+    assert!(ring::hmac::verify(&key, signed.as_bytes(), &hex::decode(signature).unwrap()).is_ok());
+
+    // Other backends get no signature
+    let other = MockApi::start().await;
+    let client = DuplexApiClient::new(other.url.clone(), Some("test-token".to_string()), &config).unwrap();
+    client.workspaces().await.unwrap();
+    assert_eq!(other.requests()[0].signature, None);
+}
+
+#[tokio::test]
+async fn test_session_synced_by_another_machine() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+    api.synced_elsewhere(SESSION_ID, "wf-desktop");
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let extract = &api.requests_to("/extraction/conversations/extract")[0];
+    assert!(extract.client.as_deref().unwrap().contains("parsers=claude-code/1"));
+    assert!(extract.user_agent.as_deref().unwrap().starts_with("DuplexStream/"));
+    let body = extract.json();
+    assert_eq!(body["sessionId"], SESSION_ID);
+    assert_eq!(body["machineId"], duplex_core::machine::machine_id());
+    assert_eq!(body["contentHash"].as_str().unwrap().len(), 64);
+
+    let state = fixture.state(&path);
+    assert_eq!(state.status, SyncStatus::Complete);
+    assert_eq!(state.workflow_id.as_deref(), Some("wf-desktop"));
+
+    // Large sessions skip the storage upload entirely
+    let big = fixture.write_session("/work/demo", SESSION_ID, &"x".repeat(600 * 1024));
+    engine.handle_file_change(session_changed(&big)).unwrap();
+    engine.process_all().await.unwrap();
+    assert!(api.requests().iter().all(|r| !r.path.starts_with("/r2/")));
+}
+
+#[tokio::test]
+async fn test_newer_version_from_another_machine() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    api.newer_elsewhere("wf-laptop");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    // Uploaded again beside the newer version instead of over it
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 2);
+    assert!(extracts[0].json().get("conflictsWith").is_none());
+    let body = extracts[1].json();
+    assert_eq!(body["conflictsWith"], "wf-laptop");
+    assert_eq!(body["relatedSessions"][0]["workflowId"], "wf-laptop");
+    assert_eq!(body["relatedSessions"][0]["sessionId"], SESSION_ID);
+
+    let state = fixture.state(&path);
+    assert_eq!(state.status, SyncStatus::Complete);
+    let conflicts = fixture.db().list_conflicts(10).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path, state.file_path);
+    assert_eq!(conflicts[0].remote_machine_id.as_deref(), Some("other-machine"));
+    assert_eq!(conflicts[0].workflow_id, state.workflow_id);
+
+    // Later versions stay beside it, after the conflict is dismissed too
+    assert_eq!(fixture.db().dismiss_conflicts().unwrap(), 1);
+    fixture.write_session("/work/demo", SESSION_ID, "Add a README, then a LICENSE");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts[2].json()["conflictsWith"], "wf-laptop");
+    assert!(fixture.db().list_conflicts(10).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_pull_results() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+    let state = fixture.state(&path);
+    let workflow_id = state.workflow_id.clone().unwrap();
+
+    assert_eq!(engine.pull_results().await.unwrap(), 1);
+    let results = fixture.db().remote_results_for(&state.file_path).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].workflow_id, workflow_id);
+    assert!(results[0].finished);
+    assert_eq!(results[0].summary.as_deref(), Some(format!("Summary of {}", workflow_id).as_str()));
+    assert_eq!(results[0].tags, vec!["demo"]);
+    assert_eq!(results[0].output, Some(json!({ "decisions": [] })));
+
+    // Finished results aren't fetched again right away
+    assert_eq!(engine.pull_results().await.unwrap(), 0);
+    assert_eq!(api.requests_to(&format!("/extraction/workflows/{}", workflow_id)).len(), 1);
+
+    let mut config = Config::default();
+    config.sync.pull_results = false;
+    let mut engine = fixture.engine(&api, &config);
+    let other = fixture.write_session("/work/demo", "b1b2c3d4-e5f6-7890-abcd-ef1234567890", "Add a LICENSE");
+    engine.handle_file_change(session_changed(&other)).unwrap();
+    engine.process_all().await.unwrap();
+    assert_eq!(engine.pull_results().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_push_annotations() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+    engine.pull_results().await.unwrap();
+    let state = fixture.state(&path);
+    let workflow_id = state.workflow_id.clone().unwrap();
+
+    let tags = vec!["demo".to_string(), "docs".to_string()];
+    fixture.db().edit_annotations(&workflow_id, Some("README"), Some(&tags)).unwrap();
+    let results = fixture.db().remote_results_for(&state.file_path).unwrap();
+    assert!(results[0].edited);
+    assert_eq!(results[0].title.as_deref(), Some("README"));
+
+    assert_eq!(engine.push_annotations().await.unwrap(), 1);
+    let patches = api.requests_to(&format!("/extraction/workflows/{}", workflow_id));
+    let patch = patches.iter().find(|r| r.method == Method::PATCH).unwrap();
+    assert_eq!(patch.json(), json!({ "title": "README", "tags": ["demo", "docs"] }));
+
+    // The pulled result takes the edit on
+    let results = fixture.db().remote_results_for(&state.file_path).unwrap();
+    assert!(!results[0].edited);
+    assert_eq!(results[0].title.as_deref(), Some("README"));
+    assert_eq!(results[0].tags, tags);
+
+    // Failed pushes stay queued; edits to workflows the server lost are dropped
+    fixture.db().edit_annotations(&workflow_id, Some("README and LICENSE"), None).unwrap();
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(engine.push_annotations().await.is_err());
+    assert_eq!(fixture.db().pending_annotation_edits().unwrap().len(), 1);
+    fixture.db().edit_annotations("wf-gone", Some("Gone"), None).unwrap();
+    assert_eq!(engine.push_annotations().await.unwrap(), 1);
+    assert!(fixture.db().pending_annotation_edits().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_disabled_parser_skips_queued_sessions() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let registry = Arc::new(ParserRegistry::new());
+    let mut engine = fixture.engine_with_registry(&api, &Config::default(), registry.clone());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.queue_len(), 1);
+
+    let changes = registry.set_enabled(&[]);
+    assert!(changes.disabled.contains(&"claude-code".to_string()));
+    assert_eq!(engine.skip_parser("claude-code").unwrap(), 1);
+    assert_eq!(engine.queue_len(), 0);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Skipped);
+
+    // Changes to a disabled parser's files are ignored
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.queue_len(), 0);
+
+    registry.set_enabled(&["claude-code".to_string()]);
+    assert_eq!(engine.unskip_parser("claude-code").unwrap(), 1);
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_structured_payload_after_server_advertises_v2() {
+    let api = MockApi::start().await;
+    api.accept_payload_version(2);
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    // Until the server has answered once, uploads use v1
+    let first = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&first)).unwrap();
+    engine.process_all().await.unwrap();
+
+    let second = fixture.write_session(
+        "/work/demo",
+        "b1b2c3d4-e5f6-7890-abcd-ef1234567890",
+        "Fix the build",
+    );
+    engine.handle_file_change(session_changed(&second)).unwrap();
+    engine.process_all().await.unwrap();
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 2);
+
+    let v1 = extracts[0].json();
+    assert_eq!(v1["version"], 1);
+    assert!(v1["content"].as_str().unwrap().contains("Add a README"));
+    assert!(v1.get("messages").is_none());
+
+    let v2 = extracts[1].json();
+    assert_eq!(v2["version"], 2);
+    assert!(v2.get("content").is_none());
+    assert_eq!(v2["messages"][0]["role"], "user");
+    assert_eq!(v2["messages"][0]["content"], "Fix the build");
+    assert_eq!(fixture.state(&second).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_back_to_back_upload_of_same_content_is_skipped() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    // A reconciliation pass forces the same file straight after
+    engine.force_sync(&path).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 0);
+    assert_eq!(
+        api.requests_to("/extraction/conversations/extract").len(),
+        1
+    );
+    let state = fixture.state(&path);
+    assert_eq!(state.status, SyncStatus::Complete);
+    assert_eq!(state.workflow_id.as_deref(), Some("wf-1"));
+
+    // Changed content goes out at once
+    fixture.write_session("/work/demo", SESSION_ID, "Add a README and a LICENSE");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(
+        api.requests_to("/extraction/conversations/extract").len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_appended_lines_upload_alone() {
+    let api = MockApi::start().await;
+    api.accept_appends();
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+    let append = |path: &Path, text: &str| {
+        let line = session_line("user", text, "2024-05-01T10:05:00Z");
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        writeln!(file, "{}", line).unwrap();
+    };
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    let first_len = std::fs::metadata(&path).unwrap().len();
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    append(&path, "Now add a LICENSE");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    let first = extracts[0].json();
+    assert!(first.get("append").is_none());
+    assert!(first["content"].as_str().unwrap().contains("Add a README"));
+    let appended = extracts[1].json();
+    assert_eq!(appended["append"]["workflowId"], "wf-1");
+    assert_eq!(appended["append"]["offset"], first_len);
+    assert_eq!(appended["append"]["lines"], 2);
+    let content = appended["content"].as_str().unwrap();
+    assert!(content.contains("Now add a LICENSE"));
+    assert!(!content.contains("Add a README"));
+
+    // Each upload is kept as a version, and what the last one added can be shown
+    let versions = fixture.db().sync_versions(&path.to_string_lossy(), 10).unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[1].bytes, first_len);
+    let diff = diff::latest(&fixture.db(), &ParserRegistry::new(), &Policy::default(), &fixture.state(&path)).unwrap();
+    match diff.change {
+        diff::Change::Appended { messages: Some(messages), .. } => {
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0].content, "Now add a LICENSE");
+        }
+        change => panic!("expected appended messages, got {:?}", change),
+    }
+
+    // A rewritten file goes up whole
+    fixture.write_session("/work/demo", SESSION_ID, "Start over");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    let rewritten = api.requests_to("/extraction/conversations/extract")[2].json();
+    assert!(rewritten.get("append").is_none());
+    assert!(rewritten["content"].as_str().unwrap().contains("Start over"));
+
+    // So does one whose server copy doesn't match
+    append(&path, "And a CHANGELOG");
+    api.fail_next(StatusCode::CONFLICT);
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 5);
+    assert!(extracts[3].json().get("append").is_some());
+    let retried = extracts[4].json();
+    assert!(retried.get("append").is_none());
+    assert!(retried["content"].as_str().unwrap().contains("Start over"));
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_streaming_session_pushes_new_lines() {
+    let api = MockApi::start().await;
+    api.accept_appends();
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.sync.streaming.enabled = true;
+    config.sync.streaming.interval_seconds = 0;
+    let mut engine = fixture.engine(&api, &config);
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert!(!engine.stream_live_session().unwrap());
+
+    // New lines are queued without a watcher event
+    let line = session_line("user", "Now add a LICENSE", "2024-05-01T10:05:00Z");
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    writeln!(file, "{}", line).unwrap();
+    assert!(engine.stream_live_session().unwrap());
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 2);
+    let streamed = extracts[1].json();
+    assert_eq!(streamed["append"]["live"], true);
+    assert!(streamed["content"].as_str().unwrap().contains("Now add a LICENSE"));
+    assert!(!engine.stream_live_session().unwrap());
+}
+
+#[tokio::test]
+async fn test_file_read_by_two_parsers_uploads_both() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.parsers.overlap = OverlapStrategy::UploadBoth;
+    let mut engine = fixture.engine(&api, &config);
+
+    // generic-jsonl reads Claude Code sessions too
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    let sources = |api: &MockApi| -> Vec<String> {
+        api.requests_to("/extraction/conversations/extract")
+            .iter()
+            .map(|request| request.json()["source"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(sources(&api), ["generic-jsonl", "claude-code"]);
+    assert_eq!(fixture.state(&path).source.as_deref(), Some("claude-code"));
+    let reading = fixture.db().get_file_conversation(&path.to_string_lossy(), "source:generic-jsonl").unwrap();
+    assert_eq!(reading.unwrap().status, SyncStatus::Complete);
+
+    // Each reading goes again once the file changes
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    writeln!(file, "{}", session_line("user", "Add a LICENSE", "2024-05-01T10:02:00Z")).unwrap();
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(sources(&api).len(), 4);
+}
+
+#[tokio::test]
+async fn test_in_memory_sessions_sync_through_fake_watcher() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let files = TestFiles::default();
+    let mut registry = ParserRegistry::new();
+    registry.register(Box::new(TestParser::new(files.clone())));
+    let mut engine = fixture.engine_with_registry(&api, &Config::default(), Arc::new(registry));
+    let mut watcher = FakeWatcher::new();
+    watcher.watch(Path::new(TestParser::ROOT), TestParser::NAME);
+
+    let path = TestFiles::path("demo", "s1");
+    files.append(&path, "user", "Add a README");
+    files.append(&path, "assistant", "Done.");
+    assert!(watcher.touch(&path));
+    assert!(!watcher.touch(Path::new("/elsewhere/s2.log")));
+    while let Some(event) = watcher.try_recv() {
+        engine.handle_file_change(event).unwrap();
+    }
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let state = fixture.state(&path);
+    assert_eq!(state.status, SyncStatus::Complete);
+    assert_eq!(state.session_id.as_deref(), Some("s1"));
+    assert_eq!(state.title.as_deref(), Some("Add a README"));
+    let upload = api.requests_to("/extraction/conversations/extract")[0].json();
+    assert_eq!(upload["source"], TestParser::NAME);
+    assert_eq!(upload["content"], "user: Add a README\nassistant: Done.\n");
+
+    // Touching an unchanged file queues nothing
+    watcher.touch(&path);
+    engine.handle_file_change(watcher.try_recv().unwrap()).unwrap();
+    assert_eq!(engine.queue_len(), 0);
+}
+
+#[tokio::test]
+async fn test_failed_uploads_retry_with_backoff() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.sync.retry.base_delay_seconds = 0;
+    config.sync.retry.max_attempts = 1;
+    let mut engine = fixture.engine(&api, &config);
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().failed.len(), 1);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Error);
+
+    assert_eq!(engine.queue_due_retries().unwrap(), 1);
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+    assert_eq!(fixture.db().get_retry_count(&path.to_string_lossy()).unwrap(), 0);
+
+    // Rejected payloads wait for the next change, as do files out of retries
+    let rejected = fixture.write_session("/work/demo", "rejected", "Add a LICENSE");
+    api.reject_next(json!({ "error": "Bad payload" }));
+    engine.handle_file_change(session_changed(&rejected)).unwrap();
+    let flaky = fixture.write_session("/work/demo", "flaky", "Add a CHANGELOG");
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    engine.handle_file_change(session_changed(&flaky)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().failed.len(), 2);
+    engine.force_sync(&flaky).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().failed.len(), 1);
+
+    assert!(fixture.db().get_due_retries(i64::MAX).unwrap().is_empty());
+    assert_eq!(fixture.db().get_retry_count(&flaky.to_string_lossy()).unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_workspace_lookups_are_cached() {
+    let api = MockApi::start().await;
+    let client = DuplexApiClient::new(api.url.clone(), Some("test-token".to_string()), &Config::default()).unwrap();
+
+    assert!(client.workspaces().await.unwrap().is_empty());
+    assert!(client.workspaces().await.unwrap().is_empty());
+    assert_eq!(api.requests_to("/workspaces").len(), 1);
+
+    // Creating a workspace makes the list stale
+    let request = CreateWorkspaceRequest {
+        name: "demo",
+        project_path: "/work/demo",
+        git_remote: None,
+    };
+    let created = client.create_workspace(&request).await.unwrap();
+    let workspaces = client.workspaces().await.unwrap();
+    assert_eq!(workspaces.len(), 1);
+    assert_eq!(workspaces[0].id, created.id);
+    assert_eq!(api.requests_to("/workspaces").len(), 3);
+
+    client.invalidate_lookups();
+    client.workspaces().await.unwrap();
+    assert_eq!(api.requests_to("/workspaces").len(), 4);
+}
+
+#[tokio::test]
+async fn test_low_power_mode_holds_recent_changes() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.power.battery_debounce_seconds = 3600;
+    let mut engine = fixture.engine(&api, &config);
+    power::set_low_power_mode(true);
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    let report = engine.process_due().await.unwrap();
+    assert!(report.is_empty());
+    assert_eq!(engine.queue_len(), 1);
+    assert!(api.requests_to("/extraction/conversations/extract").is_empty());
+
+    // An explicit sync doesn't wait
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(engine.queue_len(), 0);
+    power::set_low_power_mode(false);
+}
+
+#[tokio::test]
+async fn test_source_tool_deletions() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut engine = fixture.engine(&api, &Config::default());
+
+    let first = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    let second = fixture.write_session(
+        "/work/demo",
+        "b1b2c3d4-e5f6-7890-abcd-ef1234567890",
+        "Add a LICENSE",
+    );
+    engine.handle_file_change(session_changed(&first)).unwrap();
+    engine.handle_file_change(session_changed(&second)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 2);
+
+    // Removed while running: the watcher reports it as a change
+    std::fs::remove_file(&first).unwrap();
+    engine.handle_file_change(session_changed(&first)).unwrap();
+    assert_eq!(fixture.state(&first).status, SyncStatus::Deleted);
+
+    // Remote copies stay unless deletes are propagated
+    assert_eq!(engine.propagate_deletions().await.unwrap(), 0);
+    drop(engine);
+
+    // Removed while stopped: found on the next start
+    std::fs::remove_file(&second).unwrap();
+    let mut config = Config::default();
+    config.sync.propagate_deletes = true;
+    let mut engine = fixture.engine(&api, &config);
+    assert_eq!(engine.reconcile_deletions().unwrap(), 1);
+    assert_eq!(engine.reconcile_deletions().unwrap(), 0);
+    assert_eq!(fixture.db().get_status_counts().unwrap().deleted, 2);
+
+    assert_eq!(engine.propagate_deletions().await.unwrap(), 2);
+    let deletes: Vec<_> = api.requests().into_iter().filter(|r| r.method == hyper::Method::DELETE).collect();
+    assert_eq!(deletes.len(), 2);
+    assert_eq!(fixture.state(&first).workflow_id, None);
+    assert_eq!(engine.propagate_deletions().await.unwrap(), 0);
+
+    // A restored file syncs again
+    let restored = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&restored)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(fixture.state(&restored).status, SyncStatus::Complete);
+}
+
+#[tokio::test]
+async fn test_pruned_session_exports_from_cache() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.cache.enabled = true;
+    let mut engine = fixture.engine(&api, &config);
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    // The source tool prunes its history
+    std::fs::remove_file(&path).unwrap();
+    let registry = ParserRegistry::new();
+    let markdown = export::export_conversation(&registry, &fixture.db(), SESSION_ID, ExportFormat::Markdown).unwrap();
+    assert!(markdown.contains("Add a README"));
+    assert!(markdown.contains("## User"));
+}
+
+/// Test parser for a history file with one `session: prompt` line per session
+struct HistoryParser;
+
+impl ConversationParser for HistoryParser {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn detect(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn discover(&self, _path: &Path) -> Vec<ConversationFile> {
+        Vec::new()
+    }
+
+    fn parse(&self, file: &Path) -> Result<Conversation, ParserError> {
+        self.parse_all(file)?.into_iter().next().ok_or(ParserError::UnsupportedFormat)
+    }
+
+    fn parse_all(&self, file: &Path) -> Result<Vec<Conversation>, ParserError> {
+        let content = std::fs::read_to_string(file)?;
+        Ok(content
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .map(|(session_id, prompt)| Conversation {
+                source_path: file.to_path_buf(),
+                source: self.name().to_string(),
+                session_id: Some(session_id.to_string()),
+                project_path: None,
+                content: prompt.to_string(),
+                content_type: ContentType::Conversation,
+                title: None,
+            })
+            .collect())
+    }
+
+    fn watch_patterns(&self) -> Vec<&str> {
+        vec!["history.txt"]
+    }
+}
+
+#[tokio::test]
+async fn test_file_with_several_conversations() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut registry = ParserRegistry::new();
+    registry.register(Box::new(HistoryParser));
+    let mut engine = fixture.engine_with_registry(&api, &Config::default(), Arc::new(registry));
+
+    let path = fixture.project_dir("/work/demo").join("history.txt");
+    let changed = || FileChangeEvent {
+        path: path.clone(),
+        parser_name: "history".to_string(),
+    };
+    std::fs::write(&path, "s1: Add a README\ns2: Add a LICENSE\n").unwrap();
+    engine.handle_file_change(changed()).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    let sessions: Vec<_> = extracts.iter().map(|r| r.json()["sessionId"].clone()).collect();
+    assert_eq!(sessions, vec![json!("s1"), json!("s2")]);
+    let tracked = fixture.db().list_file_conversations(&path.to_string_lossy()).unwrap();
+    assert!(tracked.iter().all(|c| c.status == SyncStatus::Complete && c.workflow_id.is_some()));
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+
+    // Only the changed and the new session go out again
+    std::fs::write(&path, "s1: Add a README\ns2: Add a LICENSE and a NOTICE\ns3: Tag a release\n").unwrap();
+    engine.handle_file_change(changed()).unwrap();
+    engine.process_all().await.unwrap();
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    let sessions: Vec<_> = extracts[2..].iter().map(|r| r.json()["sessionId"].clone()).collect();
+    assert_eq!(sessions, vec![json!("s2"), json!("s3")]);
+    assert_eq!(fixture.db().list_file_conversations(&path.to_string_lossy()).unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_export_session_in_shared_file() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut registry = ParserRegistry::new();
+    registry.register(Box::new(HistoryParser));
+    let registry = Arc::new(registry);
+    let mut config = Config::default();
+    config.cache.enabled = true;
+    let mut engine = fixture.engine_with_registry(&api, &config, registry.clone());
+
+    let path = fixture.project_dir("/work/demo").join("history.txt");
+    std::fs::write(&path, "s1: Add a README\ns2: Add a LICENSE\n").unwrap();
+    engine
+        .handle_file_change(FileChangeEvent {
+            path: path.clone(),
+            parser_name: "history".to_string(),
+        })
+        .unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+
+    // The second session, from the cache and then parsed out of the file
+    let db = fixture.db();
+    let markdown = export::export_conversation(&registry, &db, "s2", ExportFormat::Markdown).unwrap();
+    assert!(markdown.contains("Add a LICENSE"));
+    assert!(!markdown.contains("Add a README"));
+    assert_eq!(db.evict_cached_content(0).unwrap(), 2);
+    let markdown = export::export_conversation(&registry, &db, "s2", ExportFormat::Markdown).unwrap();
+    assert!(markdown.contains("Add a LICENSE"));
+    assert!(!markdown.contains("Add a README"));
+
+    let found = db.find_session("s2").unwrap().unwrap();
+    assert_eq!(found.session_in_file(), Some("s2"));
+    assert_eq!(found.into_state().session_id.as_deref(), Some("s2"));
+}
+
+#[tokio::test]
+async fn test_engine_task_serves_handles() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let (handle, task) = fixture.engine(&api, &fixture.config()).into_task();
+    let shutdown = CancellationToken::new();
+    let local = tokio::task::LocalSet::new();
+    let running = local.spawn_local(task.run(shutdown.clone()));
+    local
+        .run_until(async {
+            let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+            handle.enqueue(session_changed(&path)).await.unwrap();
+
+            // The task may already be working the queue; either way it ends up uploaded once
+            handle.sync_now().await.unwrap();
+            assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+            assert_eq!(api.requests_to("/extraction/conversations/extract").len(), 1);
+
+            let status = handle.status().await.unwrap();
+            assert_eq!(status.queued, 0);
+            assert_eq!(status.in_flight, 0);
+            assert_eq!(handle.call(|engine| engine.queue_len()).await.unwrap(), 0);
+
+            shutdown.cancel();
+            running.await.unwrap();
+            assert!(matches!(handle.status().await, Err(SyncError::Stopped)));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn test_backend_change_holds_uploads() {
+    let first = MockApi::start().await;
+    let second = MockApi::start().await;
+    let fixture = Fixture::new();
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    let mut engine = fixture.engine(&first, &Config::default());
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    engine.process_all().await.unwrap();
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+    drop(engine);
+
+    // Pointed somewhere else, the engine waits for a choice
+    let mut engine = fixture.engine(&second, &Config::default());
+    assert_eq!(engine.held_for_backend(), Some(first.url.as_str()));
+    assert!(engine.is_paused());
+
+    let db = fixture.db();
+    assert_eq!(db.switch_backend(&first.url, &second.url, false).unwrap(), BackendSwitch::Reset);
+    engine.refresh_backend_hold();
+    assert_eq!(engine.held_for_backend(), None);
+    let state = fixture.state(&path);
+    assert_eq!(state.status, SyncStatus::Pending);
+    assert_eq!(state.workflow_id, None);
+
+    // Going back needs no choice; the first backend's state comes back
+    let mut config = Config::default();
+    config.sync.on_backend_change = BackendChange::Reset;
+    let engine = fixture.engine(&first, &config);
+    assert_eq!(engine.held_for_backend(), None);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+    assert_eq!(db.get_backend().unwrap().as_deref(), Some(first.url.as_str()));
+}
+
+#[tokio::test]
+async fn test_offline_uploads_wait_for_network() {
+    let fixture = Fixture::new();
+    // Nothing listens here until the API comes back
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut engine = SyncEngine::with_database(
+        format!("http://{}", addr),
+        Some("test-token".to_string()),
+        Arc::new(ParserRegistry::new()),
+        &Config::default(),
+        fixture.db(),
+    )
+    .unwrap();
+
+    let path = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    let report = engine.process_all().await.unwrap();
+    assert!(report.failed.is_empty());
+    assert!(engine.is_offline());
+    assert_eq!(engine.queue_len(), 1);
+    assert_eq!(fixture.state(&path).status, SyncStatus::Pending);
+    assert_eq!(fixture.db().get_retry_count(&path.to_string_lossy()).unwrap(), 0);
+    assert!(!engine.check_connectivity(true).await);
+
+    // Sync Now probes right away and drains the queue
+    let api = MockApi::start_at(&addr.to_string()).await;
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert!(!engine.is_offline());
+    assert_eq!(fixture.state(&path).status, SyncStatus::Complete);
+    assert_eq!(api.requests_to("/extraction/conversations/extract").len(), 1);
+}
+
+#[tokio::test]
+async fn test_selective_sync_by_project() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.sync.include_projects = vec!["/work/**".to_string()];
+    config.sync.exclude_projects = vec!["/work/clients/**".to_string()];
+
+    // Changes in projects that aren't selected are never reported
+    for project in ["/work/clients/bank", "/home/notes", "/work/demo"] {
+        fixture.project_dir(project);
+    }
+    let mut watcher = FileWatcher::new(Duration::from_millis(100)).unwrap();
+    watcher.set_project_filter(ProjectFilter::from_config(&config.sync).unwrap());
+    watcher.watch(&fixture.projects_dir, "claude-code").unwrap();
+    let skipped = fixture.write_session("/work/clients/bank", "s1", "Audit the ledger");
+    let notes = fixture.write_session("/home/notes", "s2", "Plan the week");
+    let kept = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    let mut seen = Vec::new();
+    while let Ok(event) = watcher.events().recv_timeout(Duration::from_secs(2)) {
+        seen.push(event.path);
+    }
+    assert!(seen.contains(&kept));
+    assert!(!seen.contains(&skipped) && !seen.contains(&notes));
+
+    // The engine checks too, for files found another way
+    let mut engine = fixture.engine(&api, &config);
+    for path in [&skipped, &notes, &kept] {
+        engine.handle_file_change(session_changed(path)).unwrap();
+    }
+    assert_eq!(engine.queue_len(), 1);
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert_eq!(fixture.state(&kept).status, SyncStatus::Complete);
+    assert!(fixture.db().get_sync_state(&skipped.to_string_lossy()).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_encrypted_upload() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = Config::default();
+    config.encryption.enabled = true;
+    let path = fixture.write_session("/work/billing", SESSION_ID, "Refactor the invoice totals");
+
+    // Without a key nothing goes up, in the clear or otherwise
+    let mut engine = fixture.engine(&api, &config);
+    assert!(engine.waiting_for_encryption_key());
+    engine.handle_file_change(session_changed(&path)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 0);
+    assert!(api.requests_to("/extraction/conversations/extract").is_empty());
+
+    // Only this test sets the key, and only engines with encryption on read it
+    let key = EncryptionKey::generate();
+    std::env::set_var("DUPLEX_ENCRYPTION_KEY", key.to_base64());
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    assert!(!engine.is_paused());
+
+    let request = api.requests_to("/extraction/conversations/extract")[0].json();
+    assert_eq!(request["encryption"]["algorithm"], "aes-256-gcm");
+    assert_eq!(request["encryption"]["keyFingerprint"], key.fingerprint());
+    assert!(request.get("title").is_none());
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!request.to_string().contains("invoice totals"));
+    assert_eq!(key.open(request["content"].as_str().unwrap()).unwrap(), content.as_bytes());
+    assert_eq!(request["contentHash"], key.content_hash(&content));
+}
+
+#[tokio::test]
+async fn test_recorded_events_replay() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let log = fixture.dir.path().join("events.jsonl");
+    let mut engine = fixture.engine(&api, &Config::default());
+    engine.set_recorder(EventRecorder::create(&log).unwrap());
+
+    let first = fixture.write_session("/work/demo", SESSION_ID, "Add a README");
+    engine.handle_file_change(session_changed(&first)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    let second = fixture.write_session("/work/other", "b1b2c3d4-e5f6-7890-abcd-ef1234567890", "Fix the build");
+    engine.handle_file_change(session_changed(&second)).unwrap();
+    engine.handle_file_change(session_changed(&second)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 1);
+    drop(engine);
+
+    let events = replay::read_log(&log).unwrap();
+    let recorded: Vec<QueueChange> = events
+        .iter()
+        .filter_map(|event| match event {
+            RecordedEvent::Queue { event, .. } => Some(event.change.clone()),
+            RecordedEvent::Change { .. } => None,
+        })
+        .filter(|change| matches!(change, QueueChange::Started { .. } | QueueChange::Finished { .. }))
+        .collect();
+    assert_eq!(recorded.len(), 4);
+    assert_eq!(recorded[0], QueueChange::Started { path: "project-1/session-1".into() });
+
+    // Replayed from in-memory files, the uploads go the same way
+    let replay_api = MockApi::start().await;
+    let replay_fixture = Fixture::new();
+    let files = TestFiles::default();
+    let mut registry = ParserRegistry::new();
+    registry.register(Box::new(TestParser::new(files.clone())));
+    let mut replayer = replay_fixture.engine_with_registry(&replay_api, &Config::default(), Arc::new(registry));
+    let report = replay::replay(&events, &mut replayer, &files).await.unwrap();
+    assert_eq!(report.changes, 3);
+    assert_eq!(report.sync.succeeded, 2);
+    let replayed: Vec<QueueChange> = report
+        .events
+        .into_iter()
+        .map(|event| event.change)
+        .filter(|change| matches!(change, QueueChange::Started { .. } | QueueChange::Finished { .. }))
+        .collect();
+    assert_eq!(replayed, recorded);
+    assert_eq!(replay_api.requests_to("/extraction/conversations/extract").len(), 2);
+}
+
+/// Replay the log in `DUPLEX_REPLAY_LOG`, as from a bug report
+#[tokio::test]
+#[ignore]
+async fn replay_log() {
+    let Some(log) = std::env::var_os("DUPLEX_REPLAY_LOG") else {
+        panic!("Set DUPLEX_REPLAY_LOG to the event log to replay");
+    };
+    let events = replay::read_log(Path::new(&log)).unwrap();
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let files = TestFiles::default();
+    let mut registry = ParserRegistry::new();
+    registry.register(Box::new(TestParser::new(files.clone())));
+    let mut engine = fixture.engine_with_registry(&api, &Config::default(), Arc::new(registry));
+
+    let started = std::time::Instant::now();
+    let report = replay::replay(&events, &mut engine, &files).await.unwrap();
+    for event in &report.events {
+        println!("{}", serde_json::to_string(event).unwrap());
+    }
+    println!(
+        "Replayed {} change(s) in {:?}: {}, {} request(s)",
+        report.changes,
+        started.elapsed(),
+        report.sync.summary(),
+        api.requests().len()
+    );
+}
+
+#[tokio::test]
+async fn test_workspace_tokens() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    let mut config = fixture.config();
+    config.policy.workspace_mapping = [
+        ("/work/*/acme".to_string(), "acme".to_string()),
+        ("/work".to_string(), "personal".to_string()),
+    ]
+    .into();
+    let token = config::WorkspaceToken {
+        env: Some("DUPLEX_TEST_ACME_TOKEN".to_string()),
+    };
+    config.workspaces.tokens = [("acme".to_string(), token)].into();
+    // Only this test reads the variable
+    std::env::set_var("DUPLEX_TEST_ACME_TOKEN", "acme-token");
+    let mut engine = fixture.engine(&api, &config);
+
+    let acme = fixture.write_session("/work/clients/acme", SESSION_ID, "Ship the landing page");
+    let personal = fixture.write_session("/work/notes", "b1b2c3d4-e5f6-7890-abcd-ef1234567890", "Sort my notes");
+    engine.handle_file_change(session_changed(&acme)).unwrap();
+    engine.handle_file_change(session_changed(&personal)).unwrap();
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 2);
+
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    let sent = |workspace: &str| {
+        let request = extracts.iter().find(|r| r.json()["workspaceId"] == workspace).unwrap();
+        request.authorization.clone()
+    };
+    assert_eq!(sent("acme").as_deref(), Some("Bearer acme-token"));
+    assert_eq!(sent("personal").as_deref(), Some("Bearer test-token"));
+}
+
+#[tokio::test]
+async fn test_org_baseline_applies_before_first_upload() {
+    let api = MockApi::start().await;
+    let fixture = Fixture::new();
+    // Only this test writes a baseline; other engines find none there
+    std::env::set_var("DUPLEX_CONFIG_DIR", fixture.dir.path().join("config"));
+    let mut engine = fixture.engine(&api, &fixture.config());
+
+    // Signing in holds uploads until the baseline is in
+    engine.hold_for_baseline();
+    let customer = fixture.write_session("/work/customer/data", SESSION_ID, "Load the export");
+    let own = fixture.write_session("/work/tools", "b1b2c3d4-e5f6-7890-abcd-ef1234567890", "Fix the build");
+    engine.handle_file_change(session_changed(&customer)).unwrap();
+    engine.handle_file_change(session_changed(&own)).unwrap();
+    api.fail_next(StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(engine.process_all().await.unwrap().succeeded, 0);
+    assert!(engine.waiting_for_baseline());
+    assert!(api.requests_to("/extraction/conversations/extract").is_empty());
+
+    api.publish_baseline(json!({ "exclude": ["/work/customer/**"] }));
+    let report = engine.process_all().await.unwrap();
+    assert!(!engine.waiting_for_baseline());
+    assert_eq!(report.succeeded, 1);
+    let extracts = api.requests_to("/extraction/conversations/extract");
+    assert_eq!(extracts.len(), 1);
+    assert_eq!(extracts[0].json()["sourcePath"], own.to_string_lossy().as_ref());
+    assert!(fixture.dir.path().join("config").join("org_baseline.json").exists());
+}
@@ -1,449 +0,0 @@
-use keyring::Entry;
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
-use thiserror::Error;
-
-/// Service name for keyring storage
-const KEYRING_SERVICE: &str = "app.duplex.desktop";
-
-/// Keyring entry names
-const KEYRING_ACCESS_TOKEN: &str = "access_token";
-const KEYRING_REFRESH_TOKEN: &str = "refresh_token";
-const KEYRING_EXPIRES_AT: &str = "expires_at";
-
-#[derive(Error, Debug)]
-pub enum ConfigError {
-    #[error("Failed to determine config directory")]
-    NoConfigDir,
-    #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
-    #[error("JSON parse error: {0}")]
-    Json(#[from] serde_json::Error),
-    #[error("Not authenticated")]
-    NotAuthenticated,
-    #[error("Token expired")]
-    TokenExpired,
-    #[error("Keyring error: {0}")]
-    Keyring(String),
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Config {
-    #[serde(default)]
-    pub sync: SyncConfig,
-    #[serde(default)]
-    pub discovery: DiscoveryConfig,
-    #[serde(default)]
-    pub parsers: ParsersConfig,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SyncConfig {
-    #[serde(default = "default_debounce_seconds")]
-    pub debounce_seconds: u64,
-    #[serde(default = "default_true")]
-    pub auto_start: bool,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct DiscoveryConfig {
-    #[serde(default = "default_true")]
-    pub auto_discover: bool,
-    #[serde(default)]
-    pub additional_paths: Vec<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ParsersConfig {
-    #[serde(default = "default_enabled_parsers")]
-    pub enabled: Vec<String>,
-}
-
-fn default_debounce_seconds() -> u64 {
-    5
-}
-
-fn default_true() -> bool {
-    true
-}
-
-fn default_enabled_parsers() -> Vec<String> {
-    vec!["claude-code".to_string()]
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            sync: SyncConfig::default(),
-            discovery: DiscoveryConfig::default(),
-            parsers: ParsersConfig::default(),
-        }
-    }
-}
-
-impl Default for SyncConfig {
-    fn default() -> Self {
-        Self {
-            debounce_seconds: default_debounce_seconds(),
-            auto_start: true,
-        }
-    }
-}
-
-impl Default for DiscoveryConfig {
-    fn default() -> Self {
-        Self {
-            auto_discover: true,
-            additional_paths: vec![],
-        }
-    }
-}
-
-impl Default for ParsersConfig {
-    fn default() -> Self {
-        Self {
-            enabled: default_enabled_parsers(),
-        }
-    }
-}
-
-/// Get the config directory path
-pub fn get_config_dir() -> Result<PathBuf, ConfigError> {
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    {
-        // Use ~/.config/duplex on Linux and macOS
-        if let Some(home) = dirs::home_dir() {
-            return Ok(home.join(".config").join("duplex"));
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        // Use AppData on Windows
-        if let Some(config) = dirs::config_dir() {
-            return Ok(config.join("duplex"));
-        }
-    }
-
-    Err(ConfigError::NoConfigDir)
-}
-
-/// Get the config file path
-pub fn get_config_path() -> Result<PathBuf, ConfigError> {
-    Ok(get_config_dir()?.join("config.jsonc"))
-}
-
-/// Get the credentials file path
-pub fn get_credentials_path() -> Result<PathBuf, ConfigError> {
-    Ok(get_config_dir()?.join("credentials.json"))
-}
-
-/// Get the database file path
-pub fn get_database_path() -> Result<PathBuf, ConfigError> {
-    Ok(get_config_dir()?.join("sync.db"))
-}
-
-/// Load config from file, creating default if it doesn't exist
-pub fn load_config() -> Result<Config, ConfigError> {
-    let config_path = get_config_path()?;
-
-    if !config_path.exists() {
-        // Create config directory and default config
-        if let Some(parent) = config_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let default_config = Config::default();
-        let json = serde_json::to_string_pretty(&default_config)?;
-
-        // Add a comment at the top
-        let jsonc = format!(
-            "// Duplex Stream configuration\n// See https://duplex.app/docs/config for options\n{}",
-            json
-        );
-
-        std::fs::write(&config_path, jsonc)?;
-        tracing::info!("Created default config at {:?}", config_path);
-
-        return Ok(default_config);
-    }
-
-    // Read and parse config (strip comments first)
-    let content = std::fs::read_to_string(&config_path)?;
-    let json = json_comments::StripComments::new(content.as_bytes());
-    let config: Config = serde_json::from_reader(json)?;
-
-    tracing::debug!("Loaded config from {:?}", config_path);
-    Ok(config)
-}
-
-/// Stored authentication credentials
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Credentials {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: u64, // Unix timestamp
-    pub user_id: String,
-    pub email: Option<String>,
-    pub org_id: Option<String>,
-}
-
-impl Credentials {
-    /// Check if the access token is expired (with 60s buffer)
-    pub fn is_expired(&self) -> bool {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        self.expires_at <= now + 60
-    }
-}
-
-/// Load credentials from the credentials file
-pub fn load_credentials() -> Result<Credentials, ConfigError> {
-    let creds_path = get_credentials_path()?;
-
-    if !creds_path.exists() {
-        return Err(ConfigError::NotAuthenticated);
-    }
-
-    let content = std::fs::read_to_string(&creds_path)?;
-    let credentials: Credentials = serde_json::from_str(&content)?;
-
-    tracing::debug!("Loaded credentials for user {}", credentials.user_id);
-    Ok(credentials)
-}
-
-/// Save credentials to the credentials file
-pub fn save_credentials(credentials: &Credentials) -> Result<(), ConfigError> {
-    let creds_path = get_credentials_path()?;
-
-    // Ensure the directory exists
-    if let Some(parent) = creds_path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-
-    let json = serde_json::to_string_pretty(credentials)?;
-    std::fs::write(&creds_path, json)?;
-
-    tracing::info!("Saved credentials to {:?}", creds_path);
-    Ok(())
-}
-
-/// Delete the credentials file (logout)
-pub fn delete_credentials() -> Result<(), ConfigError> {
-    let creds_path = get_credentials_path()?;
-
-    if creds_path.exists() {
-        std::fs::remove_file(&creds_path)?;
-        tracing::info!("Deleted credentials from {:?}", creds_path);
-    }
-
-    Ok(())
-}
-
-/// Get the simple token file path (used by desktop auth flow)
-fn get_token_file_path() -> Result<PathBuf, ConfigError> {
-    let config_dir = dirs::config_dir()
-        .ok_or(ConfigError::NoConfigDir)?
-        .join("duplex-stream");
-    Ok(config_dir.join(".token"))
-}
-
-/// Get a valid access token, checking multiple sources:
-/// 1. First try the credentials.json file (has expiry info)
-/// 2. Fall back to the simple .token file (from desktop auth flow)
-pub fn get_access_token() -> Result<String, ConfigError> {
-    // Try credentials.json first (has expiry checking)
-    match load_credentials() {
-        Ok(credentials) => {
-            if credentials.is_expired() {
-                tracing::debug!("Credentials expired, checking token file");
-            } else {
-                return Ok(credentials.access_token);
-            }
-        }
-        Err(e) => {
-            tracing::debug!("No credentials.json: {}, checking token file", e);
-        }
-    }
-
-    // Fall back to simple token file (from desktop auth)
-    let token_path = get_token_file_path()?;
-    if token_path.exists() {
-        let token = std::fs::read_to_string(&token_path)?;
-        let token = token.trim();
-        if !token.is_empty() {
-            tracing::debug!("Using token from file at {:?}", token_path);
-            return Ok(token.to_string());
-        }
-    }
-
-    Err(ConfigError::NotAuthenticated)
-}
-
-/// Token data stored in keyring
-#[derive(Debug, Clone)]
-pub struct TokenData {
-    pub access_token: String,
-    pub refresh_token: String,
-    pub expires_at: u64,
-}
-
-/// Secure token storage using the OS keyring
-#[derive(Debug, Clone)]
-pub struct SecureTokenStorage {
-    service: String,
-}
-
-impl SecureTokenStorage {
-    /// Create a new SecureTokenStorage instance
-    pub fn new() -> Self {
-        Self {
-            service: KEYRING_SERVICE.to_string(),
-        }
-    }
-
-    /// Store tokens in the keyring
-    pub fn store_tokens(
-        &self,
-        access_token: String,
-        refresh_token: String,
-        expires_at: u64,
-    ) -> Result<(), ConfigError> {
-        // Store access token
-        let entry = Entry::new(&self.service, KEYRING_ACCESS_TOKEN)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-        entry.set_password(&access_token)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-
-        // Store refresh token
-        let entry = Entry::new(&self.service, KEYRING_REFRESH_TOKEN)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-        entry.set_password(&refresh_token)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-
-        // Store expires_at as string
-        let entry = Entry::new(&self.service, KEYRING_EXPIRES_AT)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-        entry.set_password(&expires_at.to_string())
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-
-        tracing::info!("Stored tokens in keyring");
-        Ok(())
-    }
-
-    /// Get tokens from the keyring
-    pub fn get_tokens(&self) -> Result<TokenData, ConfigError> {
-        // Get access token
-        let entry = Entry::new(&self.service, KEYRING_ACCESS_TOKEN)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-        let access_token = entry.get_password()
-            .map_err(|_| ConfigError::NotAuthenticated)?;
-
-        // Get refresh token
-        let entry = Entry::new(&self.service, KEYRING_REFRESH_TOKEN)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-        let refresh_token = entry.get_password()
-            .map_err(|_| ConfigError::NotAuthenticated)?;
-
-        // Get expires_at
-        let entry = Entry::new(&self.service, KEYRING_EXPIRES_AT)
-            .map_err(|e| ConfigError::Keyring(e.to_string()))?;
-        let expires_at_str = entry.get_password()
-            .map_err(|_| ConfigError::NotAuthenticated)?;
-        let expires_at: u64 = expires_at_str
-            .parse()
-            .map_err(|_| ConfigError::Keyring("Invalid expires_at value".to_string()))?;
-
-        tracing::debug!("Retrieved tokens from keyring");
-        Ok(TokenData {
-            access_token,
-            refresh_token,
-            expires_at,
-        })
-    }
-
-    /// Clear all tokens from the keyring
-    pub fn clear_tokens(&self) -> Result<(), ConfigError> {
-        // Delete access token
-        if let Ok(entry) = Entry::new(&self.service, KEYRING_ACCESS_TOKEN) {
-            let _ = entry.delete_credential();
-        }
-
-        // Delete refresh token
-        if let Ok(entry) = Entry::new(&self.service, KEYRING_REFRESH_TOKEN) {
-            let _ = entry.delete_credential();
-        }
-
-        // Delete expires_at
-        if let Ok(entry) = Entry::new(&self.service, KEYRING_EXPIRES_AT) {
-            let _ = entry.delete_credential();
-        }
-
-        tracing::info!("Cleared tokens from keyring");
-        Ok(())
-    }
-
-    /// Check if tokens exist in keyring
-    pub fn has_tokens(&self) -> bool {
-        if let Ok(entry) = Entry::new(&self.service, KEYRING_ACCESS_TOKEN) {
-            entry.get_password().is_ok()
-        } else {
-            false
-        }
-    }
-
-    /// Migrate from legacy .token file to keyring
-    ///
-    /// This checks for a legacy token file and migrates it to keyring storage.
-    /// Note: Legacy tokens don't have refresh tokens or expiry, so they'll need
-    /// to be re-authenticated eventually.
-    pub fn migrate_from_legacy(&self) -> Result<bool, ConfigError> {
-        let legacy_path = get_token_file_path()?;
-
-        if !legacy_path.exists() {
-            return Ok(false);
-        }
-
-        let token = std::fs::read_to_string(&legacy_path)?;
-        let token = token.trim();
-
-        if token.is_empty() {
-            return Ok(false);
-        }
-
-        tracing::info!("Migrating legacy token from {:?}", legacy_path);
-
-        // Store in keyring with a far-future expiry (legacy tokens don't have expiry info)
-        // The token will be refreshed when used
-        let far_future = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() + 3600; // 1 hour from now
-
-        self.store_tokens(
-            token.to_string(),
-            String::new(), // No refresh token from legacy flow
-            far_future,
-        )?;
-
-        // Delete the legacy file
-        std::fs::remove_file(&legacy_path)?;
-        tracing::info!("Deleted legacy token file");
-
-        Ok(true)
-    }
-}
-
-impl Default for SecureTokenStorage {
-    fn default() -> Self {
-        Self::new()
-    }
-}
@@ -0,0 +1,98 @@
+//! System sleep and power state notifications (macOS)
+//!
+//! Registers an observer with the NSWorkspace notification center so uploads
+//! pause before the machine sleeps rather than being cut off mid-request,
+//! and one with the default center so Low Power Mode changes reach
+//! [`duplex_core::power`] without waiting for its next poll.
+
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use objc::declare::ClassDecl;
+use objc::runtime::{Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+use std::sync::OnceLock;
+
+use duplex_core::shutdown::SharedShutdown;
+
+static SHUTDOWN: OnceLock<SharedShutdown> = OnceLock::new();
+
+extern "C" fn will_sleep(_this: &Object, _cmd: Sel, _notification: id) {
+    if let Some(shutdown) = SHUTDOWN.get() {
+        shutdown.suspend();
+    }
+}
+
+extern "C" fn did_wake(_this: &Object, _cmd: Sel, _notification: id) {
+    if let Some(shutdown) = SHUTDOWN.get() {
+        shutdown.resume();
+    }
+}
+
+extern "C" fn power_state_changed(_this: &Object, _cmd: Sel, _notification: id) {
+    report_low_power_mode();
+}
+
+/// Pass the current Low Power Mode setting to the core
+fn report_low_power_mode() {
+    let enabled: bool = unsafe {
+        let process_info: id = msg_send![class!(NSProcessInfo), processInfo];
+        msg_send![process_info, isLowPowerModeEnabled]
+    };
+    duplex_core::power::set_low_power_mode(enabled);
+    duplex_core::power::power_changed();
+}
+
+/// Follow Low Power Mode as it is switched. Call once, on the main thread.
+pub fn observe_power_state() {
+    let Some(mut decl) = ClassDecl::new("DuplexPowerStateObserver", class!(NSObject)) else {
+        tracing::warn!("Power state observer class already registered");
+        return;
+    };
+
+    unsafe {
+        decl.add_method(
+            sel!(powerStateChanged:),
+            power_state_changed as extern "C" fn(&Object, Sel, id),
+        );
+        let observer_class = decl.register();
+
+        // Lives for the rest of the process
+        let observer: id = msg_send![observer_class, new];
+        let center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
+        let name = NSString::alloc(nil).init_str("NSProcessInfoPowerStateDidChangeNotification");
+        let _: () = msg_send![center, addObserver: observer selector: sel!(powerStateChanged:) name: name object: nil];
+    }
+
+    report_low_power_mode();
+    tracing::debug!("Observing Low Power Mode");
+}
+
+/// Suspend `shutdown` while the system sleeps. Call once, on the main thread.
+pub fn observe_sleep(shutdown: SharedShutdown) {
+    if SHUTDOWN.set(shutdown).is_err() {
+        return;
+    }
+
+    let Some(mut decl) = ClassDecl::new("DuplexSleepObserver", class!(NSObject)) else {
+        tracing::warn!("Sleep observer class already registered");
+        return;
+    };
+
+    unsafe {
+        decl.add_method(sel!(willSleep:), will_sleep as extern "C" fn(&Object, Sel, id));
+        decl.add_method(sel!(didWake:), did_wake as extern "C" fn(&Object, Sel, id));
+        let observer_class = decl.register();
+
+        // Lives for the rest of the process
+        let observer: id = msg_send![observer_class, new];
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let center: id = msg_send![workspace, notificationCenter];
+
+        let will_sleep_name = NSString::alloc(nil).init_str("NSWorkspaceWillSleepNotification");
+        let did_wake_name = NSString::alloc(nil).init_str("NSWorkspaceDidWakeNotification");
+        let _: () = msg_send![center, addObserver: observer selector: sel!(willSleep:) name: will_sleep_name object: nil];
+        let _: () = msg_send![center, addObserver: observer selector: sel!(didWake:) name: did_wake_name object: nil];
+    }
+
+    tracing::debug!("Observing system sleep");
+}
@@ -0,0 +1,378 @@
+//! Windows service wrapper for `duplex watch`
+//!
+//! `duplex service install` registers the headless sync agent with the
+//! service control manager so it starts at boot and is restarted if it
+//! fails, letting IT deploy it without anyone opening the tray app. The
+//! service has no console, so it logs to the Application event log.
+
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use duplex_core::{control, logging, shutdown};
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+use windows_service::service::{
+    Service, ServiceAccess, ServiceAction, ServiceActionType, ServiceControl, ServiceControlAccept,
+    ServiceErrorControl, ServiceExitCode, ServiceFailureActions, ServiceFailureResetPeriod, ServiceInfo,
+    ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::System::EventLog::{
+    RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+};
+
+/// Name registered with the service control manager and the event log
+const SERVICE_NAME: &str = "DuplexStream";
+
+const DISPLAY_NAME: &str = "Duplex Stream Sync Agent";
+
+const DESCRIPTION: &str = "Syncs coding agent conversations to Duplex Stream.";
+
+/// Registry key that makes `SERVICE_NAME` an event log source
+const EVENT_SOURCE_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Services\EventLog\Application\DuplexStream";
+
+/// Message file whose events 1-1000 display their text unchanged
+const EVENT_MESSAGE_FILE: &str = r"%SystemRoot%\System32\EventCreate.exe";
+
+const EVENT_ID: u32 = 1;
+
+/// Restart delays after the first, second and later failures
+const RESTART_DELAYS: [Duration; 3] = [
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+];
+
+/// The failure count resets after a day without failures
+const FAILURE_RESET: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Time the service control manager is told to allow for starting
+const START_WAIT_HINT: Duration = Duration::from_secs(30);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Environment variable holding the `--account` password for unattended installs
+const PASSWORD_ENV: &str = "DUPLEX_SERVICE_PASSWORD";
+
+/// Register the service to start at boot and start it now
+///
+/// Runs as LocalSystem unless `account` is given. Conversation history is
+/// found under the account's home directory, so deployments normally pass
+/// the account of the user whose agents should be synced. Its password is
+/// read from `password_file`, `DUPLEX_SERVICE_PASSWORD` or stdin, and only
+/// prompted for at a terminal; managed and virtual accounts need none.
+///
+/// If any step after registering fails, the service is removed again
+/// rather than left half configured.
+pub fn install(account: Option<String>, password_file: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+
+    let account_password = match &account {
+        Some(account) => account_password(account, password_file.as_deref())?,
+        None => None,
+    };
+
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(DISPLAY_NAME),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![OsString::from("service"), OsString::from("run")],
+        dependencies: vec![],
+        account_name: account.map(OsString::from),
+        account_password: account_password.map(OsString::from),
+    };
+    let service = manager.create_service(
+        &info,
+        ServiceAccess::CHANGE_CONFIG | ServiceAccess::START | ServiceAccess::DELETE,
+    )?;
+
+    if let Err(e) = configure_and_start(&service) {
+        if let Err(delete_error) = service.delete() {
+            eprintln!("Failed to remove the partly installed service: {}", delete_error);
+        }
+        unregister_event_source();
+        return Err(e);
+    }
+
+    println!("Installed and started the {} service", SERVICE_NAME);
+    Ok(())
+}
+
+/// Everything `install` does once the service is registered
+fn configure_and_start(service: &Service) -> Result<(), Box<dyn std::error::Error>> {
+    service.set_description(DESCRIPTION)?;
+
+    service.update_failure_actions(ServiceFailureActions {
+        reset_period: ServiceFailureResetPeriod::After(FAILURE_RESET),
+        reboot_msg: None,
+        command: None,
+        actions: Some(
+            RESTART_DELAYS
+                .iter()
+                .map(|&delay| ServiceAction {
+                    action_type: ServiceActionType::Restart,
+                    delay,
+                })
+                .collect(),
+        ),
+    })?;
+    // Also restart when the agent exits with an error, not only on a crash
+    service.set_failure_actions_on_non_crash_failures(true)?;
+
+    register_event_source()?;
+    service.start(&[] as &[&OsStr])?;
+    Ok(())
+}
+
+/// Whether Windows manages `account`'s password: a virtual account
+/// (`NT SERVICE\...`), a built-in service account or a group managed
+/// service account (`DOMAIN\name$`)
+fn is_managed_account(account: &str) -> bool {
+    let account = account.to_ascii_lowercase();
+    account.starts_with(r"nt service\")
+        || matches!(
+            account.as_str(),
+            r"nt authority\localservice" | r"nt authority\networkservice" | r"nt authority\system"
+        )
+        || account.ends_with('$')
+}
+
+/// The password to register `account` with, without prompting unless at a terminal
+fn account_password(account: &str, password_file: Option<&Path>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if is_managed_account(account) {
+        return Ok(None);
+    }
+
+    let password = if let Some(path) = password_file {
+        std::fs::read_to_string(path)?
+    } else if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        password
+    } else if std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        rpassword::prompt_password(format!("Password for {}: ", account))?
+    } else {
+        let mut password = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut password)?;
+        password
+    };
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        return Err(format!("No password given for {}", account).into());
+    }
+    Ok(Some(password.to_string()))
+}
+
+/// Stop and remove the service
+pub fn uninstall() -> Result<(), Box<dyn std::error::Error>> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+
+    if !matches!(service.query_status()?.current_state, ServiceState::Stopped) {
+        service.stop()?;
+    }
+    // Removal completes once the last handle to the service closes
+    service.delete()?;
+    unregister_event_source();
+
+    println!("Removed the {} service", SERVICE_NAME);
+    Ok(())
+}
+
+/// Hand the process to the service control manager
+///
+/// Only works when started by the service control manager.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+    Ok(())
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("Service failed: {}", e);
+    }
+}
+
+fn run_service() -> windows_service::Result<()> {
+    let (stop_tx, stop_rx) = mpsc::channel();
+
+    // `duplex uninstall` asks over the control socket rather than the SCM
+    let quit_tx = stop_tx.clone();
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(control::quit_requested());
+        let _ = quit_tx.send(());
+    });
+
+    let status = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = stop_tx.send(());
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    set_state(&status, ServiceState::StartPending, ServiceExitCode::Win32(0), START_WAIT_HINT)?;
+
+    let result = crate::run_headless(false, || {
+        if let Err(e) = set_state(&status, ServiceState::Running, ServiceExitCode::Win32(0), Duration::ZERO) {
+            tracing::warn!("Failed to report service running: {}", e);
+        }
+        let _ = stop_rx.recv();
+        let _ = set_state(
+            &status,
+            ServiceState::StopPending,
+            ServiceExitCode::Win32(0),
+            shutdown::DEFAULT_GRACE,
+        );
+    });
+
+    // A service-specific exit code counts as a failure for the recovery actions
+    let exit_code = match result {
+        Ok(()) => ServiceExitCode::Win32(0),
+        Err(e) => {
+            tracing::error!("Sync agent failed: {}", e);
+            ServiceExitCode::ServiceSpecific(1)
+        }
+    };
+    set_state(&status, ServiceState::Stopped, exit_code, Duration::ZERO)
+}
+
+fn set_state(
+    status: &ServiceStatusHandle,
+    state: ServiceState,
+    exit_code: ServiceExitCode,
+    wait_hint: Duration,
+) -> windows_service::Result<()> {
+    let controls_accepted = match state {
+        ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        _ => ServiceControlAccept::empty(),
+    };
+    status.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint,
+        process_id: None,
+    })
+}
+
+/// Make the service name a source the event log can display messages for
+fn register_event_source() -> std::io::Result<()> {
+    let values = [
+        ["EventMessageFile", "REG_EXPAND_SZ", EVENT_MESSAGE_FILE],
+        ["TypesSupported", "REG_DWORD", "7"],
+    ];
+    for [name, kind, data] in values {
+        let output = std::process::Command::new("reg")
+            .args(["add", EVENT_SOURCE_KEY, "/v", name, "/t", kind, "/d", data, "/f"])
+            .output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "failed to register event source: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Remove the event log source, if it was registered
+fn unregister_event_source() {
+    let _ = std::process::Command::new("reg")
+        .args(["delete", EVENT_SOURCE_KEY, "/f"])
+        .output();
+}
+
+/// Log to the Application event log, or stderr if the source can't be opened
+pub fn init_logging() {
+    match EventLogLayer::open() {
+        Some(layer) => logging::init_with(layer),
+        None => logging::init(),
+    }
+}
+
+/// Tracing layer writing info, warning and error events to the event log
+struct EventLogLayer {
+    source: HANDLE,
+}
+
+// SAFETY: event log handles may be used from any thread
+unsafe impl Send for EventLogLayer {}
+unsafe impl Sync for EventLogLayer {}
+
+impl EventLogLayer {
+    fn open() -> Option<Self> {
+        let name = wide(SERVICE_NAME);
+        // SAFETY: `name` is a NUL-terminated UTF-16 string that outlives the call
+        let source = unsafe { RegisterEventSourceW(std::ptr::null(), name.as_ptr()) };
+        (!source.is_null()).then_some(Self { source })
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for EventLogLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let event_type = match *event.metadata().level() {
+            tracing::Level::ERROR => EVENTLOG_ERROR_TYPE,
+            tracing::Level::WARN => EVENTLOG_WARNING_TYPE,
+            tracing::Level::INFO => EVENTLOG_INFORMATION_TYPE,
+            // Too chatty for the event log
+            _ => return,
+        };
+
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        let text = wide(&message.0);
+        let strings = [text.as_ptr()];
+
+        // SAFETY: `strings` holds one NUL-terminated string that outlives the call
+        unsafe {
+            ReportEventW(
+                self.source,
+                event_type,
+                0,
+                EVENT_ID,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            );
+        }
+    }
+}
+
+/// Formats an event as its message followed by its other fields
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// NUL-terminated UTF-16 for Win32 calls
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}